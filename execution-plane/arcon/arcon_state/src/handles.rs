@@ -112,6 +112,20 @@ impl<A: Aggregator> Handle<AggregatorState<A>> {
     }
 }
 
+/// A handle whose item key is of type `IK`
+///
+/// Lets [KeyedBundle](crate::KeyedBundle) implementations scope handles of any state type.
+pub trait ItemKeyed<IK> {
+    fn set_item_key(&self, item_key: IK);
+}
+
+impl<S: StateType, IK: Metakey, N: Metakey> ItemKeyed<IK> for Handle<S, IK, N> {
+    #[inline]
+    fn set_item_key(&self, item_key: IK) {
+        self.item_key.set(item_key);
+    }
+}
+
 impl<S: StateType, IK: Metakey, N: Metakey> Handle<S, IK, N> {
     pub fn with_item_key<NIK: Metakey>(self, item_key: NIK) -> Handle<S, NIK, N> {
         Handle {
@@ -252,6 +252,20 @@ pub trait Bundle<'this, 'session, 'backend, B: Backend>: Send {
     fn activate(&'this self, session: &'session mut Session<'backend, B>) -> Self::Active;
 }
 
+/// A bundle whose handles can all be scoped to an item key of type `IK`
+///
+/// Implemented by the [`bundle!`] macro for every bundle whose handles all use `IK` as item key.
+///
+/// [`bundle!`]: macro.bundle.html
+pub trait KeyedBundle<IK: Metakey> {
+    /// Scope every handle of the bundle to `item_key`
+    fn set_item_key(&self, item_key: IK);
+}
+
+impl<IK: Metakey> KeyedBundle<IK> for () {
+    fn set_item_key(&self, _item_key: IK) {}
+}
+
 impl<'this, 'session, 'backend, B: Backend> Bundle<'this, 'session, 'backend, B> for () {
     type Active = ();
     fn register_states(&mut self, _registration_token: &mut RegistrationToken<B>) {}
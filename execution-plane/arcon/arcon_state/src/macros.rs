@@ -146,6 +146,24 @@ macro_rules! bundle {
                     }
                 }
             }
+
+            impl<
+                $($($generic_lifetime_param,)*)?
+                __IK: $crate::Metakey,
+                $($($generic_param $(: $first_bound $(+ $other_bounds)*)?,)*)?
+            > $crate::KeyedBundle<__IK> for $name$(<
+                $($generic_lifetime_param,)* $($generic_param,)*
+            >)?
+            where
+                $(
+                    $crate::Handle<$state_type $(, $item_key_type $(, $namespace_type)?)?>:
+                        $crate::handles::ItemKeyed<__IK>,
+                )*
+            {
+                fn set_item_key(&self, item_key: __IK) {
+                    $($crate::handles::ItemKeyed::set_item_key(&self.$state_name, item_key);)*
+                }
+            }
         };
     };
 }
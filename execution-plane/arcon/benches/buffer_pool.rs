@@ -75,7 +75,7 @@ fn buffer_pool_single_thread(b: &mut Bencher, buffer_size: usize) {
 
     b.iter(|| {
         // fetch BufferWriter
-        let mut writer = pool.get();
+        let mut writer = pool.get().unwrap();
         for i in 0..buffer_size {
             black_box(writer.push(i as u64));
             ()
@@ -106,7 +106,7 @@ fn buffer_pool_multi_threaded(buffer_size: usize) {
 
     for _ in 0..THREAD_ITERATIONS {
         // fetch BufferWriter
-        let mut writer = pool.get();
+        let mut writer = pool.get().unwrap();
         for i in 0..buffer_size {
             black_box(writer.push(i as u64));
             ()
@@ -4,6 +4,15 @@
 // Benchmarks for serialisation/deserialisation
 
 use abomonation_derive::*;
+#[cfg(feature = "arcon_serde")]
+use arcon::data::flight_serde::bincode_remote::BincodeSerde;
+use arcon::{
+    data::flight_serde::compressed_remote::{CompressedSerde, Lz4Serde, ZstdSerde},
+    prelude::{
+        ArconElement, ArconEvent, Compression, Deserialiser, FlightSerde, RawArconMessage,
+        ReliableSerde, Serialisable, UnsafeSerde,
+    },
+};
 use criterion::{black_box, criterion_group, criterion_main, BatchSize, Bencher, Criterion};
 use lz4_compression::prelude::{compress, decompress};
 use prost::Message;
@@ -258,5 +267,81 @@ pub fn abomonation_full_serde_lz4(b: &mut Bencher) {
     });
 }
 
-criterion_group!(benches, arcon_serde_bench);
+fn flight_serde_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("flight_serde_bench");
+    group.bench_function("reliable flight serde", reliable_flight_serde);
+    group.bench_function("unsafe flight serde", unsafe_flight_serde);
+    #[cfg(feature = "arcon_serde")]
+    group.bench_function("bincode flight serde", bincode_flight_serde);
+    group.bench_function("reliable flight serde with lz4", lz4_reliable_flight_serde);
+    group.bench_function("unsafe flight serde with lz4", lz4_unsafe_flight_serde);
+    group.bench_function(
+        "reliable flight serde with zstd",
+        zstd_reliable_flight_serde,
+    );
+    group.finish()
+}
+
+/// A batch of elements like the ones a ChannelStrategy sends
+fn flight_message() -> RawArconMessage<u64> {
+    RawArconMessage {
+        events: (0..1024)
+            .map(|i| ArconEvent::Element(ArconElement::new(i)).into())
+            .collect(),
+        sender: 1.into(),
+    }
+}
+
+/// Serialises a batch with `serialiser` and deserialises it again with `D`
+fn flight_serde<D, S>(b: &mut Bencher, serialiser: impl Fn(RawArconMessage<u64>) -> S)
+where
+    D: Deserialiser<RawArconMessage<u64>>,
+    S: Serialisable,
+{
+    let msg = flight_message();
+    b.iter_batched(
+        || msg.clone(),
+        |msg| {
+            let ser = serialiser(msg);
+            let mut bytes: Vec<u8> = Vec::with_capacity(ser.size_hint().unwrap_or(0));
+            ser.serialise(&mut bytes).unwrap();
+            let mut buf: &[u8] = &bytes;
+            black_box(D::deserialise(&mut buf).unwrap());
+        },
+        BatchSize::SmallInput,
+    );
+}
+
+pub fn reliable_flight_serde(b: &mut Bencher) {
+    flight_serde::<ReliableSerde<u64>, _>(b, ReliableSerde);
+}
+
+pub fn unsafe_flight_serde(b: &mut Bencher) {
+    flight_serde::<UnsafeSerde<u64>, _>(b, UnsafeSerde);
+}
+
+#[cfg(feature = "arcon_serde")]
+pub fn bincode_flight_serde(b: &mut Bencher) {
+    flight_serde::<BincodeSerde<u64>, _>(b, BincodeSerde);
+}
+
+pub fn lz4_reliable_flight_serde(b: &mut Bencher) {
+    flight_serde::<Lz4Serde<u64>, _>(b, |msg| {
+        CompressedSerde::new(msg, &FlightSerde::Reliable, Compression::Lz4).unwrap()
+    });
+}
+
+pub fn lz4_unsafe_flight_serde(b: &mut Bencher) {
+    flight_serde::<Lz4Serde<u64>, _>(b, |msg| {
+        CompressedSerde::new(msg, &FlightSerde::Unsafe, Compression::Lz4).unwrap()
+    });
+}
+
+pub fn zstd_reliable_flight_serde(b: &mut Bencher) {
+    flight_serde::<ZstdSerde<u64>, _>(b, |msg| {
+        CompressedSerde::new(msg, &FlightSerde::Reliable, Compression::Zstd).unwrap()
+    });
+}
+
+criterion_group!(benches, arcon_serde_bench, flight_serde_bench);
 criterion_main!(benches);
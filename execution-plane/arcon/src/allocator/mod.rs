@@ -1,6 +1,8 @@
 // Copyright (c) 2020, KTH Royal Institute of Technology.
 // SPDX-License-Identifier: AGPL-3.0-only
 
+use crate::metrics::memory::AllocatorMetrics;
+use arcon_error::*;
 use fxhash::FxHashMap;
 use std::alloc::{GlobalAlloc, Layout, System};
 
@@ -26,6 +28,32 @@ pub enum AllocResult {
     CapacityErr(String),
 }
 
+/// Fraction of the memory limit above which the allocator is under pressure
+const PRESSURE_THRESHOLD: f64 = 0.9;
+
+/// A share of the allocator's memory that is handed out to a part of [arcon]
+///
+/// Memory charged to a Budget may not exceed its limit, while memory that is not
+/// charged to any Budget is only bound by the limit of the allocator itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Budget {
+    /// Buffers used by the Channel Strategies
+    Channels,
+    /// Caches of the state backends
+    State,
+    /// Buffers that remote messages are received into
+    Network,
+    /// Memory of a single Node, identified by the id of its NodeID
+    Node(u32),
+}
+
+/// Limit and current usage of a [Budget]
+#[derive(Debug, Default)]
+struct BudgetUsage {
+    limit: usize,
+    used: usize,
+}
+
 /// An Allocator for [arcon]
 ///
 /// The allocator is not meant to handle all heap allocations
@@ -34,8 +62,10 @@ pub enum AllocResult {
 /// network buffers, state backends...
 #[derive(Debug)]
 pub struct ArconAllocator {
-    /// HashMap keeping track of allocations
-    allocations: FxHashMap<AllocId, (AllocPtr, Layout)>,
+    /// HashMap keeping track of allocations and the Budget they are charged to
+    allocations: FxHashMap<AllocId, (AllocPtr, Layout, Option<Budget>)>,
+    /// Budgets handed out by the allocator
+    budgets: FxHashMap<Budget, BudgetUsage>,
     /// Memory limit
     limit: usize,
     /// Current alloc epoch
@@ -44,6 +74,8 @@ pub struct ArconAllocator {
     alloc_counter: u64,
     /// Bytes allocated currently
     curr_alloc: usize,
+    /// Bytes reserved for memory that is managed outside of the allocator
+    curr_reserved: usize,
 }
 
 impl ArconAllocator {
@@ -51,14 +83,41 @@ impl ArconAllocator {
     pub fn new(limit: usize) -> ArconAllocator {
         ArconAllocator {
             allocations: FxHashMap::default(),
+            budgets: FxHashMap::default(),
             limit,
             alloc_epoch: 1,
             alloc_counter: 0,
             curr_alloc: 0,
+            curr_reserved: 0,
         }
     }
     /// Allocate memory block of type T with given capacity
     pub unsafe fn alloc<T>(&mut self, capacity: usize) -> AllocResult {
+        self.alloc_budgeted::<T>(None, capacity)
+    }
+    /// Allocate memory block of type T with given capacity and charge it to `budget`
+    ///
+    /// Returns [AllocResult::ArconOOM] with the bytes left in the budget if it is exhausted.
+    pub unsafe fn alloc_in<T>(&mut self, budget: Budget, capacity: usize) -> AllocResult {
+        self.alloc_budgeted::<T>(Some(budget), capacity)
+    }
+    /// Allocate `bytes` aligned to `align` and charge them to `budget`
+    ///
+    /// Used for byte buffers that later hold values of another type.
+    /// `align` must be a power of two, e.g., the result of [std::mem::align_of].
+    pub unsafe fn alloc_bytes_in(
+        &mut self,
+        budget: Budget,
+        bytes: usize,
+        align: usize,
+    ) -> AllocResult {
+        if bytes == 0 {
+            return AllocResult::CapacityErr("Cannot alloc for 0 sized pointer".into());
+        }
+        self.alloc_layout(Some(budget), bytes, align)
+    }
+
+    unsafe fn alloc_budgeted<T>(&mut self, budget: Option<Budget>, capacity: usize) -> AllocResult {
         if capacity == 0 {
             return AllocResult::CapacityErr("Cannot alloc for 0 sized pointer".into());
         }
@@ -69,7 +128,22 @@ impl ArconAllocator {
             None => return AllocResult::CapacityErr("Capacity overflow".into()),
         };
 
-        if self.curr_alloc + required_bytes > self.limit {
+        self.alloc_layout(budget, required_bytes, align)
+    }
+
+    unsafe fn alloc_layout(
+        &mut self,
+        budget: Option<Budget>,
+        required_bytes: usize,
+        align: usize,
+    ) -> AllocResult {
+        if let Some(remaining) = budget.and_then(|b| self.budget_remaining(b)) {
+            if required_bytes > remaining {
+                return AllocResult::ArconOOM(remaining);
+            }
+        }
+
+        if required_bytes > self.bytes_remaining() {
             return AllocResult::ArconOOM(self.bytes_remaining());
         }
 
@@ -81,6 +155,9 @@ impl ArconAllocator {
         }
 
         self.curr_alloc += layout.size();
+        if let Some(usage) = budget.and_then(|b| self.budgets.get_mut(&b)) {
+            usage.used += layout.size();
+        }
 
         if self.alloc_counter == u64::max_value() {
             self.alloc_epoch += 1;
@@ -90,16 +167,75 @@ impl ArconAllocator {
         let id = self.alloc_counter;
         self.alloc_counter += 1;
         self.allocations
-            .insert((self.alloc_epoch, id), (mem, layout));
+            .insert((self.alloc_epoch, id), (mem, layout, budget));
 
         AllocResult::Alloc((self.alloc_epoch, id), mem)
     }
     /// Deallocate memory through the given AllocId
     pub unsafe fn dealloc(&mut self, id: AllocId) {
-        if let Some((ptr, layout)) = self.allocations.remove(&id) {
+        if let Some((ptr, layout, budget)) = self.allocations.remove(&id) {
             System.dealloc(ptr, layout);
             self.curr_alloc -= layout.size();
+            if let Some(usage) = budget.and_then(|b| self.budgets.get_mut(&b)) {
+                usage.used = usage.used.saturating_sub(layout.size());
+            }
+        }
+    }
+    /// Hands out a budget of `bytes` to `budget`, or resizes it if it already exists
+    ///
+    /// Fails if the budgets would add up to more than the memory limit.
+    pub fn set_budget(&mut self, budget: Budget, bytes: usize) -> ArconResult<()> {
+        let others: usize = self
+            .budgets
+            .iter()
+            .filter(|(b, _)| **b != budget)
+            .map(|(_, usage)| usage.limit)
+            .sum();
+        if others + bytes > self.limit {
+            return arcon_err!(
+                "Budget of {} bytes for {:?} exceeds the remaining {} bytes",
+                bytes,
+                budget,
+                self.limit - others
+            );
+        }
+        self.budgets.entry(budget).or_default().limit = bytes;
+        Ok(())
+    }
+    /// Returns how many bytes are left in `budget`, or None if no such budget has been handed out
+    pub fn budget_remaining(&self, budget: Budget) -> Option<usize> {
+        self.budgets
+            .get(&budget)
+            .map(|usage| usage.limit.saturating_sub(usage.used))
+    }
+    /// Reserves `bytes` of `budget` for memory that is managed outside of the allocator
+    ///
+    /// Returns false, without reserving anything, if the budget or the allocator lacks the space.
+    pub fn reserve(&mut self, budget: Budget, bytes: usize) -> bool {
+        let in_budget = self
+            .budget_remaining(budget)
+            .map_or(true, |remaining| bytes <= remaining);
+        if !in_budget || bytes > self.bytes_remaining() {
+            return false;
+        }
+        self.curr_reserved += bytes;
+        if let Some(usage) = self.budgets.get_mut(&budget) {
+            usage.used += bytes;
         }
+        true
+    }
+    /// Releases `bytes` that were reserved through [ArconAllocator::reserve]
+    pub fn release(&mut self, budget: Budget, bytes: usize) {
+        self.curr_reserved = self.curr_reserved.saturating_sub(bytes);
+        if let Some(usage) = self.budgets.get_mut(&budget) {
+            usage.used = usage.used.saturating_sub(bytes);
+        }
+    }
+    /// Returns whether the allocator is close to its memory limit
+    ///
+    /// Components should hold back on acquiring more memory while this is the case.
+    pub fn under_pressure(&self) -> bool {
+        (self.curr_alloc + self.curr_reserved) as f64 >= self.limit as f64 * PRESSURE_THRESHOLD
     }
     /// Returns amount of bytes that are currently allocated
     pub fn allocated_bytes(&self) -> usize {
@@ -118,7 +254,17 @@ impl ArconAllocator {
     }
     /// Returns how much bytes are available to allocate
     pub fn bytes_remaining(&self) -> usize {
-        self.limit - self.curr_alloc
+        self.limit - self.curr_alloc - self.curr_reserved
+    }
+    /// Returns a snapshot of the allocator's memory usage
+    pub fn metrics(&self) -> AllocatorMetrics {
+        AllocatorMetrics {
+            limit: self.limit,
+            allocated_bytes: self.curr_alloc,
+            reserved_bytes: self.curr_reserved,
+            bytes_remaining: self.bytes_remaining(),
+            total_allocations: self.total_allocations(),
+        }
     }
 }
 
@@ -179,4 +325,45 @@ mod tests {
         assert_eq!(a.total_allocations(), 2);
         assert_eq!(a.bytes_remaining(), total_bytes);
     }
+
+    #[test]
+    fn budget_test() {
+        let total_bytes = 1024;
+        let mut a = ArconAllocator::new(total_bytes);
+
+        a.set_budget(Budget::Channels, 512).unwrap();
+        assert!(a.set_budget(Budget::Network, 1024).is_err());
+        a.set_budget(Budget::Node(1), 256).unwrap();
+
+        let id = match unsafe { a.alloc_in::<u64>(Budget::Channels, 48) } {
+            AllocResult::Alloc(id, _) => id,
+            _ => panic!("not supposed to happen"),
+        };
+        assert_eq!(a.budget_remaining(Budget::Channels), Some(128));
+
+        // The budget is exhausted while the allocator still has room
+        match unsafe { a.alloc_in::<u64>(Budget::Channels, 32) } {
+            AllocResult::ArconOOM(remaining_bytes) => assert_eq!(remaining_bytes, 128),
+            _ => panic!("not supposed to happen"),
+        };
+
+        assert!(a.reserve(Budget::Node(1), 256));
+        assert!(!a.reserve(Budget::Node(1), 1));
+        assert!(!a.under_pressure());
+        assert!(a.reserve(Budget::State, 300));
+        assert!(a.under_pressure());
+
+        let metrics = a.metrics();
+        assert_eq!(metrics.allocated_bytes, 384);
+        assert_eq!(metrics.reserved_bytes, 556);
+        assert_eq!(metrics.bytes_remaining, 84);
+        assert_eq!(metrics.total_allocations, 1);
+
+        a.release(Budget::State, 300);
+        a.release(Budget::Node(1), 256);
+        unsafe { a.dealloc(id) };
+        assert_eq!(a.budget_remaining(Budget::Channels), Some(512));
+        assert_eq!(a.budget_remaining(Budget::Node(1)), Some(256));
+        assert_eq!(a.bytes_remaining(), total_bytes);
+    }
 }
@@ -1,14 +1,23 @@
 // Copyright (c) 2020, KTH Royal Institute of Technology.
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use crate::allocator::{AllocId, AllocResult, ArconAllocator};
+use crate::{
+    allocator::{AllocId, AllocResult, ArconAllocator, Budget},
+    metrics::memory::BufferPoolMetrics,
+};
 use arcon_error::*;
 use crossbeam_utils::CachePadded;
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc, Mutex,
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
+/// Default time in milliseconds that [BufferPool::get] waits for a buffer to be released
+pub const DEFAULT_POOL_WAIT_TIMEOUT_MS: u64 = 10_000;
+
 /// A reusable buffer allocated through [ArconAllocator]
 ///
 /// Assumes a single-writer, single-reader setup.
@@ -26,6 +35,8 @@ pub struct EventBuffer<T> {
     capacity: usize,
     /// Flag indicating whether the buffer is available or not
     free: CachePadded<AtomicBool>,
+    /// How many times the buffer has been released by a reader
+    releases: CachePadded<AtomicUsize>,
 }
 
 impl<T> EventBuffer<T> {
@@ -36,13 +47,15 @@ impl<T> EventBuffer<T> {
     ) -> ArconResult<EventBuffer<T>> {
         let mut a = allocator.lock().unwrap();
 
-        if let AllocResult::Alloc(id, ptr) = unsafe { a.alloc::<T>(capacity) } {
+        if let AllocResult::Alloc(id, ptr) = unsafe { a.alloc_in::<T>(Budget::Channels, capacity) }
+        {
             Ok(EventBuffer {
                 ptr: ptr as *mut T,
                 allocator: allocator.clone(),
                 id,
                 capacity,
                 free: AtomicBool::new(true).into(),
+                releases: AtomicUsize::new(0).into(),
             })
         } else {
             arcon_err!("EventBuffer Alloc err")
@@ -86,6 +99,7 @@ impl<T> EventBuffer<T> {
     /// Should only be called by the reader
     #[inline]
     pub fn release(&self) {
+        self.releases.fetch_add(1, Ordering::Relaxed);
         self.free.store(true, Ordering::Relaxed);
     }
 
@@ -158,6 +172,15 @@ impl<T> BufferWriter<T> {
         }
     }
 
+    /// Generate a handle that tracks the reader of the buffer in its current use
+    #[inline]
+    pub fn handle(&self) -> BufferHandle<T> {
+        BufferHandle {
+            releases: self.buffer.releases.load(Ordering::Relaxed),
+            buffer: self.buffer.clone(),
+        }
+    }
+
     /// Copy data from another BufferWriter
     pub fn copy_from_writer(&mut self, other: &BufferWriter<T>) {
         let other_ptr = other.as_ptr();
@@ -176,6 +199,27 @@ impl<T> BufferWriter<T> {
     }
 }
 
+/// A handle to an EventBuffer that has been handed to a reader
+///
+/// Unlike a [BufferReader], the handle does not release the buffer once dropped.
+#[derive(Debug, Clone)]
+pub struct BufferHandle<T> {
+    /// Reference to the EventBuffer
+    buffer: Arc<EventBuffer<T>>,
+    /// Releases of the buffer at the time the handle was generated
+    releases: usize,
+}
+
+impl<T> BufferHandle<T> {
+    /// Returns whether the reader has released the buffer
+    ///
+    /// Stays true even if the buffer has been reserved by a new writer since.
+    #[inline]
+    pub fn is_released(&self) -> bool {
+        self.buffer.releases.load(Ordering::Relaxed) != self.releases
+    }
+}
+
 /// An EventBuffer reader
 ///
 /// Once dropped, it will notify the [BufferPool]
@@ -303,6 +347,7 @@ pub struct PoolInfo {
     pub(crate) capacity: usize,
     pub(crate) limit: usize,
     pub(crate) allocator: Arc<Mutex<ArconAllocator>>,
+    pub(crate) wait_timeout: Duration,
 }
 
 impl PoolInfo {
@@ -317,21 +362,37 @@ impl PoolInfo {
             capacity,
             limit,
             allocator,
+            wait_timeout: Duration::from_millis(DEFAULT_POOL_WAIT_TIMEOUT_MS),
         }
     }
+
+    /// Sets how long the BufferPools wait for a buffer to be released
+    pub fn with_wait_timeout(mut self, wait_timeout: Duration) -> Self {
+        self.wait_timeout = wait_timeout;
+        self
+    }
 }
 
 /// A preallocated pool of EventBuffers
-#[allow(dead_code)]
+///
+/// If all buffers are in use, the pool grows up to its limit as long as
+/// the [ArconAllocator] is not under memory pressure. Otherwise, the writer
+/// is held back until a reader releases a buffer or the wait times out.
 pub struct BufferPool<T> {
     /// Reference to an ArconAllocator
     allocator: Arc<Mutex<ArconAllocator>>,
     /// Size per buffer
     buffer_size: usize,
+    /// Max amount of buffers the pool may grow to
+    limit: usize,
     /// Vec of buffers in the pool
     buffers: Vec<Arc<EventBuffer<T>>>,
     /// Index of which buffer is next in line.
     curr_buffer: usize,
+    /// Max time to wait for a buffer to be released
+    wait_timeout: Duration,
+    /// Metrics collected by the pool
+    metrics: BufferPoolMetrics,
 }
 impl<T> BufferPool<T> {
     /// Create a new BufferPool
@@ -349,14 +410,33 @@ impl<T> BufferPool<T> {
             buffers.push(Arc::new(buffer));
         }
 
+        let mut metrics = BufferPoolMetrics::new();
+        metrics.capacity.inc_n(capacity);
+        metrics.buffer_size.inc_n(buffer_size);
+
         Ok(BufferPool {
             allocator,
             buffer_size,
+            limit: capacity,
             buffers,
             curr_buffer: 0,
+            wait_timeout: Duration::from_millis(DEFAULT_POOL_WAIT_TIMEOUT_MS),
+            metrics,
         })
     }
 
+    /// Lets the pool grow up to `limit` buffers
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(self.buffers.len());
+        self
+    }
+
+    /// Sets how long [BufferPool::get] waits for a buffer to be released
+    pub fn with_wait_timeout(mut self, wait_timeout: Duration) -> Self {
+        self.wait_timeout = wait_timeout;
+        self
+    }
+
     /// Attempt to fetch a BufferWriter
     ///
     /// Returns None if it fails to find Writer for the current index
@@ -373,17 +453,68 @@ impl<T> BufferPool<T> {
         opt
     }
 
-    /// Busy waiting for a BufferWriter
+    /// Waits for a BufferWriter
     ///
-    /// Should be used carefully
+    /// Grows the pool if no buffer is free, and otherwise waits for a reader
+    /// to release one. Returns an error if none is released within the wait timeout.
     #[inline]
-    pub fn get(&mut self) -> BufferWriter<T> {
+    pub fn get(&mut self) -> ArconResult<BufferWriter<T>> {
+        if let Some(writer) = self.try_get_any() {
+            return Ok(writer);
+        }
+
+        // The pool is exhausted, wait for a reader to release a buffer
+        self.metrics.exhaustion_counter.inc();
+        let start = Instant::now();
         loop {
-            match self.try_get() {
-                None => {}
-                Some(v) => return v,
+            std::thread::yield_now();
+
+            if let Some(writer) = self.try_get_any() {
+                let waited = start.elapsed().as_micros() as usize;
+                self.metrics.wait_micros.inc_n(waited);
+                return Ok(writer);
+            }
+
+            if start.elapsed() >= self.wait_timeout {
+                self.metrics
+                    .wait_micros
+                    .inc_n(start.elapsed().as_micros() as usize);
+                return arcon_err!(
+                    "BufferPool exhausted, no buffer was released within {:?}",
+                    self.wait_timeout
+                );
+            }
+        }
+    }
+
+    /// Attempt to fetch a BufferWriter from any buffer in the pool, growing it if needed
+    #[inline]
+    fn try_get_any(&mut self) -> Option<BufferWriter<T>> {
+        for _ in 0..self.buffers.len() {
+            if let Some(writer) = self.try_get() {
+                return Some(writer);
             }
         }
+        self.try_grow()
+    }
+
+    /// Attempt to add a new buffer to the pool and fetch a BufferWriter for it
+    ///
+    /// Returns None if the pool is at its limit or the allocator is under pressure
+    fn try_grow(&mut self) -> Option<BufferWriter<T>> {
+        if self.buffers.len() >= self.limit || self.allocator.lock().unwrap().under_pressure() {
+            return None;
+        }
+
+        let buffer: EventBuffer<T> =
+            EventBuffer::new(self.buffer_size, self.allocator.clone()).ok()?;
+        let buffer = Arc::new(buffer);
+        buffer.try_reserve();
+        self.buffers.push(buffer.clone());
+        self.metrics.capacity.inc();
+
+        let capacity = buffer.capacity();
+        Some(BufferWriter::new(buffer, 0, capacity))
     }
 
     /// Bumps the buffer index
@@ -393,7 +524,7 @@ impl<T> BufferPool<T> {
     #[inline]
     fn index_incr(&mut self) {
         self.curr_buffer += 1;
-        if self.curr_buffer == self.buffers.capacity() {
+        if self.curr_buffer >= self.buffers.len() {
             // Reset
             self.curr_buffer = 0;
         }
@@ -403,7 +534,7 @@ impl<T> BufferPool<T> {
     #[inline]
     #[allow(dead_code)]
     pub fn capacity(&self) -> usize {
-        self.buffers.capacity()
+        self.buffers.len()
     }
 
     /// Returns the size each buffer holds
@@ -412,6 +543,12 @@ impl<T> BufferPool<T> {
     pub fn buffer_size(&self) -> usize {
         self.buffer_size
     }
+
+    /// Returns the metrics collected by the pool
+    #[inline]
+    pub fn metrics(&self) -> &BufferPoolMetrics {
+        &self.metrics
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -484,4 +621,68 @@ mod tests {
 
         assert_eq!(pool.try_get().is_some(), true);
     }
+
+    #[test]
+    fn buffer_pool_grow_test() {
+        let total_bytes = 10000;
+        let allocator = Arc::new(Mutex::new(ArconAllocator::new(total_bytes)));
+        let buffer_size = 100;
+        let mut pool: BufferPool<u64> = BufferPool::new(1, buffer_size, allocator.clone())
+            .unwrap()
+            .with_limit(3);
+
+        let reader_one = pool.get().unwrap().reader();
+        // The only buffer is in use, so the pool grows
+        let reader_two = pool.get().unwrap().reader();
+        assert_eq!(pool.capacity(), 2);
+
+        // Under memory pressure, the pool waits for a buffer instead
+        assert!(allocator
+            .lock()
+            .unwrap()
+            .reserve(crate::allocator::Budget::State, 8000));
+        assert!(pool.try_grow().is_none());
+        assert_eq!(pool.capacity(), 2);
+
+        assert_eq!(pool.metrics().capacity.get(), 2);
+        assert_eq!(pool.metrics().exhaustion_counter.get(), 0);
+
+        // Both buffers are in use, so the pool waits until one is released
+        let releaser = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            drop(reader_one);
+        });
+        let _writer = pool.get().unwrap();
+        releaser.join().unwrap();
+        assert_eq!(pool.capacity(), 2);
+        assert_eq!(pool.metrics().exhaustion_counter.get(), 1);
+        assert!(pool.metrics().wait_micros.get() >= 10_000);
+        drop(reader_two);
+    }
+
+    #[test]
+    fn buffer_pool_exhausted_test() {
+        let total_bytes = 10000;
+        let allocator = Arc::new(Mutex::new(ArconAllocator::new(total_bytes)));
+        let mut pool: BufferPool<u64> = BufferPool::new(1, 100, allocator.clone())
+            .unwrap()
+            .with_limit(2)
+            .with_wait_timeout(Duration::from_millis(10));
+
+        // The allocator is under memory pressure, so the pool cannot grow
+        assert!(allocator
+            .lock()
+            .unwrap()
+            .reserve(crate::allocator::Budget::State, 9000));
+        let reader = pool.get().unwrap().reader();
+
+        // No buffer is released, so the pool gives up instead of waiting forever
+        assert!(pool.get().is_err());
+        assert_eq!(pool.capacity(), 1);
+        assert_eq!(pool.metrics().exhaustion_counter.get(), 1);
+        assert!(pool.metrics().wait_micros.get() >= 10_000);
+
+        drop(reader);
+        assert!(pool.get().is_ok());
+    }
 }
@@ -1,62 +1,144 @@
 // Copyright (c) 2020, KTH Royal Institute of Technology.
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use crate::allocator::{AllocId, AllocResult, ArconAllocator};
+use crate::allocator::{AllocId, AllocResult, ArconAllocator, Budget};
 use arcon_error::*;
 use kompact::prelude::Chunk;
-use std::sync::{Arc, Mutex};
+use std::{
+    alloc::Layout,
+    cell::RefCell,
+    sync::{Arc, Mutex},
+};
 
-/// A Buffer backed by the [ArconAllocator]
+thread_local! {
+    /// Allocator backing the NetworkBuffers of the deserialisers running on this thread
+    static RECEIVE_ALLOCATOR: RefCell<Option<Arc<Mutex<ArconAllocator>>>> = RefCell::new(None);
+}
+
+/// Runs `f` with `allocator` backing the NetworkBuffers that are fetched through [receive_buffer]
+///
+/// [kompact] deserialisers have no access to the receiving component, so this is how
+/// a Node hands its allocator to them.
+pub(crate) fn with_receive_allocator<R>(
+    allocator: &Arc<Mutex<ArconAllocator>>,
+    f: impl FnOnce() -> R,
+) -> R {
+    let prev = RECEIVE_ALLOCATOR.with(|a| a.replace(Some(allocator.clone())));
+    let res = f();
+    RECEIVE_ALLOCATOR.with(|a| a.replace(prev));
+    res
+}
+
+/// Fetches a NetworkBuffer aligned for `T`
+///
+/// The buffer is taken from the allocator set by [with_receive_allocator]
+/// and from the heap if there is none.
+pub(crate) fn receive_buffer<T>(capacity: usize) -> ArconResult<NetworkBuffer> {
+    RECEIVE_ALLOCATOR.with(|a| match &*a.borrow() {
+        Some(allocator) => NetworkBuffer::aligned::<T>(capacity, allocator.clone()),
+        None => NetworkBuffer::heap::<T>(capacity),
+    })
+}
+
+/// A Buffer backed by the [ArconAllocator] or the heap
 ///
 /// Is intended to be used by Kompact's network implementation
 pub(crate) struct NetworkBuffer {
     /// A raw pointer to our allocated memory block
     ptr: *mut u8,
-    /// Reference to the allocator
+    /// Reference to the allocator and the unique identifier of the allocation
     ///
-    /// Used to dealloc `ptr` when the NetworkBuffer is dropped
-    allocator: Arc<Mutex<ArconAllocator>>,
-    /// A unique identifier for the allocation
-    id: AllocId,
-    /// How many data elements there are in `ptr`
-    capacity: usize,
+    /// Used to dealloc `ptr` when the NetworkBuffer is dropped.
+    /// Buffers without one are returned to the heap.
+    allocation: Option<(Arc<Mutex<ArconAllocator>>, AllocId)>,
+    /// Layout of the bytes in `ptr`
+    layout: Layout,
 }
 
 impl NetworkBuffer {
     /// Creates a new NetworkBuffer
     #[inline]
-    #[allow(dead_code)]
     pub fn new(
         capacity: usize,
         allocator: Arc<Mutex<ArconAllocator>>,
     ) -> ArconResult<NetworkBuffer> {
+        Self::aligned::<u8>(capacity, allocator)
+    }
+
+    /// Creates a new NetworkBuffer of `capacity` bytes that is aligned for values of type `T`
+    ///
+    /// Buffers that values are decoded into must be aligned for them,
+    /// as the decoded values are read straight from the buffer.
+    #[inline]
+    pub fn aligned<T>(
+        capacity: usize,
+        allocator: Arc<Mutex<ArconAllocator>>,
+    ) -> ArconResult<NetworkBuffer> {
+        let layout = Self::layout::<T>(capacity)?;
         let mut a = allocator.lock().unwrap();
 
-        if let AllocResult::Alloc(id, ptr) = unsafe { a.alloc::<u8>(capacity) } {
+        if let AllocResult::Alloc(id, ptr) =
+            unsafe { a.alloc_bytes_in(Budget::Network, layout.size(), layout.align()) }
+        {
             Ok(NetworkBuffer {
                 ptr,
-                allocator: allocator.clone(),
-                id,
-                capacity,
+                allocation: Some((allocator.clone(), id)),
+                layout,
             })
         } else {
             arcon_err!("NetworkBuffer Alloc err")
         }
     }
 
+    /// Creates a new NetworkBuffer of `capacity` bytes on the heap that is aligned for values of type `T`
+    ///
+    /// Is used by Nodes that do not manage their memory through an [ArconAllocator].
+    #[inline]
+    pub fn heap<T>(capacity: usize) -> ArconResult<NetworkBuffer> {
+        let layout = Self::layout::<T>(capacity)?;
+        let ptr = unsafe { std::alloc::alloc(layout) };
+        if ptr.is_null() {
+            return arcon_err!("NetworkBuffer Alloc err");
+        }
+        Ok(NetworkBuffer {
+            ptr,
+            allocation: None,
+            layout,
+        })
+    }
+
+    fn layout<T>(capacity: usize) -> ArconResult<Layout> {
+        if capacity == 0 {
+            return arcon_err!("Cannot alloc for 0 sized pointer");
+        }
+        Layout::from_size_align(capacity, std::mem::align_of::<T>())
+            .map_err(|e| arcon_err_kind!("Invalid NetworkBuffer layout {}", e))
+    }
+
     /// Returns the capacity of the buffer
     #[inline]
     #[allow(dead_code)]
     pub fn capacity(&self) -> usize {
-        self.capacity
+        self.layout.size()
+    }
+
+    /// Returns the buffer as a mutable slice of bytes
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.layout.size()) }
     }
 }
 
 impl Drop for NetworkBuffer {
     fn drop(&mut self) {
-        let mut allocator = self.allocator.lock().unwrap();
-        // Instruct the allocator to dealloc
-        unsafe { allocator.dealloc(self.id) };
+        match &self.allocation {
+            Some((allocator, id)) => {
+                let mut allocator = allocator.lock().unwrap();
+                // Instruct the allocator to dealloc
+                unsafe { allocator.dealloc(*id) };
+            }
+            None => unsafe { std::alloc::dealloc(self.ptr, self.layout) },
+        }
     }
 }
 
@@ -67,7 +149,109 @@ impl Chunk for NetworkBuffer {
         self.ptr
     }
     fn len(&self) -> usize {
-        self.capacity
+        self.layout.size()
+    }
+}
+
+/// A read-only view of a batch of events that were decoded in a [NetworkBuffer]
+///
+/// Like a [BufferReader](crate::buffer::event::BufferReader), the reader moves the events
+/// out of the buffer as it is iterated over, given that they are self-contained.
+/// Events that hold data elsewhere in the buffer, e.g., a non-empty Vec, keep pointing
+/// into it and are cloned instead. The buffer is released once the reader is dropped.
+pub(crate) struct NetworkReader<T> {
+    /// Buffer holding the decoded events
+    buffer: Arc<NetworkBuffer>,
+    /// Pointer to the first event in `buffer`
+    ptr: *const T,
+    /// Total events in the buffer
+    len: usize,
+    /// Whether the events own all of their data and can be moved out of `buffer`
+    self_contained: bool,
+}
+
+impl<T> NetworkReader<T> {
+    /// Creates a reader over `len` events starting at `ptr`
+    ///
+    /// # Safety
+    ///
+    /// The events must lie within `buffer` and stay valid for as long as it lives.
+    /// If `self_contained` is set, the events must not point into `buffer` or own
+    /// any allocation, so that moving them out does not leave a dangling pointer.
+    #[inline]
+    pub unsafe fn new(
+        buffer: Arc<NetworkBuffer>,
+        ptr: *const T,
+        len: usize,
+        self_contained: bool,
+    ) -> NetworkReader<T> {
+        NetworkReader {
+            buffer,
+            ptr,
+            len,
+            self_contained,
+        }
+    }
+
+    /// Return reader as slice
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    /// Length of buffer
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the events are moved out of the buffer rather than cloned
+    #[inline]
+    #[allow(dead_code)]
+    pub fn is_self_contained(&self) -> bool {
+        self.self_contained
+    }
+}
+
+/// Turn NetworkReader into an Iterator
+pub(crate) struct IntoIter<A> {
+    reader: NetworkReader<A>,
+    current: usize,
+}
+
+impl<A: Clone> Iterator for IntoIter<A> {
+    type Item = A;
+
+    #[inline]
+    fn next(&mut self) -> Option<A> {
+        if self.current == self.reader.len() {
+            return None;
+        }
+        let current = self.current;
+        self.current += 1;
+        if self.reader.self_contained {
+            // The buffer is released without dropping the events, so each is read once
+            Some(unsafe { std::ptr::read(self.reader.ptr.add(current)) })
+        } else {
+            Some(self.reader.as_slice()[current].clone())
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let size = self.reader.len() - self.current;
+        (size, Some(size))
+    }
+}
+
+impl<A: Clone> IntoIterator for NetworkReader<A> {
+    type IntoIter = IntoIter<A>;
+    type Item = A;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            reader: self,
+            current: 0,
+        }
     }
 }
 
@@ -89,4 +273,57 @@ mod tests {
         assert_eq!(a.total_allocations(), 1);
         assert_eq!(a.bytes_remaining(), total_bytes);
     }
+
+    #[test]
+    fn receive_buffer_test() {
+        let total_bytes = 1024;
+        let allocator = Arc::new(Mutex::new(ArconAllocator::new(total_bytes)));
+        {
+            let buffer = with_receive_allocator(&allocator, || receive_buffer::<u8>(512)).unwrap();
+            assert_eq!(buffer.capacity(), 512);
+            assert_eq!(
+                allocator.lock().unwrap().bytes_remaining(),
+                total_bytes - 512
+            );
+        }
+        assert_eq!(allocator.lock().unwrap().bytes_remaining(), total_bytes);
+        // Without an allocator, the buffer is taken from the heap
+        let buffer = receive_buffer::<u64>(512).unwrap();
+        assert_eq!(buffer.capacity(), 512);
+        assert_eq!(allocator.lock().unwrap().bytes_remaining(), total_bytes);
+    }
+
+    #[test]
+    fn aligned_network_buffer_test() {
+        #[repr(align(64))]
+        struct Aligned(u8);
+
+        let total_bytes = 1024;
+        let allocator = Arc::new(Mutex::new(ArconAllocator::new(total_bytes)));
+        let mut buffer = NetworkBuffer::aligned::<Aligned>(100, allocator.clone()).unwrap();
+        assert_eq!(buffer.capacity(), 100);
+        assert_eq!(
+            buffer.as_mut_ptr() as usize % std::mem::align_of::<Aligned>(),
+            0
+        );
+        assert_eq!(
+            allocator.lock().unwrap().bytes_remaining(),
+            total_bytes - 100
+        );
+    }
+
+    #[test]
+    fn network_reader_test() {
+        let items: Vec<u64> = vec![1, 2, 3, 4];
+        let bytes = items.len() * std::mem::size_of::<u64>();
+        for &self_contained in &[true, false] {
+            let mut buffer = NetworkBuffer::heap::<u64>(bytes).unwrap();
+            let ptr = buffer.as_mut_ptr() as *mut u64;
+            unsafe { std::ptr::copy_nonoverlapping(items.as_ptr(), ptr, items.len()) };
+            let reader =
+                unsafe { NetworkReader::new(Arc::new(buffer), ptr, items.len(), self_contained) };
+            assert_eq!(reader.as_slice(), &items[..]);
+            assert_eq!(reader.into_iter().collect::<Vec<u64>>(), items);
+        }
+    }
 }
@@ -5,7 +5,10 @@ use arcon_error::*;
 use hocon::HoconLoader;
 use kompact::prelude::{DeadletterBox, KompactConfig, NetworkConfig};
 use serde::Deserialize;
-use std::path::{Path, PathBuf};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
 
 /// Configuration for an Arcon Pipeline
 #[derive(Deserialize, Clone, Debug)]
@@ -28,9 +31,31 @@ pub struct ArconConf {
     /// A limit for amount of buffers in a BufferPool
     #[serde(default = "buffer_pool_limit_default")]
     pub buffer_pool_limit: usize,
+    /// Max time in milliseconds a channel waits for a buffer of its BufferPool to be released
+    ///
+    /// The Node fails if no buffer is released in time.
+    #[serde(default = "buffer_pool_wait_timeout_default")]
+    pub buffer_pool_wait_timeout: u64,
     /// Batch size for channels
     #[serde(default = "channel_batch_size_default")]
     pub channel_batch_size: usize,
+    /// Interval in milliseconds for flushing partially filled channel buffers at nodes
+    ///
+    /// Bounds the time an element may wait in a buffer at low input rates. Defaults to 0,
+    /// which only flushes buffers once they are full or a watermark or epoch is sent.
+    ///
+    /// The interval is opt-in since it schedules a timer on every Node and changes when
+    /// events leave it. Buffers are then sent between markers, e.g., the events in flight
+    /// on a feedback channel when an epoch is taken, instead of along with the next
+    /// watermark or epoch.
+    #[serde(default = "channel_flush_interval_default")]
+    pub channel_flush_interval: u64,
+    /// Flush channel buffers after a batch of events sized by the observed throughput
+    ///
+    /// The batch size is derived from the events a node receives per `channel_flush_interval`,
+    /// so this only takes effect if that interval is set.
+    #[serde(default = "channel_adaptive_batching_default")]
+    pub channel_adaptive_batching: bool,
     /// Max amount of bytes allowed to be allocated by the ArconAllocator
     #[serde(default = "allocator_capacity_default")]
     pub allocator_capacity: usize,
@@ -51,6 +76,58 @@ pub struct ArconConf {
     /// It is set as optional as it is not necessary for local deployments
     #[serde(default = "kompact_network_host_default")]
     pub kompact_network_host: Option<String>,
+    /// Path to the PEM encoded certificate chain that the pipeline presents to its peers
+    ///
+    /// Setting it along with `tls_key` and `tls_ca` enables [Channel::Secure] channels, which
+    /// send with TLS and mutual authentication, see [tls](crate::stream::channel::tls).
+    /// Nodes of the pipeline then refuse remote messages that arrive over Kompact's
+    /// plain network, which is still used for everything else.
+    ///
+    /// [Channel::Secure]: crate::stream::channel::Channel::Secure
+    #[serde(default = "tls_cert_default")]
+    pub tls_cert: Option<PathBuf>,
+    /// Path to the PEM encoded private key of `tls_cert`
+    #[serde(default = "tls_key_default")]
+    pub tls_key: Option<PathBuf>,
+    /// Path to the PEM encoded CA certificates that the certificates of peers must be signed by
+    #[serde(default = "tls_ca_default")]
+    pub tls_ca: Option<PathBuf>,
+    /// Address that the TlsListener of the pipeline binds to
+    ///
+    /// The listener is started by [ArconPipeline::start_tls_listener] and only
+    /// needed by pipelines that receive over TLS.
+    ///
+    /// [ArconPipeline::start_tls_listener]: crate::pipeline::ArconPipeline::start_tls_listener
+    #[serde(default = "tls_host_default")]
+    pub tls_host: Option<String>,
+    /// The sender NodeIDs that each peer of the TlsListener may send messages as
+    ///
+    /// A peer is bound to the NodeIDs of every entry whose name its certificate is valid for.
+    /// Connections of peers that are bound to no NodeID are closed after the handshake.
+    #[serde(default = "tls_peers_default")]
+    pub tls_peers: Vec<TlsPeer>,
+    /// Max amount of connections that the TlsListener serves at once
+    ///
+    /// Further connections are closed until one of the served connections ends.
+    #[serde(default = "tls_max_connections_default")]
+    pub tls_max_connections: usize,
+    /// Max time in milliseconds that a peer of the TlsListener may take to complete its handshake
+    #[serde(default = "tls_handshake_timeout_default")]
+    pub tls_handshake_timeout: u64,
+    /// Amount of messages that a TLS channel queues for its sending thread
+    ///
+    /// The Node fails if the queue is full, i.e., if the peer does not keep up.
+    #[serde(default = "tls_send_queue_size_default")]
+    pub tls_send_queue_size: usize,
+}
+
+/// Binds the sender NodeIDs that a TLS peer may use to the name in its certificate
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct TlsPeer {
+    /// DNS name that the certificate of the peer must be valid for
+    pub name: String,
+    /// NodeIDs that the peer may send messages as
+    pub senders: Vec<u32>,
 }
 
 impl ArconConf {
@@ -59,8 +136,14 @@ impl ArconConf {
         let mut cfg = KompactConfig::default();
         // inject checkpoint_dir into Kompact
         let component_cfg = format!(
-            "{{ checkpoint_dir = {:?}, node_metrics_interval = {} }}",
-            self.checkpoint_dir, self.node_metrics_interval
+            "{{ checkpoint_dir = {:?}, node_metrics_interval = {}, channel_batch_size = {}, \
+             channel_flush_interval = {}, channel_adaptive_batching = {}, tls_enabled = {} }}",
+            self.checkpoint_dir,
+            self.node_metrics_interval,
+            self.channel_batch_size,
+            self.channel_flush_interval,
+            self.channel_adaptive_batching,
+            self.tls_enabled()
         );
         cfg.load_config_str(component_cfg);
         cfg.threads(self.kompact_threads);
@@ -76,6 +159,46 @@ impl ArconConf {
         cfg
     }
 
+    /// Returns whether remote channels are secured with TLS
+    ///
+    /// This is the case as soon as any of the TLS paths is set, so that a partial
+    /// configuration fails to validate rather than falling back to plain networking.
+    pub fn tls_enabled(&self) -> bool {
+        self.tls_cert.is_some() || self.tls_key.is_some() || self.tls_ca.is_some()
+    }
+
+    /// Checks that the network and TLS settings are complete and well-formed
+    pub fn validate(&self) -> ArconResult<()> {
+        if let Some(host) = &self.kompact_network_host {
+            if host.parse::<SocketAddr>().is_err() {
+                return arcon_err!("Invalid kompact_network_host {}", host);
+            }
+        }
+        if !self.tls_enabled() {
+            if self.tls_host.is_some() || !self.tls_peers.is_empty() {
+                return arcon_err!("tls_host and tls_peers require TLS to be enabled");
+            }
+            return Ok(());
+        }
+        if self.tls_cert.is_none() || self.tls_key.is_none() || self.tls_ca.is_none() {
+            return arcon_err!("TLS requires tls_cert, tls_key and tls_ca to be set");
+        }
+        if let Some(host) = &self.tls_host {
+            if host.parse::<SocketAddr>().is_err() {
+                return arcon_err!("Invalid tls_host {}", host);
+            }
+        }
+        for peer in &self.tls_peers {
+            if webpki::DNSNameRef::try_from_ascii_str(&peer.name).is_err() {
+                return arcon_err!("Invalid DNS name {} in tls_peers", peer.name);
+            }
+        }
+        if self.tls_max_connections == 0 || self.tls_send_queue_size == 0 {
+            return arcon_err!("tls_max_connections and tls_send_queue_size must be positive");
+        }
+        Ok(())
+    }
+
     /// Returns the default Arcon Configuration
     pub fn default() -> ArconConf {
         ArconConf {
@@ -86,12 +209,23 @@ impl ArconConf {
             buffer_pool_size: buffer_pool_size_default(),
             network_buffer_size: network_buffer_size_default(),
             buffer_pool_limit: buffer_pool_limit_default(),
+            buffer_pool_wait_timeout: buffer_pool_wait_timeout_default(),
             channel_batch_size: channel_batch_size_default(),
+            channel_flush_interval: channel_flush_interval_default(),
+            channel_adaptive_batching: channel_adaptive_batching_default(),
             allocator_capacity: allocator_capacity_default(),
             kompact_threads: kompact_threads_default(),
             kompact_throughput: kompact_throughput_default(),
             kompact_msg_priority: kompact_msg_priority_default(),
             kompact_network_host: kompact_network_host_default(),
+            tls_cert: tls_cert_default(),
+            tls_key: tls_key_default(),
+            tls_ca: tls_ca_default(),
+            tls_host: tls_host_default(),
+            tls_peers: tls_peers_default(),
+            tls_max_connections: tls_max_connections_default(),
+            tls_handshake_timeout: tls_handshake_timeout_default(),
+            tls_send_queue_size: tls_send_queue_size_default(),
         }
     }
 
@@ -104,9 +238,10 @@ impl ArconConf {
             .load_str(&data)
             .map_err(|e| arcon_err_kind!("Failed to load Hocon Loader with err {}", e))?;
 
-        let conf = loader
+        let conf: ArconConf = loader
             .resolve()
             .map_err(|e| arcon_err_kind!("Failed to resolve ArconConf with err {}", e))?;
+        conf.validate()?;
         Ok(conf)
     }
 }
@@ -143,10 +278,24 @@ fn buffer_pool_limit_default() -> usize {
     buffer_pool_size_default() * 2
 }
 
+fn buffer_pool_wait_timeout_default() -> u64 {
+    // in milliseconds
+    crate::buffer::event::DEFAULT_POOL_WAIT_TIMEOUT_MS
+}
+
 fn channel_batch_size_default() -> usize {
     248
 }
 
+fn channel_flush_interval_default() -> u64 {
+    // in milliseconds, 0 disables the interval
+    0
+}
+
+fn channel_adaptive_batching_default() -> bool {
+    false
+}
+
 fn network_buffer_size_default() -> usize {
     64000
 }
@@ -172,6 +321,39 @@ fn kompact_network_host_default() -> Option<String> {
     None
 }
 
+fn tls_cert_default() -> Option<PathBuf> {
+    None
+}
+
+fn tls_key_default() -> Option<PathBuf> {
+    None
+}
+
+fn tls_ca_default() -> Option<PathBuf> {
+    None
+}
+
+fn tls_host_default() -> Option<String> {
+    None
+}
+
+fn tls_peers_default() -> Vec<TlsPeer> {
+    Vec::new()
+}
+
+fn tls_max_connections_default() -> usize {
+    64
+}
+
+fn tls_handshake_timeout_default() -> u64 {
+    // in milliseconds
+    10000
+}
+
+fn tls_send_queue_size_default() -> usize {
+    1024
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -196,12 +378,69 @@ mod tests {
         assert_eq!(conf.state_dir, state_dir_default());
         assert_eq!(conf.node_metrics_interval, node_metrics_interval_default());
         assert_eq!(conf.channel_batch_size, channel_batch_size_default());
+        assert_eq!(
+            conf.channel_flush_interval,
+            channel_flush_interval_default()
+        );
+        assert_eq!(
+            conf.channel_adaptive_batching,
+            channel_adaptive_batching_default()
+        );
         assert_eq!(conf.buffer_pool_size, buffer_pool_size_default());
+        assert_eq!(
+            conf.buffer_pool_wait_timeout,
+            buffer_pool_wait_timeout_default()
+        );
         assert_eq!(conf.network_buffer_size, network_buffer_size_default());
         assert_eq!(conf.allocator_capacity, allocator_capacity_default());
         assert_eq!(conf.kompact_threads, kompact_threads_default());
         assert_eq!(conf.kompact_throughput, kompact_throughput_default());
         assert_eq!(conf.kompact_msg_priority, kompact_msg_priority_default());
         assert_eq!(conf.kompact_network_host, kompact_network_host_default());
+        assert_eq!(conf.tls_cert, tls_cert_default());
+        assert_eq!(conf.tls_key, tls_key_default());
+        assert_eq!(conf.tls_ca, tls_ca_default());
+        assert_eq!(conf.tls_host, tls_host_default());
+        assert_eq!(conf.tls_peers, tls_peers_default());
+        assert_eq!(conf.tls_max_connections, tls_max_connections_default());
+        assert_eq!(conf.tls_handshake_timeout, tls_handshake_timeout_default());
+        assert_eq!(conf.tls_send_queue_size, tls_send_queue_size_default());
+        assert!(!conf.tls_enabled());
+    }
+
+    #[test]
+    fn tls_conf_test() {
+        let mut file = NamedTempFile::new().unwrap();
+        let file_path = file.path().to_string_lossy().into_owned();
+        let config_str = r#"{
+            tls_cert: /etc/arcon/node.pem,
+            tls_key: /etc/arcon/node.key,
+            tls_ca: /etc/arcon/ca.pem,
+            tls_host: "127.0.0.1:3000",
+            tls_peers: [{ name: "node1.arcon.local", senders: [1, 2] }]
+        }"#;
+        file.write_all(config_str.as_bytes()).unwrap();
+
+        let conf: ArconConf = ArconConf::from_file(&file_path).unwrap();
+        assert!(conf.tls_enabled());
+        assert_eq!(conf.tls_cert, Some(PathBuf::from("/etc/arcon/node.pem")));
+        assert_eq!(conf.tls_host, Some(String::from("127.0.0.1:3000")));
+        assert_eq!(conf.tls_peers, vec![TlsPeer {
+            name: String::from("node1.arcon.local"),
+            senders: vec![1, 2],
+        }]);
+    }
+
+    #[test]
+    fn invalid_tls_conf_test() {
+        let load = |config_str: &str| {
+            let mut file = NamedTempFile::new().unwrap();
+            file.write_all(config_str.as_bytes()).unwrap();
+            ArconConf::from_file(file.path())
+        };
+        // A partial TLS configuration does not fall back to plain networking
+        assert!(load(r#"{tls_cert: /etc/arcon/node.pem, tls_key: /etc/arcon/node.key}"#).is_err());
+        assert!(load(r#"{tls_host: "127.0.0.1:3000"}"#).is_err());
+        assert!(load(r#"{kompact_network_host: "localhost"}"#).is_err());
     }
 }
@@ -1,7 +1,17 @@
 // Copyright (c) 2020, KTH Royal Institute of Technology.
 // SPDX-License-Identifier: AGPL-3.0-only
 
+use crate::data::{ser_id, ArconType, RawArconMessage};
+use kompact::prelude::{SerError, SerId, Serialisable};
+use std::io::Read;
+
 /// An Enum holding possible serialisation/deserialisation options for in-flight data
+///
+/// A schema-based zero-copy option, e.g., on top of FlatBuffers or Cap'n Proto, is out of scope.
+/// It would need a schema and generated accessors per ArconType, and operators take owned
+/// elements, so the accessors would have to be decoded into ArconTypes anyway. Unsafe is the
+/// closest to zero-copy: self-contained events are moved out of the receive buffer, while
+/// events with data behind a pointer, e.g., a Vec or String, are still cloned.
 #[derive(Clone)]
 pub enum FlightSerde {
     /// Unsafe, but highly performant option
@@ -14,6 +24,11 @@ pub enum FlightSerde {
     ///
     /// Reliable is the default serde option.
     Reliable,
+    /// An option using the serde implementations of ArconTypes with bincode
+    ///
+    /// Bincode is compact and fast, but it relies on both ends having the same field order.
+    #[cfg(feature = "arcon_serde")]
+    Bincode,
 }
 
 impl Default for FlightSerde {
@@ -22,6 +37,165 @@ impl Default for FlightSerde {
     }
 }
 
+impl FlightSerde {
+    /// Serialises `msg` into bytes and returns them along with the ser id they were written with
+    pub(crate) fn serialise<A: ArconType>(
+        &self,
+        msg: RawArconMessage<A>,
+    ) -> Result<(SerId, Vec<u8>), SerError> {
+        let serialisable: Box<dyn Serialisable> = match self {
+            FlightSerde::Unsafe => Box::new(unsafe_remote::UnsafeSerde(msg)),
+            FlightSerde::Reliable => Box::new(reliable_remote::ReliableSerde(msg)),
+            #[cfg(feature = "arcon_serde")]
+            FlightSerde::Bincode => Box::new(bincode_remote::BincodeSerde(msg)),
+        };
+        let mut bytes: Vec<u8> = Vec::with_capacity(serialisable.size_hint().unwrap_or(0));
+        serialisable.serialise(&mut bytes)?;
+        Ok((serialisable.ser_id(), bytes))
+    }
+}
+
+/// Compression codecs that may be applied on top of a [FlightSerde]
+///
+/// Each codec has its own ser id, which lets the receiver detect how a message was compressed.
+#[derive(Clone, Copy, Debug)]
+pub enum Compression {
+    /// Fast compression with a moderate ratio
+    Lz4,
+    /// Slower compression with a better ratio, for bandwidth constrained links
+    Zstd,
+}
+
+impl Compression {
+    /// Returns the ser id of messages compressed with this codec
+    pub fn ser_id(&self) -> SerId {
+        match self {
+            Compression::Lz4 => ser_id::LZ4_FLIGHT_ID,
+            Compression::Zstd => ser_id::ZSTD_FLIGHT_ID,
+        }
+    }
+
+    /// Compresses `bytes` with this codec
+    pub fn compress(&self, bytes: &[u8]) -> Result<Vec<u8>, SerError> {
+        match self {
+            Compression::Lz4 => Ok(lz4_compression::prelude::compress(bytes)),
+            // Level 0 picks the default zstd level
+            Compression::Zstd => zstd::stream::encode_all(bytes, 0)
+                .map_err(|e| SerError::InvalidData(format!("Failed to compress with zstd {}", e))),
+        }
+    }
+
+    /// Decompresses `bytes` that were compressed with this codec
+    ///
+    /// Fails if the data decompresses to more than [MAX_DECOMPRESSED_BYTES].
+    pub fn decompress(&self, bytes: &[u8]) -> Result<Vec<u8>, SerError> {
+        self.decompress_with_limit(bytes, MAX_DECOMPRESSED_BYTES)
+    }
+
+    fn decompress_with_limit(&self, bytes: &[u8], limit: usize) -> Result<Vec<u8>, SerError> {
+        match self {
+            Compression::Lz4 => lz4_decompress(bytes, limit),
+            Compression::Zstd => {
+                let decoder = zstd::stream::read::Decoder::new(bytes).map_err(|e| {
+                    SerError::InvalidData(format!("Failed to decompress with zstd {}", e))
+                })?;
+                let mut output = Vec::new();
+                // Read one byte past the limit to tell if the data exceeds it
+                decoder
+                    .take(limit as u64 + 1)
+                    .read_to_end(&mut output)
+                    .map_err(|e| {
+                        SerError::InvalidData(format!("Failed to decompress with zstd {}", e))
+                    })?;
+                if output.len() > limit {
+                    return Err(exceeds_limit(limit));
+                }
+                Ok(output)
+            }
+        }
+    }
+}
+
+/// Upper bound on the size of a decompressed message, guards against compression bombs
+pub const MAX_DECOMPRESSED_BYTES: usize = 1 << 28;
+
+fn exceeds_limit(limit: usize) -> SerError {
+    SerError::InvalidData(format!(
+        "Decompressed data exceeds the limit of {} bytes",
+        limit
+    ))
+}
+
+// Decodes an LZ4 block, which unlike `lz4_compression::prelude::decompress`
+// stops as soon as the output would exceed `limit` bytes
+fn lz4_decompress(input: &[u8], limit: usize) -> Result<Vec<u8>, SerError> {
+    let corrupt = || SerError::InvalidData(String::from("Failed to decompress with lz4"));
+
+    // Adds the extension bytes that follow a length of 15 in a token
+    fn length(input: &[u8], pos: &mut usize, mut len: usize) -> Option<usize> {
+        if len == 15 {
+            loop {
+                let byte = *input.get(*pos)?;
+                *pos += 1;
+                len = len.checked_add(byte as usize)?;
+                if byte != 255 {
+                    break;
+                }
+            }
+        }
+        Some(len)
+    }
+
+    let mut output = Vec::new();
+    let mut pos = 0;
+    while pos < input.len() {
+        let token = input[pos];
+        pos += 1;
+
+        let literals = length(input, &mut pos, (token >> 4) as usize).ok_or_else(corrupt)?;
+        let end = pos
+            .checked_add(literals)
+            .filter(|end| *end <= input.len())
+            .ok_or_else(corrupt)?;
+        if output.len() + literals > limit {
+            return Err(exceeds_limit(limit));
+        }
+        output.extend_from_slice(&input[pos..end]);
+        pos = end;
+
+        // The last sequence of a block only holds literals
+        if pos == input.len() {
+            break;
+        }
+
+        let offset = match input.get(pos..pos + 2) {
+            Some(offset) => u16::from_le_bytes([offset[0], offset[1]]) as usize,
+            None => return Err(corrupt()),
+        };
+        pos += 2;
+        if offset == 0 || offset > output.len() {
+            return Err(corrupt());
+        }
+
+        let match_len = length(input, &mut pos, (token & 0xf) as usize)
+            .and_then(|len| len.checked_add(4))
+            .ok_or_else(corrupt)?;
+        if output
+            .len()
+            .checked_add(match_len)
+            .map_or(true, |len| len > limit)
+        {
+            return Err(exceeds_limit(limit));
+        }
+        // The match may overlap the bytes it produces, so copy them one at a time
+        for _ in 0..match_len {
+            let byte = output[output.len() - offset];
+            output.push(byte);
+        }
+    }
+    Ok(output)
+}
+
 /// Module containing the [kompact] serialiser/deserialiser implementation for [FlightSerde::Reliable]
 pub mod reliable_remote {
     use crate::data::{ArconType, RawArconMessage};
@@ -61,8 +235,15 @@ pub mod reliable_remote {
 
 /// Module containing the [kompact] serialiser/deserialiser implementation for [FlightSerde::Unsafe]
 pub mod unsafe_remote {
-    use crate::data::{ArconType, BufMutWriter, RawArconMessage, VersionId};
+    use crate::{
+        buffer::network::{receive_buffer, NetworkReader},
+        data::{
+            ArconEventWrapper, ArconType, BufMutWriter, NetworkArconMessage, RawArconMessage,
+            VersionId,
+        },
+    };
     use kompact::prelude::*;
+    use std::{marker::PhantomData, sync::Arc};
 
     #[derive(Clone, Debug)]
     pub struct UnsafeSerde<A: ArconType>(pub RawArconMessage<A>);
@@ -98,6 +279,66 @@ pub mod unsafe_remote {
         }
     }
 
+    /// Deserialiser that decodes [FlightSerde::Unsafe] messages in a NetworkBuffer
+    ///
+    /// The received bytes are copied once into a NetworkBuffer aligned for the events, which
+    /// is fetched from the allocator set through [with_receive_allocator](crate::buffer::network::with_receive_allocator)
+    /// or from the heap. The batch is then handed out as a [NetworkReader] that moves
+    /// self-contained events out of the buffer, just like a local message is read.
+    pub(crate) struct UnsafeNetworkSerde<A: ArconType>(PhantomData<A>);
+
+    impl<A: ArconType> Deserialiser<NetworkArconMessage<A>> for UnsafeNetworkSerde<A> {
+        const SER_ID: SerId = A::UNSAFE_SER_ID;
+
+        fn deserialise(buf: &mut dyn Buf) -> Result<NetworkArconMessage<A>, SerError> {
+            let version_id = buf.get_u32();
+            if version_id != A::VERSION_ID {
+                let err = format!(
+                    "Mismatch on ArconType version. Got {} while expecting {}",
+                    version_id,
+                    A::VERSION_ID
+                );
+                return Err(SerError::InvalidData(err));
+            }
+
+            let bytes = buf.bytes();
+            let mut buffer = receive_buffer::<ArconEventWrapper<A>>(bytes.len())
+                .map_err(|e| SerError::Unknown(e.to_string()))?;
+            buffer.as_mut_slice().copy_from_slice(bytes);
+
+            let (events, len, self_contained, sender) =
+                match unsafe { abomonation::decode::<RawArconMessage<A>>(buffer.as_mut_slice()) } {
+                    Some((msg, _)) => {
+                        // Events that take up no bytes beyond their own size hold no pointers
+                        // into the buffer and may be moved out of it
+                        let self_contained = msg
+                            .events
+                            .iter()
+                            .all(|e| abomonation::measure(e) == std::mem::size_of_val(e));
+                        (
+                            msg.events.as_ptr(),
+                            msg.events.len(),
+                            self_contained,
+                            msg.sender,
+                        )
+                    }
+                    None => {
+                        return Err(SerError::InvalidData(
+                            "Failed to decode flight data".to_string(),
+                        ))
+                    }
+                };
+
+            // The decoded events live in the heap block of `buffer`, which does not move
+            Ok(NetworkArconMessage {
+                events: unsafe {
+                    NetworkReader::new(Arc::new(buffer), events, len, self_contained)
+                },
+                sender,
+            })
+        }
+    }
+
     impl<A: ArconType> Serialisable for UnsafeSerde<A> {
         fn ser_id(&self) -> u64 {
             A::UNSAFE_SER_ID
@@ -123,6 +364,169 @@ pub mod unsafe_remote {
     }
 }
 
+/// Module containing the [kompact] serialiser/deserialiser implementation for [FlightSerde::Bincode]
+///
+/// Messages carry the reliable ser id and version of their ArconType, since the
+/// ser id of the message itself is the same for all types.
+#[cfg(feature = "arcon_serde")]
+pub mod bincode_remote {
+    use crate::data::{ser_id, ArconType, BufMutWriter, RawArconMessage, VersionId};
+    use kompact::prelude::*;
+
+    #[derive(Clone, Debug)]
+    pub struct BincodeSerde<A: ArconType>(pub RawArconMessage<A>);
+
+    impl<A: ArconType> Deserialiser<RawArconMessage<A>> for BincodeSerde<A> {
+        const SER_ID: SerId = ser_id::BINCODE_FLIGHT_ID;
+
+        fn deserialise(buf: &mut dyn Buf) -> Result<RawArconMessage<A>, SerError> {
+            let type_id = buf.get_u64();
+            if type_id != A::RELIABLE_SER_ID {
+                let err = format!(
+                    "Mismatch on ArconType. Got {} while expecting {}",
+                    type_id,
+                    A::RELIABLE_SER_ID
+                );
+                return Err(SerError::InvalidType(err));
+            }
+            let version_id = buf.get_u32();
+            if version_id != A::VERSION_ID {
+                let err = format!(
+                    "Mismatch on ArconType version. Got {} while expecting {}",
+                    version_id,
+                    A::VERSION_ID
+                );
+                return Err(SerError::InvalidData(err));
+            }
+
+            bincode::deserialize(buf.bytes()).map_err(|e| SerError::InvalidData(e.to_string()))
+        }
+    }
+
+    impl<A: ArconType> Serialisable for BincodeSerde<A> {
+        fn ser_id(&self) -> u64 {
+            ser_id::BINCODE_FLIGHT_ID
+        }
+        fn size_hint(&self) -> Option<usize> {
+            let header = std::mem::size_of::<SerId>() + std::mem::size_of::<VersionId>();
+            bincode::serialized_size(&self.0)
+                .ok()
+                .map(|size| header + size as usize)
+        }
+        fn serialise(&self, buf: &mut dyn BufMut) -> Result<(), SerError> {
+            buf.put_u64(A::RELIABLE_SER_ID);
+            buf.put_u32(A::VERSION_ID);
+            bincode::serialize_into(BufMutWriter::new(buf), &self.0)
+                .map_err(|e| SerError::InvalidData(e.to_string()))
+        }
+        fn local(self: Box<Self>) -> Result<Box<dyn Any + Send>, Box<dyn Serialisable>> {
+            Ok(self)
+        }
+    }
+}
+
+/// Module containing the [kompact] serialiser/deserialisers for compressed flight data
+///
+/// A compressed message carries the ser id of its inner [FlightSerde] followed by the
+/// compressed bytes that the inner serialiser produced.
+pub mod compressed_remote {
+    use super::{
+        reliable_remote::ReliableSerde, unsafe_remote::UnsafeSerde, Compression, FlightSerde,
+    };
+    use crate::data::{ser_id, ArconType, RawArconMessage};
+    use kompact::prelude::*;
+    use std::marker::PhantomData;
+
+    #[derive(Clone, Debug)]
+    pub struct CompressedSerde {
+        compression: Compression,
+        inner_id: SerId,
+        payload: Vec<u8>,
+    }
+
+    impl CompressedSerde {
+        /// Serialises `msg` with `serde` and compresses the result with `compression`
+        pub fn new<A: ArconType>(
+            msg: RawArconMessage<A>,
+            serde: &FlightSerde,
+            compression: Compression,
+        ) -> Result<CompressedSerde, SerError> {
+            let (inner_id, bytes) = serde.serialise(msg)?;
+
+            Ok(CompressedSerde {
+                compression,
+                inner_id,
+                payload: compression.compress(&bytes)?,
+            })
+        }
+    }
+
+    impl Serialisable for CompressedSerde {
+        fn ser_id(&self) -> u64 {
+            self.compression.ser_id()
+        }
+        fn size_hint(&self) -> Option<usize> {
+            Some(std::mem::size_of::<SerId>() + self.payload.len())
+        }
+        fn serialise(&self, buf: &mut dyn BufMut) -> Result<(), SerError> {
+            buf.put_u64(self.inner_id);
+            buf.put_slice(&self.payload);
+            Ok(())
+        }
+        fn local(self: Box<Self>) -> Result<Box<dyn Any + Send>, Box<dyn Serialisable>> {
+            Ok(self)
+        }
+    }
+
+    /// Deserialiser for messages compressed with [Compression::Lz4]
+    pub struct Lz4Serde<A: ArconType>(PhantomData<A>);
+
+    impl<A: ArconType> Deserialiser<RawArconMessage<A>> for Lz4Serde<A> {
+        const SER_ID: SerId = ser_id::LZ4_FLIGHT_ID;
+
+        fn deserialise(buf: &mut dyn Buf) -> Result<RawArconMessage<A>, SerError> {
+            unpack(Compression::Lz4, buf)
+        }
+    }
+
+    /// Deserialiser for messages compressed with [Compression::Zstd]
+    pub struct ZstdSerde<A: ArconType>(PhantomData<A>);
+
+    impl<A: ArconType> Deserialiser<RawArconMessage<A>> for ZstdSerde<A> {
+        const SER_ID: SerId = ser_id::ZSTD_FLIGHT_ID;
+
+        fn deserialise(buf: &mut dyn Buf) -> Result<RawArconMessage<A>, SerError> {
+            unpack(Compression::Zstd, buf)
+        }
+    }
+
+    fn unpack<A: ArconType>(
+        compression: Compression,
+        buf: &mut dyn Buf,
+    ) -> Result<RawArconMessage<A>, SerError> {
+        let inner_id = buf.get_u64();
+        let bytes = compression.decompress(buf.bytes())?;
+        let mut payload: &[u8] = &bytes;
+
+        if inner_id == A::RELIABLE_SER_ID {
+            ReliableSerde::<A>::deserialise(&mut payload)
+        } else if inner_id == A::UNSAFE_SER_ID {
+            UnsafeSerde::<A>::deserialise(&mut payload)
+        } else {
+            #[cfg(feature = "arcon_serde")]
+            {
+                if inner_id == ser_id::BINCODE_FLIGHT_ID {
+                    return super::bincode_remote::BincodeSerde::<A>::deserialise(&mut payload);
+                }
+            }
+            Err(SerError::InvalidType(format!(
+                "Unexpected ser id {} in compressed flight data",
+                inner_id
+            )))
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -241,6 +645,147 @@ mod test {
         }
     }
 
+    #[test]
+    fn lz4_reliable_serde_test() {
+        let data = compressed_flight_test::<ArconDataTest>(FlightSerde::Reliable, Compression::Lz4);
+        for d in data {
+            assert_eq!(d.data.items, *ITEMS);
+            assert_eq!(d.data.price, PRICE);
+            assert_eq!(d.data.id, ID);
+        }
+    }
+
+    #[test]
+    fn zstd_unsafe_serde_test() {
+        let data = compressed_flight_test::<ArconDataTest>(FlightSerde::Unsafe, Compression::Zstd);
+        for d in data {
+            assert_eq!(d.data.items, *ITEMS);
+            assert_eq!(d.data.price, PRICE);
+            assert_eq!(d.data.id, ID);
+        }
+    }
+
+    #[test]
+    fn decompress_roundtrip_test() {
+        let bytes: Vec<u8> = (0..10_000u32).map(|i| (i % 7) as u8).collect();
+        for compression in &[Compression::Lz4, Compression::Zstd] {
+            let compressed = compression.compress(&bytes).unwrap();
+            assert_eq!(compression.decompress(&compressed).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn decompress_limit_test() {
+        let bytes = vec![0u8; 10_000];
+        for compression in &[Compression::Lz4, Compression::Zstd] {
+            let compressed = compression.compress(&bytes).unwrap();
+            assert!(compression
+                .decompress_with_limit(&compressed, bytes.len())
+                .is_ok());
+            assert!(compression
+                .decompress_with_limit(&compressed, bytes.len() - 1)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn unsafe_network_serde_test() {
+        use crate::{
+            allocator::ArconAllocator,
+            buffer::network::with_receive_allocator,
+            data::{flight_serde::unsafe_remote::UnsafeNetworkSerde, RawArconMessage},
+        };
+        use std::sync::{Arc, Mutex};
+
+        let data = ArconDataTest {
+            id: ID,
+            items: ITEMS.clone(),
+            price: PRICE,
+        };
+        let msg = RawArconMessage {
+            events: vec![ArconEvent::Element(ArconElement::new(data)).into(); 4],
+            sender: 1.into(),
+        };
+        let mut bytes: Vec<u8> = Vec::new();
+        UnsafeSerde(msg).serialise(&mut bytes).unwrap();
+
+        let total_bytes = 1024 * 1024;
+        let allocator = Arc::new(Mutex::new(ArconAllocator::new(total_bytes)));
+        let mut buf: &[u8] = &bytes;
+        let msg = with_receive_allocator(&allocator, || {
+            UnsafeNetworkSerde::<ArconDataTest>::deserialise(&mut buf)
+        })
+        .unwrap();
+
+        assert!(allocator.lock().unwrap().bytes_remaining() < total_bytes);
+        assert_eq!(msg.sender, 1.into());
+        assert_eq!(msg.events.len(), 4);
+        // The items of each event point into the buffer
+        assert!(!msg.events.is_self_contained());
+        for event in msg.events {
+            match event.unwrap() {
+                ArconEvent::Element(e) => {
+                    assert_eq!(e.data.items, *ITEMS);
+                    assert_eq!(e.data.price, PRICE);
+                    assert_eq!(e.data.id, ID);
+                }
+                _ => panic!("Expected an element"),
+            }
+        }
+        // The NetworkBuffer is returned once the events have been consumed
+        assert_eq!(allocator.lock().unwrap().bytes_remaining(), total_bytes);
+    }
+
+    #[test]
+    fn unsafe_network_serde_moves_events_test() {
+        use crate::data::{flight_serde::unsafe_remote::UnsafeNetworkSerde, RawArconMessage};
+
+        let msg = RawArconMessage::<u64> {
+            events: (0..4)
+                .map(|i| ArconEvent::Element(ArconElement::with_timestamp(i, i)).into())
+                .collect(),
+            sender: 1.into(),
+        };
+        let mut bytes: Vec<u8> = Vec::new();
+        UnsafeSerde(msg).serialise(&mut bytes).unwrap();
+
+        // Without an allocator, the events are decoded in a heap NetworkBuffer
+        let mut buf: &[u8] = &bytes;
+        let msg = UnsafeNetworkSerde::<u64>::deserialise(&mut buf).unwrap();
+        assert!(msg.events.is_self_contained());
+        let data: Vec<u64> = msg
+            .events
+            .into_iter()
+            .filter_map(|e| match e.unwrap() {
+                ArconEvent::Element(e) => Some(e.data),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(data, vec![0, 1, 2, 3]);
+    }
+
+    #[cfg(feature = "arcon_serde")]
+    #[test]
+    fn bincode_serde_test() {
+        let data = flight_test::<ArconDataTest>(FlightSerde::Bincode);
+        for d in data {
+            assert_eq!(d.data.items, *ITEMS);
+            assert_eq!(d.data.price, PRICE);
+            assert_eq!(d.data.id, ID);
+        }
+    }
+
+    #[cfg(feature = "arcon_serde")]
+    #[test]
+    fn lz4_bincode_serde_test() {
+        let data = compressed_flight_test::<ArconDataTest>(FlightSerde::Bincode, Compression::Lz4);
+        for d in data {
+            assert_eq!(d.data.items, *ITEMS);
+            assert_eq!(d.data.price, PRICE);
+            assert_eq!(d.data.id, ID);
+        }
+    }
+
     #[test]
     #[should_panic]
     fn unsafe_version_mismatch_test() {
@@ -252,7 +797,8 @@ mod test {
     #[test]
     #[should_panic]
     fn serde_id_mismatch_test() {
-        // Should panic with Unexpected Deserializer.
+        // The DebugNode logs and drops messages with an unknown ser_id,
+        // so the test panics as no elements are received.
         // reliable/unsafe ser_ids do not match
         // NOTE: does not matter whether it is Unsafe/Reliable
         let _ = flight_test::<UpdatedSerId>(FlightSerde::Unsafe);
@@ -288,6 +834,25 @@ mod test {
     }
 
     fn flight_test<ReceivingType>(serde: FlightSerde) -> Vec<ArconElement<ReceivingType>>
+    where
+        ReceivingType: ArconType,
+    {
+        channel_test(move |path| Channel::Remote(path, serde))
+    }
+
+    fn compressed_flight_test<ReceivingType>(
+        serde: FlightSerde,
+        compression: Compression,
+    ) -> Vec<ArconElement<ReceivingType>>
+    where
+        ReceivingType: ArconType,
+    {
+        channel_test(move |path| Channel::CompressedRemote(path, serde, compression))
+    }
+
+    fn channel_test<ReceivingType>(
+        channel: impl FnOnce(ActorPath) -> Channel<ArconDataTest>,
+    ) -> Vec<ArconElement<ReceivingType>>
     where
         ReceivingType: ArconType,
     {
@@ -311,7 +876,7 @@ mod test {
             comp_id.into(),
         ]));
 
-        let channel = Channel::Remote(remote_path, serde);
+        let channel = channel(remote_path);
         let mut channel_strategy: ChannelStrategy<ArconDataTest> =
             ChannelStrategy::Forward(Forward::new(channel, 1.into(), pool_info));
 
@@ -4,13 +4,13 @@
 /// Serialisers and Deserialiser for in-flight data
 pub mod flight_serde;
 /// Known Serialisation IDs for Arcon Types
-mod ser_id;
+pub(crate) mod ser_id;
 
-use crate::buffer::event::BufferReader;
+use crate::buffer::{event::BufferReader, network::NetworkReader};
 use abomonation::Abomonation;
 use abomonation_derive::*;
 use kompact::prelude::*;
-use prost::{Message as PMessage, Oneof as POneof};
+use prost::{Enumeration as PEnumeration, Message as PMessage, Oneof as POneof};
 #[cfg(feature = "arcon_serde")]
 use serde::{Deserialize, Serialize};
 use std::{
@@ -115,6 +115,30 @@ impl<A: ArconType> From<ArconEvent<A>> for ArconEventWrapper<A> {
     }
 }
 
+/// Describes how an [ArconElement] changes the result of a changelog stream
+#[cfg_attr(feature = "arcon_serde", derive(Serialize, Deserialize))]
+#[derive(PEnumeration, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ChangeKind {
+    /// A new row, which is the default for append-only streams
+    Insert = 0,
+    /// Retracts the previous version of an updated row
+    UpdateBefore = 1,
+    /// The new version of an updated row
+    UpdateAfter = 2,
+    /// Retracts a row
+    Delete = 3,
+}
+
+impl ChangeKind {
+    /// Returns true if the change removes a previously emitted row
+    pub fn is_retraction(self) -> bool {
+        match self {
+            ChangeKind::UpdateBefore | ChangeKind::Delete => true,
+            ChangeKind::Insert | ChangeKind::UpdateAfter => false,
+        }
+    }
+}
+
 /// A Stream element containing some data and timestamp
 #[cfg_attr(feature = "arcon_serde", derive(Serialize, Deserialize))]
 #[derive(PMessage, Clone, Abomonation)]
@@ -124,6 +148,9 @@ pub struct ArconElement<A: ArconType> {
     pub data: A,
     #[prost(message, tag = "2")]
     pub timestamp: Option<u64>,
+    /// The [ChangeKind] of the element, stored as its protobuf value
+    #[prost(enumeration = "ChangeKind", tag = "3")]
+    pub change: i32,
 }
 
 impl<A: ArconType> ArconElement<A> {
@@ -132,6 +159,7 @@ impl<A: ArconType> ArconElement<A> {
         ArconElement {
             data,
             timestamp: None,
+            change: ChangeKind::Insert as i32,
         }
     }
 
@@ -140,8 +168,25 @@ impl<A: ArconType> ArconElement<A> {
         ArconElement {
             data,
             timestamp: Some(ts),
+            change: ChangeKind::Insert as i32,
         }
     }
+
+    /// Creates an ArconElement with an optional timestamp and a [ChangeKind]
+    pub fn with_change(data: A, timestamp: Option<u64>, change: ChangeKind) -> Self {
+        ArconElement {
+            data,
+            timestamp,
+            change: change as i32,
+        }
+    }
+
+    /// Returns the [ChangeKind] of the element
+    ///
+    /// Unknown values are treated as [ChangeKind::Insert].
+    pub fn change_kind(&self) -> ChangeKind {
+        ChangeKind::from_i32(self.change).unwrap_or(ChangeKind::Insert)
+    }
 }
 
 /// Watermark message containing a [u64] timestamp
@@ -194,6 +239,23 @@ pub struct RawArconMessage<A: ArconType> {
     pub sender: NodeID,
 }
 
+/// An ArconMessage received over the network and decoded in a NetworkBuffer
+pub(crate) struct NetworkArconMessage<A: ArconType> {
+    /// Batch of ArconEvents backed by a NetworkBuffer
+    pub events: NetworkReader<ArconEventWrapper<A>>,
+    /// ID identifying where the message is sent from
+    pub sender: NodeID,
+}
+
+impl<A: ArconType> From<NetworkArconMessage<A>> for RawArconMessage<A> {
+    fn from(msg: NetworkArconMessage<A>) -> Self {
+        RawArconMessage {
+            events: msg.events.as_slice().to_vec(),
+            sender: msg.sender,
+        }
+    }
+}
+
 impl<A: ArconType> From<ArconMessage<A>> for RawArconMessage<A> {
     fn from(msg: ArconMessage<A>) -> Self {
         RawArconMessage {
@@ -238,7 +300,30 @@ impl<A: ArconType> ArconMessage<A> {
     /// This function should only be used for development and test purposes.
     pub fn element(data: A, timestamp: Option<u64>, sender: NodeID) -> ArconMessage<A> {
         ArconMessage {
-            events: vec![ArconEvent::Element(ArconElement { data, timestamp }).into()].into(),
+            events: vec![ArconEvent::Element(ArconElement {
+                data,
+                timestamp,
+                change: ChangeKind::Insert as i32,
+            })
+            .into()]
+            .into(),
+            sender,
+        }
+    }
+    /// Creates an ArconMessage with a single [ArconEvent::Element] event of the given [ChangeKind]
+    ///
+    /// This function should only be used for development and test purposes.
+    pub fn change(
+        data: A,
+        timestamp: Option<u64>,
+        change: ChangeKind,
+        sender: NodeID,
+    ) -> ArconMessage<A> {
+        ArconMessage {
+            events: vec![
+                ArconEvent::Element(ArconElement::with_change(data, timestamp, change)).into(),
+            ]
+            .into(),
             sender,
         }
     }
@@ -26,3 +26,45 @@ pub const RELIABLE_STRING_ID: SerId = 63;
 
 pub const UNSAFE_BOOLEAN_ID: SerId = 64;
 pub const RELIABLE_BOOLEAN_ID: SerId = 65;
+
+// Serialisation IDs for the sketches of built-in aggregators
+pub const UNSAFE_HLL_ID: SerId = 66;
+pub const RELIABLE_HLL_ID: SerId = 67;
+
+pub const UNSAFE_COUNT_MIN_ID: SerId = 68;
+pub const RELIABLE_COUNT_MIN_ID: SerId = 69;
+
+pub const UNSAFE_TDIGEST_ID: SerId = 70;
+pub const RELIABLE_TDIGEST_ID: SerId = 71;
+
+// Base serialisation IDs for the input of broadcast process operators,
+// see generic_id for how the id of an instance is derived
+pub const UNSAFE_BROADCAST_INPUT_ID: SerId = 72;
+pub const RELIABLE_BROADCAST_INPUT_ID: SerId = 73;
+
+// Base serialisation IDs for the output of CEP operators,
+// see generic_id for how the id of an instance is derived
+pub const UNSAFE_PATTERN_MATCH_ID: SerId = 74;
+pub const RELIABLE_PATTERN_MATCH_ID: SerId = 75;
+
+// Serialisation IDs for compressed in-flight data
+pub const LZ4_FLIGHT_ID: SerId = 76;
+pub const ZSTD_FLIGHT_ID: SerId = 77;
+
+// Serialisation IDs for in-flight data that is not tied to a single ArconType
+pub const BINCODE_FLIGHT_ID: SerId = 78;
+
+// Serialisation ID of the messages that a TlsListener delivers to local components
+pub const TLS_FRAME_ID: SerId = 79;
+
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Derives the serialisation id of a generic type from its base id and the id of a type parameter
+///
+/// Instances of a generic type with different type parameters are serialised differently, so
+/// they must not share an id. The id of an instance is derived by folding the ids of all its
+/// type parameters into the base id of the type, e.g.,
+/// `generic_id(generic_id(BASE_ID, A::UNSAFE_SER_ID), B::UNSAFE_SER_ID)`.
+pub const fn generic_id(id: SerId, param: SerId) -> SerId {
+    (id ^ param).wrapping_mul(FNV_PRIME)
+}
@@ -81,24 +81,44 @@ pub mod prelude {
         source::socket::{SocketKind, SocketSource},
     };
     pub use crate::{
-        allocator::{AllocResult, ArconAllocator},
-        buffer::event::{BufferPool, BufferReader, BufferWriter},
+        allocator::{AllocResult, ArconAllocator, Budget},
+        buffer::event::{BufferPool, BufferReader, BufferWriter, PoolInfo},
         conf::ArconConf,
         data::VersionId,
+        metrics::memory::{AllocatorMetrics, BufferPoolMetrics},
         pipeline::ArconPipeline,
         stream::{
             channel::{
                 strategy::{
-                    broadcast::Broadcast, forward::Forward, key_by::KeyBy, round_robin::RoundRobin,
+                    broadcast::Broadcast,
+                    custom::Custom,
+                    forward::Forward,
+                    key_by::{KeyBy, KeyGroups},
+                    partial_key_grouping::PartialKeyGrouping,
+                    random_shuffle::RandomShuffle,
+                    rebalance::Rebalance,
+                    round_robin::RoundRobin,
                     ChannelStrategy,
                 },
                 Channel,
             },
             node::{debug::DebugNode, Node, NodeDescriptor},
             operator::{
+                aggregate::{
+                    Avg, Count, CountMin, HyperLogLog, Max, MergeAggregate, Min, Quantiles,
+                    Retractable, RollingAggregate, Sum, TopK,
+                },
+                cep::{Cep, Pattern, PatternMatch},
                 function::*,
-                sink::local_file::LocalFileSink,
-                window::{AppenderWindow, EventTimeWindowAssigner, IncrementalWindow, Window},
+                sink::{
+                    local_file::LocalFileSink,
+                    upsert::{UpsertSink, UpsertTarget},
+                },
+                window::{
+                    AggregatorWindow, AppenderWindow, CountTrigger, CountWindowAssigner,
+                    EventTimeWindowAssigner, GlobalWindowAssigner, IncrementalWindow,
+                    PunctuationTrigger, Trigger, TriggerResult, Window,
+                },
                 Operator,
             },
             source::{collection::CollectionSource, local_file::LocalFileSource, SourceContext},
@@ -111,7 +131,9 @@ pub mod prelude {
     pub use crate::stream::{operator::sink::kafka::KafkaSink, source::kafka::KafkaSource};
 
     pub use crate::data::{
-        flight_serde::{reliable_remote::ReliableSerde, unsafe_remote::UnsafeSerde, FlightSerde},
+        flight_serde::{
+            reliable_remote::ReliableSerde, unsafe_remote::UnsafeSerde, Compression, FlightSerde,
+        },
         *,
     };
     pub use error::{arcon_err, arcon_err_kind, ArconResult};
@@ -23,6 +23,8 @@ pub struct MetricReport {
 #[derive(Debug, Clone)]
 pub enum NodeEvent {
     Metrics(NodeID, NodeMetrics),
+    /// A Node failed with the given reason and has stopped
+    Failed(NodeID, String),
 }
 
 /// A [kompact] port for communication
@@ -186,6 +188,16 @@ where
 
                 self.node_metrics.insert(id, metrics);
             }
+            NodeEvent::Failed(id, reason) => {
+                error!(
+                    self.ctx.log(),
+                    "Node {:?} of {} failed: {}", id, self.node_description, reason
+                );
+                if self.nodes.remove(&id).is_some() {
+                    self.node_parallelism -= 1;
+                }
+                self.node_metrics.remove(&id);
+            }
         }
         Handled::Ok
     }
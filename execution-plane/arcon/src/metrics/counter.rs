@@ -24,4 +24,10 @@ impl Counter {
     pub fn inc_n(&mut self, n: usize) {
         self.counter += n as u128;
     }
+
+    /// Returns the current count
+    #[inline]
+    pub fn get(&self) -> u128 {
+        self.counter
+    }
 }
@@ -0,0 +1,44 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::metrics::{counter::Counter, gauge::Gauge};
+
+/// A snapshot of the memory usage of an [ArconAllocator](crate::allocator::ArconAllocator)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocatorMetrics {
+    /// Memory limit of the allocator
+    pub limit: usize,
+    /// Bytes that are currently allocated
+    pub allocated_bytes: usize,
+    /// Bytes that are reserved for memory managed outside of the allocator
+    pub reserved_bytes: usize,
+    /// Bytes that are still available
+    pub bytes_remaining: usize,
+    /// Allocations made so far
+    pub total_allocations: u128,
+}
+
+/// Metrics of a [BufferPool](crate::buffer::event::BufferPool)
+#[derive(Debug, Clone)]
+pub struct BufferPoolMetrics {
+    /// Gauge Metric representing number of buffers in the pool
+    pub capacity: Gauge,
+    /// Gauge Metric representing number of events each buffer holds
+    pub buffer_size: Gauge,
+    /// Counter for how often no buffer was free when one was requested
+    pub exhaustion_counter: Counter,
+    /// Counter for microseconds spent waiting for a buffer to be released
+    pub wait_micros: Counter,
+}
+
+impl BufferPoolMetrics {
+    /// Creates a BufferPoolMetrics struct
+    pub fn new() -> BufferPoolMetrics {
+        BufferPoolMetrics {
+            capacity: Gauge::new(),
+            buffer_size: Gauge::new(),
+            exhaustion_counter: Counter::new(),
+            wait_micros: Counter::new(),
+        }
+    }
+}
@@ -9,5 +9,7 @@ pub mod ewma;
 pub mod gauge;
 /// Histogram implementation
 pub mod histogram;
+/// Memory metrics of the allocator and buffer pools
+pub mod memory;
 /// Meter implementation
 pub mod meter;
@@ -4,12 +4,26 @@
 #[cfg(feature = "arcon_tui")]
 use crate::tui::{component::TuiComponent, widgets::node::Node as TuiNode};
 use crate::{
-    allocator::ArconAllocator, buffer::event::PoolInfo, conf::ArconConf, manager::node_manager::*,
-    prelude::*, util::SafelySendableFn,
+    allocator::{ArconAllocator, Budget},
+    buffer::event::PoolInfo,
+    conf::ArconConf,
+    manager::node_manager::*,
+    prelude::*,
+    stream::channel::tls::{self, TlsListener, TlsRemote},
+    timer,
+    util::SafelySendableFn,
 };
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use kompact::{component::AbstractComponent, prelude::KompactSystem};
-use std::sync::{Arc, Mutex};
+use std::{
+    marker::PhantomData,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 /// A struct meant to simplify the creation of an Arcon Pipeline
 #[derive(Clone)]
@@ -22,6 +36,10 @@ pub struct ArconPipeline {
     allocator: Arc<Mutex<ArconAllocator>>,
     /// NodeManagers launched on top of this ArconPipeline
     node_managers: FxHashMap<String, ActorRefStrong<NodeEvent>>,
+    /// Monotonically increasing counter used to hand out NodeIDs
+    node_id_counter: Arc<AtomicU32>,
+    /// Receives remote messages over TLS once started, see [ArconPipeline::start_tls_listener]
+    tls_listener: Option<Arc<TlsListener>>,
     #[cfg(feature = "arcon_tui")]
     tui_component: Arc<Component<TuiComponent>>,
     #[cfg(feature = "arcon_tui")]
@@ -35,6 +53,69 @@ pub type CreatedDynamicNode<IN> = Arc<dyn AbstractComponent<Message = ArconMessa
 /// A Source with operator type, state backend type, and timer type erased
 pub type DynamicSource = Box<dyn CreateErased<()>>;
 
+/// The output side of a pipeline stage
+///
+/// Holds the NodeIDs that the Nodes of a stage are created with, and that they embed in
+/// their outgoing messages. The Nodes of a stage are created through
+/// [ArconPipeline::create_stage_node_manager], which also uses the NodeIDs of the
+/// upstream stage as their `in_channels`.
+#[derive(Debug)]
+pub struct Stage<A: ArconType> {
+    /// NodeIDs of the Nodes in the stage
+    ids: Vec<NodeID>,
+    /// NodeID counter of the pipeline that allocated the stage
+    pipeline: Arc<AtomicU32>,
+    _marker: PhantomData<A>,
+}
+
+impl<A: ArconType> Stage<A> {
+    /// Returns the NodeIDs of the stage
+    pub fn ids(&self) -> &[NodeID] {
+        &self.ids
+    }
+
+    /// Returns the amount of Nodes in the stage
+    pub fn parallelism(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns the NodeIDs of the stage to be used as the `in_channels` of a downstream Node
+    pub fn in_channels(&self) -> Vec<NodeID> {
+        self.ids.clone()
+    }
+}
+
+/// The forward-connected operators that every Node of a pipeline stage runs
+///
+/// An operator added through [then](OperatorStage::then) is fused behind the previous
+/// ones, so that a Node of the stage passes elements between them by direct calls
+/// instead of through a ChannelStrategy and a Kompact message. The Nodes of the stage
+/// are created through [ArconPipeline::create_operator_stage_node_manager].
+pub struct OperatorStage<OP> {
+    /// Creates the fused operator of a Node
+    operator_fn: Arc<dyn Fn() -> OP + Send + Sync>,
+}
+
+impl<OP: 'static> OperatorStage<OP> {
+    /// Starts a stage with the operator that `operator_fn` creates for every Node
+    pub fn new(operator_fn: impl Fn() -> OP + Send + Sync + 'static) -> Self {
+        OperatorStage {
+            operator_fn: Arc::new(operator_fn),
+        }
+    }
+
+    /// Fuses the operator that `next_fn` creates behind the operators of the stage
+    ///
+    /// Both operators have to implement [ChainableOperator] for the Nodes to be created.
+    pub fn then<N: 'static>(
+        self,
+        next_fn: impl Fn() -> N + Send + Sync + 'static,
+    ) -> OperatorStage<Chain<OP, N>> {
+        let operator_fn = self.operator_fn;
+        OperatorStage::new(move || Chain::new(operator_fn(), next_fn()))
+    }
+}
+
 impl ArconPipeline {
     /// Creates a new ArconPipeline using the default ArconConf
     pub fn new() -> ArconPipeline {
@@ -50,6 +131,8 @@ impl ArconPipeline {
             conf,
             allocator,
             node_managers: FxHashMap::default(),
+            node_id_counter: Arc::new(AtomicU32::new(0)),
+            tls_listener: None,
             #[cfg(feature = "arcon_tui")]
             tui_component,
             #[cfg(feature = "arcon_tui")]
@@ -70,6 +153,8 @@ impl ArconPipeline {
             conf,
             allocator,
             node_managers: FxHashMap::default(),
+            node_id_counter: Arc::new(AtomicU32::new(0)),
+            tls_listener: None,
             #[cfg(feature = "arcon_tui")]
             tui_component,
             #[cfg(feature = "arcon_tui")]
@@ -85,6 +170,82 @@ impl ArconPipeline {
             self.conf.buffer_pool_limit,
             self.allocator.clone(),
         )
+        .with_wait_timeout(Duration::from_millis(self.conf.buffer_pool_wait_timeout))
+    }
+
+    /// Returns the ArconAllocator of the pipeline
+    ///
+    /// It may be handed to [Node::with_allocator](crate::stream::node::Node::with_allocator).
+    pub fn allocator(&self) -> Arc<Mutex<ArconAllocator>> {
+        self.allocator.clone()
+    }
+
+    /// Returns a snapshot of the memory usage of the pipeline's ArconAllocator
+    pub fn allocator_metrics(&self) -> AllocatorMetrics {
+        self.allocator.lock().unwrap().metrics()
+    }
+
+    /// Hands out a memory budget of `bytes` to `budget` in the allocator of the pipeline
+    pub fn set_memory_budget(&self, budget: Budget, bytes: usize) -> ArconResult<()> {
+        self.allocator.lock().unwrap().set_budget(budget, bytes)
+    }
+
+    /// Allocates a new stage of `parallelism` Nodes with NodeIDs that
+    /// do not collide with any other stage allocated by this pipeline
+    pub fn stage<A: ArconType>(&mut self, parallelism: usize) -> Stage<A> {
+        let start = self
+            .node_id_counter
+            .fetch_add(parallelism as u32, Ordering::Relaxed);
+        let ids = (start..start + parallelism as u32)
+            .map(NodeID::new)
+            .collect();
+        Stage {
+            ids,
+            pipeline: self.node_id_counter.clone(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns an error if `stage` was not allocated by this pipeline
+    fn check_stage<A: ArconType>(&self, stage: &Stage<A>) -> ArconResult<()> {
+        if Arc::ptr_eq(&stage.pipeline, &self.node_id_counter) {
+            Ok(())
+        } else {
+            arcon_err!("Stage {:?} was allocated by another pipeline", stage.ids)
+        }
+    }
+
+    /// Merges several upstream stages of the same type into a single stage
+    ///
+    /// The resulting stage is the upstream of the Nodes created through
+    /// [ArconPipeline::create_stage_node_manager], which then process events from all
+    /// of the stages while tracking the lowest watermark across every input.
+    ///
+    /// Returns an error if a stage was allocated by another pipeline, or if a Node
+    /// would be connected twice, e.g., if a stage is passed more than once or two
+    /// unions that share a stage are merged.
+    pub fn union<A: ArconType>(&self, stages: &[&Stage<A>]) -> ArconResult<Stage<A>> {
+        let mut seen = FxHashSet::default();
+        let mut ids = Vec::new();
+        for stage in stages {
+            self.check_stage(stage)?;
+            for &id in &stage.ids {
+                if !seen.insert(id) {
+                    return arcon_err!("NodeID {} is used by more than one stage", id.id);
+                }
+                ids.push(id);
+            }
+        }
+
+        if ids.is_empty() {
+            return arcon_err!("Cannot create a union of empty stages");
+        }
+
+        Ok(Stage {
+            ids,
+            pipeline: self.node_id_counter.clone(),
+            _marker: PhantomData,
+        })
     }
 
     /// Helper function to set up internals of the pipeline
@@ -117,6 +278,54 @@ impl ArconPipeline {
         (system, tui_component, arcon_receiver)
     }
 
+    /// Starts the TlsListener of the pipeline on its `tls_host` and returns its bound address
+    ///
+    /// Fails if the TLS settings of the pipeline are incomplete or its certificates fail to load.
+    pub fn start_tls_listener(&mut self) -> ArconResult<SocketAddr> {
+        self.conf.validate()?;
+        let host = self
+            .conf
+            .tls_host
+            .as_ref()
+            .ok_or_else(|| arcon_err_kind!("TlsListener requires tls_host to be set"))?;
+        let addr = host
+            .parse()
+            .map_err(|e| arcon_err_kind!("Invalid tls_host {} with err {}", host, e))?;
+        let listener = TlsListener::bind(addr, &self.conf, self.system.clone())?;
+        let addr = listener.local_addr();
+        self.tls_listener = Some(Arc::new(listener));
+        Ok(addr)
+    }
+
+    /// Returns the address that the TlsListener of the pipeline accepts connections on
+    pub fn tls_addr(&self) -> Option<SocketAddr> {
+        self.tls_listener
+            .as_ref()
+            .map(|listener| listener.local_addr())
+    }
+
+    /// Creates a Channel to the component at `path` of the pipeline behind the TlsListener at `addr`
+    ///
+    /// The pipeline authenticates itself with its `tls_cert` and only sends to a listener
+    /// whose certificate is valid for `server_name` and signed by its `tls_ca`.
+    pub fn tls_channel<A: ArconType>(
+        &self,
+        addr: SocketAddr,
+        server_name: &str,
+        path: Vec<String>,
+        serde: FlightSerde,
+    ) -> ArconResult<Channel<A>> {
+        let config = tls::client_config(&self.conf)?;
+        let remote = TlsRemote::new(
+            addr,
+            server_name,
+            path,
+            config,
+            self.conf.tls_send_queue_size,
+        );
+        Ok(Channel::Secure(remote, serde))
+    }
+
     /// Give out a mutable reference to the KompactSystem of the pipeline
     pub fn system(&mut self) -> &mut KompactSystem {
         &mut self.system
@@ -128,6 +337,9 @@ impl ArconPipeline {
     }
 
     /// Adds a NodeManager to the Arcon Pipeline
+    ///
+    /// The pipeline runs the operators of `nodes` as given. Forward-connected operators
+    /// are fused into a single Node by building their stage as an [OperatorStage].
     pub fn create_node_manager<IN, OUT>(
         &mut self,
         node_description: String,
@@ -181,6 +393,120 @@ impl ArconPipeline {
         node_comps
     }
 
+    /// Adds a NodeManager that runs the Nodes of `stage` on the events of the `upstream` stage
+    ///
+    /// Every Node is created through `node_fn` with its NodeID of `stage`, the NodeIDs of
+    /// `upstream` as its `in_channels`, and the ChannelStrategy that `channel_strategy`
+    /// returns for its NodeID.
+    ///
+    /// Returns an error if either stage was allocated by another pipeline.
+    pub fn create_stage_node_manager<IN, OUT, F>(
+        &mut self,
+        node_description: String,
+        node_fn: &'static dyn SafelySendableFn(
+            NodeDescriptor,
+            NodeID,
+            Vec<NodeID>,
+            ChannelStrategy<OUT>,
+            state::BackendType,
+        ) -> DynamicNode<IN>,
+        upstream: &Stage<IN>,
+        stage: &Stage<OUT>,
+        mut channel_strategy: F,
+        backend_type: state::BackendType,
+    ) -> ArconResult<Vec<CreatedDynamicNode<IN>>>
+    where
+        IN: ArconType,
+        OUT: ArconType,
+        F: FnMut(NodeID) -> ChannelStrategy<OUT>,
+    {
+        self.check_stage(upstream)?;
+        self.check_stage(stage)?;
+
+        let nodes = stage
+            .ids
+            .iter()
+            .map(|&id| {
+                node_fn(
+                    node_description.clone(),
+                    id,
+                    upstream.in_channels(),
+                    channel_strategy(id),
+                    backend_type,
+                )
+            })
+            .collect();
+
+        Ok(self.create_node_manager(node_description, node_fn, upstream.in_channels(), nodes))
+    }
+
+    /// Adds a NodeManager that runs the fused operators of `operators` in every Node of `stage`
+    ///
+    /// The Nodes are connected like those of [ArconPipeline::create_stage_node_manager]
+    /// and keep their state in a backend of type `B` under the `state_dir` of the pipeline.
+    ///
+    /// Returns an error if either stage was allocated by another pipeline.
+    pub fn create_operator_stage_node_manager<B, OP, F>(
+        &mut self,
+        node_description: String,
+        operators: OperatorStage<OP>,
+        upstream: &Stage<OP::IN>,
+        stage: &Stage<OP::OUT>,
+        mut channel_strategy: F,
+    ) -> ArconResult<Vec<CreatedDynamicNode<OP::IN>>>
+    where
+        B: state::Backend,
+        OP: Operator<B, TimerState = ArconNever> + 'static,
+        F: FnMut(NodeID) -> ChannelStrategy<OP::OUT>,
+    {
+        self.check_stage(upstream)?;
+        self.check_stage(stage)?;
+
+        let state_dir = self.conf.state_dir.clone();
+        let operator_fn = operators.operator_fn;
+        let create_node = move |descriptor: NodeDescriptor,
+                                id: NodeID,
+                                in_channels: Vec<NodeID>,
+                                channel_strategy: ChannelStrategy<OP::OUT>|
+              -> DynamicNode<OP::IN> {
+            let state_backend = B::create(&state_dir.join(format!("{}_{}", descriptor, id.id)))
+                .expect("Failed to create state backend");
+            Box::new(Node::new(
+                descriptor,
+                id,
+                in_channels,
+                channel_strategy,
+                operator_fn(),
+                state_backend,
+                timer::none(),
+            ))
+        };
+
+        let mut nodes = Vec::with_capacity(stage.parallelism());
+        for &id in &stage.ids {
+            nodes.push(create_node(
+                node_description.clone(),
+                id,
+                upstream.in_channels(),
+                channel_strategy(id),
+            ));
+        }
+
+        // The NodeManager holds on to its node_fn for as long as the KompactSystem runs,
+        // and the backend type of the stage is already fixed by `B`
+        let node_fn = Box::leak(Box::new(
+            move |descriptor: NodeDescriptor,
+                  id: NodeID,
+                  in_channels: Vec<NodeID>,
+                  channel_strategy: ChannelStrategy<OP::OUT>,
+                  _backend_type: state::BackendType| {
+                create_node(descriptor, id, in_channels, channel_strategy)
+            },
+        ));
+
+        Ok(self.create_node_manager(node_description, node_fn, upstream.in_channels(), nodes))
+    }
+
     /// Awaits termination from the pipeline
     pub fn await_termination(self) {
         // NOTE: Blocking call
@@ -205,7 +531,12 @@ impl ArconPipeline {
         let mem_interval = Ratio::from_integer(1);
         let schemes = Colorschemes::from_str("default").unwrap();
         let colorscheme = read_colorscheme(std::path::Path::new(""), &schemes);
-        let mut app = app::setup_app(cpu_interval, mem_interval, &colorscheme);
+        let mut app = app::setup_app(
+            cpu_interval,
+            mem_interval,
+            self.allocator.clone(),
+            &colorscheme,
+        );
 
         let backend = CrosstermBackend::new(std::io::stdout());
         let mut terminal = Terminal::new(backend).unwrap();
@@ -240,7 +571,9 @@ impl ArconPipeline {
                     break;
                 }
                 recv(self.arcon_event_receiver) -> message => {
-                    app.widgets.node.node_update(message.expect("Recv Error"));
+                    let node = message.expect("Recv Error");
+                    app.widgets.mem.pool_update(&node);
+                    app.widgets.node.node_update(node);
                     // let the normal ticker update do the drawing
                 }
                 recv(ticker) -> _ => {
@@ -362,6 +695,213 @@ impl ArconPipeline {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        state::{Backend, InMemory},
+        timer,
+    };
+
+    fn filter_node(
+        descriptor: NodeDescriptor,
+        id: NodeID,
+        in_channels: Vec<NodeID>,
+        channel_strategy: ChannelStrategy<i32>,
+        _backend_type: state::BackendType,
+    ) -> DynamicNode<i32> {
+        fn node_fn(x: &i32) -> bool {
+            *x >= 0
+        }
+
+        Box::new(Node::new(
+            descriptor,
+            id,
+            in_channels,
+            channel_strategy,
+            Filter::new(&node_fn),
+            InMemory::create("test".as_ref()).unwrap(),
+            timer::none(),
+        ))
+    }
+
+    #[test]
+    fn stage_ids_do_not_collide() {
+        let mut pipeline = ArconPipeline::new();
+        let first: Stage<u64> = pipeline.stage(2);
+        let second: Stage<u64> = pipeline.stage(3);
+
+        let union = pipeline.union(&[&first, &second]).unwrap();
+        assert_eq!(union.parallelism(), 5);
+        assert_eq!(
+            union.in_channels(),
+            (0..5).map(NodeID::new).collect::<Vec<_>>()
+        );
+        pipeline.shutdown();
+    }
+
+    #[test]
+    fn union_rejects_shared_ids() {
+        let mut pipeline = ArconPipeline::new();
+        let first: Stage<u64> = pipeline.stage(2);
+        let second: Stage<u64> = pipeline.stage(2);
+
+        assert!(pipeline.union(&[&first, &first]).is_err());
+        let union = pipeline.union(&[&first, &second]).unwrap();
+        assert!(pipeline.union(&[&union, &second]).is_err());
+        pipeline.shutdown();
+    }
+
+    #[test]
+    fn union_rejects_foreign_stages() {
+        let mut pipeline = ArconPipeline::new();
+        let mut other = ArconPipeline::new();
+        let first: Stage<u64> = pipeline.stage(1);
+        let foreign: Stage<u64> = other.stage(1);
+
+        assert!(pipeline.union(&[&first, &foreign]).is_err());
+        pipeline.shutdown();
+        other.shutdown();
+    }
+
+    #[test]
+    fn union_feeds_one_node() {
+        let mut pipeline = ArconPipeline::new();
+        let input: Stage<i32> = pipeline.stage(1);
+        let first: Stage<i32> = pipeline.stage(1);
+        let second: Stage<i32> = pipeline.stage(1);
+        let union = pipeline.union(&[&first, &second]).unwrap();
+        let merged: Stage<i32> = pipeline.stage(1);
+
+        let sink = pipeline.system().create(move || DebugNode::<i32>::new());
+        pipeline.system().start(&sink);
+        let sink_ref: ActorRefStrong<ArconMessage<i32>> =
+            sink.actor_ref().hold().expect("Failed to fetch");
+
+        // The merging Node only accepts events from the Nodes of the union
+        let pool_info = pipeline.get_pool_info();
+        let merge_node = pipeline
+            .create_stage_node_manager(
+                String::from("merge_node"),
+                &filter_node,
+                &union,
+                &merged,
+                |id| {
+                    ChannelStrategy::Forward(Forward::new(
+                        Channel::Local(sink_ref.clone()),
+                        id,
+                        pool_info.clone(),
+                    ))
+                },
+                state::BackendType::InMemory,
+            )
+            .unwrap();
+        let merge_ref: ActorRefStrong<ArconMessage<i32>> =
+            merge_node[0].actor_ref().hold().expect("Failed to fetch");
+
+        let mut upstream = Vec::new();
+        for stage in &[&first, &second] {
+            let id = stage.ids()[0];
+            let channel_strategy = ChannelStrategy::Forward(Forward::new(
+                Channel::Local(merge_ref.clone()),
+                id,
+                pipeline.get_pool_info(),
+            ));
+            let node = filter_node(
+                String::from("upstream_node"),
+                id,
+                input.in_channels(),
+                channel_strategy,
+                state::BackendType::InMemory,
+            );
+            let node = pipeline.system().create_erased(node);
+            pipeline.system().start(&node);
+            upstream.push(node);
+        }
+
+        let input_id = input.ids()[0];
+        upstream[0]
+            .actor_ref()
+            .tell(ArconMessage::element(1, Some(1), input_id));
+        upstream[0]
+            .actor_ref()
+            .tell(ArconMessage::watermark(5, input_id));
+        upstream[1]
+            .actor_ref()
+            .tell(ArconMessage::element(2, Some(1), input_id));
+        upstream[1]
+            .actor_ref()
+            .tell(ArconMessage::watermark(3, input_id));
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        sink.on_definition(|cd| {
+            let mut data: Vec<i32> = cd.data.iter().map(|e| e.data).collect();
+            data.sort();
+            assert_eq!(data, vec![1, 2]);
+            // The lowest watermark of the two upstream Nodes
+            let timestamps: Vec<u64> = cd.watermarks.iter().map(|w| w.timestamp).collect();
+            assert_eq!(timestamps, vec![3]);
+        });
+        pipeline.shutdown();
+    }
+
+    #[test]
+    fn operator_stage_fuses_operators() {
+        let mut pipeline = ArconPipeline::new();
+        let input: Stage<i32> = pipeline.stage(1);
+        let fused: Stage<i32> = pipeline.stage(1);
+
+        let sink = pipeline.system().create(move || DebugNode::<i32>::new());
+        pipeline.system().start(&sink);
+        let sink_ref: ActorRefStrong<ArconMessage<i32>> =
+            sink.actor_ref().hold().expect("Failed to fetch");
+
+        fn double(x: i32) -> i32 {
+            x * 2
+        }
+        fn is_positive(x: &i32) -> bool {
+            *x > 0
+        }
+        let operators = OperatorStage::new(|| Map::new(&double)).then(|| Filter::new(&is_positive));
+
+        let pool_info = pipeline.get_pool_info();
+        let nodes = pipeline
+            .create_operator_stage_node_manager::<InMemory, _, _>(
+                String::from("fused_node"),
+                operators,
+                &input,
+                &fused,
+                |id| {
+                    ChannelStrategy::Forward(Forward::new(
+                        Channel::Local(sink_ref.clone()),
+                        id,
+                        pool_info.clone(),
+                    ))
+                },
+            )
+            .unwrap();
+        // Both operators run in the single Node of the stage
+        assert_eq!(nodes.len(), 1);
+
+        let input_id = input.ids()[0];
+        for x in &[-1, 2, 3] {
+            nodes[0]
+                .actor_ref()
+                .tell(ArconMessage::element(*x, Some(1), input_id));
+        }
+        nodes[0]
+            .actor_ref()
+            .tell(ArconMessage::watermark(2, input_id));
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        sink.on_definition(|cd| {
+            let data: Vec<i32> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(data, vec![4, 6]);
+        });
+        pipeline.shutdown();
+    }
+}
+
 #[cfg(feature = "arcon_tui")]
 pub(crate) mod tui_helpers {
     pub(crate) use crate::tui::{
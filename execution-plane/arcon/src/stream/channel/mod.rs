@@ -3,9 +3,15 @@
 
 /// Available Channel Strategies
 pub mod strategy;
+/// Remote channels secured with TLS and mutual authentication
+pub mod tls;
 
-use crate::data::{flight_serde::FlightSerde, ArconMessage, ArconType};
+use crate::data::{
+    flight_serde::{Compression, FlightSerde},
+    ArconMessage, ArconType,
+};
 use kompact::prelude::{ActorPath, ActorRefStrong};
+use tls::TlsRemote;
 
 /// A Channel represents a connection to another Component
 #[derive(Clone)]
@@ -14,4 +20,8 @@ pub enum Channel<A: ArconType> {
     Local(ActorRefStrong<ArconMessage<A>>),
     /// Remote based queue containing a remote ActorPath identifier and an Arcon Serialiser
     Remote(ActorPath, FlightSerde),
+    /// Remote based queue that compresses the serialised messages with the given codec
+    CompressedRemote(ActorPath, FlightSerde, Compression),
+    /// Remote based queue that sends the serialised messages over TLS with mutual authentication
+    Secure(TlsRemote, FlightSerde),
 }
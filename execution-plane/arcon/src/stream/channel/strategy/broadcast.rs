@@ -4,9 +4,14 @@
 use crate::{
     buffer::event::{BufferPool, BufferWriter, PoolInfo},
     data::{ArconEvent, ArconEventWrapper, ArconMessage, ArconType, NodeID},
-    stream::channel::{strategy::send, Channel},
+    metrics::memory::BufferPoolMetrics,
+    stream::channel::{
+        strategy::{next_writer, send},
+        Channel,
+    },
 };
-use kompact::prelude::{ComponentDefinition, SerError};
+use arcon_error::*;
+use kompact::prelude::ComponentDefinition;
 
 /// A Broadcast strategy for one-to-many message sending
 #[allow(dead_code)]
@@ -24,6 +29,8 @@ where
     sender_id: NodeID,
     /// Struct holding information regarding the BufferPool
     pool_info: PoolInfo,
+    /// The error that made the strategy stop sending
+    failure: Option<Error>,
 }
 
 impl<A> Broadcast<A>
@@ -45,7 +52,9 @@ where
             pool_info.buffer_size,
             pool_info.allocator.clone(),
         )
-        .expect("failed to initialise BufferPool");
+        .expect("failed to initialise BufferPool")
+        .with_limit(pool_info.limit)
+        .with_wait_timeout(pool_info.wait_timeout);
 
         let curr_buffer = buffer_pool
             .try_get()
@@ -57,6 +66,7 @@ where
             curr_buffer,
             sender_id,
             pool_info,
+            failure: None,
         }
     }
 
@@ -65,6 +75,9 @@ where
     where
         CD: ComponentDefinition + Sized + 'static,
     {
+        if self.failure.is_some() {
+            return;
+        }
         if let ArconEvent::Element(_) = &event {
             if let Some(e) = self.curr_buffer.push(event.into()) {
                 // buffer is full, flush.
@@ -87,42 +100,56 @@ where
     where
         CD: ComponentDefinition + Sized + 'static,
     {
-        for (i, channel) in self.channels.iter().enumerate() {
-            if i == self.channels.len() - 1 {
-                // This is the last channel, thus we can use curr_buffer
-                let reader = self.curr_buffer.reader();
-                let msg = ArconMessage {
-                    events: reader,
-                    sender: self.sender_id,
-                };
-                if let Err(SerError::BufferError(err)) = send(channel, msg, source) {
-                    // TODO: Figure out how to get more space for `tell_serialised`
-                    panic!(format!("Buffer Error {}", err));
-                };
-            } else {
-                // Get a new writer
-                let mut writer = self.buffer_pool.get();
-                // Copy data from our current writer into the new writer...
-                writer.copy_from_writer(&self.curr_buffer);
-                let msg = ArconMessage {
-                    events: writer.reader(),
-                    sender: self.sender_id,
-                };
-                if let Err(SerError::BufferError(err)) = send(channel, msg, source) {
-                    // TODO: Figure out how to get more space for `tell_serialised`
-                    panic!(format!("Buffer Error {}", err));
-                };
-            }
+        if self.curr_buffer.len() == 0 || self.failure.is_some() {
+            return;
+        }
+        if let Err(err) = self.try_flush(source) {
+            self.failure = Some(err);
+        }
+    }
+
+    fn try_flush<CD>(&mut self, source: &CD) -> ArconResult<()>
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let last = self.channels.len() - 1;
+        for channel in &self.channels[..last] {
+            // Get a new writer
+            let mut writer = self.buffer_pool.get()?;
+            // Copy data from our current writer into the new writer...
+            writer.copy_from_writer(&self.curr_buffer);
+            let msg = ArconMessage {
+                events: writer.reader(),
+                sender: self.sender_id,
+            };
+            send(channel, msg, source)?;
         }
-        // We are finished, set a new BufferWriter to curr_buffer
-        // TODO: Should probably not busy wait here..
-        self.curr_buffer = self.buffer_pool.get();
+        // This is the last channel, thus we can use curr_buffer
+        // once a new BufferWriter has been set in its place
+        let full = next_writer(&mut self.buffer_pool, &mut self.curr_buffer)?;
+        let msg = ArconMessage {
+            events: full.reader(),
+            sender: self.sender_id,
+        };
+        send(&self.channels[last], msg, source)
     }
 
     #[inline]
     pub fn num_channels(&self) -> usize {
         self.channels.len()
     }
+
+    /// Takes the error that made the strategy stop sending, if any
+    #[inline]
+    pub(crate) fn take_failure(&mut self) -> Option<Error> {
+        self.failure.take()
+    }
+
+    /// Returns the metrics of the strategy's BufferPool
+    #[inline]
+    pub fn pool_metrics(&self) -> &BufferPoolMetrics {
+        self.buffer_pool.metrics()
+    }
 }
 
 #[cfg(test)]
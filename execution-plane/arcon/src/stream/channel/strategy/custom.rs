@@ -0,0 +1,210 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    buffer::event::PoolInfo,
+    data::{ArconEvent, ArconType, NodeID},
+    metrics::memory::BufferPoolMetrics,
+    stream::channel::{strategy::ChannelWriters, Channel},
+    util::SafelySendableFn,
+};
+use kompact::prelude::ComponentDefinition;
+
+/// A Channel Strategy that partitions data with a user-defined function
+///
+/// The partitioner is given an element and the number of Channels, and returns the index
+/// of the Channel the element is sent to. Indices past the last Channel wrap around, so
+/// the element goes to Channel `index % num_channels`. Watermarks and epochs are sent to
+/// all Channels.
+pub struct Custom<A>
+where
+    A: ArconType,
+{
+    /// Channels and their respective outgoing buffer
+    writers: ChannelWriters<A>,
+    /// Function picking the Channel of an element
+    partitioner: &'static dyn SafelySendableFn(&A, usize) -> usize,
+}
+
+impl<A> Custom<A>
+where
+    A: ArconType,
+{
+    /// Creates a Custom strategy
+    pub fn new(
+        partitioner: &'static dyn SafelySendableFn(&A, usize) -> usize,
+        channels: Vec<Channel<A>>,
+        sender_id: NodeID,
+        pool_info: PoolInfo,
+    ) -> Custom<A> {
+        assert!(
+            !channels.is_empty(),
+            "A Custom strategy requires at least one Channel"
+        );
+
+        Custom {
+            writers: ChannelWriters::new(channels, sender_id, pool_info),
+            partitioner,
+        }
+    }
+
+    #[inline]
+    pub fn add<CD>(&mut self, event: ArconEvent<A>, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        match &event {
+            ArconEvent::Element(element) => {
+                let channels = self.writers.len();
+                let index = (self.partitioner)(&element.data, channels) % channels;
+                self.writers.push(index, event, source);
+            }
+            _ => {
+                // Push watermark/epoch into all outgoing buffers
+                self.writers.push_all(event, source);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn flush<CD>(&mut self, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        self.writers.flush(source);
+    }
+
+    #[inline]
+    pub fn num_channels(&self) -> usize {
+        self.writers.len()
+    }
+
+    /// Takes the error that made the strategy stop sending, if any
+    #[inline]
+    pub(crate) fn take_failure(&mut self) -> Option<arcon_error::Error> {
+        self.writers.take_failure()
+    }
+
+    /// Returns the metrics of the strategy's BufferPool
+    #[inline]
+    pub fn pool_metrics(&self) -> &BufferPoolMetrics {
+        self.writers.pool_metrics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data::ArconElement,
+        pipeline::ArconPipeline,
+        prelude::{ChannelStrategy, DebugNode},
+        stream::channel::strategy::tests::*,
+    };
+    use kompact::prelude::*;
+    use std::sync::Arc;
+
+    // Route by the lowest digit of the id, e.g., a region code
+    fn by_region(input: &Input, channels: usize) -> usize {
+        (input.id % 10) as usize % channels
+    }
+
+    #[test]
+    fn custom_partitioner_test() {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let components: usize = 4;
+
+        let mut channels: Vec<Channel<Input>> = Vec::new();
+        let mut comps: Vec<Arc<crate::prelude::Component<DebugNode<Input>>>> = Vec::new();
+
+        for _i in 0..components {
+            let comp = system.create(move || DebugNode::<Input>::new());
+            system.start(&comp);
+            let actor_ref: ActorRefStrong<ArconMessage<Input>> =
+                comp.actor_ref().hold().expect("failed to fetch");
+            channels.push(Channel::Local(actor_ref));
+            comps.push(comp);
+        }
+
+        let mut channel_strategy: ChannelStrategy<Input> =
+            ChannelStrategy::Custom(Custom::new(&by_region, channels, NodeID::new(1), pool_info));
+
+        let src_comp = &comps[0];
+        src_comp.on_definition(|cd| {
+            for id in 0..100 {
+                let elem = ArconElement::new(Input { id });
+                channel_strategy.add(ArconEvent::Element(elem), cd);
+            }
+            channel_strategy.flush(cd);
+        });
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        for (index, comp) in comps.into_iter().enumerate() {
+            comp.on_definition(|cd| {
+                assert!(cd.data.len() > 0);
+                assert!(cd
+                    .data
+                    .iter()
+                    .all(|e| by_region(&e.data, components) == index));
+            });
+        }
+
+        pipeline.shutdown();
+    }
+
+    // Ignores the number of channels and may return any index
+    fn by_id(input: &Input, _channels: usize) -> usize {
+        input.id as usize
+    }
+
+    #[test]
+    fn custom_partitioner_index_wraps_around() {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let components: usize = 3;
+
+        let mut channels: Vec<Channel<Input>> = Vec::new();
+        let mut comps: Vec<Arc<crate::prelude::Component<DebugNode<Input>>>> = Vec::new();
+
+        for _i in 0..components {
+            let comp = system.create(move || DebugNode::<Input>::new());
+            system.start(&comp);
+            let actor_ref: ActorRefStrong<ArconMessage<Input>> =
+                comp.actor_ref().hold().expect("failed to fetch");
+            channels.push(Channel::Local(actor_ref));
+            comps.push(comp);
+        }
+
+        let mut channel_strategy: ChannelStrategy<Input> =
+            ChannelStrategy::Custom(Custom::new(&by_id, channels, NodeID::new(1), pool_info));
+
+        let src_comp = &comps[0];
+        src_comp.on_definition(|cd| {
+            for id in 0..10 {
+                let elem = ArconElement::new(Input { id });
+                channel_strategy.add(ArconEvent::Element(elem), cd);
+            }
+            channel_strategy.flush(cd);
+        });
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        for (index, comp) in comps.into_iter().enumerate() {
+            comp.on_definition(|cd| {
+                let ids: Vec<u32> = cd.data.iter().map(|e| e.data.id).collect();
+                let expected: Vec<u32> = (0..10)
+                    .filter(|id| *id as usize % components == index)
+                    .collect();
+                assert_eq!(ids, expected);
+            });
+        }
+
+        pipeline.shutdown();
+    }
+}
@@ -4,9 +4,13 @@
 use crate::{
     buffer::event::{BufferPool, BufferWriter, PoolInfo},
     prelude::*,
-    stream::channel::{strategy::send, Channel},
+    stream::channel::{
+        strategy::{next_writer, send},
+        Channel,
+    },
 };
-use kompact::prelude::{ComponentDefinition, SerError};
+use arcon_error::Error;
+use kompact::prelude::ComponentDefinition;
 
 /// `Forward` is a one-to-one channel strategy between two components
 #[allow(dead_code)]
@@ -24,6 +28,8 @@ where
     sender_id: NodeID,
     /// Struct holding information regarding the BufferPool
     pool_info: PoolInfo,
+    /// The error that made the strategy stop sending
+    failure: Option<Error>,
 }
 
 impl<A> Forward<A>
@@ -37,7 +43,9 @@ where
             pool_info.buffer_size,
             pool_info.allocator.clone(),
         )
-        .expect("failed to initialise buffer pool");
+        .expect("failed to initialise buffer pool")
+        .with_limit(pool_info.limit)
+        .with_wait_timeout(pool_info.wait_timeout);
 
         let curr_buffer = buffer_pool
             .try_get()
@@ -48,6 +56,7 @@ where
             channel,
             sender_id,
             pool_info,
+            failure: None,
         }
     }
 
@@ -56,6 +65,9 @@ where
     where
         CD: ComponentDefinition + Sized + 'static,
     {
+        if self.failure.is_some() {
+            return;
+        }
         if let ArconEvent::Element(_) = &event {
             if let Some(e) = self.curr_buffer.push(event.into()) {
                 // buffer is full, flush.
@@ -82,19 +94,32 @@ where
     where
         CD: ComponentDefinition + Sized + 'static,
     {
-        let reader = self.curr_buffer.reader();
-        let msg = ArconMessage {
-            events: reader,
-            sender: self.sender_id,
-        };
-
-        if let Err(SerError::BufferError(err)) = send(&self.channel, msg, source) {
-            // TODO: Figure out how to get more space for `tell_serialised`
-            panic!(format!("Buffer Error {}", err));
-        };
-
-        // TODO: Should probably not busy wait here..
-        self.curr_buffer = self.buffer_pool.get();
+        if self.curr_buffer.len() == 0 || self.failure.is_some() {
+            return;
+        }
+
+        let result = next_writer(&mut self.buffer_pool, &mut self.curr_buffer).and_then(|full| {
+            let msg = ArconMessage {
+                events: full.reader(),
+                sender: self.sender_id,
+            };
+            send(&self.channel, msg, source)
+        });
+        if let Err(err) = result {
+            self.failure = Some(err);
+        }
+    }
+
+    /// Takes the error that made the strategy stop sending, if any
+    #[inline]
+    pub(crate) fn take_failure(&mut self) -> Option<Error> {
+        self.failure.take()
+    }
+
+    /// Returns the metrics of the strategy's BufferPool
+    #[inline]
+    pub fn pool_metrics(&self) -> &BufferPoolMetrics {
+        self.buffer_pool.metrics()
     }
 }
 
@@ -132,4 +157,29 @@ mod tests {
         });
         let _ = pipeline.shutdown();
     }
+
+    #[test]
+    fn forward_fails_when_pool_is_exhausted() {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = PoolInfo::new(1, 2, 2, pipeline.allocator())
+            .with_wait_timeout(std::time::Duration::from_millis(10));
+        let system = pipeline.system();
+
+        let comp = system.create(move || DebugNode::<Input>::new());
+        system.start(&comp);
+        let actor_ref: ActorRefStrong<ArconMessage<Input>> =
+            comp.actor_ref().hold().expect("failed to fetch");
+        let mut channel_strategy: ChannelStrategy<Input> =
+            ChannelStrategy::Forward(Forward::new(Channel::Local(actor_ref), 1.into(), pool_info));
+
+        // The receiver cannot release buffers while its definition is borrowed
+        comp.on_definition(|cd| {
+            for _i in 0..3 {
+                let elem = ArconElement::new(Input { id: 1 });
+                channel_strategy.add(ArconEvent::Element(elem), cd);
+            }
+        });
+        assert!(channel_strategy.take_failure().is_some());
+        let _ = pipeline.shutdown();
+    }
 }
@@ -2,75 +2,136 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 
 use crate::{
-    buffer::event::{BufferPool, BufferWriter, PoolInfo},
+    buffer::event::PoolInfo,
     data::{ArconEvent, ArconType},
     prelude::*,
-    stream::channel::strategy::send,
+    stream::channel::strategy::ChannelWriters,
 };
-use fxhash::FxHashMap;
-use kompact::prelude::{ComponentDefinition, SerError};
+use kompact::prelude::ComponentDefinition;
+use std::{collections::BTreeMap, hash::Hasher};
+use twox_hash::XxHash64;
+
+/// Number of points that every channel occupies on the hash ring of [KeyGroups]
+const VIRTUAL_NODES: u64 = 64;
+
+/// Assignment of keys to a fixed number of key groups, and of key groups to channels
+///
+/// A key always belongs to the same key group, regardless of the number of channels, and
+/// key groups are mapped to channels through a consistent-hashing ring. Adding a channel
+/// therefore only moves the key groups that the new channel takes over, and removing the
+/// last channel only moves the key groups it owned. The assignment depends on nothing but
+/// the number of key groups and channels, so other components, such as those placing
+/// keyed state, can compute it on their own.
+#[derive(Debug, Clone)]
+pub struct KeyGroups {
+    /// Channel index of every key group
+    assignment: Vec<usize>,
+}
+
+impl KeyGroups {
+    /// Creates the assignment of `key_groups` key groups onto `channels` channels
+    pub fn new(key_groups: u64, channels: usize) -> KeyGroups {
+        assert!(key_groups > 0, "There must be at least one key group");
+        assert!(channels > 0, "There must be at least one channel");
+
+        let mut ring = BTreeMap::new();
+        for channel in 0..channels {
+            for virtual_node in 0..VIRTUAL_NODES {
+                ring.insert(Self::hash(0, &[channel as u64, virtual_node]), channel);
+            }
+        }
+
+        let assignment = (0..key_groups)
+            .map(|key_group| {
+                let point = Self::hash(1, &[key_group]);
+                // The first channel clockwise from the key group on the ring
+                let (_, channel) = ring
+                    .range(point..)
+                    .next()
+                    .or_else(|| ring.iter().next())
+                    .expect("ring cannot be empty");
+                *channel
+            })
+            .collect();
+
+        KeyGroups { assignment }
+    }
+
+    fn hash(seed: u64, values: &[u64]) -> u64 {
+        let mut hasher = XxHash64::with_seed(seed);
+        for value in values {
+            hasher.write_u64(*value);
+        }
+        hasher.finish()
+    }
+
+    /// Returns the number of key groups
+    #[inline]
+    pub fn key_groups(&self) -> u64 {
+        self.assignment.len() as u64
+    }
+
+    /// Returns the key group of `key`, as given by [ArconType::get_key]
+    #[inline]
+    pub fn key_group(&self, key: u64) -> u64 {
+        key % self.key_groups()
+    }
+
+    /// Returns the index of the channel that owns `key_group`
+    #[inline]
+    pub fn channel(&self, key_group: u64) -> usize {
+        self.assignment[key_group as usize]
+    }
+
+    /// Returns the index of the channel that owns `key`
+    #[inline]
+    pub fn channel_of_key(&self, key: u64) -> usize {
+        self.channel(self.key_group(key))
+    }
+
+    /// Returns the key groups owned by the channel at `index`
+    pub fn key_groups_of(&self, index: usize) -> impl Iterator<Item = u64> + '_ {
+        self.assignment
+            .iter()
+            .enumerate()
+            .filter(move |(_, channel)| **channel == index)
+            .map(|(key_group, _)| key_group as u64)
+    }
+}
 
 /// A Channel Strategy for Keyed Data Streams
 ///
-/// Data is split onto a contiguous key space containing N key ranges.
+/// Keys are hashed into a fixed number of key groups, which are spread over the channels
+/// as described by [KeyGroups].
 pub struct KeyBy<A>
 where
     A: ArconType,
 {
-    /// A buffer pool of EventBuffer's
-    buffer_pool: BufferPool<ArconEventWrapper<A>>,
-    /// The highest possible key value
+    /// Assignment of keys to channels
     ///
-    /// This should not be set too low or ridiculously high
-    max_key: u64,
-    /// Number of ranges on the contiguous key space
-    key_ranges: u64,
-    /// An identifier that is embedded with outgoing messages
-    sender_id: NodeID,
-    /// A map with a key range id and its respective Channel/Buffer
-    buffer_map: FxHashMap<usize, (Channel<A>, BufferWriter<ArconEventWrapper<A>>)>,
-    /// Struct holding information regarding the BufferPool
-    _pool_info: PoolInfo,
+    /// The number of key groups bounds the parallelism the stream can be rescaled to, so
+    /// it should not be set too low or ridiculously high
+    key_groups: KeyGroups,
+    /// Channels and their respective outgoing buffer, indexed by [KeyGroups::channel]
+    writers: ChannelWriters<A>,
 }
 
 impl<A> KeyBy<A>
 where
     A: ArconType,
 {
-    /// Creates a KeyBy strategy
+    /// Creates a KeyBy strategy that spreads `key_groups` key groups over `channels`
     pub fn new(
-        max_key: u64,
+        key_groups: u64,
         channels: Vec<Channel<A>>,
         sender_id: NodeID,
         pool_info: PoolInfo,
     ) -> KeyBy<A> {
-        let channels_len: u64 = channels.len() as u64;
-        assert!(
-            channels.len() < pool_info.capacity,
-            "Strategy must be initialised with a pool capacity larger than amount of channels"
-        );
-        let mut buffer_pool: BufferPool<ArconEventWrapper<A>> = BufferPool::new(
-            pool_info.capacity,
-            pool_info.buffer_size,
-            pool_info.allocator.clone(),
-        )
-        .expect("failed to initialise BufferPool");
-
-        let mut buffer_map = FxHashMap::default();
-        for (i, channel) in channels.into_iter().enumerate() {
-            let writer = buffer_pool
-                .try_get()
-                .expect("failed to fetch initial buffer");
-            buffer_map.insert(i, (channel, writer));
-        }
+        let key_groups = KeyGroups::new(key_groups, channels.len());
 
         KeyBy {
-            buffer_pool,
-            key_ranges: channels_len,
-            max_key,
-            sender_id,
-            buffer_map,
-            _pool_info: pool_info,
+            key_groups,
+            writers: ChannelWriters::new(channels, sender_id, pool_info),
         }
     }
 
@@ -81,45 +142,12 @@ where
     {
         match &event {
             ArconEvent::Element(element) => {
-                // Get key placement
-                let key = element.data.get_key() % self.max_key;
-                // Calculate which key range index is responsible for this key
-                let index = (key * self.key_ranges / self.max_key) as usize;
-
-                if let Some((chan, buffer)) = self.buffer_map.get_mut(&index) {
-                    if let Some(e) = buffer.push(event.into()) {
-                        // buffer is full
-                        Self::flush_buffer(
-                            self.sender_id,
-                            &mut self.buffer_pool,
-                            chan,
-                            buffer,
-                            source,
-                        );
-                        // This push should now not fail
-                        let _ = buffer.push(e);
-                    }
-                } else {
-                    panic!("Bad KeyBy setup");
-                }
+                let index = self.key_groups.channel_of_key(element.data.get_key());
+                self.writers.push(index, event, source);
             }
             _ => {
                 // Push watermark/epoch into all outgoing buffers
-                for (_, (chan, buffer)) in self.buffer_map.iter_mut() {
-                    if let Some(e) = buffer.push(event.clone().into()) {
-                        // buffer is full...
-                        Self::flush_buffer(
-                            self.sender_id,
-                            &mut self.buffer_pool,
-                            chan,
-                            buffer,
-                            source,
-                        );
-                        // This push should now not fail
-                        let _ = buffer.push(e);
-                    }
-                }
-                self.flush(source);
+                self.writers.push_all(event, source);
             }
         }
     }
@@ -129,43 +157,30 @@ where
     where
         CD: ComponentDefinition + Sized + 'static,
     {
-        for (_, (ref channel, buffer)) in self.buffer_map.iter_mut() {
-            Self::flush_buffer(
-                self.sender_id,
-                &mut self.buffer_pool,
-                channel,
-                buffer,
-                source,
-            );
-        }
+        self.writers.flush(source);
     }
 
-    // Helper function to reduce duplication of code...
-    #[inline(always)]
-    fn flush_buffer<CD>(
-        sender_id: NodeID,
-        buffer_pool: &mut BufferPool<ArconEventWrapper<A>>,
-        channel: &Channel<A>,
-        writer: &mut BufferWriter<ArconEventWrapper<A>>,
-        source: &CD,
-    ) where
-        CD: ComponentDefinition + Sized + 'static,
-    {
-        let msg = ArconMessage {
-            events: writer.reader(),
-            sender: sender_id,
-        };
-        if let Err(SerError::BufferError(err)) = send(channel, msg, source) {
-            // TODO: Figure out how to get more space for `tell_serialised`
-            panic!(format!("Buffer Error {}", err));
-        };
-        // set a new writer
-        *writer = buffer_pool.get();
+    #[inline]
+    pub fn num_channels(&self) -> usize {
+        self.writers.len()
     }
 
+    /// Takes the error that made the strategy stop sending, if any
     #[inline]
-    pub fn num_channels(&self) -> usize {
-        self.buffer_map.len()
+    pub(crate) fn take_failure(&mut self) -> Option<arcon_error::Error> {
+        self.writers.take_failure()
+    }
+
+    /// Returns the metrics of the strategy's BufferPool
+    #[inline]
+    pub fn pool_metrics(&self) -> &BufferPoolMetrics {
+        self.writers.pool_metrics()
+    }
+
+    /// Returns the assignment of keys to channels
+    #[inline]
+    pub fn key_groups(&self) -> &KeyGroups {
+        &self.key_groups
     }
 }
 
@@ -198,9 +213,9 @@ mod tests {
             comps.push(comp);
         }
 
-        let max_key = 256;
+        let key_groups = 256;
         let mut channel_strategy =
-            ChannelStrategy::KeyBy(KeyBy::new(max_key, channels, NodeID::new(1), pool_info));
+            ChannelStrategy::KeyBy(KeyBy::new(key_groups, channels, NodeID::new(1), pool_info));
 
         let mut rng = rand::thread_rng();
 
@@ -233,4 +248,28 @@ mod tests {
         }
         pipeline.shutdown();
     }
+
+    #[test]
+    fn key_groups_rescale_test() {
+        let key_groups = 1024;
+        let before = KeyGroups::new(key_groups, 8);
+        let after = KeyGroups::new(key_groups, 9);
+
+        for channel in 0..8 {
+            assert!(before.key_groups_of(channel).count() > 0);
+        }
+
+        // Only the key groups taken over by the new channel move
+        let moved: Vec<u64> = (0..key_groups)
+            .filter(|g| before.channel(*g) != after.channel(*g))
+            .collect();
+        assert!(moved.iter().all(|g| after.channel(*g) == 8));
+        assert_eq!(moved.len(), after.key_groups_of(8).count());
+        assert!(moved.len() > 0 && moved.len() < key_groups as usize / 4);
+
+        // The assignment can be recomputed anywhere
+        let again = KeyGroups::new(key_groups, 9);
+        assert!((0..key_groups).all(|g| again.channel(g) == after.channel(g)));
+        assert_eq!(after.key_group(key_groups + 5), 5);
+    }
 }
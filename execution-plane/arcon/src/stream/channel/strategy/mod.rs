@@ -1,18 +1,30 @@
 // Copyright (c) 2020, KTH Royal Institute of Technology.
 // SPDX-License-Identifier: AGPL-3.0-only
 
+#[cfg(feature = "arcon_serde")]
+use crate::data::flight_serde::bincode_remote::BincodeSerde;
 use crate::{
+    buffer::event::{BufferPool, BufferWriter, PoolInfo},
     data::{
-        flight_serde::{reliable_remote::ReliableSerde, unsafe_remote::UnsafeSerde, FlightSerde},
-        ArconEvent, ArconMessage, ArconType,
+        flight_serde::{
+            compressed_remote::CompressedSerde, reliable_remote::ReliableSerde,
+            unsafe_remote::UnsafeSerde, FlightSerde,
+        },
+        ArconEvent, ArconEventWrapper, ArconMessage, ArconType, NodeID,
     },
+    metrics::memory::BufferPoolMetrics,
     stream::channel::Channel,
 };
-use kompact::prelude::{ComponentDefinition, SerError};
+use arcon_error::*;
+use kompact::prelude::{ActorPath, ComponentDefinition, SerError};
 
 pub mod broadcast;
+pub mod custom;
 pub mod forward;
 pub mod key_by;
+pub mod partial_key_grouping;
+pub mod random_shuffle;
+pub mod rebalance;
 pub mod round_robin;
 
 /// A `ChannelStrategy` defines a strategy of how messages are sent downstream
@@ -30,6 +42,14 @@ where
     KeyBy(key_by::KeyBy<A>),
     /// Send messages to a Vec of `Channels` in a Round Robin fashion
     RoundRobin(round_robin::RoundRobin<A>),
+    /// Send every element to a random `Channel`
+    RandomShuffle(random_shuffle::RandomShuffle<A>),
+    /// Send messages to the `Channel` with the fewest outstanding buffers
+    Rebalance(rebalance::Rebalance<A>),
+    /// Split every key across two `Channels` based on keyed hash and load
+    PartialKeyGrouping(partial_key_grouping::PartialKeyGrouping<A>),
+    /// Partition data to a set of `Channels` with a user-defined function
+    Custom(custom::Custom<A>),
     /// A strategy that simply does nothing
     Mute,
 }
@@ -49,6 +69,10 @@ where
             ChannelStrategy::Broadcast(s) => s.add(event, source),
             ChannelStrategy::KeyBy(s) => s.add(event, source),
             ChannelStrategy::RoundRobin(s) => s.add(event, source),
+            ChannelStrategy::RandomShuffle(s) => s.add(event, source),
+            ChannelStrategy::Rebalance(s) => s.add(event, source),
+            ChannelStrategy::PartialKeyGrouping(s) => s.add(event, source),
+            ChannelStrategy::Custom(s) => s.add(event, source),
             ChannelStrategy::Mute => (),
         }
     }
@@ -63,6 +87,10 @@ where
             ChannelStrategy::Broadcast(s) => s.flush(source),
             ChannelStrategy::KeyBy(s) => s.flush(source),
             ChannelStrategy::RoundRobin(s) => s.flush(source),
+            ChannelStrategy::RandomShuffle(s) => s.flush(source),
+            ChannelStrategy::Rebalance(s) => s.flush(source),
+            ChannelStrategy::PartialKeyGrouping(s) => s.flush(source),
+            ChannelStrategy::Custom(s) => s.flush(source),
             ChannelStrategy::Mute => (),
         }
     }
@@ -74,16 +102,56 @@ where
             ChannelStrategy::Broadcast(s) => s.num_channels(),
             ChannelStrategy::KeyBy(s) => s.num_channels(),
             ChannelStrategy::RoundRobin(s) => s.num_channels(),
+            ChannelStrategy::RandomShuffle(s) => s.num_channels(),
+            ChannelStrategy::Rebalance(s) => s.num_channels(),
+            ChannelStrategy::PartialKeyGrouping(s) => s.num_channels(),
+            ChannelStrategy::Custom(s) => s.num_channels(),
             ChannelStrategy::Mute => 0,
         }
     }
+    /// Takes the error that made the strategy stop sending, if any
+    ///
+    /// A strategy fails if its BufferPool releases no buffer within the wait timeout,
+    /// or if a message fails to be sent, see [send]. Until the error is taken,
+    /// it drops the events it is handed, so the Node is expected to fail along with it.
+    #[inline]
+    pub(crate) fn take_failure(&mut self) -> Option<Error> {
+        match self {
+            ChannelStrategy::Forward(s) => s.take_failure(),
+            ChannelStrategy::Broadcast(s) => s.take_failure(),
+            ChannelStrategy::KeyBy(s) => s.take_failure(),
+            ChannelStrategy::RoundRobin(s) => s.take_failure(),
+            ChannelStrategy::RandomShuffle(s) => s.take_failure(),
+            ChannelStrategy::Rebalance(s) => s.take_failure(),
+            ChannelStrategy::PartialKeyGrouping(s) => s.take_failure(),
+            ChannelStrategy::Custom(s) => s.take_failure(),
+            ChannelStrategy::Mute => None,
+        }
+    }
+    /// Returns the metrics of the BufferPool used by the strategy
+    #[inline]
+    pub(crate) fn pool_metrics(&self) -> Option<&BufferPoolMetrics> {
+        match self {
+            ChannelStrategy::Forward(s) => Some(s.pool_metrics()),
+            ChannelStrategy::Broadcast(s) => Some(s.pool_metrics()),
+            ChannelStrategy::KeyBy(s) => Some(s.pool_metrics()),
+            ChannelStrategy::RoundRobin(s) => Some(s.pool_metrics()),
+            ChannelStrategy::RandomShuffle(s) => Some(s.pool_metrics()),
+            ChannelStrategy::Rebalance(s) => Some(s.pool_metrics()),
+            ChannelStrategy::PartialKeyGrouping(s) => Some(s.pool_metrics()),
+            ChannelStrategy::Custom(s) => Some(s.pool_metrics()),
+            ChannelStrategy::Mute => None,
+        }
+    }
 }
 
 /// `send` pushes an ArconMessage onto a Component queue
 ///
-/// The message may be sent to a local or remote component
+/// The message may be sent to a local or remote component.
+/// Fails if the message cannot be serialised or does not fit in the network buffers,
+/// or if a TLS channel fails to send it.
 #[inline]
-fn send<A, CD>(channel: &Channel<A>, message: ArconMessage<A>, source: &CD) -> Result<(), SerError>
+fn send<A, CD>(channel: &Channel<A>, message: ArconMessage<A>, source: &CD) -> ArconResult<()>
 where
     A: ArconType,
     CD: ComponentDefinition + Sized + 'static,
@@ -93,14 +161,231 @@ where
             actor_ref.tell(message);
             Ok(())
         }
-        Channel::Remote(actor_path, FlightSerde::Unsafe) => {
+        Channel::Remote(actor_path, serde) => {
+            tell_remote(actor_path, message, serde, source).map_err(ser_err)
+        }
+        Channel::CompressedRemote(actor_path, serde, compression) => {
+            CompressedSerde::new(message.into(), serde, *compression)
+                .and_then(|compressed_msg| actor_path.tell_serialised(compressed_msg, source))
+                .map_err(ser_err)
+        }
+        Channel::Secure(remote, serde) => {
+            let (ser_id, bytes) = serde.serialise(message.into()).map_err(ser_err)?;
+            remote.send(ser_id, bytes)
+        }
+    }
+}
+
+#[inline]
+fn tell_remote<A, CD>(
+    actor_path: &ActorPath,
+    message: ArconMessage<A>,
+    serde: &FlightSerde,
+    source: &CD,
+) -> Result<(), SerError>
+where
+    A: ArconType,
+    CD: ComponentDefinition + Sized + 'static,
+{
+    match serde {
+        FlightSerde::Unsafe => {
             let unsafe_msg = UnsafeSerde(message.into());
             actor_path.tell_serialised(unsafe_msg, source)
         }
-        Channel::Remote(actor_path, FlightSerde::Reliable) => {
+        FlightSerde::Reliable => {
             let reliable_msg = ReliableSerde(message.into());
             actor_path.tell_serialised(reliable_msg, source)
         }
+        #[cfg(feature = "arcon_serde")]
+        FlightSerde::Bincode => {
+            let bincode_msg = BincodeSerde(message.into());
+            actor_path.tell_serialised(bincode_msg, source)
+        }
+    }
+}
+
+fn ser_err(err: SerError) -> Error {
+    match err {
+        SerError::BufferError(err) => arcon_err_kind!("Buffer Error {}", err),
+        err => arcon_err_kind!("Failed to send message with err {:?}", err),
+    }
+}
+
+/// Replaces `writer` with a new BufferWriter from the BufferPool of a strategy and returns the old one
+///
+/// The new writer is fetched first, so `writer` keeps its events if no buffer
+/// is released within the wait timeout of the pool.
+#[inline]
+fn next_writer<A>(
+    buffer_pool: &mut BufferPool<ArconEventWrapper<A>>,
+    writer: &mut BufferWriter<ArconEventWrapper<A>>,
+) -> ArconResult<BufferWriter<ArconEventWrapper<A>>>
+where
+    A: ArconType,
+{
+    let next = buffer_pool.get()?;
+    Ok(std::mem::replace(writer, next))
+}
+
+/// Channels with an outgoing buffer each, drawn from a shared BufferPool
+///
+/// Used by the strategies that pick one Channel per element and send
+/// watermarks and epochs to all Channels.
+pub(crate) struct ChannelWriters<A>
+where
+    A: ArconType,
+{
+    /// A buffer pool of EventBuffer's
+    buffer_pool: BufferPool<ArconEventWrapper<A>>,
+    /// Channels and their respective outgoing buffer
+    channels: Vec<(Channel<A>, BufferWriter<ArconEventWrapper<A>>)>,
+    /// An identifier that is embedded with outgoing messages
+    sender_id: NodeID,
+    /// Struct holding information regarding the BufferPool
+    _pool_info: PoolInfo,
+    /// The error that made the writers stop sending
+    failure: Option<Error>,
+}
+
+impl<A> ChannelWriters<A>
+where
+    A: ArconType,
+{
+    pub(crate) fn new(
+        channels: Vec<Channel<A>>,
+        sender_id: NodeID,
+        pool_info: PoolInfo,
+    ) -> ChannelWriters<A> {
+        assert!(
+            channels.len() < pool_info.capacity,
+            "Strategy must be initialised with a pool capacity larger than amount of channels"
+        );
+
+        let mut buffer_pool: BufferPool<ArconEventWrapper<A>> = BufferPool::new(
+            pool_info.capacity,
+            pool_info.buffer_size,
+            pool_info.allocator.clone(),
+        )
+        .expect("failed to initialise buffer pool")
+        .with_limit(pool_info.limit)
+        .with_wait_timeout(pool_info.wait_timeout);
+
+        let channels = channels
+            .into_iter()
+            .map(|channel| {
+                let writer = buffer_pool
+                    .try_get()
+                    .expect("failed to fetch initial buffer");
+                (channel, writer)
+            })
+            .collect();
+
+        ChannelWriters {
+            buffer_pool,
+            channels,
+            sender_id,
+            _pool_info: pool_info,
+            failure: None,
+        }
+    }
+
+    /// Adds `event` to the outgoing buffer of the Channel at `index`
+    #[inline]
+    pub(crate) fn push<CD>(&mut self, index: usize, event: ArconEvent<A>, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        if self.failure.is_some() {
+            return;
+        }
+        let (channel, buffer) = &mut self.channels[index];
+        if let Some(e) = buffer.push(event.into()) {
+            // buffer is full
+            if let Err(err) = Self::flush_buffer(
+                self.sender_id,
+                &mut self.buffer_pool,
+                channel,
+                buffer,
+                source,
+            ) {
+                self.failure = Some(err);
+                return;
+            }
+            // This push should now not fail
+            let _ = buffer.push(e);
+        }
+    }
+
+    /// Adds `event` to all outgoing buffers and flushes them
+    #[inline]
+    pub(crate) fn push_all<CD>(&mut self, event: ArconEvent<A>, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        for index in 0..self.channels.len() {
+            self.push(index, event.clone(), source);
+        }
+        self.flush(source);
+    }
+
+    #[inline]
+    pub(crate) fn flush<CD>(&mut self, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        if self.failure.is_some() {
+            return;
+        }
+        for (channel, buffer) in self.channels.iter_mut() {
+            if let Err(err) = Self::flush_buffer(
+                self.sender_id,
+                &mut self.buffer_pool,
+                channel,
+                buffer,
+                source,
+            ) {
+                self.failure = Some(err);
+                return;
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn flush_buffer<CD>(
+        sender_id: NodeID,
+        buffer_pool: &mut BufferPool<ArconEventWrapper<A>>,
+        channel: &Channel<A>,
+        writer: &mut BufferWriter<ArconEventWrapper<A>>,
+        source: &CD,
+    ) -> ArconResult<()>
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        if writer.len() == 0 {
+            return Ok(());
+        }
+
+        let full = next_writer(buffer_pool, writer)?;
+        let msg = ArconMessage {
+            events: full.reader(),
+            sender: sender_id,
+        };
+        send(channel, msg, source)
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    #[inline]
+    pub(crate) fn take_failure(&mut self) -> Option<Error> {
+        self.failure.take()
+    }
+
+    #[inline]
+    pub(crate) fn pool_metrics(&self) -> &BufferPoolMetrics {
+        self.buffer_pool.metrics()
     }
 }
 
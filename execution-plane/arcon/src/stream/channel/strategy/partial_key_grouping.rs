@@ -0,0 +1,183 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    buffer::event::PoolInfo,
+    data::{ArconEvent, ArconType, NodeID},
+    metrics::memory::BufferPoolMetrics,
+    stream::channel::{strategy::ChannelWriters, Channel},
+};
+use kompact::prelude::ComponentDefinition;
+use std::hash::Hasher;
+use twox_hash::XxHash64;
+
+/// A Channel Strategy for Keyed Data Streams with skewed keys
+///
+/// Every key has two candidate Channels, and each element goes to the candidate that
+/// this strategy has sent the fewest elements to so far. A hot key is thereby split
+/// across two Channels, while every key still only touches two of them.
+///
+/// Since the elements of a key may end up on either of its candidates, keyed results
+/// computed downstream of this strategy are partial. They have to be merged by a second
+/// stage behind a [KeyBy](super::key_by::KeyBy) strategy, e.g., partial aggregates
+/// that are combined by the same aggregation again.
+pub struct PartialKeyGrouping<A>
+where
+    A: ArconType,
+{
+    /// Channels and their respective outgoing buffer
+    writers: ChannelWriters<A>,
+    /// Number of elements sent on every Channel
+    sent: Vec<u64>,
+}
+
+impl<A> PartialKeyGrouping<A>
+where
+    A: ArconType,
+{
+    /// Creates a PartialKeyGrouping strategy
+    pub fn new(
+        channels: Vec<Channel<A>>,
+        sender_id: NodeID,
+        pool_info: PoolInfo,
+    ) -> PartialKeyGrouping<A> {
+        assert!(
+            channels.len() > 1,
+            "Number of Channels must exceed 1 for a PartialKeyGrouping strategy"
+        );
+
+        PartialKeyGrouping {
+            sent: vec![0; channels.len()],
+            writers: ChannelWriters::new(channels, sender_id, pool_info),
+        }
+    }
+
+    /// Returns the indexes of the two distinct candidate Channels of `key`
+    ///
+    /// The candidates only depend on the key and the number of Channels.
+    pub fn candidates(key: u64, channels: usize) -> (usize, usize) {
+        let hash = |seed| {
+            let mut hasher = XxHash64::with_seed(seed);
+            hasher.write_u64(key);
+            hasher.finish() as usize
+        };
+        let first = hash(0) % channels;
+        let second = (first + 1 + hash(1) % (channels - 1)) % channels;
+        (first, second)
+    }
+
+    #[inline]
+    pub fn add<CD>(&mut self, event: ArconEvent<A>, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        match &event {
+            ArconEvent::Element(element) => {
+                let (first, second) = Self::candidates(element.data.get_key(), self.sent.len());
+                let index = if self.sent[second] < self.sent[first] {
+                    second
+                } else {
+                    first
+                };
+
+                self.sent[index] += 1;
+                self.writers.push(index, event, source);
+            }
+            _ => {
+                // Push watermark/epoch into all outgoing buffers
+                self.writers.push_all(event, source);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn flush<CD>(&mut self, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        self.writers.flush(source);
+    }
+
+    #[inline]
+    pub fn num_channels(&self) -> usize {
+        self.writers.len()
+    }
+
+    /// Takes the error that made the strategy stop sending, if any
+    #[inline]
+    pub(crate) fn take_failure(&mut self) -> Option<arcon_error::Error> {
+        self.writers.take_failure()
+    }
+
+    /// Returns the metrics of the strategy's BufferPool
+    #[inline]
+    pub fn pool_metrics(&self) -> &BufferPoolMetrics {
+        self.writers.pool_metrics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data::ArconElement,
+        pipeline::ArconPipeline,
+        prelude::{ChannelStrategy, DebugNode},
+        stream::channel::strategy::tests::*,
+    };
+    use kompact::prelude::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn partial_key_grouping_test() {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let components: usize = 4;
+        let total_msgs: usize = 1000;
+
+        let mut channels: Vec<Channel<Input>> = Vec::new();
+        let mut comps: Vec<Arc<crate::prelude::Component<DebugNode<Input>>>> = Vec::new();
+
+        for _i in 0..components {
+            let comp = system.create(move || DebugNode::<Input>::new());
+            system.start(&comp);
+            let actor_ref: ActorRefStrong<ArconMessage<Input>> =
+                comp.actor_ref().hold().expect("failed to fetch");
+            channels.push(Channel::Local(actor_ref));
+            comps.push(comp);
+        }
+
+        let mut channel_strategy: ChannelStrategy<Input> = ChannelStrategy::PartialKeyGrouping(
+            PartialKeyGrouping::new(channels, NodeID::new(1), pool_info),
+        );
+
+        // A single hot key
+        let hot = Input { id: 1 };
+        let (first, second) = PartialKeyGrouping::<Input>::candidates(hot.get_key(), components);
+
+        let src_comp = &comps[0];
+        src_comp.on_definition(|cd| {
+            for _i in 0..total_msgs {
+                let elem = ArconElement::new(hot.clone());
+                channel_strategy.add(ArconEvent::Element(elem), cd);
+            }
+            channel_strategy.flush(cd);
+        });
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        for (index, comp) in comps.into_iter().enumerate() {
+            comp.on_definition(|cd| {
+                if index == first || index == second {
+                    assert_eq!(cd.data.len(), total_msgs / 2);
+                } else {
+                    assert_eq!(cd.data.len(), 0);
+                }
+            });
+        }
+
+        pipeline.shutdown();
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    buffer::event::PoolInfo,
+    data::{ArconEvent, ArconType, NodeID},
+    metrics::memory::BufferPoolMetrics,
+    stream::channel::{strategy::ChannelWriters, Channel},
+};
+use kompact::prelude::ComponentDefinition;
+use rand::{rngs::SmallRng, FromEntropy, Rng};
+
+/// A strategy that sends every element to a uniformly random Channel
+///
+/// Watermarks and epochs are sent to all Channels.
+pub struct RandomShuffle<A>
+where
+    A: ArconType,
+{
+    /// Channels and their respective outgoing buffer
+    writers: ChannelWriters<A>,
+    /// Source of the Channel choices
+    rng: SmallRng,
+}
+
+impl<A> RandomShuffle<A>
+where
+    A: ArconType,
+{
+    /// Creates a RandomShuffle strategy
+    pub fn new(
+        channels: Vec<Channel<A>>,
+        sender_id: NodeID,
+        pool_info: PoolInfo,
+    ) -> RandomShuffle<A> {
+        assert!(
+            channels.len() > 1,
+            "Number of Channels must exceed 1 for a RandomShuffle strategy"
+        );
+
+        RandomShuffle {
+            writers: ChannelWriters::new(channels, sender_id, pool_info),
+            rng: SmallRng::from_entropy(),
+        }
+    }
+
+    #[inline]
+    pub fn add<CD>(&mut self, event: ArconEvent<A>, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        if let ArconEvent::Element(_) = &event {
+            let index = self.rng.gen_range(0, self.writers.len());
+            self.writers.push(index, event, source);
+        } else {
+            // Push watermark/epoch into all outgoing buffers
+            self.writers.push_all(event, source);
+        }
+    }
+
+    #[inline]
+    pub fn flush<CD>(&mut self, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        self.writers.flush(source);
+    }
+
+    #[inline]
+    pub fn num_channels(&self) -> usize {
+        self.writers.len()
+    }
+
+    /// Takes the error that made the strategy stop sending, if any
+    #[inline]
+    pub(crate) fn take_failure(&mut self) -> Option<arcon_error::Error> {
+        self.writers.take_failure()
+    }
+
+    /// Returns the metrics of the strategy's BufferPool
+    #[inline]
+    pub fn pool_metrics(&self) -> &BufferPoolMetrics {
+        self.writers.pool_metrics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data::{ArconElement, Watermark},
+        pipeline::ArconPipeline,
+        prelude::{ChannelStrategy, DebugNode},
+        stream::channel::strategy::tests::*,
+    };
+    use kompact::prelude::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn random_shuffle_test() {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let components: u64 = 4;
+        let total_msgs: u64 = 1000;
+
+        let mut channels: Vec<Channel<Input>> = Vec::new();
+        let mut comps: Vec<Arc<crate::prelude::Component<DebugNode<Input>>>> = Vec::new();
+
+        for _i in 0..components {
+            let comp = system.create(move || DebugNode::<Input>::new());
+            system.start(&comp);
+            let actor_ref: ActorRefStrong<ArconMessage<Input>> =
+                comp.actor_ref().hold().expect("failed to fetch");
+            channels.push(Channel::Local(actor_ref));
+            comps.push(comp);
+        }
+
+        let mut channel_strategy: ChannelStrategy<Input> =
+            ChannelStrategy::RandomShuffle(RandomShuffle::new(channels, NodeID::new(1), pool_info));
+
+        // take one comp as channel source
+        // just for testing...
+        let src_comp = &comps[0];
+        src_comp.on_definition(|cd| {
+            for i in 0..total_msgs {
+                let elem = ArconElement::new(Input { id: i as u32 });
+                channel_strategy.add(ArconEvent::Element(elem), cd);
+            }
+            channel_strategy.add(ArconEvent::Watermark(Watermark::new(1)), cd);
+        });
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let mut received = 0;
+        for comp in comps {
+            comp.on_definition(|cd| {
+                // Every Channel should at least get some hits
+                assert!(cd.data.len() > 0);
+                assert_eq!(cd.watermarks.len(), 1);
+                received += cd.data.len() as u64;
+            });
+        }
+        assert_eq!(received, total_msgs);
+
+        pipeline.shutdown();
+    }
+}
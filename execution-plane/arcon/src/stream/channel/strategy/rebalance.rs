@@ -0,0 +1,255 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    buffer::event::{BufferHandle, BufferPool, BufferWriter, PoolInfo},
+    data::{ArconEvent, ArconEventWrapper, ArconMessage, ArconType, NodeID},
+    metrics::memory::BufferPoolMetrics,
+    stream::channel::{
+        strategy::{next_writer, send},
+        Channel,
+    },
+};
+use arcon_error::*;
+use kompact::prelude::ComponentDefinition;
+use std::collections::VecDeque;
+
+/// A strategy that sends every batch of elements to the least loaded Channel
+///
+/// The load of a Channel is the number of buffers sent on it that its receiver has not
+/// released yet. Ties go to the Channel after the one that was picked last, so that
+/// idle receivers are used in a Round-Robin fashion. Watermarks and epochs are sent to
+/// all Channels.
+///
+/// Remote Channels release their buffers as soon as the messages are serialised, so for
+/// those the strategy behaves like [RoundRobin](super::round_robin::RoundRobin).
+pub struct Rebalance<A>
+where
+    A: ArconType,
+{
+    /// A buffer pool of EventBuffer's
+    buffer_pool: BufferPool<ArconEventWrapper<A>>,
+    /// A buffer holding outgoing elements
+    curr_buffer: BufferWriter<ArconEventWrapper<A>>,
+    /// Channels and the buffers they have not released yet
+    channels: Vec<(Channel<A>, VecDeque<BufferHandle<ArconEventWrapper<A>>>)>,
+    /// An identifier that is embedded with outgoing messages
+    sender_id: NodeID,
+    /// Struct holding information regarding the BufferPool
+    _pool_info: PoolInfo,
+    /// Which channel was picked last
+    last_index: usize,
+    /// The error that made the strategy stop sending
+    failure: Option<Error>,
+}
+
+impl<A> Rebalance<A>
+where
+    A: ArconType,
+{
+    /// Creates a Rebalance strategy
+    pub fn new(channels: Vec<Channel<A>>, sender_id: NodeID, pool_info: PoolInfo) -> Rebalance<A> {
+        assert!(
+            channels.len() > 1,
+            "Number of Channels must exceed 1 for a Rebalance strategy"
+        );
+        assert!(
+            channels.len() < pool_info.capacity,
+            "Strategy must be initialised with a pool capacity larger than amount of channels"
+        );
+
+        let mut buffer_pool: BufferPool<ArconEventWrapper<A>> = BufferPool::new(
+            pool_info.capacity,
+            pool_info.buffer_size,
+            pool_info.allocator.clone(),
+        )
+        .expect("failed to initialise buffer pool")
+        .with_limit(pool_info.limit)
+        .with_wait_timeout(pool_info.wait_timeout);
+
+        let curr_buffer = buffer_pool
+            .try_get()
+            .expect("failed to fetch initial buffer");
+
+        let last_index = channels.len() - 1;
+        let channels = channels
+            .into_iter()
+            .map(|channel| (channel, VecDeque::new()))
+            .collect();
+
+        Rebalance {
+            buffer_pool,
+            curr_buffer,
+            channels,
+            sender_id,
+            _pool_info: pool_info,
+            last_index,
+            failure: None,
+        }
+    }
+
+    #[inline]
+    pub fn add<CD>(&mut self, event: ArconEvent<A>, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        if self.failure.is_some() {
+            return;
+        }
+        if let ArconEvent::Element(_) = &event {
+            if let Some(e) = self.curr_buffer.push(event.into()) {
+                // buffer is full, flush.
+                self.flush(source);
+                self.curr_buffer.push(e);
+            }
+        } else {
+            // Elements that came before the Watermark/Epoch must be sent before it
+            self.flush(source);
+
+            for index in 0..self.channels.len() {
+                let result = self.buffer_pool.get().and_then(|mut writer| {
+                    writer.push(event.clone().into());
+                    self.send(index, writer, source)
+                });
+                if let Err(err) = result {
+                    self.failure = Some(err);
+                    return;
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub fn flush<CD>(&mut self, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        if self.curr_buffer.len() == 0 || self.failure.is_some() {
+            return;
+        }
+
+        let index = self.least_loaded();
+        self.last_index = index;
+
+        let result = next_writer(&mut self.buffer_pool, &mut self.curr_buffer)
+            .and_then(|writer| self.send(index, writer, source));
+        if let Err(err) = result {
+            self.failure = Some(err);
+        }
+    }
+
+    /// Returns the index of the Channel with the fewest unreleased buffers
+    fn least_loaded(&mut self) -> usize {
+        let last_index = self.last_index;
+        let channels = &mut self.channels;
+        let len = channels.len();
+        (1..=len)
+            .map(|offset| (last_index + offset) % len)
+            .min_by_key(|index| {
+                let (_, in_flight) = &mut channels[*index];
+                in_flight.retain(|handle| !handle.is_released());
+                in_flight.len()
+            })
+            .expect("there is at least one channel")
+    }
+
+    fn send<CD>(
+        &mut self,
+        index: usize,
+        writer: BufferWriter<ArconEventWrapper<A>>,
+        source: &CD,
+    ) -> ArconResult<()>
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let (channel, in_flight) = &mut self.channels[index];
+        in_flight.push_back(writer.handle());
+        let msg = ArconMessage {
+            events: writer.reader(),
+            sender: self.sender_id,
+        };
+        send(channel, msg, source)
+    }
+
+    #[inline]
+    pub fn num_channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Takes the error that made the strategy stop sending, if any
+    #[inline]
+    pub(crate) fn take_failure(&mut self) -> Option<Error> {
+        self.failure.take()
+    }
+
+    /// Returns the metrics of the strategy's BufferPool
+    #[inline]
+    pub fn pool_metrics(&self) -> &BufferPoolMetrics {
+        self.buffer_pool.metrics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data::{ArconElement, Watermark},
+        pipeline::ArconPipeline,
+        prelude::{ChannelStrategy, DebugNode},
+        stream::channel::strategy::tests::*,
+    };
+    use kompact::prelude::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn rebalance_test() {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let components: u64 = 4;
+        let total_msgs: u64 = components * 4;
+
+        let mut channels: Vec<Channel<Input>> = Vec::new();
+        let mut comps: Vec<Arc<crate::prelude::Component<DebugNode<Input>>>> = Vec::new();
+
+        for _i in 0..components {
+            let comp = system.create(move || DebugNode::<Input>::new());
+            system.start(&comp);
+            let actor_ref: ActorRefStrong<ArconMessage<Input>> =
+                comp.actor_ref().hold().expect("failed to fetch");
+            channels.push(Channel::Local(actor_ref));
+            comps.push(comp);
+        }
+
+        let mut channel_strategy: ChannelStrategy<Input> =
+            ChannelStrategy::Rebalance(Rebalance::new(channels, NodeID::new(1), pool_info));
+
+        // The source comp is busy while sending, so it holds on to the buffers sent to
+        // itself and is picked no more than once
+        let src_comp = &comps[0];
+        src_comp.on_definition(|cd| {
+            for _i in 0..total_msgs {
+                let elem = ArconElement::new(Input { id: 1 });
+                channel_strategy.add(ArconEvent::Element(elem), cd);
+                channel_strategy.flush(cd);
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            channel_strategy.add(ArconEvent::Watermark(Watermark::new(1)), cd);
+        });
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let mut received = Vec::new();
+        for comp in comps {
+            comp.on_definition(|cd| {
+                assert_eq!(cd.watermarks.len(), 1);
+                received.push(cd.data.len() as u64);
+            });
+        }
+        assert_eq!(received[0], 1);
+        assert_eq!(received.iter().sum::<u64>(), total_msgs);
+
+        pipeline.shutdown();
+    }
+}
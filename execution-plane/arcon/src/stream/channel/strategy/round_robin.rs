@@ -4,9 +4,14 @@
 use crate::{
     buffer::event::{BufferPool, BufferWriter, PoolInfo},
     data::{ArconEvent, ArconEventWrapper, ArconMessage, ArconType, NodeID},
-    stream::channel::{strategy::send, Channel},
+    metrics::memory::BufferPoolMetrics,
+    stream::channel::{
+        strategy::{next_writer, send},
+        Channel,
+    },
 };
-use kompact::prelude::{ComponentDefinition, SerError};
+use arcon_error::*;
+use kompact::prelude::ComponentDefinition;
 
 /// A strategy that sends message downstream in a Round-Robin fashion
 pub struct RoundRobin<A>
@@ -25,6 +30,8 @@ where
     _pool_info: PoolInfo,
     /// Which channel is currently the target
     curr_index: usize,
+    /// The error that made the strategy stop sending
+    failure: Option<Error>,
 }
 
 impl<A> RoundRobin<A>
@@ -43,7 +50,9 @@ where
             pool_info.buffer_size,
             pool_info.allocator.clone(),
         )
-        .expect("failed to initialise buffer pool");
+        .expect("failed to initialise buffer pool")
+        .with_limit(pool_info.limit)
+        .with_wait_timeout(pool_info.wait_timeout);
 
         let curr_buffer = buffer_pool
             .try_get()
@@ -56,6 +65,7 @@ where
             sender_id,
             _pool_info: pool_info,
             curr_index: 0,
+            failure: None,
         }
     }
 
@@ -64,6 +74,9 @@ where
     where
         CD: ComponentDefinition + Sized + 'static,
     {
+        if self.failure.is_some() {
+            return;
+        }
         if let ArconEvent::Element(_) = &event {
             if let Some(e) = self.curr_buffer.push(event.into()) {
                 // buffer is full, flush.
@@ -89,25 +102,29 @@ where
     where
         CD: ComponentDefinition + Sized + 'static,
     {
+        if self.curr_buffer.len() == 0 || self.failure.is_some() {
+            return;
+        }
+
         if let Some(channel) = self.channels.get(self.curr_index) {
-            let reader = self.curr_buffer.reader();
-            let msg = ArconMessage {
-                events: reader,
-                sender: self.sender_id,
-            };
-            if let Err(SerError::BufferError(err)) = send(channel, msg, source) {
-                // TODO: Figure out how to get more space for `tell_serialised`
-                panic!(format!("Buffer Error {}", err));
-            };
+            let result =
+                next_writer(&mut self.buffer_pool, &mut self.curr_buffer).and_then(|full| {
+                    let msg = ArconMessage {
+                        events: full.reader(),
+                        sender: self.sender_id,
+                    };
+                    send(channel, msg, source)
+                });
+            if let Err(err) = result {
+                self.failure = Some(err);
+                return;
+            }
 
             self.curr_index += 1;
 
             if self.curr_index >= self.channels.len() {
                 self.curr_index = 0;
             }
-
-            // TODO: Should probably not busy wait here..
-            self.curr_buffer = self.buffer_pool.get();
         } else {
             panic!("Bad channel setup");
         }
@@ -117,6 +134,18 @@ where
     pub fn num_channels(&self) -> usize {
         self.channels.len()
     }
+
+    /// Takes the error that made the strategy stop sending, if any
+    #[inline]
+    pub(crate) fn take_failure(&mut self) -> Option<Error> {
+        self.failure.take()
+    }
+
+    /// Returns the metrics of the strategy's BufferPool
+    #[inline]
+    pub fn pool_metrics(&self) -> &BufferPoolMetrics {
+        self.buffer_pool.metrics()
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,751 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    conf::{ArconConf, TlsPeer},
+    data::{ser_id::TLS_FRAME_ID, NodeID},
+};
+use arcon_error::{Error, *};
+use kompact::prelude::*;
+use rustls::{
+    internal::pemfile, AllowAnyAuthenticatedClient, Certificate, ClientConfig, ClientSession,
+    PrivateKey, RootCertStore, ServerConfig, ServerSession, Session, StreamOwned,
+};
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Upper bound on the size of a frame field, guards against allocating for a corrupt length
+const MAX_FRAME_BYTES: usize = 1 << 28;
+/// Upper bound on the amount of segments in the path of a frame
+const MAX_PATH_SEGMENTS: usize = 64;
+
+/// Builds the config of a [TlsListener] from the TLS paths of `conf`
+///
+/// The listener presents `tls_cert` and only accepts peers whose certificate is signed by `tls_ca`.
+pub fn server_config(conf: &ArconConf) -> ArconResult<Arc<ServerConfig>> {
+    let (cert, key, ca) = tls_paths(conf)?;
+    let mut config = ServerConfig::new(AllowAnyAuthenticatedClient::new(load_roots(ca)?));
+    config
+        .set_single_cert(load_certs(cert)?, load_key(key)?)
+        .map_err(|e| arcon_err_kind!("Invalid TLS certificate or key with err {}", e))?;
+    Ok(Arc::new(config))
+}
+
+/// Builds the config of a [TlsRemote] from the TLS paths of `conf`
+///
+/// The remote authenticates itself with `tls_cert` and only trusts listeners whose
+/// certificate is signed by `tls_ca`.
+pub fn client_config(conf: &ArconConf) -> ArconResult<Arc<ClientConfig>> {
+    let (cert, key, ca) = tls_paths(conf)?;
+    let mut config = ClientConfig::new();
+    config.root_store = load_roots(ca)?;
+    config
+        .set_single_client_cert(load_certs(cert)?, load_key(key)?)
+        .map_err(|e| arcon_err_kind!("Invalid TLS certificate or key with err {}", e))?;
+    Ok(Arc::new(config))
+}
+
+fn tls_paths(conf: &ArconConf) -> ArconResult<(&Path, &Path, &Path)> {
+    match (&conf.tls_cert, &conf.tls_key, &conf.tls_ca) {
+        (Some(cert), Some(key), Some(ca)) => Ok((cert, key, ca)),
+        _ => arcon_err!("TLS requires tls_cert, tls_key and tls_ca to be set"),
+    }
+}
+
+fn open_pem(path: &Path) -> ArconResult<BufReader<File>> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| arcon_err_kind!("Failed to open {} with err {}", path.display(), e))
+}
+
+fn load_certs(path: &Path) -> ArconResult<Vec<Certificate>> {
+    let certs = pemfile::certs(&mut open_pem(path)?)
+        .map_err(|_| arcon_err_kind!("Failed to parse certificates in {}", path.display()))?;
+    if certs.is_empty() {
+        return arcon_err!("No certificates found in {}", path.display());
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> ArconResult<PrivateKey> {
+    let parse_err = |_| arcon_err_kind!("Failed to parse private key in {}", path.display());
+    let mut keys = pemfile::pkcs8_private_keys(&mut open_pem(path)?).map_err(parse_err)?;
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut open_pem(path)?).map_err(parse_err)?;
+    }
+    keys.into_iter()
+        .next()
+        .ok_or_else(|| arcon_err_kind!("No private key found in {}", path.display()))
+}
+
+fn load_roots(path: &Path) -> ArconResult<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(&cert).map_err(|e| {
+            arcon_err_kind!(
+                "Invalid CA certificate in {} with err {:?}",
+                path.display(),
+                e
+            )
+        })?;
+    }
+    Ok(roots)
+}
+
+/// Accepts TLS connections from peers and delivers the messages they send to local components
+///
+/// Peers must present a certificate signed by the CA of the pipeline. The connections
+/// of any other peer are refused during the handshake, before a single message is read.
+/// After the handshake, a peer is bound to the sender NodeIDs of the `tls_peers` whose
+/// name its certificate is valid for, and its connection is closed if there are none.
+///
+/// Each message is framed with the path of the receiving component in the local
+/// KompactSystem and the ser id it was serialised with. It is then handed to that
+/// component as a [TlsFrame], which holds the NodeIDs that the peer may send as.
+pub struct TlsListener {
+    /// Address that the listener accepts connections on
+    addr: SocketAddr,
+    /// Set once the listener is dropped
+    shutdown: Arc<AtomicBool>,
+}
+
+impl TlsListener {
+    /// Binds a listener to `addr` that delivers messages to the components of `system`
+    ///
+    /// The certificates, peers and limits of the listener are taken from `conf`.
+    pub fn bind(
+        addr: SocketAddr,
+        conf: &ArconConf,
+        system: KompactSystem,
+    ) -> ArconResult<TlsListener> {
+        let config = server_config(conf)?;
+        let peers = Arc::new(conf.tls_peers.clone());
+        let max_connections = conf.tls_max_connections;
+        let handshake_timeout = Duration::from_millis(conf.tls_handshake_timeout);
+
+        let listener = TcpListener::bind(addr).map_err(|e| {
+            arcon_err_kind!("Failed to bind TlsListener to {} with err {}", addr, e)
+        })?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| arcon_err_kind!("Failed to fetch address of TlsListener {}", e))?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let stopped = shutdown.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        error!(system.logger(), "Failed to accept connection: {}", e);
+                        continue;
+                    }
+                };
+                if connections.fetch_add(1, Ordering::AcqRel) >= max_connections {
+                    connections.fetch_sub(1, Ordering::AcqRel);
+                    warn!(
+                        system.logger(),
+                        "Refused TLS connection from {:?}: {} connections are served already",
+                        stream.peer_addr().ok(),
+                        max_connections
+                    );
+                    continue;
+                }
+                let connection = Connection {
+                    config: config.clone(),
+                    peers: peers.clone(),
+                    handshake_timeout,
+                    system: system.clone(),
+                    connections: connections.clone(),
+                };
+                thread::spawn(move || connection.serve(stream));
+            }
+        });
+
+        Ok(TlsListener { addr, shutdown })
+    }
+
+    /// Returns the address that the listener accepts connections on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for TlsListener {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        // Wake up the accepting thread so that it sees the shutdown
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+/// A connection served by a [TlsListener]
+struct Connection {
+    config: Arc<ServerConfig>,
+    peers: Arc<Vec<TlsPeer>>,
+    handshake_timeout: Duration,
+    system: KompactSystem,
+    /// Connections that the listener serves, which this one leaves once dropped
+    connections: Arc<AtomicUsize>,
+}
+
+impl Connection {
+    /// Completes the handshake with a peer and delivers its messages until it disconnects
+    fn serve(self, stream: TcpStream) {
+        let peer = stream.peer_addr().ok();
+        let mut tls = StreamOwned::new(ServerSession::new(&self.config), stream);
+        if let Err(e) = self.handshake(&mut tls) {
+            // Let the peer know why it was refused
+            let _ = tls.sess.write_tls(&mut tls.sock);
+            warn!(
+                self.system.logger(),
+                "Refused TLS connection from {:?}: {}", peer, e
+            );
+            return;
+        }
+        let senders = match self.senders(&tls.sess) {
+            Ok(senders) => Arc::new(senders),
+            Err(e) => {
+                warn!(
+                    self.system.logger(),
+                    "Refused TLS connection from {:?}: {}", peer, e
+                );
+                return;
+            }
+        };
+
+        loop {
+            match read_frame(&mut tls) {
+                Ok(Some((path, ser_id, payload))) => {
+                    let path =
+                        ActorPath::Named(NamedPath::with_system(self.system.system_path(), path));
+                    let frame: Box<dyn Serialisable> = Box::new(TlsFrame {
+                        senders: senders.clone(),
+                        ser_id,
+                        payload,
+                    });
+                    path.tell(frame, &self.system);
+                }
+                Ok(None) => return,
+                Err(e) => {
+                    warn!(
+                        self.system.logger(),
+                        "Closing TLS connection from {:?}: {}", peer, e
+                    );
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Completes the handshake within the handshake timeout
+    fn handshake(&self, tls: &mut StreamOwned<ServerSession, TcpStream>) -> io::Result<()> {
+        let deadline = Instant::now() + self.handshake_timeout;
+        while tls.sess.is_handshaking() {
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .filter(|remaining| *remaining > Duration::from_millis(0))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "Handshake timed out"))?;
+            tls.sock.set_read_timeout(Some(remaining))?;
+            tls.sock.set_write_timeout(Some(remaining))?;
+            tls.sess.complete_io(&mut tls.sock)?;
+        }
+        // Messages may be far apart on a connection once it is established
+        tls.sock.set_read_timeout(None)?;
+        tls.sock.set_write_timeout(None)
+    }
+
+    /// Returns the sender NodeIDs that the peer is bound to by its certificate
+    fn senders(&self, sess: &ServerSession) -> ArconResult<Vec<NodeID>> {
+        let certs = sess
+            .get_peer_certificates()
+            .ok_or_else(|| arcon_err_kind!("Peer presented no certificate"))?;
+        let cert = certs
+            .first()
+            .ok_or_else(|| arcon_err_kind!("Peer presented no certificate"))?;
+        let cert = webpki::EndEntityCert::from(&cert.0)
+            .map_err(|e| arcon_err_kind!("Invalid peer certificate with err {:?}", e))?;
+
+        let mut senders = Vec::new();
+        for peer in self.peers.iter() {
+            let bound = webpki::DNSNameRef::try_from_ascii_str(&peer.name)
+                .map(|name| cert.verify_is_valid_for_dns_name(name).is_ok())
+                .unwrap_or(false);
+            if bound {
+                senders.extend(peer.senders.iter().map(|id| NodeID::new(*id)));
+            }
+        }
+        if senders.is_empty() {
+            return arcon_err!("Peer certificate is not bound to any sender by tls_peers");
+        }
+        Ok(senders)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.connections.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// A message that a [TlsListener] received from an authenticated peer
+///
+/// It is only ever delivered locally, so a TlsFrame cannot be forged by sending its
+/// ser id over Kompact's plain network, as it is refused by its [Deserialiser].
+pub(crate) struct TlsFrame {
+    /// NodeIDs that the peer may send messages as
+    pub(crate) senders: Arc<Vec<NodeID>>,
+    /// Ser id that the payload was serialised with
+    pub(crate) ser_id: SerId,
+    pub(crate) payload: Vec<u8>,
+}
+
+impl TlsFrame {
+    /// Checks that the peer may send messages as `sender`
+    pub(crate) fn check_sender(&self, sender: NodeID) -> ArconResult<()> {
+        if self.senders.contains(&sender) {
+            Ok(())
+        } else {
+            arcon_err!("TLS peer is not bound to sender {:?}", sender)
+        }
+    }
+}
+
+impl Serialisable for TlsFrame {
+    fn ser_id(&self) -> SerId {
+        TLS_FRAME_ID
+    }
+    fn size_hint(&self) -> Option<usize> {
+        None
+    }
+    fn serialise(&self, _buf: &mut dyn BufMut) -> Result<(), SerError> {
+        Err(SerError::InvalidType(String::from(
+            "TlsFrames are only delivered locally",
+        )))
+    }
+    fn local(self: Box<Self>) -> Result<Box<dyn Any + Send>, Box<dyn Serialisable>> {
+        Ok(self)
+    }
+}
+
+impl Deserialiser<TlsFrame> for TlsFrame {
+    const SER_ID: SerId = TLS_FRAME_ID;
+    fn deserialise(_buf: &mut dyn Buf) -> Result<TlsFrame, SerError> {
+        Err(SerError::InvalidType(String::from(
+            "TlsFrames are only delivered locally",
+        )))
+    }
+}
+
+/// A component in the KompactSystem of another pipeline that is reached through its [TlsListener]
+///
+/// Messages are queued for a sending thread, which connects to the listener and writes them,
+/// so that a Node never blocks on the network. The thread stops once every clone of the
+/// TlsRemote is dropped.
+#[derive(Clone)]
+pub struct TlsRemote {
+    /// Address of the listener
+    addr: SocketAddr,
+    /// Queue of serialised messages for the sending thread
+    queue: SyncSender<(SerId, Vec<u8>)>,
+    /// Error that the sending thread failed to write a message with, if any
+    failure: Arc<Mutex<Option<Error>>>,
+}
+
+impl TlsRemote {
+    /// Creates a TlsRemote for the component at `path` of the listener at `addr`
+    ///
+    /// The sending thread queues up to `queue_size` messages.
+    pub fn new(
+        addr: SocketAddr,
+        server_name: impl Into<String>,
+        path: Vec<String>,
+        config: Arc<ClientConfig>,
+        queue_size: usize,
+    ) -> TlsRemote {
+        let (queue, messages) = mpsc::sync_channel(queue_size);
+        let failure = Arc::new(Mutex::new(None));
+        let sender = Sender {
+            addr,
+            server_name: server_name.into(),
+            path,
+            config,
+            failure: failure.clone(),
+        };
+        thread::spawn(move || sender.run(messages));
+        TlsRemote {
+            addr,
+            queue,
+            failure,
+        }
+    }
+
+    /// Queues a `payload` that was serialised with `ser_id` for the remote component
+    ///
+    /// Fails if the queue is full, or with the error that an earlier message failed to be
+    /// written with. The connection is opened again for the messages after a failed one.
+    pub(crate) fn send(&self, ser_id: SerId, payload: Vec<u8>) -> ArconResult<()> {
+        if let Some(err) = self.failure.lock().unwrap().take() {
+            return Err(err);
+        }
+        self.queue.try_send((ser_id, payload)).map_err(|e| match e {
+            TrySendError::Full(_) => arcon_err_kind!("TLS send queue to {} is full", self.addr),
+            TrySendError::Disconnected(_) => {
+                arcon_err_kind!("TLS sending thread to {} has stopped", self.addr)
+            }
+        })
+    }
+}
+
+/// The sending thread of a [TlsRemote]
+struct Sender {
+    addr: SocketAddr,
+    /// Name that the certificate of the listener must be valid for
+    server_name: String,
+    /// Path of the receiving component in the KompactSystem of the listener
+    path: Vec<String>,
+    config: Arc<ClientConfig>,
+    failure: Arc<Mutex<Option<Error>>>,
+}
+
+impl Sender {
+    fn run(self, messages: Receiver<(SerId, Vec<u8>)>) {
+        let mut connection = None;
+        for (ser_id, payload) in messages {
+            let written = match connection.take() {
+                Some(tls) => Ok(tls),
+                None => connect(self.addr, &self.server_name, &self.config),
+            }
+            .and_then(|mut tls| {
+                write_frame(&mut tls, &self.path, ser_id, &payload)
+                    .map(|_| tls)
+                    .map_err(|e| arcon_err_kind!("Failed to send to {} with err {}", self.addr, e))
+            });
+            match written {
+                Ok(tls) => connection = Some(tls),
+                Err(err) => *self.failure.lock().unwrap() = Some(err),
+            }
+        }
+    }
+}
+
+/// Opens a connection to the listener at `addr` and completes the handshake
+fn connect(
+    addr: SocketAddr,
+    server_name: &str,
+    config: &Arc<ClientConfig>,
+) -> ArconResult<StreamOwned<ClientSession, TcpStream>> {
+    let name = webpki::DNSNameRef::try_from_ascii_str(server_name)
+        .map_err(|_| arcon_err_kind!("Invalid TLS server name {}", server_name))?;
+    let stream = TcpStream::connect(addr)
+        .map_err(|e| arcon_err_kind!("Failed to connect to {} with err {}", addr, e))?;
+    let mut tls = StreamOwned::new(ClientSession::new(config, name), stream);
+    while tls.sess.is_handshaking() {
+        tls.sess
+            .complete_io(&mut tls.sock)
+            .map_err(|e| arcon_err_kind!("TLS handshake with {} failed with err {}", addr, e))?;
+    }
+    Ok(tls)
+}
+
+/// Writes a frame holding the path of the receiving component, the ser id and the payload
+///
+/// Every segment of the path is prefixed with its length, so segments may hold any character.
+fn write_frame(
+    out: &mut impl Write,
+    path: &[String],
+    ser_id: SerId,
+    payload: &[u8],
+) -> io::Result<()> {
+    let path_len: usize = path.iter().map(|segment| 4 + segment.len()).sum();
+    let mut frame = Vec::with_capacity(4 + path_len + 8 + 4 + payload.len());
+    frame.extend_from_slice(&(path.len() as u32).to_le_bytes());
+    for segment in path {
+        frame.extend_from_slice(&(segment.len() as u32).to_le_bytes());
+        frame.extend_from_slice(segment.as_bytes());
+    }
+    frame.extend_from_slice(&ser_id.to_le_bytes());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(payload);
+    out.write_all(&frame)?;
+    out.flush()
+}
+
+/// Reads a frame written by [write_frame], returns None once the peer has disconnected
+fn read_frame(input: &mut impl Read) -> io::Result<Option<(Vec<String>, SerId, Vec<u8>)>> {
+    let mut len = [0u8; 4];
+    match input.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let segments = u32::from_le_bytes(len) as usize;
+    if segments > MAX_PATH_SEGMENTS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Path of {} segments exceeds the limit", segments),
+        ));
+    }
+    let mut path = Vec::with_capacity(segments);
+    for _ in 0..segments {
+        input.read_exact(&mut len)?;
+        let segment = String::from_utf8(read_bytes(input, u32::from_le_bytes(len) as usize)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        path.push(segment);
+    }
+    let mut ser_id = [0u8; 8];
+    input.read_exact(&mut ser_id)?;
+    input.read_exact(&mut len)?;
+    let payload = read_bytes(input, u32::from_le_bytes(len) as usize)?;
+
+    Ok(Some((path, u64::from_le_bytes(ser_id), payload)))
+}
+
+fn read_bytes(input: &mut impl Read, len: usize) -> io::Result<Vec<u8>> {
+    if len > MAX_FRAME_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Frame field of {} bytes exceeds the limit", len),
+        ));
+    }
+    let mut bytes = vec![0u8; len];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{conf::TlsPeer, prelude::*};
+    use std::{path::PathBuf, time::Duration};
+
+    /// Writes a self-signed certificate for localhost and its key, returns their paths
+    fn self_signed(dir: &Path, name: &str) -> (PathBuf, PathBuf) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_path = dir.join(format!("{}.pem", name));
+        let key_path = dir.join(format!("{}.key", name));
+        std::fs::write(&cert_path, cert.serialize_pem().unwrap()).unwrap();
+        std::fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+        (cert_path, key_path)
+    }
+
+    fn tls_conf(cert: &Path, key: &Path, ca: &Path, host: Option<&str>) -> ArconConf {
+        ArconConf {
+            tls_cert: Some(cert.to_path_buf()),
+            tls_key: Some(key.to_path_buf()),
+            tls_ca: Some(ca.to_path_buf()),
+            tls_host: host.map(String::from),
+            // Peers with a certificate for localhost may only send as NodeID 1
+            tls_peers: vec![TlsPeer {
+                name: String::from("localhost"),
+                senders: vec![1],
+            }],
+            ..ArconConf::default()
+        }
+    }
+
+    #[test]
+    fn tls_requires_every_path() {
+        let conf = ArconConf {
+            tls_cert: Some(PathBuf::from("node.pem")),
+            ..ArconConf::default()
+        };
+        assert!(conf.validate().is_err());
+        assert!(server_config(&conf).is_err());
+        assert!(client_config(&conf).is_err());
+
+        let mut pipeline = ArconPipeline::with_conf(conf);
+        assert!(pipeline.start_tls_listener().is_err());
+        pipeline.shutdown();
+    }
+
+    #[test]
+    fn frame_path_test() {
+        let path = vec![String::from("sink/0"), String::new(), String::from("node")];
+        let mut bytes = Vec::new();
+        write_frame(&mut bytes, &path, 7, &[1, 2, 3]).unwrap();
+
+        let frame = read_frame(&mut bytes.as_slice()).unwrap();
+        assert_eq!(frame, Some((path, 7, vec![1, 2, 3])));
+        assert_eq!(read_frame(&mut io::empty()).unwrap(), None);
+
+        // A corrupt segment count is refused before anything is allocated for it
+        let mut corrupt = Vec::new();
+        corrupt.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(read_frame(&mut corrupt.as_slice()).is_err());
+    }
+
+    #[test]
+    fn tls_send_failure_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, key) = self_signed(dir.path(), "trusted");
+        let conf = tls_conf(&cert, &key, &cert, None);
+
+        // Nothing listens on the address once the listener is dropped
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let remote = TlsRemote::new(
+            addr,
+            "localhost",
+            vec![String::from("sink")],
+            client_config(&conf).unwrap(),
+            1,
+        );
+        assert!(remote.send(0, vec![0]).is_ok());
+        std::thread::sleep(Duration::from_millis(500));
+        assert!(remote.send(0, vec![0]).is_err());
+    }
+
+    #[test]
+    fn tls_loopback_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, key) = self_signed(dir.path(), "trusted");
+        let (untrusted_cert, untrusted_key) = self_signed(dir.path(), "untrusted");
+        let timeout = Duration::from_millis(500);
+
+        // Both pipelines present the trusted certificate and accept it as their CA
+        let mut receiver =
+            ArconPipeline::with_conf(tls_conf(&cert, &key, &cert, Some("127.0.0.1:0")));
+        let sender = ArconPipeline::with_conf(tls_conf(&cert, &key, &cert, None));
+        let addr = receiver.start_tls_listener().unwrap();
+
+        let system = receiver.system();
+        let sink = system.create(move || DebugNode::<u32>::new());
+        system
+            .start_notify(&sink)
+            .wait_timeout(timeout)
+            .expect("sink never started");
+        system
+            .register_by_alias(&sink, String::from("sink"))
+            .wait_expect(timeout, "Failed to register alias for DebugNode");
+
+        let channel = |serde| {
+            sender
+                .tls_channel::<u32>(addr, "localhost", vec![String::from("sink")], serde)
+                .unwrap()
+        };
+        let mut channel_strategy: ChannelStrategy<u32> = ChannelStrategy::Forward(Forward::new(
+            channel(FlightSerde::Reliable),
+            1.into(),
+            sender.get_pool_info(),
+        ));
+        // The peer is not bound to NodeID 2, so its messages are dropped by the sink
+        let mut unbound_strategy: ChannelStrategy<u32> = ChannelStrategy::Forward(Forward::new(
+            channel(FlightSerde::Unsafe),
+            2.into(),
+            sender.get_pool_info(),
+        ));
+        sink.on_definition(|cd| {
+            channel_strategy.add(ArconEvent::Element(ArconElement::new(1)), cd);
+            channel_strategy.add(ArconEvent::Element(ArconElement::new(2)), cd);
+            channel_strategy.flush(cd);
+            unbound_strategy.add(ArconEvent::Element(ArconElement::new(3)), cd);
+            unbound_strategy.flush(cd);
+        });
+        std::thread::sleep(timeout);
+
+        sink.on_definition(|cd| {
+            let data: Vec<u32> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(data, vec![1, 2]);
+        });
+        assert!(channel_strategy.take_failure().is_none());
+        assert!(unbound_strategy.take_failure().is_none());
+
+        // A peer whose certificate is not signed by the CA of the receiver is refused
+        let untrusted_conf = tls_conf(&untrusted_cert, &untrusted_key, &cert, None);
+        let untrusted_config = client_config(&untrusted_conf).unwrap();
+        match connect(addr, "localhost", &untrusted_config) {
+            // With TLS 1.2, the handshake itself fails
+            Err(_) => {}
+            // With TLS 1.3, the client completes its handshake before the listener
+            // verifies its certificate, which then closes the connection
+            Ok(mut tls) => {
+                tls.sock
+                    .set_read_timeout(Some(Duration::from_secs(5)))
+                    .unwrap();
+                let mut byte = [0u8; 1];
+                match tls.read(&mut byte) {
+                    Ok(read) => assert_eq!(read, 0),
+                    Err(e) => assert!(
+                        e.kind() != io::ErrorKind::WouldBlock
+                            && e.kind() != io::ErrorKind::TimedOut
+                    ),
+                }
+            }
+        }
+
+        let remote = TlsRemote::new(
+            addr,
+            "localhost",
+            vec![String::from("sink")],
+            untrusted_config,
+            1,
+        );
+        let msg = RawArconMessage::<u32> {
+            events: vec![ArconEvent::Element(ArconElement::new(4)).into()],
+            sender: 1.into(),
+        };
+        let (ser_id, bytes) = FlightSerde::Reliable.serialise(msg).unwrap();
+        assert!(remote.send(ser_id, bytes).is_ok());
+        std::thread::sleep(timeout);
+
+        sink.on_definition(|cd| {
+            let data: Vec<u32> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(data, vec![1, 2]);
+        });
+
+        sender.shutdown();
+        receiver.shutdown();
+    }
+
+    #[test]
+    fn tls_handshake_timeout_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, key) = self_signed(dir.path(), "trusted");
+        let conf = ArconConf {
+            tls_max_connections: 1,
+            tls_handshake_timeout: 200,
+            ..tls_conf(&cert, &key, &cert, Some("127.0.0.1:0"))
+        };
+        let mut pipeline = ArconPipeline::with_conf(conf);
+        let addr = pipeline.start_tls_listener().unwrap();
+
+        // A peer that never starts its handshake takes up the only connection
+        let mut idle = TcpStream::connect(addr).unwrap();
+        idle.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+
+        // Further connections are closed while it is served
+        let mut refused = TcpStream::connect(addr).unwrap();
+        refused
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut byte = [0u8; 1];
+        assert_eq!(refused.read(&mut byte).unwrap(), 0);
+
+        // Until its handshake times out
+        assert_eq!(idle.read(&mut byte).unwrap(), 0);
+        std::thread::sleep(Duration::from_millis(100));
+        let conf = tls_conf(&cert, &key, &cert, None);
+        assert!(connect(addr, "localhost", &client_config(&conf).unwrap()).is_ok());
+
+        pipeline.shutdown();
+    }
+}
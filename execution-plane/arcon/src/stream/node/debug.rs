@@ -1,12 +1,15 @@
 // Copyright (c) 2020, KTH Royal Institute of Technology.
 // SPDX-License-Identifier: AGPL-3.0-only
 
+use super::deserialise_raw_message;
 use crate::prelude::*;
 
 /// A DebugNode is a debug version of [Node]
 ///
 /// DebugNode's act as sinks and are useful for tests and situations
-/// when one needs to verify dev pipelines.
+/// when one needs to verify dev pipelines. Remote messages are accepted
+/// with any [FlightSerde], including [FlightSerde::Bincode] when the
+/// `arcon_serde` feature is enabled.
 #[derive(ComponentDefinition)]
 pub struct DebugNode<IN>
 where
@@ -86,25 +89,7 @@ where
         Handled::Ok
     }
     fn receive_network(&mut self, msg: NetMessage) -> Handled {
-        let unsafe_id = IN::UNSAFE_SER_ID;
-        let reliable_id = IN::RELIABLE_SER_ID;
-        let ser_id = *msg.ser_id();
-
-        let arcon_msg = {
-            if ser_id == reliable_id {
-                msg.try_deserialise::<RawArconMessage<IN>, ReliableSerde<IN>>()
-                    .map_err(|e| {
-                        arcon_err_kind!("Failed to unpack reliable ArconMessage with err {:?}", e)
-                    })
-            } else if ser_id == unsafe_id {
-                msg.try_deserialise::<RawArconMessage<IN>, UnsafeSerde<IN>>()
-                    .map_err(|e| {
-                        arcon_err_kind!("Failed to unpack unreliable ArconMessage with err {:?}", e)
-                    })
-            } else {
-                panic!("Unexpected deserialiser")
-            }
-        };
+        let arcon_msg = deserialise_raw_message::<IN>(msg);
 
         match arcon_msg {
             Ok(m) => {
@@ -4,22 +4,46 @@
 /// Debug version of [Node]
 pub mod debug;
 
+#[cfg(feature = "arcon_serde")]
+use crate::data::flight_serde::bincode_remote::BincodeSerde;
 use crate::{
-    data::RawArconMessage,
+    buffer::network::with_receive_allocator,
+    data::{
+        flight_serde::{
+            compressed_remote::{Lz4Serde, ZstdSerde},
+            unsafe_remote::UnsafeNetworkSerde,
+        },
+        ser_id::{BINCODE_FLIGHT_ID, LZ4_FLIGHT_ID, TLS_FRAME_ID, ZSTD_FLIGHT_ID},
+        NetworkArconMessage, RawArconMessage,
+    },
+    error::Error as ArconError,
     manager::node_manager::*,
-    metrics::{counter::Counter, gauge::Gauge, meter::Meter},
+    metrics::{counter::Counter, gauge::Gauge, memory::BufferPoolMetrics, meter::Meter},
     prelude::{
         state::{Bundle, Handle, MapState, ValueState, VecState},
         *,
     },
-    stream::operator::OperatorContext,
+    stream::{channel::tls::TlsFrame, operator::OperatorContext},
     timer::TimerBackend,
 };
-use std::{cell::RefCell, iter};
+use prost::Message as PMessage;
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    fs,
+    io::{self, Read, Write},
+    iter,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 /// Type alias for a Node description
 pub type NodeDescriptor = String;
 
+/// Number of adaptive batches a Node aims to send per channel flush interval
+const ADAPTIVE_BATCHES_PER_INTERVAL: usize = 10;
+
 /// Metrics reported by an Arcon Node
 #[derive(Debug, Clone)]
 pub struct NodeMetrics {
@@ -29,6 +53,10 @@ pub struct NodeMetrics {
     pub epoch_counter: Counter,
     /// Counter for total watermarks processed
     pub watermark_counter: Counter,
+    /// Counter for duplicate elements dropped by the Operator
+    pub duplicate_counter: Counter,
+    /// Counter for changelog rows other than inserts dropped by an insert-only Operator
+    pub changelog_drop_counter: Counter,
     /// Current watermark
     pub watermark: Watermark,
     /// Current epoch
@@ -37,6 +65,8 @@ pub struct NodeMetrics {
     pub outbound_channels: Gauge,
     /// Gauge Metric representing number of inbound channels
     pub inbound_channels: Gauge,
+    /// Metrics of the BufferPool used by the Node's ChannelStrategy
+    pub buffer_pool: BufferPoolMetrics,
 }
 
 impl NodeMetrics {
@@ -46,10 +76,13 @@ impl NodeMetrics {
             inbound_throughput: Meter::new(),
             epoch_counter: Counter::new(),
             watermark_counter: Counter::new(),
+            duplicate_counter: Counter::new(),
+            changelog_drop_counter: Counter::new(),
             watermark: Watermark::new(0),
             epoch: Epoch::new(0),
             outbound_channels: Gauge::new(),
             inbound_channels: Gauge::new(),
+            buffer_pool: BufferPoolMetrics::new(),
         }
     }
 }
@@ -67,6 +100,10 @@ arcon_state::bundle! {
         current_epoch: Handle<ValueState<Epoch>>,
         /// Blocked channels during epoch alignment
         blocked_channels: Handle<MapState<NodeID, ()>>,
+        /// Channels that have sent a Death event
+        terminated_channels: Handle<MapState<NodeID, ()>>,
+        /// Epoch that the blocked channels are waiting on
+        pending_epoch: Handle<ValueState<Epoch>>,
         /// Temporary message buffer used while having blocked channels
         message_buffer: Handle<VecState<RawArconMessage<OpIn>>>,
     }
@@ -79,11 +116,28 @@ impl<OpIn: ArconType> NodeState<OpIn> {
             current_watermark: Handle::value("__node_current_watermark"),
             current_epoch: Handle::value("__node_current_epoch"),
             blocked_channels: Handle::map("__node_blocked_channels"),
+            terminated_channels: Handle::map("__node_terminated_channels"),
+            pending_epoch: Handle::value("__node_pending_epoch"),
             message_buffer: Handle::vec("__node_message_buffer"),
         }
     }
 }
 
+/// Records that arrived on the feedback channels of a Node while one of its checkpoints was pending
+#[derive(PMessage, Clone)]
+pub struct FeedbackLog<OpIn: ArconType> {
+    #[prost(message, repeated, tag = "1")]
+    pub messages: Vec<RawArconMessage<OpIn>>,
+}
+
+/// A checkpoint that waits for its epoch marker to return on every feedback channel
+struct PendingCheckpoint<OpIn: ArconType> {
+    /// Feedback channels on which the epoch marker has returned
+    returned: Vec<NodeID>,
+    /// Records that were in flight on the feedback channels when the checkpoint was taken
+    log: FeedbackLog<OpIn>,
+}
+
 /// A Node is a [kompact] component that drives the execution of streaming operators
 ///
 /// Nodes receive [ArconMessage] and run some transform on the data
@@ -108,6 +162,10 @@ where
     channel_strategy: RefCell<ChannelStrategy<OP::OUT>>,
     /// Current set of IDs connected to this Node
     in_channels: Vec<NodeID>,
+    /// IDs connected to this Node through a cycle in the dataflow graph
+    feedback_channels: Vec<NodeID>,
+    /// Checkpoints waiting for their epoch marker to return on the feedback channels
+    pending_checkpoints: RefCell<BTreeMap<u64, PendingCheckpoint<OP::IN>>>,
     /// User-defined Operator
     operator: OP,
     /// Internal state of the Node
@@ -118,6 +176,28 @@ where
     pub state_backend: state::BackendContainer<B>,
     /// Timer Backend to keep track of event timers
     timer_backend: RefCell<T>,
+    /// Kompact timer armed for the earliest processing-time deadline
+    processing_wakeup: Option<(u64, ScheduledTimer)>,
+    /// Inbound events handled since the channel buffers were last flushed
+    unflushed_events: Cell<usize>,
+    /// Inbound events received since the last channel flush interval
+    interval_events: Cell<usize>,
+    /// Inbound events after which the channel buffers are flushed, if batching is adaptive
+    adaptive_batch: Cell<Option<usize>>,
+    /// Allocator of the pipeline
+    ///
+    /// Backs the NetworkBuffers that Unsafe remote messages are received into,
+    /// which are taken from the heap otherwise, and holds the memory budget of
+    /// the Node's message buffer.
+    allocator: Option<Arc<Mutex<ArconAllocator>>>,
+    /// Bytes of the message buffer that are reserved in the Node's memory budget
+    buffered_bytes: Cell<usize>,
+    /// Whether the message buffer has overflowed its budget and is spilled to disk
+    spilling: Cell<bool>,
+    /// Error that the Node fails with, which is reported to the NodeManager
+    failure: RefCell<Option<ArconError>>,
+    /// Whether remote messages must arrive over TLS, in which case plain ones are refused
+    tls_enabled: Cell<bool>,
 }
 
 // Just a shorthand to avoid repeating the OperatorContext construction everywhere
@@ -227,19 +307,76 @@ where
             id,
             channel_strategy,
             in_channels,
+            feedback_channels: Vec::new(),
+            pending_checkpoints: RefCell::new(BTreeMap::new()),
             operator,
             state,
             metrics,
             state_backend,
             timer_backend,
+            processing_wakeup: None,
+            unflushed_events: Cell::new(0),
+            interval_events: Cell::new(0),
+            adaptive_batch: Cell::new(None),
+            allocator: None,
+            buffered_bytes: Cell::new(0),
+            spilling: Cell::new(false),
+            failure: RefCell::new(None),
+            tls_enabled: Cell::new(false),
         }
     }
 
+    /// Adds inputs that close a cycle in the dataflow graph back to this Node
+    ///
+    /// Feedback channels take no part in watermark tracking or epoch alignment, since both
+    /// would end up waiting on the Node itself. Instead, a checkpoint of the Node is only
+    /// complete once its epoch marker has come back on every feedback channel, and the
+    /// records that arrived on them in the meantime are logged next to the checkpoint.
+    /// The log is replayed when the Node is restored from that checkpoint.
+    pub fn with_feedback_channels(mut self, feedback_channels: Vec<NodeID>) -> Self {
+        self.metrics
+            .get_mut()
+            .inbound_channels
+            .inc_n(feedback_channels.len());
+        self.feedback_channels.extend(feedback_channels);
+        self
+    }
+
+    /// Lets the Node manage its memory through `allocator`
+    ///
+    /// Remote messages sent with [FlightSerde::Unsafe] are then received into NetworkBuffers
+    /// of the allocator rather than the heap, which charges them to [Budget::Network](crate::prelude::Budget::Network).
+    ///
+    /// Messages that are held back during epoch alignment are charged to the
+    /// [Budget::Node](crate::prelude::Budget::Node) of the Node. Once the budget or the
+    /// allocator runs out of memory, they are spilled to disk until the alignment completes.
+    pub fn with_allocator(mut self, allocator: Arc<Mutex<ArconAllocator>>) -> Self {
+        self.allocator = Some(allocator);
+        self
+    }
+
+    /// Replaces the [ChannelStrategy] of the Node
+    ///
+    /// A cycle can only be closed once the Node at its head exists, so the Node at its
+    /// tail may have to be created with a placeholder strategy first.
+    pub fn set_channel_strategy(&mut self, channel_strategy: ChannelStrategy<OP::OUT>) {
+        let outbound_channels = &mut self.metrics.get_mut().outbound_channels;
+        outbound_channels.dec_n(self.channel_strategy.get_mut().num_channels());
+        outbound_channels.inc_n(channel_strategy.num_channels());
+        *self.channel_strategy.get_mut() = channel_strategy;
+    }
+
+    /// Returns whether `sender` is connected to this Node
+    #[inline]
+    fn is_input(&self, sender: &NodeID) -> bool {
+        self.in_channels.contains(sender) || self.feedback_channels.contains(sender)
+    }
+
     /// Handle a Raw ArconMessage that has either been sent remotely or temporarily stored in the state backend
     #[inline]
     fn handle_raw_msg(&mut self, message: RawArconMessage<OP::IN>) -> ArconResult<()> {
         // Check valid sender
-        if !self.in_channels.contains(&message.sender) {
+        if !self.is_input(&message.sender) {
             return arcon_err!("Message from invalid sender");
         }
 
@@ -251,9 +388,9 @@ where
             let mut active_state = self.state.activate(&mut sb_session);
             // Check if sender is blocked
             if active_state.blocked_channels().contains(&message.sender)? {
+                drop(active_state);
                 // Add the message to the back of the queue
-                active_state.message_buffer().append(message)?;
-                return Ok(());
+                return self.buffer_message(message, &mut sb_session);
             }
         }
 
@@ -266,33 +403,259 @@ where
     /// Handle a local ArconMessage that is backed by the [ArconAllocator]
     #[inline]
     fn handle_message(&mut self, message: ArconMessage<OP::IN>) -> ArconResult<()> {
+        let sender = message.sender;
+        let len = message.events.len();
+        self.handle_batch(sender, len, message.events, |events| RawArconMessage {
+            events: events.into_vec(),
+            sender,
+        })
+    }
+
+    /// Handle an ArconMessage that has been decoded in a NetworkBuffer
+    #[inline]
+    fn handle_network_msg(&mut self, message: NetworkArconMessage<OP::IN>) -> ArconResult<()> {
+        let sender = message.sender;
+        let len = message.events.len();
+        self.handle_batch(sender, len, message.events, |events| RawArconMessage {
+            events: events.as_slice().to_vec(),
+            sender,
+        })
+    }
+
+    /// Handle a batch of `len` events that is read straight from a buffer
+    ///
+    /// Local and remote batches share this path, which hands the events over to
+    /// [handle_events](Node::handle_events) without collecting them. The batch is only
+    /// turned into a RawArconMessage through `raw` if its sender is blocked.
+    #[inline]
+    fn handle_batch<R>(
+        &mut self,
+        sender: NodeID,
+        len: usize,
+        events: R,
+        raw: impl FnOnce(R) -> RawArconMessage<OP::IN>,
+    ) -> ArconResult<()>
+    where
+        R: IntoIterator<Item = ArconEventWrapper<OP::IN>>,
+    {
         // Check valid sender
-        if !self.in_channels.contains(&message.sender) {
+        if !self.is_input(&sender) {
             return arcon_err!("Message from invalid sender");
         }
 
-        self.record_incoming_events(message.events.len() as u64);
+        self.record_incoming_events(len as u64);
 
         let mut sb_session = self.state_backend.session();
 
         let mut state = self.state.activate(&mut sb_session);
         // Check if sender is blocked
-        if state.blocked_channels().contains(&message.sender)? {
+        if state.blocked_channels().contains(&sender)? {
+            drop(state);
             // Add the message to the back of the queue
-            state.message_buffer().append(message.into())?;
-            return Ok(());
+            return self.buffer_message(raw(events), &mut sb_session);
         }
 
         // If sender is not blocked, process events.
-        let res = self.handle_events(message.sender, message.events, &mut sb_session);
+        let res = self.handle_events(sender, events, &mut sb_session);
         drop(sb_session);
         res
     }
 
+    /// Adds a message of a blocked channel to the back of the message buffer
+    ///
+    /// The message is kept in the state backend while the Node's memory budget allows it.
+    /// Otherwise, it and all messages after it are spilled to disk, which keeps them in order.
+    /// The Node fails if a message cannot be spilled, e.g., if there is no `checkpoint_dir`.
+    /// That failure is reported to the NodeManager once the message has been handled.
+    fn buffer_message(
+        &self,
+        message: RawArconMessage<OP::IN>,
+        sb_session: &mut state::Session<B>,
+    ) -> ArconResult<()> {
+        let first_spill = !self.spilling.get();
+        if first_spill {
+            let bytes = message.encoded_len();
+            let reserved = match &self.allocator {
+                Some(allocator) => allocator
+                    .lock()
+                    .unwrap()
+                    .reserve(Budget::Node(self.id.id), bytes),
+                None => true,
+            };
+            if reserved {
+                self.buffered_bytes.set(self.buffered_bytes.get() + bytes);
+                self.state
+                    .activate(sb_session)
+                    .message_buffer()
+                    .append(message)?;
+                return Ok(());
+            }
+            debug!(
+                self.ctx.log(),
+                "Message buffer of Node {:?} is out of memory, spilling to disk", self.id
+            );
+            self.spilling.set(true);
+        }
+
+        // A message that can neither be kept in memory nor spilled would be lost,
+        // so the Node fails rather than dropping it silently
+        if let Err(err) = self.spill_message(message, first_spill) {
+            self.fail(arcon_err_kind!("Failed to spill a message: {}", err));
+        }
+        Ok(())
+    }
+
+    /// Appends a message to the spill file, which is truncated on the `first_spill`
+    fn spill_message(
+        &self,
+        message: RawArconMessage<OP::IN>,
+        first_spill: bool,
+    ) -> ArconResult<()> {
+        let mut bytes = Vec::with_capacity(4 + message.encoded_len());
+        bytes.extend_from_slice(&(message.encoded_len() as u32).to_le_bytes());
+        message
+            .encode(&mut bytes)
+            .map_err(|e| arcon_err_kind!("Failed to encode spilled message with err {:?}", e))?;
+
+        let spill_path = self.spill_path()?;
+        let mut options = fs::OpenOptions::new();
+        if first_spill {
+            // Overwrite what a previous run of the Node may have left behind
+            options.write(true).create(true).truncate(true);
+        } else {
+            options.append(true);
+        }
+        options
+            .open(&spill_path)
+            .and_then(|mut file| file.write_all(&bytes))
+            .map_err(|e| {
+                arcon_err_kind!("Failed to spill message to {} with err {:?}", spill_path, e)
+            })
+    }
+
+    /// Path of the file that the message buffer is spilled to
+    fn spill_path(&self) -> ArconResult<String> {
+        match self.ctx.config()["checkpoint_dir"].as_string() {
+            Some(base_dir) => Ok(format!("{}/message_spill_{}", base_dir, self.id.id)),
+            None => arcon_err!("Failed to fetch checkpoint_dir from Config"),
+        }
+    }
+
+    /// Handles the spilled messages in the order they were spilled and removes the spill file
+    fn replay_spilled_messages(&self, sb_session: &mut state::Session<B>) -> ArconResult<()> {
+        let spill_path = self.spill_path()?;
+        let read_err = |e: io::Error| {
+            arcon_err_kind!(
+                "Failed to read spilled messages {} with err {:?}",
+                spill_path,
+                e
+            )
+        };
+        let mut reader = io::BufReader::new(fs::File::open(&spill_path).map_err(read_err)?);
+
+        let mut len = [0u8; 4];
+        loop {
+            match reader.read_exact(&mut len) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(read_err(e)),
+            }
+            let mut bytes = vec![0u8; u32::from_le_bytes(len) as usize];
+            reader.read_exact(&mut bytes).map_err(read_err)?;
+            let message = RawArconMessage::<OP::IN>::decode(&bytes[..]).map_err(|e| {
+                arcon_err_kind!("Failed to decode spilled message with err {:?}", e)
+            })?;
+            self.handle_events(message.sender, message.events, sb_session)?;
+        }
+
+        fs::remove_file(&spill_path).map_err(read_err)
+    }
+
+    /// Puts back the spilled messages of the checkpoint that the Node was restored from
+    fn restore_spilled_messages(&self, sb_session: &mut state::Session<B>) -> ArconResult<()> {
+        let epoch = self
+            .state
+            .activate(sb_session)
+            .current_epoch()
+            .get()?
+            .ok_or_else(|| arcon_err_kind!("current epoch uninitialized"))?
+            .epoch;
+        let checkpoint_spill = self.checkpoint_path("message_spill", epoch)?;
+        if Path::new(&checkpoint_spill).exists() {
+            let spill_path = self.spill_path()?;
+            fs::copy(&checkpoint_spill, &spill_path).map_err(|e| {
+                arcon_err_kind!(
+                    "Failed to restore spilled messages {} with err {:?}",
+                    checkpoint_spill,
+                    e
+                )
+            })?;
+            self.spilling.set(true);
+        }
+        Ok(())
+    }
+
     /// Mark amount of inbound events
     #[inline(always)]
     fn record_incoming_events(&self, total: u64) {
         self.metrics.borrow_mut().inbound_throughput.mark_n(total);
+        self.unflushed_events
+            .set(self.unflushed_events.get() + total as usize);
+        self.interval_events
+            .set(self.interval_events.get() + total as usize);
+    }
+
+    /// Marks the Node as failed with `err`, unless it already failed
+    fn fail(&self, err: ArconError) {
+        let mut failure = self.failure.borrow_mut();
+        if failure.is_none() {
+            *failure = Some(err);
+        }
+    }
+
+    /// Reports a failure of the Node or its ChannelStrategy to the NodeManager and stops the Node
+    ///
+    /// Returns whether the Node failed.
+    fn report_failure(&mut self) -> bool {
+        let failure = match self.failure.get_mut().take() {
+            Some(err) => err,
+            None => match self.channel_strategy.get_mut().take_failure() {
+                Some(err) => arcon_err_kind!("Failed to send downstream: {}", err),
+                None => return false,
+            },
+        };
+        error!(self.ctx.log(), "Node {:?} failed: {}", self.id, failure);
+        self.node_manager_port
+            .trigger(NodeEvent::Failed(self.id, failure.to_string()));
+        self.ctx.suicide();
+        true
+    }
+
+    /// Flush the channel buffers, including partially filled ones
+    fn flush_channels(&self) {
+        self.channel_strategy.borrow_mut().flush(self);
+        self.unflushed_events.set(0);
+    }
+
+    /// Flush the channel buffers once an adaptive batch of events has been handled
+    fn flush_adaptive_batch(&self) {
+        if let Some(batch) = self.adaptive_batch.get() {
+            if self.unflushed_events.get() >= batch {
+                self.flush_channels();
+            }
+        }
+    }
+
+    /// Size the adaptive batch after the inbound events of the last flush interval
+    ///
+    /// At a steady rate, the channel buffers are then flushed several times per interval,
+    /// while batches grow with the throughput up to `max_batch` events.
+    fn adapt_batch(&self, max_batch: usize) {
+        let events = self.interval_events.replace(0);
+        let batch = (events / ADAPTIVE_BATCHES_PER_INTERVAL)
+            .max(1)
+            .min(max_batch);
+        self.adaptive_batch.set(Some(batch));
     }
 
     /// Iterate over a batch of ArconEvent's
@@ -306,11 +669,17 @@ where
     where
         I: IntoIterator<Item = ArconEventWrapper<OP::IN>>,
     {
+        if self.feedback_channels.contains(&sender) {
+            return self.handle_feedback_events(sender, events, sb_session);
+        }
+
         'event_loop: for event in events.into_iter() {
             let mut state = self.state.activate(sb_session);
             match event.unwrap() {
                 ArconEvent::Element(e) => {
-                    if e.timestamp.unwrap_or(u64::max_value())
+                    if e.timestamp
+                        .unwrap_or(u64::max_value())
+                        .saturating_add(self.operator.allowed_lateness())
                         <= state
                             .watermarks()
                             .get(&sender)?
@@ -326,166 +695,426 @@ where
                         .handle_element(e, self, make_context!(self, sb_session));
                 }
                 ArconEvent::Watermark(w) => {
-                    if w <= state
-                        .watermarks()
-                        .get(&sender)?
-                        .ok_or_else(|| arcon_err_kind!("uninitialized watermark"))?
+                    drop(state);
+                    self.handle_watermark(sender, w, sb_session)?;
+                }
+                ArconEvent::Epoch(e) => {
+                    if e <= state
+                        .current_epoch()
+                        .get()?
+                        .ok_or_else(|| arcon_err_kind!("uninitialized epoch"))?
                     {
                         continue 'event_loop;
                     }
 
-                    let current_watermark = state
-                        .current_watermark()
-                        .get()?
-                        .ok_or_else(|| arcon_err_kind!("current watermark uninitialized"))?;
+                    // Add the sender to the blocked set.
+                    state.blocked_channels().fast_insert(sender, ())?;
+                    state.pending_epoch().set(e)?;
+                    drop(state);
 
-                    // Insert the watermark and try early return
-                    if let Some(old) = state.watermarks().insert(sender, w)? {
-                        if old > current_watermark {
-                            continue 'event_loop;
-                        }
-                    }
-                    // A different early return
-                    if w <= current_watermark {
-                        continue 'event_loop;
-                    }
+                    self.align_epoch(sb_session)?;
+                }
+                ArconEvent::Death(s) => {
+                    state.terminated_channels().fast_insert(sender, ())?;
 
-                    // Let new_watermark take the value of the lowest watermark
-                    let new_watermark = state
-                        .watermarks()
-                        .values()?
-                        .chain(iter::once(Ok(w)))
-                        .min_by(|res_x, res_y| match (res_x, res_y) {
-                            // if both watermarks are successfully fetched, compare them
-                            (Ok(x), Ok(y)) => x.cmp(y),
-                            // otherwise prefer errors
-                            (Err(_), _) => std::cmp::Ordering::Less,
-                            (_, Err(_)) => std::cmp::Ordering::Greater,
-                        })
-                        .expect(
-                            "this cannot fail, because the iterator contains at least `Ok(w)`",
+                    if state.terminated_channels().len()? == self.in_channels.len() {
+                        // We are instructed to shutdown....
+                        self.channel_strategy
+                            .borrow_mut()
+                            .add(ArconEvent::Death(s), self);
+                        self.ctx.suicide(); // TODO: is suicide enough?
+                    } else {
+                        drop(state);
+                        // A terminated input should no longer hold back the watermark
+                        self.handle_watermark(
+                            sender,
+                            Watermark::new(u64::max_value()),
+                            sb_session,
                         )?;
+                        // nor the alignment of an epoch the remaining senders are blocked on
+                        self.align_epoch(sb_session)?;
+                        self.channel_strategy.borrow_mut().flush(self);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 
-                    // Finally, handle the watermark:
-                    if new_watermark > current_watermark {
-                        // Update the stored watermark
-                        state.current_watermark().set(new_watermark)?;
-                        drop(state);
+    /// Transition to the pending epoch if all live senders are blocked on it
+    fn align_epoch(&self, sb_session: &mut state::Session<B>) -> ArconResult<()> {
+        let mut state = self.state.activate(sb_session);
+        let e = match state.pending_epoch().get()? {
+            Some(e) => e,
+            None => return Ok(()),
+        };
 
-                        // Handle the watermark
-                        self.operator.handle_watermark(
-                            new_watermark,
-                            self,
-                            make_context!(self, sb_session),
-                        );
+        let blocked = state.blocked_channels().len()?;
+        let terminated = state.terminated_channels().len()?;
+        if blocked == 0 || blocked + terminated != self.in_channels.len() {
+            return Ok(());
+        }
 
-                        let timeouts = self
-                            .timer_backend
-                            .borrow_mut()
-                            .advance_to(new_watermark.timestamp, sb_session);
+        // update current epoch
+        state.current_epoch().set(e)?;
+        state.pending_epoch().clear()?;
+        drop(state);
 
-                        for timeout in timeouts {
-                            self.operator.handle_timeout(
-                                timeout,
-                                self,
-                                make_context!(self, sb_session),
-                            );
-                        }
+        // handle epoch
+        self.operator
+            .handle_epoch(e, self, make_context!(self, sb_session));
 
-                        let mut metrics = self.metrics.borrow_mut();
+        self.timer_backend.borrow_mut().handle_epoch(e, sb_session);
 
-                        // Set current watermark
-                        metrics.watermark = new_watermark;
+        // store the state
+        self.save_state(sb_session)?;
 
-                        // Forward the watermark
-                        self.channel_strategy
-                            .borrow_mut()
-                            .add(ArconEvent::Watermark(new_watermark), self);
+        {
+            let mut metrics = self.metrics.borrow_mut();
+            // Set current epoch
+            metrics.epoch = e;
 
-                        // increment watermark counter
-                        metrics.watermark_counter.inc();
-                    }
+            // forward the epoch
+            self.channel_strategy
+                .borrow_mut()
+                .add(ArconEvent::Epoch(e), self);
+
+            // increment epoch counter
+            metrics.epoch_counter.inc();
+        }
+
+        self.after_state_save(sb_session)
+    }
+
+    /// Iterate over a batch of ArconEvent's from a feedback channel
+    #[inline]
+    fn handle_feedback_events<I>(
+        &self,
+        sender: NodeID,
+        events: I,
+        sb_session: &mut state::Session<B>,
+    ) -> ArconResult<()>
+    where
+        I: IntoIterator<Item = ArconEventWrapper<OP::IN>>,
+    {
+        for event in events.into_iter() {
+            match event.unwrap() {
+                ArconEvent::Element(e) => {
+                    self.log_feedback(sender, &e);
+                    self.operator
+                        .handle_element(e, self, make_context!(self, sb_session));
                 }
-                ArconEvent::Epoch(e) => {
-                    if e <= state
-                        .current_epoch()
-                        .get()?
-                        .ok_or_else(|| arcon_err_kind!("uninitialized epoch"))?
-                    {
-                        continue 'event_loop;
+                ArconEvent::Epoch(e) => self.complete_checkpoint(sender, e)?,
+                // Watermarks on a cycle derive from the Node's own watermark
+                ArconEvent::Watermark(_) => {}
+                // The cycle is torn down once the regular inputs have terminated
+                ArconEvent::Death(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Log an element from a feedback channel for every checkpoint still waiting on that channel
+    fn log_feedback(&self, sender: NodeID, element: &ArconElement<OP::IN>) {
+        for pending in self.pending_checkpoints.borrow_mut().values_mut() {
+            if pending.returned.contains(&sender) {
+                continue;
+            }
+
+            let event = ArconEvent::Element(element.clone()).into();
+            match pending.log.messages.last_mut() {
+                Some(message) if message.sender == sender => message.events.push(event),
+                _ => pending.log.messages.push(RawArconMessage {
+                    events: vec![event],
+                    sender,
+                }),
+            }
+        }
+    }
+
+    /// Register the return of an epoch marker on a feedback channel, and complete the
+    /// checkpoint of that epoch once the marker has returned on all of them
+    fn complete_checkpoint(&self, sender: NodeID, epoch: Epoch) -> ArconResult<()> {
+        let pending = {
+            let mut pending_checkpoints = self.pending_checkpoints.borrow_mut();
+            match pending_checkpoints.get_mut(&epoch.epoch) {
+                Some(pending) => {
+                    if !pending.returned.contains(&sender) {
+                        pending.returned.push(sender);
+                    }
+                    if pending.returned.len() < self.feedback_channels.len() {
+                        return Ok(());
                     }
+                }
+                // Markers of checkpoints taken before the Node was restored
+                None => return Ok(()),
+            }
+            pending_checkpoints.remove(&epoch.epoch).unwrap()
+        };
 
-                    // Add the sender to the blocked set.
-                    state.blocked_channels().fast_insert(sender, ())?;
+        let mut buf = Vec::with_capacity(pending.log.encoded_len());
+        pending
+            .log
+            .encode(&mut buf)
+            .map_err(|e| arcon_err_kind!("Failed to encode feedback log with err {:?}", e))?;
+        let log_path = self.checkpoint_path("feedback_log", epoch.epoch)?;
+        fs::write(&log_path, buf).map_err(|e| {
+            arcon_err_kind!("Failed to write feedback log {} with err {:?}", log_path, e)
+        })?;
+
+        // The checkpoint only shows up as complete once its log is in place
+        let pending_dir = self.checkpoint_path("pending_checkpoint", epoch.epoch)?;
+        let checkpoint_dir = self.checkpoint_path("checkpoint", epoch.epoch)?;
+        if Path::new(&pending_dir).exists() {
+            fs::rename(&pending_dir, &checkpoint_dir).map_err(|e| {
+                arcon_err_kind!(
+                    "Failed to complete checkpoint {} with err {:?}",
+                    pending_dir,
+                    e
+                )
+            })?;
+        }
+        debug!(
+            self.ctx.log(),
+            "Completed a Checkpoint to path {}", checkpoint_dir
+        );
 
-                    // If all senders blocked we can transition to new Epoch
-                    if state.blocked_channels().len()? == self.in_channels.len() {
-                        // update current epoch
-                        state.current_epoch().set(e)?;
-                        drop(state);
+        Ok(())
+    }
 
-                        // handle epoch
-                        self.operator
-                            .handle_epoch(e, self, make_context!(self, sb_session));
+    /// Replay the records that were in flight on the feedback channels when the checkpoint
+    /// the Node was restored from was taken
+    fn replay_feedback_log(&self, sb_session: &mut state::Session<B>) -> ArconResult<()> {
+        if self.feedback_channels.is_empty() {
+            return Ok(());
+        }
 
-                        self.timer_backend.borrow_mut().handle_epoch(e, sb_session);
+        let epoch = self
+            .state
+            .activate(sb_session)
+            .current_epoch()
+            .get()?
+            .ok_or_else(|| arcon_err_kind!("current epoch uninitialized"))?
+            .epoch;
+        let log_path = self.checkpoint_path("feedback_log", epoch)?;
+        let bytes = match fs::read(&log_path) {
+            Ok(bytes) => bytes,
+            // Nothing is logged before the first checkpoint
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                return arcon_err!("Failed to read feedback log {} with err {:?}", log_path, e)
+            }
+        };
+        let log = FeedbackLog::<OP::IN>::decode(&bytes[..])
+            .map_err(|e| arcon_err_kind!("Failed to decode feedback log with err {:?}", e))?;
 
-                        // store the state
-                        self.save_state(sb_session)?;
+        for message in log.messages {
+            self.handle_events(message.sender, message.events, sb_session)?;
+        }
 
-                        let mut metrics = self.metrics.borrow_mut();
-                        // Set current epoch
-                        metrics.epoch = e;
+        Ok(())
+    }
 
-                        // forward the epoch
-                        self.channel_strategy
-                            .borrow_mut()
-                            .add(ArconEvent::Epoch(e), self);
+    /// Register a Watermark from `sender` and advance the Node's watermark
+    /// to the lowest watermark across all of its inputs
+    #[inline]
+    fn handle_watermark(
+        &self,
+        sender: NodeID,
+        w: Watermark,
+        sb_session: &mut state::Session<B>,
+    ) -> ArconResult<()> {
+        let mut state = self.state.activate(sb_session);
+        if w <= state
+            .watermarks()
+            .get(&sender)?
+            .ok_or_else(|| arcon_err_kind!("uninitialized watermark"))?
+        {
+            return Ok(());
+        }
 
-                        // increment epoch counter
-                        metrics.epoch_counter.inc();
+        let current_watermark = state
+            .current_watermark()
+            .get()?
+            .ok_or_else(|| arcon_err_kind!("current watermark uninitialized"))?;
 
-                        self.after_state_save(sb_session)?;
-                    }
-                }
-                ArconEvent::Death(s) => {
-                    // We are instructed to shutdown....
-                    self.channel_strategy
-                        .borrow_mut()
-                        .add(ArconEvent::Death(s), self);
-                    self.ctx.suicide(); // TODO: is suicide enough?
-                }
+        // Insert the watermark and try early return
+        if let Some(old) = state.watermarks().insert(sender, w)? {
+            if old > current_watermark {
+                return Ok(());
+            }
+        }
+        // A different early return
+        if w <= current_watermark {
+            return Ok(());
+        }
+
+        // Let new_watermark take the value of the lowest watermark
+        let new_watermark = state
+            .watermarks()
+            .values()?
+            .chain(iter::once(Ok(w)))
+            .min_by(|res_x, res_y| match (res_x, res_y) {
+                // if both watermarks are successfully fetched, compare them
+                (Ok(x), Ok(y)) => x.cmp(y),
+                // otherwise prefer errors
+                (Err(_), _) => std::cmp::Ordering::Less,
+                (_, Err(_)) => std::cmp::Ordering::Greater,
+            })
+            .expect("this cannot fail, because the iterator contains at least `Ok(w)`")?;
+
+        // Finally, handle the watermark:
+        if new_watermark > current_watermark {
+            // Update the stored watermark
+            state.current_watermark().set(new_watermark)?;
+            drop(state);
+
+            // Handle the watermark
+            self.operator
+                .handle_watermark(new_watermark, self, make_context!(self, sb_session));
+
+            let timeouts = self
+                .timer_backend
+                .borrow_mut()
+                .advance_to(new_watermark.timestamp, sb_session);
+
+            for timeout in timeouts {
+                self.operator
+                    .handle_timeout(timeout, self, make_context!(self, sb_session));
             }
+
+            let mut metrics = self.metrics.borrow_mut();
+
+            // Set current watermark
+            metrics.watermark = new_watermark;
+
+            // Forward the watermark
+            self.channel_strategy
+                .borrow_mut()
+                .add(ArconEvent::Watermark(new_watermark), self);
+
+            // increment watermark counter
+            metrics.watermark_counter.inc();
         }
+
         Ok(())
     }
 
-    fn save_state(&self, sb_session: &mut state::Session<B>) -> ArconResult<()> {
-        let mut state = self.state.activate(sb_session);
-        if let Some(base_dir) = &self.ctx.config()["checkpoint_dir"].as_string() {
-            let checkpoint_dir = format!(
-                "{}/checkpoint_{id}_{epoch}",
+    /// Make sure a Kompact timer is armed for the earliest pending processing-time timer
+    fn arm_processing_timer(&mut self) {
+        let deadline = match self.timer_backend.get_mut().next_processing_deadline() {
+            Some(deadline) => deadline,
+            None => return,
+        };
+
+        if let Some((armed, _)) = &self.processing_wakeup {
+            if *armed <= deadline {
+                return;
+            }
+        }
+
+        if let Some((_, timer)) = self.processing_wakeup.take() {
+            self.cancel_timer(timer);
+        }
+
+        let delay = deadline.saturating_sub(crate::util::get_system_time());
+        let timer = self.schedule_once(Duration::from_millis(delay), |c_self, _id| {
+            c_self.processing_wakeup = None;
+            c_self.fire_processing_timers();
+            Handled::Ok
+        });
+        self.processing_wakeup = Some((deadline, timer));
+    }
+
+    /// Trigger all processing-time timers that have expired
+    fn fire_processing_timers(&mut self) {
+        {
+            let sb_session = &mut self.state_backend.session();
+            let timeouts = self
+                .timer_backend
+                .borrow_mut()
+                .advance_processing_to(crate::util::get_system_time(), sb_session);
+
+            for timeout in timeouts {
+                self.operator
+                    .handle_timeout(timeout, self, make_context!(self, sb_session));
+            }
+
+            // There may be no further input to trigger a flush
+            self.channel_strategy.borrow_mut().flush(self);
+        }
+
+        if !self.report_failure() {
+            self.arm_processing_timer();
+        }
+    }
+
+    /// Path of a file or directory in the checkpoint directory that belongs to `epoch`
+    fn checkpoint_path(&self, prefix: &str, epoch: u64) -> ArconResult<String> {
+        match self.ctx.config()["checkpoint_dir"].as_string() {
+            Some(base_dir) => Ok(format!(
+                "{}/{}_{id}_{epoch}",
                 base_dir,
+                prefix,
                 id = self.id.id,
-                epoch = state
-                    .current_epoch()
-                    .get()?
-                    .ok_or_else(|| arcon_err_kind!("current epoch uninitialized"))?
-                    .epoch
-            );
+                epoch = epoch
+            )),
+            None => arcon_err!("Failed to fetch checkpoint_dir from Config"),
+        }
+    }
+
+    fn save_state(&self, sb_session: &mut state::Session<B>) -> ArconResult<()> {
+        let epoch = self
+            .state
+            .activate(sb_session)
+            .current_epoch()
+            .get()?
+            .ok_or_else(|| arcon_err_kind!("current epoch uninitialized"))?
+            .epoch;
+
+        if self.spilling.get() {
+            // Spilled messages are part of the message buffer, and thereby of the checkpoint
+            let spill_path = self.spill_path()?;
+            let checkpoint_spill = self.checkpoint_path("message_spill", epoch)?;
+            fs::copy(&spill_path, &checkpoint_spill).map_err(|e| {
+                arcon_err_kind!(
+                    "Failed to checkpoint spilled messages {} with err {:?}",
+                    spill_path,
+                    e
+                )
+            })?;
+        }
+
+        if self.feedback_channels.is_empty() {
+            let checkpoint_dir = self.checkpoint_path("checkpoint", epoch)?;
             sb_session.backend.checkpoint(checkpoint_dir.as_ref())?;
             debug!(
                 self.ctx.log(),
                 "Completed a Checkpoint to path {}", checkpoint_dir
             );
         } else {
-            return arcon_err!("Failed to fetch checkpoint_dir from Config");
+            // Completed once the epoch marker has returned on all feedback channels
+            let checkpoint_dir = self.checkpoint_path("pending_checkpoint", epoch)?;
+            sb_session.backend.checkpoint(checkpoint_dir.as_ref())?;
+            self.pending_checkpoints
+                .borrow_mut()
+                .insert(epoch, PendingCheckpoint {
+                    returned: Vec::new(),
+                    log: FeedbackLog::default(),
+                });
         }
 
         Ok(())
     }
 
     fn after_state_save(&self, sb_session: &mut state::Session<B>) -> ArconResult<()> {
+        if let Some(allocator) = &self.allocator {
+            allocator
+                .lock()
+                .unwrap()
+                .release(Budget::Node(self.id.id), self.buffered_bytes.replace(0));
+        }
+
         let mut state = self.state.activate(sb_session);
         // flush the blocked_channels list
         state.blocked_channels().clear()?;
@@ -504,6 +1133,11 @@ where
             }
         }
 
+        // The rest of the message buffer was spilled after the messages above
+        if self.spilling.replace(false) {
+            self.replay_spilled_messages(sb_session)?;
+        }
+
         Ok(())
     }
 }
@@ -520,23 +1154,66 @@ where
             "Started Arcon Node {} with Node ID {:?}", self.descriptor, self.id
         );
 
+        self.tls_enabled.set(
+            self.ctx().config()["tls_enabled"]
+                .as_bool()
+                .unwrap_or(false),
+        );
+
         // Start periodic timer reporting Node metrics
         if let Some(interval) = &self.ctx().config()["node_metrics_interval"].as_i64() {
             let time_dur = std::time::Duration::from_millis(*interval as u64);
             self.schedule_periodic(time_dur, time_dur, |c_self, _id| {
-                c_self.node_manager_port.trigger(NodeEvent::Metrics(
-                    c_self.id,
-                    c_self.metrics.borrow().clone(),
-                ));
+                let metrics = {
+                    let mut metrics = c_self.metrics.borrow_mut();
+                    c_self.operator.report_metrics(&mut metrics);
+                    if let Some(pool) = c_self.channel_strategy.borrow().pool_metrics() {
+                        metrics.buffer_pool = pool.clone();
+                    }
+                    metrics.clone()
+                };
+                c_self
+                    .node_manager_port
+                    .trigger(NodeEvent::Metrics(c_self.id, metrics));
                 Handled::Ok
             });
         }
 
+        // Start periodic timer flushing partially filled channel buffers
+        if let Some(interval) = self.ctx().config()["channel_flush_interval"].as_i64() {
+            if interval > 0 {
+                let adaptive = self.ctx().config()["channel_adaptive_batching"]
+                    .as_bool()
+                    .unwrap_or(false);
+                let max_batch = self.ctx().config()["channel_batch_size"]
+                    .as_i64()
+                    .unwrap_or(1) as usize;
+                let time_dur = Duration::from_millis(interval as u64);
+                self.schedule_periodic(time_dur, time_dur, move |c_self, _id| {
+                    c_self.flush_channels();
+                    if adaptive {
+                        c_self.adapt_batch(max_batch);
+                    }
+                    c_self.report_failure();
+                    Handled::Ok
+                });
+            }
+        }
+
         if self.state_backend.get_mut().was_restored() {
-            if let Err(e) = self.after_state_save(&mut self.state_backend.session()) {
+            let sb_session = &mut self.state_backend.session();
+            if let Err(e) = self
+                .replay_feedback_log(sb_session)
+                .and_then(|_| self.restore_spilled_messages(sb_session))
+                .and_then(|_| self.after_state_save(sb_session))
+            {
                 error!(self.ctx.log(), "restoration error: {}", e);
             }
         }
+
+        // Processing-time timers may have been restored from state
+        self.arm_processing_timer();
+
         Handled::Ok
     }
 }
@@ -575,41 +1252,134 @@ where
         if let Err(err) = self.handle_message(msg) {
             error!(self.ctx.log(), "Failed to handle message: {}", err);
         }
+        self.flush_adaptive_batch();
+        self.arm_processing_timer();
+        self.report_failure();
         Handled::Ok
     }
     fn receive_network(&mut self, msg: NetMessage) -> Handled {
-        let unsafe_id = OP::IN::UNSAFE_SER_ID;
-        let reliable_id = OP::IN::RELIABLE_SER_ID;
         let ser_id = *msg.ser_id();
-
-        let arcon_msg = {
-            if ser_id == reliable_id {
-                msg.try_deserialise::<RawArconMessage<OP::IN>, ReliableSerde<OP::IN>>()
-                    .map_err(|e| {
-                        arcon_err_kind!("Failed to unpack reliable ArconMessage with err {:?}", e)
-                    })
-            } else if ser_id == unsafe_id {
-                msg.try_deserialise::<RawArconMessage<OP::IN>, UnsafeSerde<OP::IN>>()
-                    .map_err(|e| {
-                        arcon_err_kind!("Failed to unpack unreliable ArconMessage with err {:?}", e)
-                    })
-            } else {
-                panic!("Unexpected deserialiser")
+        if self.tls_enabled.get() && ser_id != TLS_FRAME_ID {
+            // Only TlsFrames come from authenticated peers
+            error!(
+                self.ctx.log(),
+                "Refused plain remote message with ser id {}, TLS is enabled", ser_id
+            );
+        } else if ser_id == OP::IN::UNSAFE_SER_ID {
+            // The events are decoded in a NetworkBuffer and read from it like a local message
+            let deserialise =
+                || msg.try_deserialise::<NetworkArconMessage<OP::IN>, UnsafeNetworkSerde<OP::IN>>();
+            let network_msg = match &self.allocator {
+                Some(allocator) => with_receive_allocator(allocator, deserialise),
+                None => deserialise(),
+            };
+            match network_msg {
+                Ok(m) => {
+                    if let Err(err) = self.handle_network_msg(m) {
+                        error!(self.ctx.log(), "Failed to handle node message: {}", err);
+                    }
+                }
+                Err(e) => error!(self.ctx.log(), "Error ArconNetworkMessage: {:?}", e),
             }
-        };
-
-        match arcon_msg {
-            Ok(m) => {
-                if let Err(err) = self.handle_raw_msg(m) {
-                    error!(self.ctx.log(), "Failed to handle node message: {}", err);
+        } else {
+            match deserialise_raw_message::<OP::IN>(msg) {
+                Ok(m) => {
+                    if let Err(err) = self.handle_raw_msg(m) {
+                        error!(self.ctx.log(), "Failed to handle node message: {}", err);
+                    }
                 }
+                Err(e) => error!(self.ctx.log(), "Error ArconNetworkMessage: {:?}", e),
             }
-            Err(e) => error!(self.ctx.log(), "Error ArconNetworkMessage: {:?}", e),
         }
+        self.flush_adaptive_batch();
+        self.arm_processing_timer();
+        self.report_failure();
         Handled::Ok
     }
 }
 
+/// Unpacks a remote message with the [FlightSerde] that its ser id refers to
+///
+/// Messages with an unknown ser id are returned as an error so that they
+/// can be logged and dropped by the receiving node.
+pub(crate) fn deserialise_raw_message<IN: ArconType>(
+    msg: NetMessage,
+) -> ArconResult<RawArconMessage<IN>> {
+    let ser_id = *msg.ser_id();
+    if ser_id == IN::RELIABLE_SER_ID {
+        msg.try_deserialise::<RawArconMessage<IN>, ReliableSerde<IN>>()
+            .map_err(|e| arcon_err_kind!("Failed to unpack reliable ArconMessage with err {:?}", e))
+    } else if ser_id == IN::UNSAFE_SER_ID {
+        msg.try_deserialise::<RawArconMessage<IN>, UnsafeSerde<IN>>()
+            .map_err(|e| {
+                arcon_err_kind!("Failed to unpack unreliable ArconMessage with err {:?}", e)
+            })
+    } else if ser_id == BINCODE_FLIGHT_ID {
+        deserialise_bincode(msg)
+    } else if ser_id == TLS_FRAME_ID {
+        msg.try_deserialise::<TlsFrame, TlsFrame>()
+            .map_err(|e| arcon_err_kind!("Failed to unpack TlsFrame with err {:?}", e))
+            .and_then(deserialise_tls_frame)
+    } else if ser_id == LZ4_FLIGHT_ID {
+        msg.try_deserialise::<RawArconMessage<IN>, Lz4Serde<IN>>()
+            .map_err(|e| arcon_err_kind!("Failed to unpack lz4 ArconMessage with err {:?}", e))
+    } else if ser_id == ZSTD_FLIGHT_ID {
+        msg.try_deserialise::<RawArconMessage<IN>, ZstdSerde<IN>>()
+            .map_err(|e| arcon_err_kind!("Failed to unpack zstd ArconMessage with err {:?}", e))
+    } else {
+        arcon_err!("Unexpected ser id {} of ArconMessage", ser_id)
+    }
+}
+
+/// Unpacks the payload of a [TlsFrame] with the [FlightSerde] that its ser id refers to
+///
+/// The message is refused unless the peer that sent the frame is bound to its sender.
+fn deserialise_tls_frame<IN: ArconType>(frame: TlsFrame) -> ArconResult<RawArconMessage<IN>> {
+    let ser_id = frame.ser_id;
+    let mut payload = frame.payload.as_slice();
+    let buf: &mut dyn Buf = &mut payload;
+    let msg = if ser_id == IN::RELIABLE_SER_ID {
+        ReliableSerde::<IN>::deserialise(buf)
+    } else if ser_id == IN::UNSAFE_SER_ID {
+        UnsafeSerde::<IN>::deserialise(buf)
+    } else if ser_id == BINCODE_FLIGHT_ID {
+        deserialise_bincode_payload::<IN>(buf)
+    } else {
+        return arcon_err!("Unexpected ser id {} of TlsFrame", ser_id);
+    }
+    .map_err(|e| arcon_err_kind!("Failed to unpack TlsFrame payload with err {:?}", e))?;
+    frame.check_sender(msg.sender)?;
+    Ok(msg)
+}
+
+#[cfg(feature = "arcon_serde")]
+fn deserialise_bincode_payload<IN: ArconType>(
+    buf: &mut dyn Buf,
+) -> Result<RawArconMessage<IN>, SerError> {
+    BincodeSerde::<IN>::deserialise(buf)
+}
+
+#[cfg(not(feature = "arcon_serde"))]
+fn deserialise_bincode_payload<IN: ArconType>(
+    _buf: &mut dyn Buf,
+) -> Result<RawArconMessage<IN>, SerError> {
+    Err(SerError::InvalidType(String::from(
+        "Received bincode ArconMessage without the arcon_serde feature",
+    )))
+}
+
+/// Unpacks a remote message that was sent with [FlightSerde::Bincode]
+#[cfg(feature = "arcon_serde")]
+fn deserialise_bincode<IN: ArconType>(msg: NetMessage) -> ArconResult<RawArconMessage<IN>> {
+    msg.try_deserialise::<RawArconMessage<IN>, BincodeSerde<IN>>()
+        .map_err(|e| arcon_err_kind!("Failed to unpack bincode ArconMessage with err {:?}", e))
+}
+
+#[cfg(not(feature = "arcon_serde"))]
+fn deserialise_bincode<IN: ArconType>(_msg: NetMessage) -> ArconResult<RawArconMessage<IN>> {
+    arcon_err!("Received bincode ArconMessage without the arcon_serde feature")
+}
+
 #[cfg(test)]
 mod tests {
     // Tests the message logic of Node.
@@ -622,9 +1392,16 @@ mod tests {
     use std::{sync::Arc, thread, time};
 
     fn node_test_setup() -> (ActorRef<ArconMessage<i32>>, Arc<Component<DebugNode<i32>>>) {
+        filter_node_setup(ArconConf::default(), None)
+    }
+
+    fn filter_node_setup(
+        conf: ArconConf,
+        memory_budget: Option<usize>,
+    ) -> (ActorRef<ArconMessage<i32>>, Arc<Component<DebugNode<i32>>>) {
         // Returns a filter Node with input channels: sender1..sender3
         // And a debug sink receiving its results
-        let mut pipeline = ArconPipeline::new();
+        let mut pipeline = ArconPipeline::with_conf(conf);
         let pool_info = pipeline.get_pool_info();
         let system = &pipeline.system();
 
@@ -640,8 +1417,15 @@ mod tests {
             *x >= 0
         }
 
+        let allocator = pipeline.allocator();
+        if let Some(bytes) = memory_budget {
+            pipeline
+                .set_memory_budget(Budget::Node(0), bytes)
+                .expect("Failed to set budget");
+        }
+
         let filter_node = system.create(move || {
-            Node::new(
+            let node = Node::new(
                 String::from("filter_node"),
                 0.into(),
                 vec![1.into(), 2.into(), 3.into()],
@@ -649,7 +1433,11 @@ mod tests {
                 Filter::new(&node_fn),
                 InMemory::create("test".as_ref()).unwrap(),
                 timer::none(),
-            )
+            );
+            match memory_budget {
+                Some(_) => node.with_allocator(allocator),
+                None => node,
+            }
         });
 
         system.start(&filter_node);
@@ -748,7 +1536,12 @@ mod tests {
 
     #[test]
     fn node_epoch_no_continue() {
-        let (node_ref, sink) = node_test_setup();
+        // Flushes the channel buffers without terminating sender3
+        let conf = ArconConf {
+            channel_flush_interval: 100,
+            ..ArconConf::default()
+        };
+        let (node_ref, sink) = filter_node_setup(conf, None);
         node_ref.tell(element(11, 1, 1)); // not blocked
         node_ref.tell(epoch(1, 1)); // sender1 blocked
         node_ref.tell(element(12, 1, 1)); // blocked
@@ -759,7 +1552,6 @@ mod tests {
         node_ref.tell(element(23, 1, 2)); // blocked
         node_ref.tell(element(31, 1, 3)); // not blocked
 
-        node_ref.tell(death(3)); // send death marker on unblocked channel to flush
         wait(1);
         sink.on_definition(|cd| {
             let data_len = cd.data.len();
@@ -805,4 +1597,269 @@ mod tests {
             assert_eq!(data_len, 6);
         });
     }
+
+    #[test]
+    fn node_epoch_death_align() {
+        let (node_ref, sink) = node_test_setup();
+        node_ref.tell(death(3));
+        node_ref.tell(element(11, 1, 1)); // not blocked
+        node_ref.tell(epoch(1, 1)); // sender1 blocked
+        node_ref.tell(element(12, 1, 1)); // blocked
+                                          // sender2 was the last one holding back epoch 1
+        node_ref.tell(death(2));
+
+        wait(1);
+        sink.on_definition(|cd| {
+            assert_eq!(cd.epochs.len(), 1);
+            let data: Vec<i32> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(data, vec![11, 12]);
+        });
+    }
+
+    #[test]
+    fn node_epoch_death_continue() {
+        // Same as node_epoch_no_continue, but sender3 terminates instead of sending the epochs
+        let (node_ref, sink) = node_test_setup();
+        node_ref.tell(element(11, 1, 1)); // not blocked
+        node_ref.tell(epoch(1, 1)); // sender1 blocked
+        node_ref.tell(element(12, 1, 1)); // blocked
+        node_ref.tell(element(21, 1, 2)); // not blocked
+        node_ref.tell(epoch(2, 1)); // blocked
+        node_ref.tell(epoch(1, 2)); // sender2 blocked
+        node_ref.tell(epoch(2, 2)); // blocked
+        node_ref.tell(element(23, 1, 2)); // blocked
+        node_ref.tell(element(31, 1, 3)); // not blocked
+        node_ref.tell(death(3)); // completes both epochs
+
+        wait(1);
+        sink.on_definition(|cd| {
+            let epochs: Vec<u64> = cd.epochs.iter().map(|e| e.epoch).collect();
+            assert_eq!(epochs, vec![1, 2]);
+            let data: Vec<i32> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(data, vec![11, 21, 31, 12, 23]);
+        });
+    }
+
+    #[test]
+    fn node_epoch_spill() {
+        // Same as node_epoch_continue, but the blocked messages do not fit in the memory budget
+        let checkpoint_dir = tempfile::tempdir().unwrap();
+        let conf = ArconConf {
+            checkpoint_dir: checkpoint_dir.path().to_path_buf(),
+            ..ArconConf::default()
+        };
+        let (node_ref, sink) = filter_node_setup(conf, Some(1));
+        node_ref.tell(element(11, 1, 1)); // not blocked
+        node_ref.tell(epoch(1, 1)); // sender1 blocked
+        node_ref.tell(element(12, 1, 1)); // spilled
+        node_ref.tell(element(21, 1, 2)); // not blocked
+        node_ref.tell(epoch(2, 1)); // spilled
+        node_ref.tell(element(13, 1, 1)); // spilled
+        node_ref.tell(epoch(1, 2)); // sender2 blocked
+        node_ref.tell(epoch(2, 2)); // spilled
+        node_ref.tell(element(22, 1, 2)); // spilled
+        node_ref.tell(element(31, 1, 3)); // not blocked
+        node_ref.tell(epoch(1, 3)); // Complete our epochs
+        node_ref.tell(epoch(2, 3));
+
+        node_ref.tell(death(3)); // send death marker on unblocked channel to flush
+        wait(1);
+        sink.on_definition(|cd| {
+            assert_eq!(cd.epochs.len(), 2);
+            let data: Vec<i32> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(data, vec![11, 21, 31, 12, 13, 22]);
+        });
+
+        // The spilled messages are part of the checkpoint of epoch 1
+        assert!(checkpoint_dir.path().join("message_spill_0_1").exists());
+        assert!(!checkpoint_dir.path().join("message_spill_0").exists());
+    }
+
+    #[test]
+    fn node_fails_if_spill_fails() {
+        // The checkpoint_dir does not exist, so the blocked message cannot be spilled
+        let checkpoint_dir = tempfile::tempdir().unwrap();
+        let conf = ArconConf {
+            checkpoint_dir: checkpoint_dir.path().join("missing"),
+            ..ArconConf::default()
+        };
+        let (node_ref, sink) = filter_node_setup(conf, Some(1));
+        node_ref.tell(epoch(1, 1)); // sender1 blocked
+        node_ref.tell(element(12, 1, 1)); // spill fails
+        node_ref.tell(element(21, 1, 2));
+        node_ref.tell(death(2));
+
+        wait(1);
+        // A running Node would have flushed element 21 on the death marker
+        sink.on_definition(|cd| {
+            assert!(cd.data.is_empty());
+        });
+    }
+
+    #[test]
+    fn node_partial_death() {
+        let (node_ref, sink) = node_test_setup();
+        node_ref.tell(watermark(2, 1));
+        node_ref.tell(watermark(3, 2));
+        // sender3 terminates and no longer holds back the watermark
+        node_ref.tell(death(3));
+        // the Node should still process events from the live senders
+        node_ref.tell(element(11, 4, 1));
+        node_ref.tell(watermark(5, 1));
+
+        wait(1);
+        sink.on_definition(|cd| {
+            assert_eq!(cd.data.len(), 1);
+            assert_eq!(cd.data[0].data, 11i32);
+            let timestamps: Vec<u64> = cd.watermarks.iter().map(|w| w.timestamp).collect();
+            assert_eq!(timestamps, vec![2, 3]);
+        });
+    }
+
+    #[test]
+    fn node_feedback_channel() {
+        let checkpoint_dir = tempfile::tempdir().unwrap();
+        let conf = ArconConf {
+            checkpoint_dir: checkpoint_dir.path().to_path_buf(),
+            ..ArconConf::default()
+        };
+        let mut pipeline = ArconPipeline::with_conf(conf);
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let sink = system.create(move || DebugNode::<i32>::new());
+        system.start(&sink);
+
+        // Counts down every element it receives, and feeds the result back to itself
+        let loop_node = system.create(move || {
+            Node::new(
+                String::from("loop_node"),
+                1.into(),
+                vec![0.into()],
+                ChannelStrategy::Mute,
+                FlatMap::new(|x: i32| if x > 0 { vec![x - 1] } else { vec![] }),
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::none(),
+            )
+            .with_feedback_channels(vec![1.into()])
+        });
+
+        let sink_ref: ActorRefStrong<ArconMessage<i32>> =
+            sink.actor_ref().hold().expect("Failed to fetch");
+        let loop_ref: ActorRefStrong<ArconMessage<i32>> =
+            loop_node.actor_ref().hold().expect("Failed to fetch");
+        loop_node.on_definition(|cd| {
+            cd.set_channel_strategy(ChannelStrategy::Broadcast(Broadcast::new(
+                vec![Channel::Local(sink_ref), Channel::Local(loop_ref.clone())],
+                1.into(),
+                pool_info,
+            )));
+        });
+        system.start(&loop_node);
+
+        loop_ref.tell(ArconMessage::element(3, Some(1), 0.into()));
+        // The 2 is in flight on the feedback channel during the checkpoint
+        loop_ref.tell(ArconMessage::epoch(1, 0.into()));
+        wait(1);
+        // Watermarks flush the remaining rounds of the loop
+        loop_ref.tell(ArconMessage::watermark(10, 0.into()));
+        wait(1);
+        loop_ref.tell(ArconMessage::watermark(20, 0.into()));
+        wait(1);
+
+        sink.on_definition(|cd| {
+            let data: Vec<i32> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(data, vec![2, 1, 0]);
+            assert_eq!(cd.epochs.len(), 1);
+        });
+
+        let log = std::fs::read(checkpoint_dir.path().join("feedback_log_1_1")).unwrap();
+        let log = FeedbackLog::<i32>::decode(&log[..]).unwrap();
+        let logged: Vec<i32> = log
+            .messages
+            .into_iter()
+            .flat_map(|m| m.events)
+            .filter_map(|e| match e.unwrap() {
+                ArconEvent::Element(e) => Some(e.data),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(logged, vec![2]);
+
+        pipeline.shutdown();
+    }
+
+    fn flush_test_setup(
+        conf: ArconConf,
+    ) -> (
+        ArconPipeline,
+        ActorRefStrong<ArconMessage<i32>>,
+        Arc<Component<DebugNode<i32>>>,
+    ) {
+        let pipeline = ArconPipeline::with_conf(conf);
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let sink = system.create(move || DebugNode::<i32>::new());
+        system.start(&sink);
+        let actor_ref: ActorRefStrong<ArconMessage<i32>> =
+            sink.actor_ref().hold().expect("Failed to fetch");
+        let channel_strategy =
+            ChannelStrategy::Forward(Forward::new(Channel::Local(actor_ref), 1.into(), pool_info));
+
+        let node = system.create(move || {
+            Node::new(
+                String::from("map_node"),
+                1.into(),
+                vec![0.into()],
+                channel_strategy,
+                Map::new(|x: i32| x + 1),
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::none(),
+            )
+        });
+        system.start(&node);
+        let node_ref = node.actor_ref().hold().expect("Failed to fetch");
+
+        (pipeline, node_ref, sink)
+    }
+
+    #[test]
+    fn node_flush_interval() {
+        let conf = ArconConf {
+            channel_flush_interval: 100,
+            ..ArconConf::default()
+        };
+        let (mut pipeline, node_ref, sink) = flush_test_setup(conf);
+
+        // Without a watermark, only the flush interval pushes the element out
+        node_ref.tell(ArconMessage::element(1, Some(1), 0.into()));
+        wait(1);
+        sink.on_definition(|cd| {
+            assert_eq!(cd.data.len(), 1);
+            assert_eq!(cd.data[0].data, 2);
+        });
+
+        pipeline.shutdown();
+    }
+
+    #[test]
+    fn node_adaptive_batch() {
+        let conf = ArconConf {
+            channel_flush_interval: 2000,
+            channel_adaptive_batching: true,
+            ..ArconConf::default()
+        };
+        let (mut pipeline, node_ref, sink) = flush_test_setup(conf);
+
+        // An idle interval shrinks the batch to a single event
+        thread::sleep(time::Duration::from_millis(2500));
+        node_ref.tell(ArconMessage::element(1, Some(1), 0.into()));
+        thread::sleep(time::Duration::from_millis(500));
+        sink.on_definition(|cd| {
+            assert_eq!(cd.data.len(), 1);
+        });
+
+        pipeline.shutdown();
+    }
 }
@@ -0,0 +1,511 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    data::{ArconF32, ArconF64},
+    prelude::state::{Aggregator, Value},
+    util::prost_helpers::ProstOption,
+};
+use prost::Message;
+use std::marker::PhantomData;
+
+/// Numeric types that the built-in aggregators operate on
+pub trait Numeric: Value + Send + Sync {
+    /// The additive identity
+    fn zero() -> Self;
+    /// Returns the sum of `self` and `other`
+    fn plus(&self, other: &Self) -> Self;
+    /// Returns the difference of `self` and `other`, or None if it is out of range
+    fn minus(&self, other: &Self) -> Option<Self>;
+    /// Returns true if `self` is strictly lower than `other`
+    fn less_than(&self, other: &Self) -> bool;
+    /// Converts the value into a [f64]
+    fn as_f64(&self) -> f64;
+}
+
+// `$minus` computes the difference of `$a` and `$b`, or None if it is out of range
+macro_rules! impl_numeric {
+    (|$a:ident, $b:ident| $minus:expr; $($t:ty),*) => {
+        $(
+            impl Numeric for $t {
+                fn zero() -> Self {
+                    0 as $t
+                }
+                fn plus(&self, other: &Self) -> Self {
+                    *self + *other
+                }
+                fn minus(&self, other: &Self) -> Option<Self> {
+                    let ($a, $b) = (*self, *other);
+                    $minus
+                }
+                fn less_than(&self, other: &Self) -> bool {
+                    *self < *other
+                }
+                fn as_f64(&self) -> f64 {
+                    *self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_numeric!(|a, b| a.checked_sub(b); u32, u64, i32, i64);
+impl_numeric!(|a, b| Some(a - b); f32, f64);
+
+macro_rules! impl_numeric_float_wrapper {
+    ($($t:ident),*) => {
+        $(
+            impl Numeric for $t {
+                fn zero() -> Self {
+                    $t::new(0.0)
+                }
+                fn plus(&self, other: &Self) -> Self {
+                    $t::new(self.value + other.value)
+                }
+                fn minus(&self, other: &Self) -> Option<Self> {
+                    Some($t::new(self.value - other.value))
+                }
+                fn less_than(&self, other: &Self) -> bool {
+                    self.value < other.value
+                }
+                fn as_f64(&self) -> f64 {
+                    self.value as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_numeric_float_wrapper!(ArconF32, ArconF64);
+
+/// An [Aggregator] whose inputs can be taken out of an accumulator again
+///
+/// Retractable aggregators can process changelog streams, see
+/// [MergeAggregate](super::MergeAggregate).
+pub trait Retractable: Aggregator {
+    /// Removes `value` from `acc`, which it has been added to before
+    ///
+    /// Returns false and leaves `acc` unchanged if `value` cannot be taken out of it,
+    /// e.g., because the result would underflow.
+    fn retract(&self, acc: &mut Self::Accumulator, value: Self::Input) -> bool;
+}
+
+/// Sums up its inputs
+#[derive(Debug, Clone)]
+pub struct Sum<T: Numeric>(PhantomData<T>);
+
+impl<T: Numeric> Sum<T> {
+    pub fn new() -> Self {
+        Sum(PhantomData)
+    }
+}
+
+impl<T: Numeric> Aggregator for Sum<T> {
+    type Input = T;
+    type Accumulator = T;
+    type Result = T;
+
+    fn create_accumulator(&self) -> T {
+        T::zero()
+    }
+
+    fn add(&self, acc: &mut T, value: T) {
+        *acc = acc.plus(&value);
+    }
+
+    fn merge_accumulators(&self, fst: T, snd: T) -> T {
+        fst.plus(&snd)
+    }
+
+    fn accumulator_into_result(&self, acc: T) -> T {
+        acc
+    }
+}
+
+impl<T: Numeric> Retractable for Sum<T> {
+    fn retract(&self, acc: &mut T, value: T) -> bool {
+        match acc.minus(&value) {
+            Some(difference) => {
+                *acc = difference;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Counts its inputs
+#[derive(Debug, Clone)]
+pub struct Count<T: Value>(PhantomData<fn(T)>);
+
+impl<T: Value> Count<T> {
+    pub fn new() -> Self {
+        Count(PhantomData)
+    }
+}
+
+impl<T: Value> Aggregator for Count<T> {
+    type Input = T;
+    type Accumulator = u64;
+    type Result = u64;
+
+    fn create_accumulator(&self) -> u64 {
+        0
+    }
+
+    fn add(&self, acc: &mut u64, _value: T) {
+        *acc += 1;
+    }
+
+    fn merge_accumulators(&self, fst: u64, snd: u64) -> u64 {
+        fst + snd
+    }
+
+    fn accumulator_into_result(&self, acc: u64) -> u64 {
+        acc
+    }
+}
+
+impl<T: Value> Retractable for Count<T> {
+    fn retract(&self, acc: &mut u64, _value: T) -> bool {
+        match acc.checked_sub(1) {
+            Some(count) => {
+                *acc = count;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// Keeps the lower of two optional values, or the higher one if `keep_max` is set
+fn select<T: Numeric>(fst: Option<T>, snd: Option<T>, keep_max: bool) -> Option<T> {
+    match (fst, snd) {
+        (Some(a), Some(b)) => {
+            if b.less_than(&a) != keep_max {
+                Some(b)
+            } else {
+                Some(a)
+            }
+        }
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// Keeps the lowest input
+///
+/// The result of an empty accumulator is the default value of `T`.
+#[derive(Debug, Clone)]
+pub struct Min<T: Numeric>(PhantomData<T>);
+
+impl<T: Numeric> Min<T> {
+    pub fn new() -> Self {
+        Min(PhantomData)
+    }
+}
+
+impl<T: Numeric> Aggregator for Min<T> {
+    type Input = T;
+    type Accumulator = ProstOption<T>;
+    type Result = T;
+
+    fn create_accumulator(&self) -> ProstOption<T> {
+        None.into()
+    }
+
+    fn add(&self, acc: &mut ProstOption<T>, value: T) {
+        acc.inner = select(acc.inner.take(), Some(value), false);
+    }
+
+    fn merge_accumulators(&self, fst: ProstOption<T>, snd: ProstOption<T>) -> ProstOption<T> {
+        select(fst.inner, snd.inner, false).into()
+    }
+
+    fn accumulator_into_result(&self, acc: ProstOption<T>) -> T {
+        acc.inner.unwrap_or_default()
+    }
+}
+
+/// Keeps the highest input
+///
+/// The result of an empty accumulator is the default value of `T`.
+#[derive(Debug, Clone)]
+pub struct Max<T: Numeric>(PhantomData<T>);
+
+impl<T: Numeric> Max<T> {
+    pub fn new() -> Self {
+        Max(PhantomData)
+    }
+}
+
+impl<T: Numeric> Aggregator for Max<T> {
+    type Input = T;
+    type Accumulator = ProstOption<T>;
+    type Result = T;
+
+    fn create_accumulator(&self) -> ProstOption<T> {
+        None.into()
+    }
+
+    fn add(&self, acc: &mut ProstOption<T>, value: T) {
+        acc.inner = select(acc.inner.take(), Some(value), true);
+    }
+
+    fn merge_accumulators(&self, fst: ProstOption<T>, snd: ProstOption<T>) -> ProstOption<T> {
+        select(fst.inner, snd.inner, true).into()
+    }
+
+    fn accumulator_into_result(&self, acc: ProstOption<T>) -> T {
+        acc.inner.unwrap_or_default()
+    }
+}
+
+/// Accumulator of the [Avg] aggregator
+#[cfg_attr(feature = "arcon_serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Message, Clone, PartialEq)]
+pub struct AvgAccumulator {
+    #[prost(double, tag = "1")]
+    pub sum: f64,
+    #[prost(uint64, tag = "2")]
+    pub count: u64,
+}
+
+/// Computes the arithmetic mean of its inputs
+///
+/// The result of an empty accumulator is NaN.
+#[derive(Debug, Clone)]
+pub struct Avg<T: Numeric>(PhantomData<T>);
+
+impl<T: Numeric> Avg<T> {
+    pub fn new() -> Self {
+        Avg(PhantomData)
+    }
+}
+
+impl<T: Numeric> Aggregator for Avg<T> {
+    type Input = T;
+    type Accumulator = AvgAccumulator;
+    type Result = ArconF64;
+
+    fn create_accumulator(&self) -> AvgAccumulator {
+        AvgAccumulator::default()
+    }
+
+    fn add(&self, acc: &mut AvgAccumulator, value: T) {
+        acc.sum += value.as_f64();
+        acc.count += 1;
+    }
+
+    fn merge_accumulators(&self, fst: AvgAccumulator, snd: AvgAccumulator) -> AvgAccumulator {
+        AvgAccumulator {
+            sum: fst.sum + snd.sum,
+            count: fst.count + snd.count,
+        }
+    }
+
+    fn accumulator_into_result(&self, acc: AvgAccumulator) -> ArconF64 {
+        ArconF64::new(acc.sum / acc.count as f64)
+    }
+}
+
+/// Accumulator of the [TopK] aggregator, holding at most k values in descending order
+#[cfg_attr(feature = "arcon_serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Message, Clone)]
+#[cfg_attr(feature = "arcon_serde", serde(bound = "T: Numeric"))]
+pub struct TopKAccumulator<T: Numeric> {
+    #[prost(message, repeated, tag = "1")]
+    pub values: Vec<T>,
+}
+
+/// Keeps the k highest inputs
+#[derive(Debug, Clone)]
+pub struct TopK<T: Numeric> {
+    k: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Numeric> TopK<T> {
+    pub fn new(k: usize) -> Self {
+        TopK {
+            k,
+            _marker: PhantomData,
+        }
+    }
+
+    fn insert(&self, values: &mut Vec<T>, value: T) {
+        let pos = values
+            .iter()
+            .position(|v| v.less_than(&value))
+            .unwrap_or_else(|| values.len());
+        if pos < self.k {
+            values.insert(pos, value);
+            values.truncate(self.k);
+        }
+    }
+}
+
+impl<T: Numeric> Aggregator for TopK<T> {
+    type Input = T;
+    type Accumulator = TopKAccumulator<T>;
+    type Result = Vec<T>;
+
+    fn create_accumulator(&self) -> TopKAccumulator<T> {
+        TopKAccumulator { values: Vec::new() }
+    }
+
+    fn add(&self, acc: &mut TopKAccumulator<T>, value: T) {
+        self.insert(&mut acc.values, value);
+    }
+
+    fn merge_accumulators(
+        &self,
+        mut fst: TopKAccumulator<T>,
+        snd: TopKAccumulator<T>,
+    ) -> TopKAccumulator<T> {
+        for value in snd.values {
+            self.insert(&mut fst.values, value);
+        }
+        fst
+    }
+
+    fn accumulator_into_result(&self, acc: TopKAccumulator<T>) -> Vec<T> {
+        acc.values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::state::{
+        with_backend_type, Backend, BackendContainer, BackendType, Handle, RegistrationToken,
+    };
+
+    fn fold<A: Aggregator>(aggregator: &A, values: Vec<A::Input>) -> A::Accumulator {
+        let mut acc = aggregator.create_accumulator();
+        for value in values {
+            aggregator.add(&mut acc, value);
+        }
+        acc
+    }
+
+    // Aggregates `values` for two different keys and checks the result of each key
+    fn check_aggregator<A, B>(
+        backend: &BackendContainer<B>,
+        id: &'static str,
+        aggregator: A,
+        values: Vec<A::Input>,
+        expected: A::Result,
+    ) where
+        A: Aggregator,
+        A::Input: Clone,
+        A::Result: PartialEq + std::fmt::Debug,
+        B: Backend,
+    {
+        let mut session = backend.session();
+        let handle = Handle::aggregator(id, aggregator).with_item_key(0u64);
+        handle.register(&mut unsafe { RegistrationToken::new(&mut session) });
+
+        for key in 0..2u64 {
+            handle.set_item_key(key);
+            let mut state = handle.activate(&mut session);
+            for value in values.iter().skip(key as usize) {
+                state.aggregate(value.clone()).unwrap();
+            }
+        }
+
+        handle.set_item_key(0);
+        assert_eq!(handle.activate(&mut session).get().unwrap(), expected);
+    }
+
+    fn check_all_aggregators<B: Backend>(backend: BackendContainer<B>) {
+        check_aggregator(&backend, "sum", Sum::<u64>::new(), vec![1, 2, 3], 6);
+        check_aggregator(&backend, "count", Count::<i32>::new(), vec![5, 5, 5, 5], 4);
+        check_aggregator(&backend, "min", Min::<i64>::new(), vec![3, -2, 7], -2);
+        check_aggregator(&backend, "max", Max::<i64>::new(), vec![3, -2, 7], 7);
+        check_aggregator(
+            &backend,
+            "avg",
+            Avg::<u32>::new(),
+            vec![1, 2, 3, 6],
+            ArconF64::new(3.0),
+        );
+        check_aggregator(
+            &backend,
+            "top_k",
+            TopK::<u64>::new(3),
+            vec![4, 9, 1, 7, 3, 9],
+            vec![9, 9, 7],
+        );
+    }
+
+    #[test]
+    fn aggregators_on_every_backend() {
+        for backend_type in BackendType::VARIANTS {
+            let dir = tempfile::tempdir().unwrap();
+            with_backend_type!(*backend_type, |SB| check_all_aggregators(
+                SB::create(dir.path()).unwrap()
+            ));
+        }
+    }
+
+    #[test]
+    fn retract_values() {
+        let sum = Sum::<u64>::new();
+        let mut acc = fold(&sum, vec![4, 5, 6]);
+        assert!(sum.retract(&mut acc, 5));
+        assert_eq!(sum.accumulator_into_result(acc), 10);
+
+        let count = Count::<u32>::new();
+        let mut acc = fold(&count, vec![1, 2, 3]);
+        assert!(count.retract(&mut acc, 2));
+        assert_eq!(count.accumulator_into_result(acc), 2);
+    }
+
+    #[test]
+    fn retract_rejects_underflow() {
+        let sum = Sum::<u64>::new();
+        let mut acc = fold(&sum, vec![4]);
+        assert!(!sum.retract(&mut acc, 5));
+        assert_eq!(sum.accumulator_into_result(acc), 4);
+
+        let count = Count::<u32>::new();
+        let mut acc = count.create_accumulator();
+        assert!(!count.retract(&mut acc, 1));
+        assert_eq!(count.accumulator_into_result(acc), 0);
+    }
+
+    #[test]
+    fn merge_accumulators() {
+        let sum = Sum::<i32>::new();
+        let merged = sum.merge_accumulators(fold(&sum, vec![1, 2]), fold(&sum, vec![3]));
+        assert_eq!(sum.accumulator_into_result(merged), 6);
+
+        let count = Count::<u32>::new();
+        let merged = count.merge_accumulators(fold(&count, vec![1, 2]), fold(&count, vec![]));
+        assert_eq!(count.accumulator_into_result(merged), 2);
+
+        let min = Min::<ArconF64>::new();
+        let merged = min.merge_accumulators(
+            fold(&min, vec![ArconF64::new(2.5)]),
+            fold(&min, vec![ArconF64::new(-1.0), ArconF64::new(4.0)]),
+        );
+        assert_eq!(min.accumulator_into_result(merged), ArconF64::new(-1.0));
+
+        let max = Max::<u64>::new();
+        let merged = max.merge_accumulators(fold(&max, vec![]), fold(&max, vec![8, 2]));
+        assert_eq!(max.accumulator_into_result(merged), 8);
+
+        let avg = Avg::<i64>::new();
+        let merged = avg.merge_accumulators(fold(&avg, vec![1, 2]), fold(&avg, vec![6]));
+        assert_eq!(avg.accumulator_into_result(merged), ArconF64::new(3.0));
+        assert!(avg
+            .accumulator_into_result(avg.create_accumulator())
+            .is_nan());
+
+        let top_k = TopK::<i32>::new(2);
+        let merged =
+            top_k.merge_accumulators(fold(&top_k, vec![5, 1]), fold(&top_k, vec![3, 8, 2]));
+        assert_eq!(top_k.accumulator_into_result(merged), vec![8, 5]);
+    }
+}
@@ -0,0 +1,611 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+/// Built-in implementations of [Aggregator](arcon_state::Aggregator)
+pub mod aggregators;
+/// Approximate aggregators backed by mergeable sketches
+pub mod sketches;
+
+pub use aggregators::{Avg, Count, Max, Min, Numeric, Retractable, Sum, TopK};
+pub use sketches::{CountMin, CountMinSketch, HyperLogLog, HyperLogLogSketch, Quantiles, TDigest};
+
+use crate::{
+    prelude::{
+        state::{Aggregator, AggregatorState, Handle, ValueState},
+        *,
+    },
+    stream::{
+        node::NodeMetrics,
+        operator::{InsertOnly, OperatorContext},
+    },
+    timer::TimerBackend,
+    util::SafelySendableFn,
+};
+
+/// A keyed rolling aggregation
+///
+/// Elements are aggregated per key, as given by [ArconType::get_key], and the current
+/// result of the key is emitted for every element. The output forms a changelog: the
+/// first result of a key is an insert, and every later result is emitted as an
+/// [ChangeKind::UpdateBefore] of the previous result followed by an [ChangeKind::UpdateAfter].
+///
+/// `output` builds the output element from the latest input and the aggregation result,
+/// so it should only depend on the key fields of the input.
+///
+/// The input must be insert-only. Aggregators cannot retract values, so elements of any
+/// other [ChangeKind] are dropped and counted in the [NodeMetrics].
+pub struct RollingAggregate<IN, OUT, A>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    A: Aggregator,
+{
+    extract: &'static dyn SafelySendableFn(&IN) -> A::Input,
+    output: &'static dyn SafelySendableFn(&IN, A::Result) -> OUT,
+    aggregate: Handle<AggregatorState<A>, u64>,
+    seen: Handle<ValueState<bool>, u64>,
+    insert_only: InsertOnly,
+}
+
+impl<IN, OUT, A> RollingAggregate<IN, OUT, A>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    A: Aggregator,
+{
+    pub fn new(
+        aggregator: A,
+        extract: &'static dyn SafelySendableFn(&IN) -> A::Input,
+        output: &'static dyn SafelySendableFn(&IN, A::Result) -> OUT,
+    ) -> Self {
+        RollingAggregate {
+            extract,
+            output,
+            aggregate: Handle::aggregator("rolling_aggregate", aggregator).with_item_key(0),
+            seen: Handle::value("rolling_aggregate_seen").with_item_key(0),
+            insert_only: InsertOnly::default(),
+        }
+    }
+}
+
+impl<IN, OUT, A, B> Operator<B> for RollingAggregate<IN, OUT, A>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    A: Aggregator,
+    B: state::Backend,
+{
+    type IN = IN;
+    type OUT = OUT;
+    type TimerState = ArconNever;
+
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+        self.aggregate.register(registration_token);
+        self.seen.register(registration_token);
+    }
+
+    fn init(&mut self, _session: &mut state::Session<B>) {}
+
+    fn handle_element<CD>(
+        &self,
+        element: ArconElement<IN>,
+        source: &CD,
+        mut ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        if self.insert_only.reject(&element, source) {
+            return;
+        }
+
+        let key = element.data.get_key();
+        self.aggregate.set_item_key(key);
+        self.seen.set_item_key(key);
+
+        let mut seen = self.seen.activate(ctx.state_session);
+        let previous = if seen.get().expect("seen state get error").unwrap_or(false) {
+            drop(seen);
+            let mut aggregate = self.aggregate.activate(ctx.state_session);
+            Some(aggregate.get().expect("aggregate state get error"))
+        } else {
+            seen.set(true).expect("seen state set error");
+            None
+        };
+
+        let mut aggregate = self.aggregate.activate(ctx.state_session);
+        aggregate
+            .aggregate((self.extract)(&element.data))
+            .expect("aggregate state error");
+        let result = aggregate.get().expect("aggregate state get error");
+
+        let timestamp = element.timestamp;
+        let new_kind = if let Some(previous) = previous {
+            let retraction = ArconElement::with_change(
+                (self.output)(&element.data, previous),
+                timestamp,
+                ChangeKind::UpdateBefore,
+            );
+            ctx.output(ArconEvent::Element(retraction), source);
+            ChangeKind::UpdateAfter
+        } else {
+            ChangeKind::Insert
+        };
+
+        let update =
+            ArconElement::with_change((self.output)(&element.data, result), timestamp, new_kind);
+        ctx.output(ArconEvent::Element(update), source);
+    }
+
+    crate::ignore_watermark!(B);
+    crate::ignore_epoch!(B);
+    crate::ignore_timeout!(B);
+
+    fn report_metrics(&self, metrics: &mut NodeMetrics) {
+        self.insert_only.report_metrics(metrics);
+    }
+}
+
+/// A keyed aggregation over a changelog stream
+///
+/// Inserts and [ChangeKind::UpdateAfter] rows are added to the aggregate of their key, while
+/// [ChangeKind::UpdateBefore] and [ChangeKind::Delete] rows are retracted from it, so the
+/// aggregator has to be [Retractable]. The result of the key is emitted as a changelog in the
+/// same way as by [RollingAggregate].
+///
+/// This is the merge step behind a [PartialKeyGrouping](crate::prelude::PartialKeyGrouping)
+/// strategy. A hot key is split across two partial [RollingAggregate]s, whose results are
+/// routed by key to a MergeAggregate that combines them into the total of the key:
+///
+/// ```text
+/// PartialKeyGrouping -> RollingAggregate(Sum) -> KeyBy -> MergeAggregate(Sum)
+/// ```
+///
+/// Every retraction must have been preceded by the row it retracts. Retractions of keys
+/// without an aggregate, or that the aggregator cannot take out of it, are logged and
+/// dropped. Once every row of a key has been retracted, its aggregate is deleted.
+pub struct MergeAggregate<IN, OUT, A>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    A: Retractable,
+{
+    aggregator: A,
+    extract: &'static dyn SafelySendableFn(&IN) -> A::Input,
+    output: &'static dyn SafelySendableFn(&IN, A::Result) -> OUT,
+    accumulator: Handle<ValueState<A::Accumulator>, u64>,
+    // number of rows in the accumulator
+    rows: Handle<ValueState<u64>, u64>,
+}
+
+impl<IN, OUT, A> MergeAggregate<IN, OUT, A>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    A: Retractable,
+{
+    pub fn new(
+        aggregator: A,
+        extract: &'static dyn SafelySendableFn(&IN) -> A::Input,
+        output: &'static dyn SafelySendableFn(&IN, A::Result) -> OUT,
+    ) -> Self {
+        MergeAggregate {
+            aggregator,
+            extract,
+            output,
+            accumulator: Handle::value("merge_aggregate").with_item_key(0),
+            rows: Handle::value("merge_aggregate_rows").with_item_key(0),
+        }
+    }
+
+    // Stores the aggregate of the current key along with the number of rows it holds
+    fn set_aggregate<B: state::Backend>(
+        &self,
+        accumulator: A::Accumulator,
+        rows: u64,
+        session: &mut state::Session<B>,
+    ) {
+        self.accumulator
+            .activate(session)
+            .set(accumulator)
+            .expect("merge aggregate state set error");
+        self.rows
+            .activate(session)
+            .set(rows)
+            .expect("merge aggregate rows set error");
+    }
+}
+
+impl<IN, OUT, A, B> Operator<B> for MergeAggregate<IN, OUT, A>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    A: Retractable,
+    B: state::Backend,
+{
+    type IN = IN;
+    type OUT = OUT;
+    type TimerState = ArconNever;
+
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+        self.accumulator.register(registration_token);
+        self.rows.register(registration_token);
+    }
+
+    fn init(&mut self, _session: &mut state::Session<B>) {}
+
+    fn handle_element<CD>(
+        &self,
+        element: ArconElement<IN>,
+        source: &CD,
+        mut ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let kind = element.change_kind();
+        let key = element.data.get_key();
+        self.accumulator.set_item_key(key);
+        self.rows.set_item_key(key);
+        let previous = self
+            .accumulator
+            .activate(ctx.state_session)
+            .get()
+            .expect("merge aggregate state get error");
+
+        let previous = match previous {
+            Some(previous) => previous,
+            None if kind.is_retraction() => {
+                error!(
+                    source.ctx().log(),
+                    "Dropping {:?} element of a key without an aggregate", kind
+                );
+                return;
+            }
+            None => {
+                let mut accumulator = self.aggregator.create_accumulator();
+                self.aggregator
+                    .add(&mut accumulator, (self.extract)(&element.data));
+                self.set_aggregate(accumulator.clone(), 1, ctx.state_session);
+
+                let result = self.aggregator.accumulator_into_result(accumulator);
+                let insert = ArconElement::with_change(
+                    (self.output)(&element.data, result),
+                    element.timestamp,
+                    ChangeKind::Insert,
+                );
+                ctx.output(ArconEvent::Element(insert), source);
+                return;
+            }
+        };
+
+        let rows = self
+            .rows
+            .activate(ctx.state_session)
+            .get()
+            .expect("merge aggregate rows get error")
+            .unwrap_or(0);
+
+        let mut accumulator = previous.clone();
+        let value = (self.extract)(&element.data);
+        let rows = if kind.is_retraction() {
+            if !self.aggregator.retract(&mut accumulator, value) {
+                error!(
+                    source.ctx().log(),
+                    "Dropping {:?} element that cannot be retracted from the aggregate", kind
+                );
+                return;
+            }
+            rows.saturating_sub(1)
+        } else {
+            self.aggregator.add(&mut accumulator, value);
+            rows + 1
+        };
+
+        let timestamp = element.timestamp;
+        let previous = self.aggregator.accumulator_into_result(previous);
+        if rows == 0 {
+            // Every row of the key was retracted, so its aggregate is gone
+            self.accumulator
+                .activate(ctx.state_session)
+                .clear()
+                .expect("merge aggregate state clear error");
+            self.rows
+                .activate(ctx.state_session)
+                .clear()
+                .expect("merge aggregate rows clear error");
+
+            let delete = ArconElement::with_change(
+                (self.output)(&element.data, previous),
+                timestamp,
+                ChangeKind::Delete,
+            );
+            ctx.output(ArconEvent::Element(delete), source);
+            return;
+        }
+
+        self.set_aggregate(accumulator.clone(), rows, ctx.state_session);
+
+        let retraction = ArconElement::with_change(
+            (self.output)(&element.data, previous),
+            timestamp,
+            ChangeKind::UpdateBefore,
+        );
+        ctx.output(ArconEvent::Element(retraction), source);
+
+        let result = self.aggregator.accumulator_into_result(accumulator);
+        let update = ArconElement::with_change(
+            (self.output)(&element.data, result),
+            timestamp,
+            ChangeKind::UpdateAfter,
+        );
+        ctx.output(ArconEvent::Element(update), source);
+    }
+
+    crate::ignore_watermark!(B);
+    crate::ignore_epoch!(B);
+    crate::ignore_timeout!(B);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prelude::state::{with_backend_type, Backend, BackendContainer, BackendType, InMemory},
+        timer,
+    };
+
+    fn rolling_sum_test<B: Backend>(state_backend: BackendContainer<B>) {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let sink = system.create(move || DebugNode::<u64>::new());
+        system.start(&sink);
+
+        let sink_ref: ActorRefStrong<ArconMessage<u64>> =
+            sink.actor_ref().hold().expect("failed to fetch");
+        let channel_strategy =
+            ChannelStrategy::Forward(Forward::new(Channel::Local(sink_ref), 1.into(), pool_info));
+
+        fn extract(i: &u64) -> u64 {
+            *i
+        }
+        fn output(_i: &u64, sum: u64) -> u64 {
+            sum
+        }
+
+        let node = system.create(move || {
+            Node::new(
+                String::from("rolling_aggregate_node"),
+                1.into(),
+                vec![0.into()],
+                channel_strategy,
+                RollingAggregate::new(Sum::new(), &extract, &output),
+                state_backend,
+                timer::none(),
+            )
+        });
+        system.start(&node);
+
+        let node_ref: ActorRefStrong<ArconMessage<u64>> =
+            node.actor_ref().hold().expect("failed to fetch");
+        node_ref.tell(ArconMessage::element(3, None, 0.into()));
+        node_ref.tell(ArconMessage::element(4, None, 0.into()));
+        // retractions cannot be aggregated and must not be counted as inserts
+        node_ref.tell(ArconMessage::change(3, None, ChangeKind::Delete, 0.into()));
+        node_ref.tell(ArconMessage::change(
+            4,
+            None,
+            ChangeKind::UpdateBefore,
+            0.into(),
+        ));
+        node_ref.tell(ArconMessage::element(3, None, 0.into()));
+        // flush the aggregates out of the Node's channel buffers
+        node_ref.tell(ArconMessage::watermark(1, 0.into()));
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        sink.on_definition(|cd| {
+            let results: Vec<(u64, ChangeKind)> =
+                cd.data.iter().map(|e| (e.data, e.change_kind())).collect();
+            assert_eq!(results, vec![
+                (3, ChangeKind::Insert),
+                (4, ChangeKind::Insert),
+                (3, ChangeKind::UpdateBefore),
+                (6, ChangeKind::UpdateAfter),
+            ]);
+        });
+
+        pipeline.shutdown();
+    }
+
+    #[test]
+    fn rolling_aggregate_on_every_backend() {
+        for backend_type in BackendType::VARIANTS {
+            let dir = tempfile::tempdir().unwrap();
+            with_backend_type!(*backend_type, |SB| rolling_sum_test(
+                SB::create(dir.path()).unwrap()
+            ));
+        }
+    }
+
+    #[cfg_attr(feature = "arcon_serde", derive(serde::Serialize, serde::Deserialize))]
+    #[derive(Arcon, prost::Message, Clone, abomonation_derive::Abomonation)]
+    #[arcon(unsafe_ser_id = 12, reliable_ser_id = 13, version = 1, keys = "key")]
+    pub struct Reading {
+        #[prost(uint64, tag = "1")]
+        pub key: u64,
+        #[prost(uint64, tag = "2")]
+        pub value: u64,
+    }
+
+    fn value(reading: &Reading) -> u64 {
+        reading.value
+    }
+    fn total(reading: &Reading, value: u64) -> Reading {
+        Reading {
+            key: reading.key,
+            value,
+        }
+    }
+
+    #[test]
+    fn merge_deletes_emptied_aggregate() {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let sink = system.create(move || DebugNode::<Reading>::new());
+        system.start(&sink);
+        let sink_ref: ActorRefStrong<ArconMessage<Reading>> =
+            sink.actor_ref().hold().expect("failed to fetch");
+        let channel_strategy =
+            ChannelStrategy::Forward(Forward::new(Channel::Local(sink_ref), 1.into(), pool_info));
+
+        let node = system.create(move || {
+            Node::new(
+                String::from("merge_aggregate_node"),
+                1.into(),
+                vec![0.into()],
+                channel_strategy,
+                MergeAggregate::new(Sum::new(), &value, &total),
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::none(),
+            )
+        });
+        system.start(&node);
+
+        let node_ref: ActorRefStrong<ArconMessage<Reading>> =
+            node.actor_ref().hold().expect("failed to fetch");
+        let change =
+            |key, value, kind| ArconMessage::change(Reading { key, value }, None, kind, 0.into());
+        node_ref.tell(change(1, 5, ChangeKind::Insert));
+        node_ref.tell(change(1, 2, ChangeKind::Insert));
+        node_ref.tell(change(1, 5, ChangeKind::Delete));
+        node_ref.tell(change(1, 2, ChangeKind::Delete));
+        // key 1 no longer has an aggregate
+        node_ref.tell(change(1, 3, ChangeKind::UpdateBefore));
+        node_ref.tell(change(2, 1, ChangeKind::Insert));
+        // would underflow the sum of key 2
+        node_ref.tell(change(2, 4, ChangeKind::Delete));
+        node_ref.tell(ArconMessage::watermark(1, 0.into()));
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        sink.on_definition(|cd| {
+            let results: Vec<(u64, u64, ChangeKind)> = cd
+                .data
+                .iter()
+                .map(|e| (e.data.key, e.data.value, e.change_kind()))
+                .collect();
+            assert_eq!(results, vec![
+                (1, 5, ChangeKind::Insert),
+                (1, 5, ChangeKind::UpdateBefore),
+                (1, 7, ChangeKind::UpdateAfter),
+                (1, 7, ChangeKind::UpdateBefore),
+                (1, 2, ChangeKind::UpdateAfter),
+                (1, 2, ChangeKind::Delete),
+                (2, 1, ChangeKind::Insert),
+            ]);
+        });
+
+        pipeline.shutdown();
+    }
+
+    #[test]
+    fn merge_partial_key_grouping_test() {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let sink = system.create(move || DebugNode::<Reading>::new());
+        system.start(&sink);
+        let sink_ref: ActorRefStrong<ArconMessage<Reading>> =
+            sink.actor_ref().hold().expect("failed to fetch");
+
+        let sink_strategy = ChannelStrategy::Forward(Forward::new(
+            Channel::Local(sink_ref),
+            3.into(),
+            pool_info.clone(),
+        ));
+        let merge = system.create(move || {
+            Node::new(
+                String::from("merge_aggregate_node"),
+                3.into(),
+                vec![1.into(), 2.into()],
+                sink_strategy,
+                MergeAggregate::new(Sum::new(), &value, &total),
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::none(),
+            )
+        });
+        system.start(&merge);
+
+        // Two partial aggregates that each see part of every key
+        let mut partials = Vec::new();
+        for id in 1..=2u32 {
+            let merge_ref: ActorRefStrong<ArconMessage<Reading>> =
+                merge.actor_ref().hold().expect("failed to fetch");
+            let merge_strategy = ChannelStrategy::Forward(Forward::new(
+                Channel::Local(merge_ref),
+                id.into(),
+                pool_info.clone(),
+            ));
+            let partial = system.create(move || {
+                Node::new(
+                    String::from("rolling_aggregate_node"),
+                    id.into(),
+                    vec![0.into()],
+                    merge_strategy,
+                    RollingAggregate::new(Sum::new(), &value, &total),
+                    InMemory::create("test".as_ref()).unwrap(),
+                    timer::none(),
+                )
+            });
+            system.start(&partial);
+            let partial_ref: ActorRefStrong<ArconMessage<Reading>> =
+                partial.actor_ref().hold().expect("failed to fetch");
+            partials.push(Channel::Local(partial_ref));
+        }
+
+        let mut channel_strategy = ChannelStrategy::PartialKeyGrouping(PartialKeyGrouping::new(
+            partials,
+            0.into(),
+            pool_info,
+        ));
+        sink.on_definition(|cd| {
+            for value in 1..=10 {
+                let reading = Reading { key: 1, value };
+                channel_strategy.add(ArconEvent::Element(ArconElement::new(reading)), cd);
+            }
+            for value in &[5, 5] {
+                let reading = Reading {
+                    key: 2,
+                    value: *value,
+                };
+                channel_strategy.add(ArconEvent::Element(ArconElement::new(reading)), cd);
+            }
+            // the watermark flushes every Node on the way to the sink
+            channel_strategy.add(ArconEvent::Watermark(Watermark::new(1)), cd);
+        });
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        sink.on_definition(|cd| {
+            // Apply the merged changelog to get the final total of every key
+            let mut totals = std::collections::BTreeMap::new();
+            for element in cd.data.iter() {
+                if element.change_kind().is_retraction() {
+                    assert_eq!(totals.remove(&element.data.key), Some(element.data.value));
+                } else {
+                    assert_eq!(totals.insert(element.data.key, element.data.value), None);
+                }
+            }
+            assert_eq!(totals.into_iter().collect::<Vec<_>>(), vec![
+                (1, 55),
+                (2, 10)
+            ]);
+        });
+
+        pipeline.shutdown();
+    }
+}
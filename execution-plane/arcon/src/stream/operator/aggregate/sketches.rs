@@ -0,0 +1,653 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::aggregators::Numeric;
+use crate::{
+    data::{ser_id, ArconType, VersionId},
+    prelude::state::Aggregator,
+};
+use abomonation_derive::*;
+use kompact::prelude::SerId;
+use prost::Message;
+#[cfg(feature = "arcon_serde")]
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+/*
+    Sketches
+        * Approximate summaries of a stream that use a fixed amount of memory per key
+        * Every sketch is a prost message, so it is checkpointed as the accumulator of
+          an AggregatorState and can be used in an AggregatorWindow
+        * Sketches of the same configuration are mergeable, which the Aggregator impls rely on
+*/
+
+// Spreads the bits of an ArconType key, as user keys are not necessarily well distributed
+fn mix(mut x: u64) -> u64 {
+    x ^= x >> 30;
+    x = x.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x ^= x >> 27;
+    x = x.wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+macro_rules! impl_sketch_arcon_type {
+    ($t:ty, $unsafe_id:expr, $reliable_id:expr) => {
+        impl ArconType for $t {
+            const UNSAFE_SER_ID: SerId = $unsafe_id;
+            const RELIABLE_SER_ID: SerId = $reliable_id;
+            const VERSION_ID: VersionId = 1;
+
+            fn get_key(&self) -> u64 {
+                let mut buf = Vec::with_capacity(self.encoded_len());
+                self.encode(&mut buf).expect("failed to encode sketch");
+                let mut s = DefaultHasher::new();
+                buf.hash(&mut s);
+                s.finish()
+            }
+        }
+    };
+}
+
+/// A HyperLogLog sketch for estimating the number of distinct elements
+#[cfg_attr(feature = "arcon_serde", derive(Serialize, Deserialize))]
+#[derive(Message, Clone, PartialEq, Abomonation)]
+pub struct HyperLogLogSketch {
+    #[prost(uint32, tag = "1")]
+    pub precision: u32,
+    #[prost(bytes, tag = "2")]
+    pub registers: Vec<u8>,
+}
+
+impl HyperLogLogSketch {
+    /// Creates an empty sketch with 2^precision registers
+    pub fn new(precision: u32) -> Self {
+        HyperLogLogSketch {
+            precision,
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    /// Adds an element to the sketch
+    pub fn insert<T: ArconType>(&mut self, element: &T) {
+        let hash = mix(element.get_key());
+        let index = (hash >> (64 - self.precision)) as usize;
+        let rest = hash << self.precision;
+        let rank = (rest.leading_zeros() + 1).min(64 - self.precision + 1) as u8;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    /// Returns the estimated number of distinct elements
+    pub fn estimate(&self) -> u64 {
+        if self.registers.is_empty() {
+            return 0;
+        }
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self.registers.iter().map(|r| 2f64.powi(-(*r as i32))).sum();
+        let raw = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate for small cardinalities
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            raw.round() as u64
+        }
+    }
+
+    /// Merges `other` into this sketch
+    ///
+    /// Panics if the sketches have different precisions.
+    pub fn merge(&mut self, other: &HyperLogLogSketch) {
+        if other.registers.is_empty() {
+            return;
+        }
+        if self.registers.is_empty() {
+            *self = other.clone();
+            return;
+        }
+        assert_eq!(
+            self.precision, other.precision,
+            "cannot merge HyperLogLog sketches of different precision"
+        );
+        for (r, o) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *r = (*r).max(*o);
+        }
+    }
+}
+
+impl_sketch_arcon_type!(
+    HyperLogLogSketch,
+    ser_id::UNSAFE_HLL_ID,
+    ser_id::RELIABLE_HLL_ID
+);
+
+/// Estimates the number of distinct inputs with a [HyperLogLogSketch]
+///
+/// The relative standard error of the estimate is about 1.04 / sqrt(2^precision).
+#[derive(Debug, Clone)]
+pub struct HyperLogLog<T: ArconType> {
+    precision: u32,
+    _marker: PhantomData<fn(T)>,
+}
+
+impl<T: ArconType> HyperLogLog<T> {
+    pub fn new(precision: u32) -> Self {
+        if precision < 4 || precision > 16 {
+            panic!("HyperLogLog precision must be between 4 and 16!");
+        }
+        HyperLogLog {
+            precision,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ArconType> Aggregator for HyperLogLog<T> {
+    type Input = T;
+    type Accumulator = HyperLogLogSketch;
+    type Result = u64;
+
+    fn create_accumulator(&self) -> HyperLogLogSketch {
+        HyperLogLogSketch::new(self.precision)
+    }
+
+    fn add(&self, acc: &mut HyperLogLogSketch, value: T) {
+        acc.insert(&value);
+    }
+
+    fn merge_accumulators(
+        &self,
+        mut fst: HyperLogLogSketch,
+        snd: HyperLogLogSketch,
+    ) -> HyperLogLogSketch {
+        fst.merge(&snd);
+        fst
+    }
+
+    fn accumulator_into_result(&self, acc: HyperLogLogSketch) -> u64 {
+        acc.estimate()
+    }
+}
+
+/// A Count-Min sketch for estimating the frequency of elements
+///
+/// Estimates never undercount, and overcount by at most e / width of the total count
+/// with probability 1 - e^-depth.
+#[cfg_attr(feature = "arcon_serde", derive(Serialize, Deserialize))]
+#[derive(Message, Clone, PartialEq, Abomonation)]
+pub struct CountMinSketch {
+    #[prost(uint32, tag = "1")]
+    pub width: u32,
+    #[prost(uint32, tag = "2")]
+    pub depth: u32,
+    #[prost(uint64, repeated, tag = "3")]
+    pub counters: Vec<u64>,
+}
+
+impl CountMinSketch {
+    /// Creates an empty sketch with `depth` rows of `width` counters
+    ///
+    /// Panics if either dimension is zero or the sketch has more counters than fit in memory.
+    pub fn new(width: u32, depth: u32) -> Self {
+        CountMinSketch {
+            width,
+            depth,
+            counters: vec![0; Self::counters_len(width, depth)],
+        }
+    }
+
+    fn counters_len(width: u32, depth: u32) -> usize {
+        if width == 0 || depth == 0 {
+            panic!("Count-Min sketch dimensions must be greater than zero!");
+        }
+        (width as usize)
+            .checked_mul(depth as usize)
+            .expect("Count-Min sketch dimensions are too large!")
+    }
+
+    // Index of the counter of `element` in every row, using double hashing
+    fn cells<T: ArconType>(&self, element: &T) -> impl Iterator<Item = usize> {
+        let h1 = mix(element.get_key());
+        let h2 = mix(h1 ^ 0x9e37_79b9_7f4a_7c15) | 1;
+        let width = self.width as u64;
+        (0..self.depth as u64).map(move |row| {
+            let column = h1.wrapping_add(row.wrapping_mul(h2)) % width;
+            (row * width + column) as usize
+        })
+    }
+
+    /// Adds `count` occurrences of an element to the sketch
+    pub fn insert<T: ArconType>(&mut self, element: &T, count: u64) {
+        for cell in self.cells(element) {
+            self.counters[cell] += count;
+        }
+    }
+
+    /// Returns the estimated number of occurrences of an element
+    pub fn estimate<T: ArconType>(&self, element: &T) -> u64 {
+        self.cells(element)
+            .map(|cell| self.counters[cell])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Merges `other` into this sketch
+    ///
+    /// Panics if the sketches have different dimensions.
+    pub fn merge(&mut self, other: &CountMinSketch) {
+        if other.counters.is_empty() {
+            return;
+        }
+        if self.counters.is_empty() {
+            *self = other.clone();
+            return;
+        }
+        assert!(
+            self.width == other.width && self.depth == other.depth,
+            "cannot merge Count-Min sketches of different dimensions"
+        );
+        for (c, o) in self.counters.iter_mut().zip(other.counters.iter()) {
+            *c += *o;
+        }
+    }
+}
+
+impl_sketch_arcon_type!(
+    CountMinSketch,
+    ser_id::UNSAFE_COUNT_MIN_ID,
+    ser_id::RELIABLE_COUNT_MIN_ID
+);
+
+/// Summarises the frequencies of its inputs in a [CountMinSketch]
+#[derive(Debug, Clone)]
+pub struct CountMin<T: ArconType> {
+    width: u32,
+    depth: u32,
+    _marker: PhantomData<fn(T)>,
+}
+
+impl<T: ArconType> CountMin<T> {
+    pub fn new(width: u32, depth: u32) -> Self {
+        // Check the dimensions up front rather than when the first accumulator is created
+        CountMinSketch::counters_len(width, depth);
+        CountMin {
+            width,
+            depth,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ArconType> Aggregator for CountMin<T> {
+    type Input = T;
+    type Accumulator = CountMinSketch;
+    type Result = CountMinSketch;
+
+    fn create_accumulator(&self) -> CountMinSketch {
+        CountMinSketch::new(self.width, self.depth)
+    }
+
+    fn add(&self, acc: &mut CountMinSketch, value: T) {
+        acc.insert(&value, 1);
+    }
+
+    fn merge_accumulators(&self, mut fst: CountMinSketch, snd: CountMinSketch) -> CountMinSketch {
+        fst.merge(&snd);
+        fst
+    }
+
+    fn accumulator_into_result(&self, acc: CountMinSketch) -> CountMinSketch {
+        acc
+    }
+}
+
+/// A merging t-digest for estimating quantiles
+///
+/// Values are buffered and periodically merged into centroids, whose size is bounded by
+/// the `compression` parameter. Larger values give more accurate quantiles at the cost
+/// of more centroids.
+#[cfg_attr(feature = "arcon_serde", derive(Serialize, Deserialize))]
+#[derive(Message, Clone, PartialEq, Abomonation)]
+pub struct TDigest {
+    #[prost(double, tag = "1")]
+    pub compression: f64,
+    #[prost(double, repeated, tag = "2")]
+    pub means: Vec<f64>,
+    #[prost(double, repeated, tag = "3")]
+    pub weights: Vec<f64>,
+    #[prost(double, repeated, tag = "4")]
+    pub buffer: Vec<f64>,
+    #[prost(double, tag = "5")]
+    pub min: f64,
+    #[prost(double, tag = "6")]
+    pub max: f64,
+}
+
+impl TDigest {
+    /// Creates an empty digest
+    pub fn new(compression: f64) -> Self {
+        TDigest {
+            compression,
+            means: Vec::new(),
+            weights: Vec::new(),
+            buffer: Vec::new(),
+            min: std::f64::INFINITY,
+            max: std::f64::NEG_INFINITY,
+        }
+    }
+
+    /// Returns the number of values in the digest
+    pub fn count(&self) -> u64 {
+        (self.weights.iter().sum::<f64>() + self.buffer.len() as f64) as u64
+    }
+
+    /// Returns true if no values have been added
+    pub fn is_empty(&self) -> bool {
+        self.means.is_empty() && self.buffer.is_empty()
+    }
+
+    /// Adds a value to the digest
+    pub fn insert(&mut self, value: f64) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.buffer.push(value);
+        if self.buffer.len() as f64 > 5.0 * self.compression {
+            self.compress();
+        }
+    }
+
+    /// Merges the buffered values into the centroids
+    pub fn compress(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let (means, weights) = self.centroids().into_iter().unzip();
+        self.means = means;
+        self.weights = weights;
+        self.buffer.clear();
+    }
+
+    // Scale function bounding the size of centroids, small at the tails and large at the median
+    fn k(&self, q: f64) -> f64 {
+        self.compression / (2.0 * std::f64::consts::PI) * (2.0 * q.min(1.0) - 1.0).asin()
+    }
+
+    // Merged centroids of the digest, including the buffered values, ordered by mean
+    fn centroids(&self) -> Vec<(f64, f64)> {
+        let mut centroids: Vec<(f64, f64)> = self
+            .means
+            .iter()
+            .cloned()
+            .zip(self.weights.iter().cloned())
+            .chain(self.buffer.iter().map(|v| (*v, 1.0)))
+            .collect();
+        if self.buffer.is_empty() {
+            return centroids;
+        }
+        centroids.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        self.merge_sorted(centroids)
+    }
+
+    // Greedily merges neighbouring centroids as long as the scale function allows it
+    fn merge_sorted(&self, centroids: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+        let total: f64 = centroids.iter().map(|c| c.1).sum();
+        let mut merged = Vec::with_capacity(centroids.len());
+        let mut centroids = centroids.into_iter();
+        let mut current = match centroids.next() {
+            Some(c) => c,
+            None => return merged,
+        };
+        let mut weight_before = 0.0;
+        for next in centroids {
+            let q_lower = weight_before / total;
+            let q_upper = (weight_before + current.1 + next.1) / total;
+            if self.k(q_upper) - self.k(q_lower) <= 1.0 {
+                let weight = current.1 + next.1;
+                current.0 += (next.0 - current.0) * next.1 / weight;
+                current.1 = weight;
+            } else {
+                weight_before += current.1;
+                merged.push(current);
+                current = next;
+            }
+        }
+        merged.push(current);
+        merged
+    }
+
+    /// Returns the estimated value at quantile `q`, or None if the digest is empty
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let centroids = self.centroids();
+        if centroids.is_empty() {
+            return None;
+        }
+        if centroids.len() == 1 {
+            return Some(centroids[0].0);
+        }
+
+        let total: f64 = centroids.iter().map(|c| c.1).sum();
+        let target = q.max(0.0).min(1.0) * total;
+
+        // Values are interpolated between the centers of neighbouring centroids
+        let (first_mean, first_weight) = centroids[0];
+        if target < first_weight / 2.0 {
+            return Some(self.min + (first_mean - self.min) * target / (first_weight / 2.0));
+        }
+        let mut weight_before = 0.0;
+        for pair in centroids.windows(2) {
+            let (left, right) = (pair[0], pair[1]);
+            let left_center = weight_before + left.1 / 2.0;
+            let right_center = weight_before + left.1 + right.1 / 2.0;
+            if target <= right_center {
+                let fraction = (target - left_center) / (right_center - left_center);
+                return Some(left.0 + (right.0 - left.0) * fraction);
+            }
+            weight_before += left.1;
+        }
+        let (last_mean, last_weight) = centroids[centroids.len() - 1];
+        let last_center = total - last_weight / 2.0;
+        let fraction = (target - last_center) / (last_weight / 2.0);
+        Some(last_mean + (self.max - last_mean) * fraction)
+    }
+
+    /// Merges `other` into this digest
+    pub fn merge(&mut self, other: &TDigest) {
+        if other.is_empty() {
+            return;
+        }
+        if self.is_empty() {
+            *self = other.clone();
+            return;
+        }
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+
+        let mut centroids = self.centroids();
+        centroids.extend(other.centroids());
+        centroids.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        let (means, weights) = self.merge_sorted(centroids).into_iter().unzip();
+        self.means = means;
+        self.weights = weights;
+        self.buffer.clear();
+    }
+}
+
+impl_sketch_arcon_type!(
+    TDigest,
+    ser_id::UNSAFE_TDIGEST_ID,
+    ser_id::RELIABLE_TDIGEST_ID
+);
+
+/// Summarises the distribution of its inputs in a [TDigest]
+#[derive(Debug, Clone)]
+pub struct Quantiles<T: Numeric> {
+    compression: f64,
+    _marker: PhantomData<fn(T)>,
+}
+
+impl<T: Numeric> Quantiles<T> {
+    pub fn new(compression: f64) -> Self {
+        if compression < 1.0 {
+            panic!("t-digest compression must be at least 1!");
+        }
+        Quantiles {
+            compression,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Numeric> Aggregator for Quantiles<T> {
+    type Input = T;
+    type Accumulator = TDigest;
+    type Result = TDigest;
+
+    fn create_accumulator(&self) -> TDigest {
+        TDigest::new(self.compression)
+    }
+
+    fn add(&self, acc: &mut TDigest, value: T) {
+        acc.insert(value.as_f64());
+    }
+
+    fn merge_accumulators(&self, mut fst: TDigest, snd: TDigest) -> TDigest {
+        fst.merge(&snd);
+        fst
+    }
+
+    fn accumulator_into_result(&self, mut acc: TDigest) -> TDigest {
+        acc.compress();
+        acc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prelude::state::{self, Backend},
+        state::InMemory,
+        stream::operator::window::{AggregatorWindow, Window, WindowContext},
+    };
+
+    fn fold<A: Aggregator>(
+        aggregator: &A,
+        values: impl Iterator<Item = A::Input>,
+    ) -> A::Accumulator {
+        let mut acc = aggregator.create_accumulator();
+        for value in values {
+            aggregator.add(&mut acc, value);
+        }
+        acc
+    }
+
+    #[test]
+    fn hyperloglog_test() {
+        let hll = HyperLogLog::<u64>::new(12);
+        let acc = fold(&hll, (0..10_000u64).chain(0..5_000u64));
+        let estimate = hll.accumulator_into_result(acc.clone()) as f64;
+        assert!((estimate - 10_000.0).abs() < 500.0, "estimate {}", estimate);
+
+        // Merging the sketches of two halves gives the sketch of the whole stream
+        let merged = hll.merge_accumulators(fold(&hll, 0..4_000u64), fold(&hll, 4_000..10_000u64));
+        assert_eq!(merged, acc);
+
+        let small = hll.accumulator_into_result(fold(&hll, 0..10u64));
+        assert!((9..=11).contains(&small), "estimate {}", small);
+    }
+
+    #[test]
+    fn count_min_test() {
+        let cms = CountMin::<u64>::new(512, 4);
+        let values = (0..1_000u64).chain(std::iter::repeat(7).take(500));
+        let sketch = cms.accumulator_into_result(fold(&cms, values));
+
+        let estimate = sketch.estimate(&7u64);
+        assert!(
+            estimate >= 501 && estimate < 501 + 20,
+            "estimate {}",
+            estimate
+        );
+        assert!(sketch.estimate(&5_000u64) < 20);
+
+        let merged = cms.merge_accumulators(sketch.clone(), sketch);
+        assert!(merged.estimate(&7u64) >= 1002);
+    }
+
+    #[test]
+    #[should_panic(expected = "dimensions must be greater than zero")]
+    fn count_min_rejects_empty_sketch() {
+        CountMinSketch::new(0, 4);
+    }
+
+    #[test]
+    fn tdigest_test() {
+        let quantiles = Quantiles::<u64>::new(100.0);
+        let digest = quantiles.accumulator_into_result(fold(&quantiles, 1..=10_000u64));
+
+        assert_eq!(digest.count(), 10_000);
+        assert!(digest.means.len() < 200);
+        let median = digest.quantile(0.5).unwrap();
+        assert!((median - 5_000.0).abs() < 50.0, "median {}", median);
+        let p99 = digest.quantile(0.99).unwrap();
+        assert!((p99 - 9_900.0).abs() < 30.0, "p99 {}", p99);
+        assert_eq!(digest.quantile(0.0), Some(1.0));
+        assert_eq!(digest.quantile(1.0), Some(10_000.0));
+
+        let merged = quantiles.merge_accumulators(
+            fold(&quantiles, (1..=10_000u64).filter(|v| v % 2 == 0)),
+            fold(&quantiles, (1..=10_000u64).filter(|v| v % 2 == 1)),
+        );
+        assert_eq!(merged.count(), 10_000);
+        let median = merged.quantile(0.5).unwrap();
+        assert!((median - 5_000.0).abs() < 50.0, "median {}", median);
+    }
+
+    #[test]
+    fn sketches_survive_encoding() {
+        let hll = fold(&HyperLogLog::<u64>::new(8), 0..100u64);
+        let cms = fold(&CountMin::<u64>::new(64, 3), 0..100u64);
+        let digest = fold(&Quantiles::<u64>::new(50.0), 0..1_000u64);
+
+        fn roundtrip<M: Message + Default>(msg: &M) -> M {
+            let mut buf = Vec::with_capacity(msg.encoded_len());
+            msg.encode(&mut buf).unwrap();
+            M::decode(&buf[..]).unwrap()
+        }
+
+        assert_eq!(roundtrip(&hll), hll);
+        assert_eq!(roundtrip(&cms), cms);
+        assert_eq!(roundtrip(&digest), digest);
+    }
+
+    #[test]
+    fn sketch_in_aggregator_window() {
+        let state_backend = InMemory::create("test".as_ref()).unwrap();
+        let mut session = state_backend.session();
+
+        let mut window = AggregatorWindow::new(HyperLogLog::<u64>::new(10));
+        window.register_states(&mut unsafe { state::RegistrationToken::new(&mut session) });
+
+        for i in 0..50u64 {
+            let _ = window.on_element(i % 20, WindowContext::new(&mut session, 0, 0));
+        }
+
+        let distinct = window
+            .result(WindowContext::new(&mut session, 0, 0))
+            .unwrap();
+        assert!((19..=21).contains(&distinct), "estimate {}", distinct);
+    }
+}
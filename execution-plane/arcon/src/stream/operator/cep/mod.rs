@@ -0,0 +1,347 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+/// Pattern builder and the automaton it compiles to
+pub mod pattern;
+
+pub use pattern::{Pattern, PatternMatch};
+
+use crate::{
+    prelude::{
+        state::{Handle, VecState},
+        *,
+    },
+    stream::operator::OperatorContext,
+    timer::TimerBackend,
+};
+use pattern::{Nfa, PartialMatch};
+use prost::Message;
+use std::cell::{Cell, RefCell};
+
+/// Timer registered by a [Cep] operator for a key with buffered events
+#[cfg_attr(feature = "arcon_serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Message, PartialEq, Clone)]
+pub struct CepTimer {
+    #[prost(uint64, tag = "1")]
+    key: u64,
+}
+
+/// Complex event processing operator
+///
+/// Matches the events of every key, as given by [ArconType::get_key], against a [Pattern].
+/// Elements are buffered per key until the watermark passes them and are then fed to the
+/// pattern in event time order, so the operator has to run with an
+/// [EventTimer](crate::timer::event_timer::EventTimer) backend. Elements without a
+/// timestamp, or with a timestamp the watermark has already passed, are dropped.
+///
+/// Every complete match is emitted with the timestamp of its last event. Partial matches
+/// that exceed the [within](Pattern::within) duration of the pattern are sent to the
+/// timeout output, if one is set, timestamped with their deadline.
+pub struct Cep<T: ArconType> {
+    nfa: Nfa<T>,
+    buffer: Handle<VecState<ArconElement<T>>, u64>,
+    runs: Handle<VecState<PartialMatch<T>>, u64>,
+    timeout_output: Option<RefCell<ChannelStrategy<PatternMatch<T>>>>,
+    // Watermark to forward on the timeout output once its timers have fired
+    pending_watermark: Cell<Option<Watermark>>,
+}
+
+impl<T: ArconType> Cep<T> {
+    pub fn new(pattern: Pattern<T>) -> Self {
+        Cep {
+            nfa: pattern.compile(),
+            buffer: Handle::vec("cep_buffer").with_item_key(0),
+            runs: Handle::vec("cep_partial_matches").with_item_key(0),
+            timeout_output: None,
+            pending_watermark: Cell::new(None),
+        }
+    }
+
+    /// Sends timed out partial matches to `channel_strategy`
+    ///
+    /// Watermarks on the timeout output trail the main output by one watermark, since
+    /// timeouts for a watermark are only found after the Node has handed it to the operator.
+    pub fn with_timeout_output(
+        mut self,
+        channel_strategy: ChannelStrategy<PatternMatch<T>>,
+    ) -> Self {
+        self.timeout_output = Some(RefCell::new(channel_strategy));
+        self
+    }
+
+    fn timeout<CD>(&self, timeout: PatternMatch<T>, deadline: u64, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        if let Some(output) = &self.timeout_output {
+            let element = ArconElement::with_timestamp(timeout, deadline);
+            output
+                .borrow_mut()
+                .add(ArconEvent::Element(element), source);
+        }
+    }
+
+    fn forward_pending_watermark<CD>(&self, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        if let (Some(output), Some(watermark)) =
+            (&self.timeout_output, self.pending_watermark.take())
+        {
+            output
+                .borrow_mut()
+                .add(ArconEvent::Watermark(watermark), source);
+        }
+    }
+}
+
+impl<T, B> Operator<B> for Cep<T>
+where
+    T: ArconType,
+    B: state::Backend,
+{
+    type IN = T;
+    type OUT = PatternMatch<T>;
+    type TimerState = CepTimer;
+
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+        self.buffer.register(registration_token);
+        self.runs.register(registration_token);
+    }
+
+    fn init(&mut self, _session: &mut state::Session<B>) {}
+
+    fn handle_element<CD>(
+        &self,
+        element: ArconElement<T>,
+        _source: &CD,
+        mut ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let timestamp = match element.timestamp {
+            Some(timestamp) => timestamp,
+            None => return,
+        };
+        let key = element.data.get_key();
+
+        // Fails for late elements
+        if ctx.schedule_at(timestamp, CepTimer { key }).is_err() {
+            return;
+        }
+
+        self.buffer.set_item_key(key);
+        self.buffer
+            .activate(ctx.state_session)
+            .append(element)
+            .expect("cep buffer append error");
+    }
+
+    fn handle_watermark<CD>(
+        &self,
+        watermark: Watermark,
+        source: &CD,
+        _ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        self.forward_pending_watermark(source);
+        self.pending_watermark.set(Some(watermark));
+    }
+
+    fn handle_epoch<CD>(
+        &self,
+        epoch: Epoch,
+        source: &CD,
+        _ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        self.forward_pending_watermark(source);
+        if let Some(output) = &self.timeout_output {
+            output.borrow_mut().add(ArconEvent::Epoch(epoch), source);
+        }
+    }
+
+    fn handle_timeout<CD>(
+        &self,
+        timeout: Self::TimerState,
+        source: &CD,
+        mut ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let CepTimer { key } = timeout;
+        self.buffer.set_item_key(key);
+        self.runs.set_item_key(key);
+
+        // Every buffered event up to the watermark is final, since later ones are dropped as late.
+        // Later timers of the key that fire for the same watermark find an empty buffer.
+        let watermark = ctx.current_time();
+        let mut buffer = self.buffer.activate(ctx.state_session);
+        let (mut ready, pending): (Vec<_>, Vec<_>) = buffer
+            .get()
+            .expect("cep buffer get error")
+            .into_iter()
+            .partition(|e| e.timestamp.unwrap_or(0) <= watermark);
+        buffer.set(pending).expect("cep buffer set error");
+        drop(buffer);
+        ready.sort_by_key(|e| e.timestamp);
+
+        let mut runs = self
+            .runs
+            .activate(ctx.state_session)
+            .get()
+            .expect("cep state get error");
+
+        let mut timed_out = false;
+        for element in ready {
+            let timestamp = element.timestamp.unwrap_or(0);
+            let step = self.nfa.advance(runs, &element.data, timestamp);
+            runs = step.runs;
+
+            for (timeout, deadline) in step.timeouts {
+                self.timeout(timeout, deadline, source);
+                timed_out = true;
+            }
+            for pattern_match in step.matches {
+                let element = ArconElement::with_timestamp(pattern_match, timestamp);
+                ctx.output(ArconEvent::Element(element), source);
+            }
+            if step.started {
+                if let Some(deadline) = self.nfa.deadline(runs.last().unwrap()) {
+                    // Deadlines the watermark already passed are handled below
+                    let _ = ctx.schedule_at(deadline, CepTimer { key });
+                }
+            }
+        }
+
+        let (runs, timeouts) = self.nfa.expire(runs, watermark);
+        for (timeout, deadline) in timeouts {
+            self.timeout(timeout, deadline, source);
+            timed_out = true;
+        }
+        // The timeout output is otherwise only flushed by the next watermark
+        if timed_out {
+            if let Some(output) = &self.timeout_output {
+                output.borrow_mut().flush(source);
+            }
+        }
+
+        self.runs
+            .activate(ctx.state_session)
+            .set(runs)
+            .expect("cep state set error");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{state::InMemory, timer};
+
+    #[cfg_attr(feature = "arcon_serde", derive(serde::Serialize, serde::Deserialize))]
+    #[derive(Arcon, prost::Message, Clone, abomonation_derive::Abomonation)]
+    #[arcon(unsafe_ser_id = 12, reliable_ser_id = 13, version = 1, keys = "user")]
+    pub struct Login {
+        #[prost(uint64, tag = "1")]
+        pub user: u64,
+        #[prost(bool, tag = "2")]
+        pub success: bool,
+    }
+
+    fn failed(login: &Login) -> bool {
+        !login.success
+    }
+    fn succeeded(login: &Login) -> bool {
+        login.success
+    }
+
+    #[test]
+    fn cep_matches_and_timeouts_test() {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let matches = system.create(move || DebugNode::<PatternMatch<Login>>::new());
+        let timeouts = system.create(move || DebugNode::<PatternMatch<Login>>::new());
+        system.start(&matches);
+        system.start(&timeouts);
+
+        let matches_ref: ActorRefStrong<ArconMessage<PatternMatch<Login>>> =
+            matches.actor_ref().hold().expect("failed to fetch");
+        let timeouts_ref: ActorRefStrong<ArconMessage<PatternMatch<Login>>> =
+            timeouts.actor_ref().hold().expect("failed to fetch");
+        let channel_strategy = ChannelStrategy::Forward(Forward::new(
+            Channel::Local(matches_ref),
+            1.into(),
+            pool_info.clone(),
+        ));
+        let timeout_strategy = ChannelStrategy::Forward(Forward::new(
+            Channel::Local(timeouts_ref),
+            1.into(),
+            pool_info,
+        ));
+
+        let pattern = Pattern::begin("failed", &failed)
+            .times(3)
+            .followed_by("success", &succeeded)
+            .within(10);
+
+        let node = system.create(move || {
+            Node::new(
+                String::from("cep_node"),
+                1.into(),
+                vec![0.into()],
+                channel_strategy,
+                Cep::new(pattern).with_timeout_output(timeout_strategy),
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::wheel(),
+            )
+        });
+        system.start(&node);
+
+        let node_ref: ActorRefStrong<ArconMessage<Login>> =
+            node.actor_ref().hold().expect("failed to fetch");
+        let login =
+            |user, success, ts| ArconMessage::element(Login { user, success }, Some(ts), 0.into());
+
+        // Out of order within the watermark, which the operator sorts out
+        node_ref.tell(login(1, false, 2));
+        node_ref.tell(login(1, false, 1));
+        node_ref.tell(login(2, false, 1));
+        node_ref.tell(login(2, false, 2));
+        node_ref.tell(login(1, false, 3));
+        node_ref.tell(login(2, false, 3));
+        node_ref.tell(login(1, true, 4));
+        node_ref.tell(ArconMessage::watermark(20, 0.into()));
+        // Late, and therefore dropped
+        node_ref.tell(login(2, true, 5));
+        node_ref.tell(ArconMessage::watermark(30, 0.into()));
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        matches.on_definition(|cd| {
+            assert_eq!(cd.data.len(), 1);
+            let result = &cd.data[0];
+            assert_eq!(result.timestamp, Some(4));
+            assert_eq!(result.data.stage("failed").count(), 3);
+            assert!(result.data.stage("success").all(|l| l.user == 1));
+        });
+
+        timeouts.on_definition(|cd| {
+            // The partial matches of user 1 that started with its second and third
+            // failure, and the three partial matches of user 2
+            let mut deadlines: Vec<(u64, u64)> = cd
+                .data
+                .iter()
+                .map(|e| (e.data.events[0].user, e.timestamp.unwrap()))
+                .collect();
+            deadlines.sort();
+            assert_eq!(deadlines, vec![(1, 12), (1, 13), (2, 11), (2, 12), (2, 13)]);
+            assert_eq!(cd.watermarks.last().map(|w| w.timestamp), Some(20));
+        });
+
+        pipeline.shutdown();
+    }
+}
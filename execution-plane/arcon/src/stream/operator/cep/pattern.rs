@@ -0,0 +1,468 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    data::{ser_id, ArconType, VersionId},
+    util::SafelySendableFn,
+};
+use abomonation_derive::Abomonation;
+use kompact::prelude::SerId;
+use prost::Message;
+#[cfg(feature = "arcon_serde")]
+use serde::{Deserialize, Serialize};
+
+/// How an event has to follow the event matched before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Contiguity {
+    /// The event has to directly follow the previous one
+    Strict,
+    /// Non-matching events in between are ignored
+    Relaxed,
+}
+
+struct Stage<T: ArconType> {
+    name: &'static str,
+    predicate: &'static dyn SafelySendableFn(&T) -> bool,
+    contiguity: Contiguity,
+    times: u32,
+    optional: bool,
+    consecutive: bool,
+}
+
+/// A sequence of stages that events of a single key should match
+///
+/// ```ignore
+/// // Three failed logins followed by a successful one within five minutes
+/// let pattern = Pattern::begin("failed", &is_failed_login)
+///     .times(3)
+///     .followed_by("success", &is_successful_login)
+///     .within(5 * 60 * 1000);
+/// ```
+///
+/// Quantifiers such as [times](Pattern::times) and [optional](Pattern::optional)
+/// apply to the stage that was added last.
+pub struct Pattern<T: ArconType> {
+    stages: Vec<Stage<T>>,
+    within: Option<u64>,
+}
+
+impl<T: ArconType> Pattern<T> {
+    /// Starts a pattern with a stage that matches events satisfying `predicate`
+    pub fn begin(name: &'static str, predicate: &'static dyn SafelySendableFn(&T) -> bool) -> Self {
+        Pattern {
+            stages: Vec::new(),
+            within: None,
+        }
+        .stage(name, predicate, Contiguity::Relaxed)
+    }
+
+    /// Adds a stage that has to match the event directly after the previous stage
+    pub fn next(
+        self,
+        name: &'static str,
+        predicate: &'static dyn SafelySendableFn(&T) -> bool,
+    ) -> Self {
+        self.stage(name, predicate, Contiguity::Strict)
+    }
+
+    /// Adds a stage that matches some event after the previous stage
+    pub fn followed_by(
+        self,
+        name: &'static str,
+        predicate: &'static dyn SafelySendableFn(&T) -> bool,
+    ) -> Self {
+        self.stage(name, predicate, Contiguity::Relaxed)
+    }
+
+    fn stage(
+        mut self,
+        name: &'static str,
+        predicate: &'static dyn SafelySendableFn(&T) -> bool,
+        contiguity: Contiguity,
+    ) -> Self {
+        if self.stages.iter().any(|s| s.name == name) {
+            panic!("Pattern stage names must be unique, found {} twice", name);
+        }
+        self.stages.push(Stage {
+            name,
+            predicate,
+            contiguity,
+            times: 1,
+            optional: false,
+            consecutive: false,
+        });
+        self
+    }
+
+    fn last(&mut self) -> &mut Stage<T> {
+        self.stages
+            .last_mut()
+            .expect("a pattern always has a stage")
+    }
+
+    /// The last stage has to match exactly `times` events
+    pub fn times(mut self, times: u32) -> Self {
+        if times == 0 {
+            panic!("A pattern stage has to match at least one event");
+        }
+        self.last().times = times;
+        self
+    }
+
+    /// The events of the last stage have to directly follow each other
+    pub fn consecutive(mut self) -> Self {
+        self.last().consecutive = true;
+        self
+    }
+
+    /// The last stage may be skipped
+    pub fn optional(mut self) -> Self {
+        self.last().optional = true;
+        self
+    }
+
+    /// A match has to complete within `duration` of its first event
+    pub fn within(mut self, duration: u64) -> Self {
+        self.within = Some(duration);
+        self
+    }
+
+    pub(crate) fn compile(self) -> Nfa<T> {
+        if self.stages.last().map(|s| s.optional) == Some(true) {
+            panic!("A pattern can't end with an optional stage");
+        }
+
+        let mut states = Vec::new();
+        let mut stage_names = Vec::new();
+        for (index, stage) in self.stages.into_iter().enumerate() {
+            let skip_to = states.len() + stage.times as usize;
+            for copy in 0..stage.times {
+                let contiguity = if copy == 0 {
+                    stage.contiguity
+                } else if stage.consecutive {
+                    Contiguity::Strict
+                } else {
+                    Contiguity::Relaxed
+                };
+                states.push(State {
+                    stage: index as u32,
+                    predicate: stage.predicate,
+                    contiguity,
+                    // Only the whole stage may be skipped, later copies are required
+                    // once the first one has matched
+                    skip_to: if stage.optional && copy == 0 {
+                        Some(skip_to)
+                    } else {
+                        None
+                    },
+                });
+            }
+            stage_names.push(stage.name);
+        }
+
+        Nfa {
+            states,
+            stage_names,
+            within: self.within,
+        }
+    }
+}
+
+struct State<T: ArconType> {
+    stage: u32,
+    predicate: &'static dyn SafelySendableFn(&T) -> bool,
+    contiguity: Contiguity,
+    // State to continue from if this one is skipped
+    skip_to: Option<usize>,
+}
+
+/// A partial match of a [Pattern] for a single key
+#[derive(Message, Clone)]
+pub(crate) struct PartialMatch<T: ArconType> {
+    /// Index of the next NFA state to match
+    #[prost(uint32, tag = "1")]
+    pub position: u32,
+    /// Timestamp of the first matched event
+    #[prost(uint64, tag = "2")]
+    pub start: u64,
+    #[prost(message, repeated, tag = "3")]
+    pub events: Vec<T>,
+    /// Stage index of every matched event
+    #[prost(uint32, repeated, tag = "4")]
+    pub stages: Vec<u32>,
+}
+
+/// Events that matched a [Pattern], or that were matched before the pattern timed out
+#[cfg_attr(feature = "arcon_serde", derive(Serialize, Deserialize))]
+#[derive(Message, Clone, Abomonation)]
+#[cfg_attr(feature = "arcon_serde", serde(bound = "T: ArconType"))]
+pub struct PatternMatch<T: ArconType> {
+    #[prost(message, repeated, tag = "1")]
+    pub events: Vec<T>,
+    /// Name of the stage that matched each event
+    #[prost(string, repeated, tag = "2")]
+    pub stages: Vec<String>,
+}
+
+impl<T: ArconType> PatternMatch<T> {
+    /// Returns the events matched by the stage `name`
+    pub fn stage<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a T> + 'a {
+        self.events
+            .iter()
+            .zip(self.stages.iter())
+            .filter(move |(_, stage)| *stage == name)
+            .map(|(event, _)| event)
+    }
+}
+
+impl<T: ArconType> ArconType for PatternMatch<T> {
+    const UNSAFE_SER_ID: SerId =
+        ser_id::generic_id(ser_id::UNSAFE_PATTERN_MATCH_ID, T::UNSAFE_SER_ID);
+    const RELIABLE_SER_ID: SerId =
+        ser_id::generic_id(ser_id::RELIABLE_PATTERN_MATCH_ID, T::RELIABLE_SER_ID);
+    const VERSION_ID: VersionId = 1;
+
+    fn get_key(&self) -> u64 {
+        self.events.first().map(|e| e.get_key()).unwrap_or(0)
+    }
+}
+
+/// Result of feeding an event to the [Nfa]
+pub(crate) struct Step<T: ArconType> {
+    /// Partial matches that are still in progress
+    pub runs: Vec<PartialMatch<T>>,
+    /// Whether a new partial match began with this event
+    pub started: bool,
+    pub matches: Vec<PatternMatch<T>>,
+    /// Partial matches that ran out of time, with their deadlines
+    pub timeouts: Vec<(PatternMatch<T>, u64)>,
+}
+
+/// A [Pattern] compiled to a non-deterministic finite automaton
+///
+/// Every stage is unrolled into one state per event it matches. A [PartialMatch]
+/// holds the position of a run in the automaton, and a new run is started for every
+/// event that matches the beginning of the pattern.
+pub(crate) struct Nfa<T: ArconType> {
+    states: Vec<State<T>>,
+    stage_names: Vec<&'static str>,
+    within: Option<u64>,
+}
+
+impl<T: ArconType> Nfa<T> {
+    /// Returns the time at which `run` times out
+    pub fn deadline(&self, run: &PartialMatch<T>) -> Option<u64> {
+        self.within.map(|within| run.start.saturating_add(within))
+    }
+
+    /// Returns the states a run at `position` may match next
+    fn candidates(&self, position: usize) -> Vec<usize> {
+        let mut candidates = Vec::new();
+        let mut position = position;
+        while position < self.states.len() {
+            candidates.push(position);
+            match self.states[position].skip_to {
+                Some(next) => position = next,
+                None => break,
+            }
+        }
+        candidates
+    }
+
+    /// Feeds the next event of a key to all of its partial matches
+    pub fn advance(&self, runs: Vec<PartialMatch<T>>, event: &T, timestamp: u64) -> Step<T> {
+        let mut step = Step {
+            runs: Vec::new(),
+            started: false,
+            matches: Vec::new(),
+            timeouts: Vec::new(),
+        };
+
+        for run in runs {
+            match self.deadline(&run) {
+                Some(deadline) if timestamp > deadline => {
+                    step.timeouts.push((self.to_match(run), deadline));
+                }
+                _ => self.advance_run(run, event, &mut step),
+            }
+        }
+
+        let before = step.runs.len();
+        let fresh = PartialMatch {
+            position: 0,
+            start: timestamp,
+            events: Vec::new(),
+            stages: Vec::new(),
+        };
+        self.take(fresh, event, &mut step);
+        step.started = step.runs.len() > before;
+
+        step
+    }
+
+    fn advance_run(&self, mut run: PartialMatch<T>, event: &T, step: &mut Step<T>) {
+        if self.take(run.clone(), event, step) {
+            return;
+        }
+
+        // The event did not match, so the run survives only if it may be ignored
+        let relaxed = self
+            .candidates(run.position as usize)
+            .into_iter()
+            .find(|s| self.states[*s].contiguity == Contiguity::Relaxed);
+        if let Some(position) = relaxed {
+            run.position = position as u32;
+            step.runs.push(run);
+        }
+    }
+
+    /// Extends `run` with `event` for every candidate state that accepts it
+    ///
+    /// Returns false if no state accepted the event.
+    fn take(&self, run: PartialMatch<T>, event: &T, step: &mut Step<T>) -> bool {
+        let mut taken = false;
+        for position in self.candidates(run.position as usize) {
+            let state = &self.states[position];
+            if !(state.predicate)(event) {
+                continue;
+            }
+            taken = true;
+
+            let mut next = run.clone();
+            next.position = position as u32 + 1;
+            next.events.push(event.clone());
+            next.stages.push(state.stage);
+
+            if next.position as usize == self.states.len() {
+                step.matches.push(self.to_match(next));
+            } else {
+                step.runs.push(next);
+            }
+        }
+        taken
+    }
+
+    /// Removes the runs that time out at or before `time`
+    pub fn expire(
+        &self,
+        runs: Vec<PartialMatch<T>>,
+        time: u64,
+    ) -> (Vec<PartialMatch<T>>, Vec<(PatternMatch<T>, u64)>) {
+        let mut remaining = Vec::new();
+        let mut timeouts = Vec::new();
+        for run in runs {
+            match self.deadline(&run) {
+                Some(deadline) if deadline <= time => timeouts.push((self.to_match(run), deadline)),
+                _ => remaining.push(run),
+            }
+        }
+        (remaining, timeouts)
+    }
+
+    fn to_match(&self, run: PartialMatch<T>) -> PatternMatch<T> {
+        PatternMatch {
+            events: run.events,
+            stages: run
+                .stages
+                .into_iter()
+                .map(|s| self.stage_names[s as usize].to_string())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(nfa: &Nfa<u64>, events: &[u64]) -> (Vec<Vec<u64>>, Vec<PartialMatch<u64>>) {
+        let mut runs = Vec::new();
+        let mut matches = Vec::new();
+        for (ts, event) in events.iter().enumerate() {
+            let step = nfa.advance(runs, event, ts as u64);
+            runs = step.runs;
+            matches.extend(step.matches.into_iter().map(|m| m.events));
+        }
+        (matches, runs)
+    }
+
+    fn is_one(x: &u64) -> bool {
+        *x == 1
+    }
+    fn is_two(x: &u64) -> bool {
+        *x == 2
+    }
+    fn is_three(x: &u64) -> bool {
+        *x == 3
+    }
+
+    #[test]
+    fn pattern_match_ids_depend_on_event_type() {
+        let mut ids = vec![
+            PatternMatch::<u64>::UNSAFE_SER_ID,
+            PatternMatch::<u64>::RELIABLE_SER_ID,
+            PatternMatch::<u32>::UNSAFE_SER_ID,
+            PatternMatch::<u32>::RELIABLE_SER_ID,
+            PatternMatch::<PatternMatch<u64>>::UNSAFE_SER_ID,
+        ];
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 5);
+    }
+
+    #[test]
+    fn strict_and_relaxed_contiguity() {
+        let strict = Pattern::begin("a", &is_one).next("b", &is_two).compile();
+        let (matches, _) = run(&strict, &[1, 3, 2, 1, 2]);
+        assert_eq!(matches, vec![vec![1, 2]]);
+
+        let relaxed = Pattern::begin("a", &is_one)
+            .followed_by("b", &is_two)
+            .compile();
+        let (matches, _) = run(&relaxed, &[1, 3, 2, 1, 2]);
+        assert_eq!(matches, vec![vec![1, 2], vec![1, 2]]);
+    }
+
+    #[test]
+    fn times_and_optional() {
+        let nfa = Pattern::begin("a", &is_one)
+            .times(2)
+            .followed_by("b", &is_two)
+            .optional()
+            .followed_by("c", &is_three)
+            .compile();
+
+        let (matches, _) = run(&nfa, &[1, 1, 3]);
+        assert_eq!(matches, vec![vec![1, 1, 3]]);
+
+        let (matches, _) = run(&nfa, &[1, 2, 1, 2, 3]);
+        assert_eq!(matches, vec![vec![1, 1, 2, 3]]);
+
+        let consecutive = Pattern::begin("a", &is_one)
+            .times(2)
+            .consecutive()
+            .followed_by("c", &is_three)
+            .compile();
+        let (matches, _) = run(&consecutive, &[1, 2, 1, 1, 3]);
+        assert_eq!(matches, vec![vec![1, 1, 3]]);
+    }
+
+    #[test]
+    fn within_times_out_partial_matches() {
+        let nfa = Pattern::begin("a", &is_one)
+            .followed_by("b", &is_two)
+            .within(2)
+            .compile();
+
+        let (matches, runs) = run(&nfa, &[1, 3, 3, 2]);
+        assert!(matches.is_empty());
+        assert!(runs.is_empty());
+
+        let (_, runs) = run(&nfa, &[1, 3]);
+        let (runs, timeouts) = nfa.expire(runs, 2);
+        assert!(runs.is_empty());
+        assert_eq!(timeouts.len(), 1);
+        assert_eq!(timeouts[0].1, 2);
+        assert_eq!(timeouts[0].0.stage("a").collect::<Vec<_>>(), vec![&1]);
+    }
+}
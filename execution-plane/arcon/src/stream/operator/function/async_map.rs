@@ -0,0 +1,545 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    data::{ArconElement, ArconEvent, ArconType, Epoch, Watermark},
+    prelude::state,
+    stream::operator::{Operator, OperatorContext},
+    timer::TimerBackend,
+    util::SafelySendableFn,
+};
+use futures::{channel::oneshot, executor::block_on, FutureExt};
+use kompact::prelude::{error, ComponentDefinition};
+use prost::Message;
+#[cfg(feature = "arcon_serde")]
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    future::Future,
+    marker::PhantomData,
+    panic::AssertUnwindSafe,
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    thread::{Builder, JoinHandle},
+    time::Duration,
+};
+use tokio::runtime::{Handle, Runtime};
+
+// Default time the Node waits for any request to complete before giving up on the oldest one
+const DEFAULT_REQUEST_TIMEOUT_MS: u64 = 30_000;
+
+/// Order in which an [AsyncMap] emits the results of its requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsyncOrder {
+    /// Results are emitted in the order of their input elements
+    Ordered,
+    /// Results are emitted as soon as they complete
+    Unordered,
+}
+
+/// Timer entry used by [AsyncMap] to poll for completed requests
+#[cfg_attr(feature = "arcon_serde", derive(Serialize, Deserialize))]
+#[derive(Message, Clone, PartialEq)]
+pub struct AsyncPoll {}
+
+// Result of a request, or None if its future panicked
+type Completion<OUT> = (u64, Option<OUT>);
+
+// Requests that have been issued but whose results are not emitted yet
+struct InFlight<OUT> {
+    next_seq: u64,
+    // timestamp and change kind of the input element per request
+    requests: BTreeMap<u64, (Option<u64>, i32)>,
+    // results in the order they arrived
+    completed: Vec<Completion<OUT>>,
+}
+
+impl<OUT> InFlight<OUT> {
+    // Results of requests that were given up on are discarded
+    fn complete(&mut self, completion: Completion<OUT>) {
+        if self.requests.contains_key(&completion.0) {
+            self.completed.push(completion);
+        }
+    }
+}
+
+/// An Arcon operator that maps elements through asynchronous requests
+///
+/// Every element is turned into a future by the udf, e.g., a database or REST lookup,
+/// and executed on a Tokio runtime owned by the operator. At most `capacity` requests
+/// are in flight at a time; once the limit is reached, the Node blocks until a slot frees up.
+///
+/// Completed results are picked up whenever a new element arrives and by a processing-time
+/// timer every `poll_interval` milliseconds, so the Node must be given a timer backend
+/// such as `timer::wheel::<AsyncPoll>()`.
+///
+/// Watermarks and epochs act as barriers: all in-flight requests are drained before they are
+/// forwarded. Unordered results therefore never overtake a watermark, and no request is
+/// in flight when the state of the Node is checkpointed.
+///
+/// Requests that fail never block the Node. If a future panics, its element is logged and
+/// dropped. If the Node waits for `request_timeout` without any request completing, the
+/// oldest in-flight request is given up on in the same way and its late result is discarded.
+pub struct AsyncMap<IN, OUT, F, FUT>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    F: SafelySendableFn(IN) -> FUT,
+    FUT: Future<Output = OUT> + Send + 'static,
+{
+    udf: F,
+    capacity: usize,
+    order: AsyncOrder,
+    poll_interval: u64,
+    request_timeout: Duration,
+    runtime_handle: Handle,
+    shutdown: Option<oneshot::Sender<()>>,
+    _runtime_thread: JoinHandle<()>,
+    completions_tx: Sender<Completion<OUT>>,
+    completions_rx: Receiver<Completion<OUT>>,
+    in_flight: RefCell<InFlight<OUT>>,
+    poll_scheduled: Cell<bool>,
+    _marker: PhantomData<fn(IN)>,
+}
+
+impl<IN, OUT, F, FUT> AsyncMap<IN, OUT, F, FUT>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    F: SafelySendableFn(IN) -> FUT,
+    FUT: Future<Output = OUT> + Send + 'static,
+{
+    /// Creates an AsyncMap with at most `capacity` concurrent requests
+    pub fn new(udf: F, capacity: usize, order: AsyncOrder) -> Self {
+        Self::with_poll_interval(udf, capacity, order, 10)
+    }
+
+    /// Creates an AsyncMap that polls for completed requests every `poll_interval` milliseconds
+    pub fn with_poll_interval(
+        udf: F,
+        capacity: usize,
+        order: AsyncOrder,
+        poll_interval: u64,
+    ) -> Self {
+        if capacity == 0 {
+            panic!("AsyncMap capacity must be greater than zero!");
+        }
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let (tx_exec, rx_exec) = oneshot::channel();
+
+        let th = Builder::new()
+            .name(String::from("AsyncMapThread"))
+            .spawn(move || {
+                let mut runtime = Runtime::new().expect("Could not create Tokio Runtime!");
+                tx_exec
+                    .send(runtime.handle().clone())
+                    .expect("failed to send executor");
+                // Keep the runtime alive until the operator is dropped
+                let _ = runtime.block_on(shutdown_rx);
+            })
+            .map_err(|_| ())
+            .unwrap();
+
+        let runtime_handle = block_on(rx_exec).map_err(|_| ()).unwrap();
+        let (completions_tx, completions_rx) = channel();
+
+        AsyncMap {
+            udf,
+            capacity,
+            order,
+            poll_interval,
+            request_timeout: Duration::from_millis(DEFAULT_REQUEST_TIMEOUT_MS),
+            runtime_handle,
+            shutdown: Some(shutdown_tx),
+            _runtime_thread: th,
+            completions_tx,
+            completions_rx,
+            in_flight: RefCell::new(InFlight {
+                next_seq: 0,
+                requests: BTreeMap::new(),
+                completed: Vec::new(),
+            }),
+            poll_scheduled: Cell::new(false),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets how long the Node waits for any request to complete before giving up on the oldest one
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    // Moves all results that have arrived so far into the completed set
+    fn collect_completed(&self, in_flight: &mut InFlight<OUT>) {
+        while let Ok(completion) = self.completions_rx.try_recv() {
+            in_flight.complete(completion);
+        }
+    }
+
+    // Blocks until the next result arrives, or frees the slot of the oldest request on timeout
+    fn await_completion<CD>(&self, in_flight: &mut InFlight<OUT>, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        match self.completions_rx.recv_timeout(self.request_timeout) {
+            Ok(completion) => in_flight.complete(completion),
+            Err(RecvTimeoutError::Timeout) => {
+                if let Some(&seq) = in_flight.requests.keys().next() {
+                    error!(
+                        source.ctx().log(),
+                        "AsyncMap request {} timed out after {:?}", seq, self.request_timeout
+                    );
+                    in_flight.completed.push((seq, None));
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                unreachable!("AsyncMap holds a sender of its completion channel")
+            }
+        }
+    }
+
+    // Position of the next completed result that may be emitted according to `self.order`
+    fn next_ready(&self, in_flight: &InFlight<OUT>) -> Option<usize> {
+        match self.order {
+            AsyncOrder::Ordered => {
+                let head = in_flight.requests.keys().next()?;
+                in_flight.completed.iter().position(|(seq, _)| seq == head)
+            }
+            AsyncOrder::Unordered if !in_flight.completed.is_empty() => Some(0),
+            AsyncOrder::Unordered => None,
+        }
+    }
+
+    // Outputs every completed result that may be emitted according to `self.order`
+    fn emit_completed<B, CD>(
+        &self,
+        in_flight: &mut InFlight<OUT>,
+        source: &CD,
+        ctx: &mut OperatorContext<Self, B, impl TimerBackend<AsyncPoll>>,
+    ) where
+        B: state::Backend,
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        while let Some(pos) = self.next_ready(in_flight) {
+            let (seq, result) = in_flight.completed.remove(pos);
+            let (timestamp, change) = in_flight
+                .requests
+                .remove(&seq)
+                .expect("result of an unknown request");
+            let data = match result {
+                Some(data) => data,
+                None => {
+                    error!(
+                        source.ctx().log(),
+                        "AsyncMap request {} failed, dropping its element", seq
+                    );
+                    continue;
+                }
+            };
+            let element = ArconElement {
+                data,
+                timestamp,
+                change,
+            };
+            ctx.output(ArconEvent::Element(element), source);
+        }
+    }
+
+    // Blocks until every in-flight request has been emitted
+    fn drain<B, CD>(
+        &self,
+        source: &CD,
+        mut ctx: OperatorContext<Self, B, impl TimerBackend<AsyncPoll>>,
+    ) where
+        B: state::Backend,
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let mut in_flight = self.in_flight.borrow_mut();
+        self.collect_completed(&mut in_flight);
+        self.emit_completed(&mut in_flight, source, &mut ctx);
+        while !in_flight.requests.is_empty() {
+            self.await_completion(&mut in_flight, source);
+            self.emit_completed(&mut in_flight, source, &mut ctx);
+        }
+    }
+}
+
+impl<IN, OUT, F, FUT> Drop for AsyncMap<IN, OUT, F, FUT>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    F: SafelySendableFn(IN) -> FUT,
+    FUT: Future<Output = OUT> + Send + 'static,
+{
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+impl<IN, OUT, F, FUT, B> Operator<B> for AsyncMap<IN, OUT, F, FUT>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    F: SafelySendableFn(IN) -> FUT,
+    FUT: Future<Output = OUT> + Send + 'static,
+    B: state::Backend,
+{
+    type IN = IN;
+    type OUT = OUT;
+    type TimerState = AsyncPoll;
+
+    fn register_states(&mut self, _registration_token: &mut state::RegistrationToken<B>) {}
+
+    fn init(&mut self, _session: &mut state::Session<B>) {}
+
+    fn handle_element<CD>(
+        &self,
+        element: ArconElement<IN>,
+        source: &CD,
+        mut ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let mut in_flight = self.in_flight.borrow_mut();
+        self.collect_completed(&mut in_flight);
+        self.emit_completed(&mut in_flight, source, &mut ctx);
+
+        // Backpressure: wait for a free slot
+        while in_flight.requests.len() >= self.capacity {
+            self.await_completion(&mut in_flight, source);
+            self.emit_completed(&mut in_flight, source, &mut ctx);
+        }
+
+        let ArconElement {
+            data,
+            timestamp,
+            change,
+        } = element;
+        let seq = in_flight.next_seq;
+        in_flight.next_seq += 1;
+        in_flight.requests.insert(seq, (timestamp, change));
+
+        let request = AssertUnwindSafe((self.udf)(data)).catch_unwind();
+        let tx = self.completions_tx.clone();
+        self.runtime_handle.spawn(async move {
+            // A panicking request still frees its slot
+            let out = request.await.ok();
+            let _ = tx.send((seq, out));
+        });
+
+        if !self.poll_scheduled.get() {
+            let scheduled = ctx
+                .schedule_processing_after(self.poll_interval, AsyncPoll {})
+                .is_ok();
+            self.poll_scheduled.set(scheduled);
+        }
+    }
+
+    fn handle_watermark<CD>(
+        &self,
+        _watermark: Watermark,
+        source: &CD,
+        ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        self.drain(source, ctx);
+    }
+
+    fn handle_epoch<CD>(
+        &self,
+        _epoch: Epoch,
+        source: &CD,
+        ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        self.drain(source, ctx);
+    }
+
+    fn handle_timeout<CD>(
+        &self,
+        _timeout: Self::TimerState,
+        source: &CD,
+        mut ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let mut in_flight = self.in_flight.borrow_mut();
+        self.collect_completed(&mut in_flight);
+        self.emit_completed(&mut in_flight, source, &mut ctx);
+
+        let scheduled = !in_flight.requests.is_empty()
+            && ctx
+                .schedule_processing_after(self.poll_interval, AsyncPoll {})
+                .is_ok();
+        self.poll_scheduled.set(scheduled);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::*, state::InMemory, timer};
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    // A local stand-in for an external key-value service with per-key latency
+    #[derive(Clone)]
+    struct MockService {
+        table: Arc<HashMap<u64, u64>>,
+    }
+
+    impl MockService {
+        fn new() -> Self {
+            let table = (0..10).map(|k| (k, k * 100)).collect();
+            MockService {
+                table: Arc::new(table),
+            }
+        }
+
+        // Lower keys take longer to answer
+        async fn lookup(self, key: u64) -> u64 {
+            tokio::time::delay_for(Duration::from_millis((10 - key) * 50)).await;
+            self.table.get(&key).cloned().unwrap_or(0)
+        }
+    }
+
+    fn run_async_map(
+        order: AsyncOrder,
+        poll_interval: u64,
+        events: Vec<ArconEvent<u64>>,
+    ) -> (Vec<u64>, usize) {
+        let service = MockService::new();
+        let async_map = AsyncMap::with_poll_interval(
+            move |key: u64| service.clone().lookup(key),
+            4,
+            order,
+            poll_interval,
+        );
+        run_operator(async_map, events)
+    }
+
+    fn run_operator<F, FUT>(
+        async_map: AsyncMap<u64, u64, F, FUT>,
+        events: Vec<ArconEvent<u64>>,
+    ) -> (Vec<u64>, usize)
+    where
+        F: SafelySendableFn(u64) -> FUT,
+        FUT: Future<Output = u64> + Send + 'static,
+    {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let sink = system.create(move || DebugNode::<u64>::new());
+        system.start(&sink);
+
+        let sink_ref: ActorRefStrong<ArconMessage<u64>> =
+            sink.actor_ref().hold().expect("failed to fetch");
+        let channel_strategy =
+            ChannelStrategy::Forward(Forward::new(Channel::Local(sink_ref), 1.into(), pool_info));
+
+        let node = system.create(move || {
+            Node::new(
+                String::from("async_map_node"),
+                1.into(),
+                vec![0.into()],
+                channel_strategy,
+                async_map,
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::wheel::<AsyncPoll>(),
+            )
+        });
+        system.start(&node);
+
+        let node_ref: ActorRefStrong<ArconMessage<u64>> =
+            node.actor_ref().hold().expect("failed to fetch");
+        node_ref.tell(ArconMessage {
+            events: events
+                .into_iter()
+                .map(ArconEventWrapper::from)
+                .collect::<Vec<_>>()
+                .into(),
+            sender: 0.into(),
+        });
+
+        std::thread::sleep(Duration::from_secs(2));
+
+        let mut results = (Vec::new(), 0);
+        sink.on_definition(|cd| {
+            results = (cd.data.iter().map(|e| e.data).collect(), cd.epochs.len());
+        });
+        pipeline.shutdown();
+        results
+    }
+
+    fn elements(keys: impl Iterator<Item = u64>) -> Vec<ArconEvent<u64>> {
+        keys.map(|k| ArconEvent::Element(ArconElement::new(k)))
+            .collect()
+    }
+
+    #[test]
+    fn ordered_async_map_test() {
+        let (results, _) = run_async_map(AsyncOrder::Ordered, 10, elements(0..8));
+        assert_eq!(results, (0..8).map(|k| k * 100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unordered_async_map_test() {
+        let (results, _) = run_async_map(AsyncOrder::Unordered, 10, elements(0..4));
+        // All requests are in flight at once, so the fastest answer comes first
+        assert_eq!(results, vec![300, 200, 100, 0]);
+    }
+
+    #[test]
+    fn async_map_drains_on_epoch() {
+        // The poll timer never fires within the test, so results can only be emitted by the epoch
+        let mut events = elements(0..3);
+        events.push(ArconEvent::Epoch(Epoch::new(1)));
+        let (mut results, epochs) = run_async_map(AsyncOrder::Unordered, 60_000, events);
+        results.sort();
+        assert_eq!(results, vec![0, 100, 200]);
+        assert_eq!(epochs, 1);
+    }
+
+    #[test]
+    fn async_map_drops_panicking_request() {
+        let async_map = AsyncMap::new(
+            |key: u64| async move {
+                if key == 2 {
+                    panic!("lookup of key 2 failed");
+                }
+                key * 100
+            },
+            2,
+            AsyncOrder::Ordered,
+        );
+        let (results, _) = run_operator(async_map, elements(0..6));
+        assert_eq!(results, vec![0, 100, 300, 400, 500]);
+    }
+
+    #[test]
+    fn async_map_gives_up_on_stalled_request() {
+        let async_map = AsyncMap::with_poll_interval(
+            |key: u64| async move {
+                if key == 1 {
+                    futures::future::pending::<()>().await;
+                }
+                key * 100
+            },
+            4,
+            AsyncOrder::Ordered,
+            60_000,
+        )
+        .with_request_timeout(Duration::from_millis(200));
+        let mut events = elements(0..3);
+        events.push(ArconEvent::Epoch(Epoch::new(1)));
+        let (results, epochs) = run_operator(async_map, events);
+        assert_eq!(results, vec![0, 200]);
+        assert_eq!(epochs, 1);
+    }
+}
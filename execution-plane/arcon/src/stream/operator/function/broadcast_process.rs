@@ -0,0 +1,466 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    data::{ser_id, ArconElement, ArconEvent, ArconNever, ArconType, Epoch, VersionId, Watermark},
+    prelude::state::{self, handles::ActiveHandle, Handle, MapState},
+    stream::operator::{Operator, OperatorContext},
+    timer::TimerBackend,
+};
+use abomonation_derive::Abomonation;
+use kompact::prelude::{ComponentDefinition, SerId};
+use prost::{Message, Oneof};
+#[cfg(feature = "arcon_serde")]
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+/// An event on the input of a [BroadcastProcess] operator
+#[cfg_attr(feature = "arcon_serde", derive(Serialize, Deserialize))]
+#[derive(Oneof, Clone, Abomonation)]
+#[cfg_attr(
+    feature = "arcon_serde",
+    serde(bound = "IN: ArconType, CTRL: ArconType")
+)]
+pub enum BroadcastEvent<IN: ArconType, CTRL: ArconType> {
+    /// An element of the main, keyed, input
+    #[prost(message, tag = "1")]
+    Data(IN),
+    /// An element of the broadcast control input
+    #[prost(message, tag = "2")]
+    Control(CTRL),
+}
+
+/// Input type of a [BroadcastProcess] operator
+///
+/// The main input and the control input of the operator are merged into a single stream
+/// of this type. Upstream, the main input should be partitioned with a
+/// [KeyBy](crate::stream::channel::strategy::key_by::KeyBy) strategy while the control input
+/// is sent with a [Broadcast](crate::stream::channel::strategy::broadcast::Broadcast)
+/// strategy, so that every parallel instance of the operator sees every control element.
+#[cfg_attr(feature = "arcon_serde", derive(Serialize, Deserialize))]
+#[derive(Message, Clone, Abomonation)]
+#[cfg_attr(
+    feature = "arcon_serde",
+    serde(bound = "IN: ArconType, CTRL: ArconType")
+)]
+pub struct BroadcastInput<IN: ArconType, CTRL: ArconType> {
+    #[prost(oneof = "BroadcastEvent::<IN, CTRL>", tags = "1, 2")]
+    inner: Option<BroadcastEvent<IN, CTRL>>,
+}
+
+impl<IN: ArconType, CTRL: ArconType> BroadcastInput<IN, CTRL> {
+    /// Wraps an element of the main input
+    pub fn data(data: IN) -> Self {
+        BroadcastInput {
+            inner: Some(BroadcastEvent::Data(data)),
+        }
+    }
+
+    /// Wraps an element of the control input
+    pub fn control(control: CTRL) -> Self {
+        BroadcastInput {
+            inner: Some(BroadcastEvent::Control(control)),
+        }
+    }
+
+    pub fn unwrap(self) -> BroadcastEvent<IN, CTRL> {
+        self.inner
+            .expect("BroadcastInput.inner is None. Prost deserialization error?")
+    }
+}
+
+impl<IN: ArconType, CTRL: ArconType> ArconType for BroadcastInput<IN, CTRL> {
+    const UNSAFE_SER_ID: SerId = ser_id::generic_id(
+        ser_id::generic_id(ser_id::UNSAFE_BROADCAST_INPUT_ID, IN::UNSAFE_SER_ID),
+        CTRL::UNSAFE_SER_ID,
+    );
+    const RELIABLE_SER_ID: SerId = ser_id::generic_id(
+        ser_id::generic_id(ser_id::RELIABLE_BROADCAST_INPUT_ID, IN::RELIABLE_SER_ID),
+        CTRL::RELIABLE_SER_ID,
+    );
+    const VERSION_ID: VersionId = 1;
+
+    fn get_key(&self) -> u64 {
+        match &self.inner {
+            Some(BroadcastEvent::Data(data)) => data.get_key(),
+            // Control elements are broadcast, so their key is never used for partitioning
+            _ => 0,
+        }
+    }
+}
+
+/// User-defined logic that is executed by a [BroadcastProcess] operator
+///
+/// The function keeps a map of rules in broadcast state. Rules are only written by
+/// [process_broadcast](BroadcastProcessFunction::process_broadcast), and since every
+/// parallel instance receives the same control elements in the same order, all replicas
+/// of the broadcast state hold the same rules.
+pub trait BroadcastProcessFunction<B: state::Backend>: Send + Sized {
+    /// The type of elements on the main input
+    type IN: ArconType;
+    /// The type of elements on the control input
+    type CTRL: ArconType;
+    /// The type of output elements this function produces
+    type OUT: ArconType;
+    /// Key type of the broadcast state
+    type RuleKey: state::Key;
+    /// Value type of the broadcast state
+    type Rule: state::Value;
+
+    /// Register keyed states used by this function
+    ///
+    /// The broadcast state is registered by the operator.
+    fn register_states(&mut self, _registration_token: &mut state::RegistrationToken<B>) {}
+
+    /// Scope the keyed state handles of this function to `key`
+    fn set_key(&self, _key: u64) {}
+
+    /// Called for each element of the main input
+    ///
+    /// The broadcast state can only be read from here, see [BroadcastContext::broadcast_state].
+    fn process_element<CD>(
+        &self,
+        element: ArconElement<Self::IN>,
+        source: &CD,
+        ctx: BroadcastContext<Self, B, impl TimerBackend<ArconNever>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static;
+
+    /// Called for each element of the control input
+    ///
+    /// Nothing may be emitted from here, as each parallel instance would emit the same output.
+    fn process_broadcast(
+        &self,
+        element: ArconElement<Self::CTRL>,
+        rules: &mut ActiveHandle<B, MapState<Self::RuleKey, Self::Rule>>,
+    ) -> state::error::Result<()>;
+}
+
+/// Read-only view of the broadcast state of a [BroadcastProcess] operator
+pub struct ReadOnlyBroadcastState<'h, 's, B, K, V>
+where
+    B: state::Backend,
+    K: state::Key,
+    V: state::Value,
+{
+    inner: ActiveHandle<'h, 's, B, MapState<K, V>>,
+}
+
+impl<B, K, V> ReadOnlyBroadcastState<'_, '_, B, K, V>
+where
+    B: state::Backend,
+    K: state::Key,
+    V: state::Value,
+{
+    pub fn get(&self, key: &K) -> state::error::Result<Option<V>> {
+        self.inner.get(key)
+    }
+    pub fn contains(&self, key: &K) -> state::error::Result<bool> {
+        self.inner.contains(key)
+    }
+    pub fn iter(&self) -> state::error::Result<state::handles::BoxedIteratorOfResult<(K, V)>> {
+        self.inner.iter()
+    }
+    pub fn len(&self) -> state::error::Result<usize> {
+        self.inner.len()
+    }
+    pub fn is_empty(&self) -> state::error::Result<bool> {
+        self.inner.is_empty()
+    }
+}
+
+/// Context handed to a [BroadcastProcessFunction] for elements of the main input
+pub struct BroadcastContext<'c, 's, 'b, 't, 'h, P, B, T>
+where
+    P: BroadcastProcessFunction<B>,
+    B: state::Backend,
+    T: TimerBackend<ArconNever>,
+{
+    key: u64,
+    rules: &'h Handle<MapState<P::RuleKey, P::Rule>>,
+    inner: OperatorContext<'c, 's, 'b, 't, BroadcastProcess<P, B>, B, T>,
+}
+
+impl<'c, 's, 'b, 't, 'h, P, B, T> BroadcastContext<'c, 's, 'b, 't, 'h, P, B, T>
+where
+    P: BroadcastProcessFunction<B>,
+    B: state::Backend,
+    T: TimerBackend<ArconNever>,
+{
+    /// Returns the key currently being processed
+    #[inline]
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// Returns the state session of the parent Node
+    #[inline]
+    pub fn state_session(&mut self) -> &mut state::Session<'b, B> {
+        self.inner.state_session
+    }
+
+    /// Returns a read-only view of the broadcast state
+    #[inline]
+    pub fn broadcast_state(&mut self) -> ReadOnlyBroadcastState<'h, '_, B, P::RuleKey, P::Rule> {
+        ReadOnlyBroadcastState {
+            inner: self.rules.activate(self.inner.state_session),
+        }
+    }
+
+    /// Send an element downstream
+    #[inline]
+    pub fn output<CD>(&mut self, element: ArconElement<P::OUT>, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        self.inner.output(ArconEvent::Element(element), source)
+    }
+
+    /// Returns the current event time
+    #[inline]
+    pub fn current_time(&mut self) -> u64 {
+        self.inner.current_time()
+    }
+}
+
+/// An Operator that runs a [BroadcastProcessFunction]
+///
+/// The broadcast state is kept in a [MapState] that is not scoped to any key. It lives in
+/// the state backend of the Node, so it is snapshotted together with the keyed state on
+/// every epoch. Control elements travel in-band with epochs, which means that every
+/// replica snapshots the same rules for a given epoch.
+pub struct BroadcastProcess<P, B>
+where
+    P: BroadcastProcessFunction<B>,
+    B: state::Backend,
+{
+    function: P,
+    rules: Handle<MapState<P::RuleKey, P::Rule>>,
+    _marker: PhantomData<fn(B)>,
+}
+
+impl<P, B> BroadcastProcess<P, B>
+where
+    P: BroadcastProcessFunction<B>,
+    B: state::Backend,
+{
+    pub fn new(function: P) -> Self {
+        BroadcastProcess {
+            function,
+            rules: Handle::map("broadcast_state"),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<P, B> Operator<B> for BroadcastProcess<P, B>
+where
+    P: BroadcastProcessFunction<B>,
+    B: state::Backend,
+{
+    type IN = BroadcastInput<P::IN, P::CTRL>;
+    type OUT = P::OUT;
+    type TimerState = ArconNever;
+
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+        self.rules.register(registration_token);
+        self.function.register_states(registration_token);
+    }
+
+    fn init(&mut self, _session: &mut state::Session<B>) {}
+
+    fn handle_element<CD>(
+        &self,
+        element: ArconElement<Self::IN>,
+        source: &CD,
+        ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let ArconElement {
+            data,
+            timestamp,
+            change,
+        } = element;
+
+        match data.unwrap() {
+            BroadcastEvent::Data(data) => {
+                let key = data.get_key();
+                self.function.set_key(key);
+                let element = ArconElement {
+                    data,
+                    timestamp,
+                    change,
+                };
+                let ctx = BroadcastContext {
+                    key,
+                    rules: &self.rules,
+                    inner: ctx,
+                };
+                self.function.process_element(element, source, ctx);
+            }
+            BroadcastEvent::Control(control) => {
+                let element = ArconElement {
+                    data: control,
+                    timestamp,
+                    change,
+                };
+                let mut rules = self.rules.activate(ctx.state_session);
+                self.function
+                    .process_broadcast(element, &mut rules)
+                    .expect("broadcast state error");
+            }
+        }
+    }
+
+    crate::ignore_watermark!(B);
+    crate::ignore_epoch!(B);
+    crate::ignore_timeout!(B);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prelude::*,
+        state::{Backend, InMemory},
+        timer,
+    };
+
+    /// Drops elements that have been blocked on the control input
+    struct Blocklist;
+
+    impl<B: state::Backend> BroadcastProcessFunction<B> for Blocklist {
+        type IN = u64;
+        type CTRL = u64;
+        type OUT = u64;
+        type RuleKey = u64;
+        type Rule = bool;
+
+        fn process_element<CD>(
+            &self,
+            element: ArconElement<u64>,
+            source: &CD,
+            mut ctx: BroadcastContext<Self, B, impl TimerBackend<ArconNever>>,
+        ) where
+            CD: ComponentDefinition + Sized + 'static,
+        {
+            let blocked = ctx
+                .broadcast_state()
+                .get(&element.data)
+                .unwrap()
+                .unwrap_or(false);
+            if !blocked {
+                ctx.output(element, source);
+            }
+        }
+
+        fn process_broadcast(
+            &self,
+            element: ArconElement<u64>,
+            rules: &mut ActiveHandle<B, MapState<u64, bool>>,
+        ) -> state::error::Result<()> {
+            rules.fast_insert(element.data, true)
+        }
+    }
+
+    #[test]
+    fn broadcast_process_test() {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let comp = system.create(move || DebugNode::<u64>::new());
+        system.start(&comp);
+
+        let actor_ref: ActorRefStrong<ArconMessage<u64>> =
+            comp.actor_ref().hold().expect("failed to fetch");
+        let channel_strategy =
+            ChannelStrategy::Forward(Forward::new(Channel::Local(actor_ref), 1.into(), pool_info));
+
+        let node = system.create(move || {
+            Node::new(
+                String::from("broadcast_process_node"),
+                0.into(),
+                vec![1.into(), 2.into()],
+                channel_strategy,
+                BroadcastProcess::new(Blocklist),
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::none(),
+            )
+        });
+        system.start(&node);
+
+        let node_ref: ActorRefStrong<ArconMessage<BroadcastInput<u64, u64>>> =
+            node.actor_ref().hold().expect("failed to fetch");
+        let data = |x| ArconMessage::element(BroadcastInput::data(x), None, 1.into());
+        let control = |x| ArconMessage::element(BroadcastInput::control(x), None, 2.into());
+
+        node_ref.tell(data(1));
+        node_ref.tell(data(2));
+        node_ref.tell(control(2));
+        node_ref.tell(data(1));
+        node_ref.tell(data(2));
+        node_ref.tell(data(3));
+        // Once both inputs pass the watermark, the Node flushes its output
+        node_ref.tell(ArconMessage::watermark(1, 1.into()));
+        node_ref.tell(ArconMessage::watermark(1, 2.into()));
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        comp.on_definition(|cd| {
+            let results: Vec<u64> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(results, vec![1, 2, 1, 3]);
+        });
+
+        pipeline.shutdown();
+    }
+
+    #[test]
+    fn broadcast_input_ids_depend_on_type_parameters() {
+        type Input<IN, CTRL> = BroadcastInput<IN, CTRL>;
+        let unsafe_ids = [
+            Input::<u64, u64>::UNSAFE_SER_ID,
+            Input::<u64, u32>::UNSAFE_SER_ID,
+            Input::<u32, u64>::UNSAFE_SER_ID,
+        ];
+        let reliable_ids = [
+            Input::<u64, u64>::RELIABLE_SER_ID,
+            Input::<u64, u32>::RELIABLE_SER_ID,
+            Input::<u32, u64>::RELIABLE_SER_ID,
+        ];
+        let mut ids: Vec<SerId> = unsafe_ids
+            .iter()
+            .chain(reliable_ids.iter())
+            .cloned()
+            .collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 6);
+    }
+
+    #[test]
+    fn broadcast_state_is_shared_across_keys() {
+        let state_backend = InMemory::create("test".as_ref()).unwrap();
+        let mut session = state_backend.session();
+
+        let mut op = BroadcastProcess::new(Blocklist);
+        Operator::register_states(&mut op, &mut unsafe {
+            state::RegistrationToken::new(&mut session)
+        });
+
+        for x in 0..3 {
+            let mut rules = op.rules.activate(&mut session);
+            <Blocklist as BroadcastProcessFunction<InMemory>>::process_broadcast(
+                &Blocklist,
+                ArconElement::new(x * 2),
+                &mut rules,
+            )
+            .unwrap();
+        }
+
+        let rules = op.rules.activate(&mut session);
+        assert_eq!(rules.len().unwrap(), 3);
+        assert!(rules.contains(&4).unwrap());
+        assert!(!rules.contains(&1).unwrap());
+    }
+}
@@ -0,0 +1,231 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    data::{ArconElement, ArconEvent, ArconNever, ArconType, Epoch, Watermark},
+    prelude::state,
+    stream::operator::{Operator, OperatorContext},
+    timer::TimerBackend,
+};
+use kompact::prelude::ComponentDefinition;
+
+/// An operator that may be fused with its neighbours into a single [Operator]
+///
+/// Chainable operators neither use timers nor react to watermarks or epochs,
+/// so elements can be passed between them by direct calls instead of through a
+/// [ChannelStrategy](crate::stream::channel::strategy::ChannelStrategy) and a Kompact message.
+///
+/// Operators are fused automatically by `arcon_codegen` and by an
+/// [OperatorStage](crate::pipeline::OperatorStage) of the pipeline builder.
+pub trait ChainableOperator<B: state::Backend>: Send + Sized {
+    /// The type of input elements this operator processes
+    type IN: ArconType;
+    /// The type of output elements this operator produces
+    type OUT: ArconType;
+
+    /// Called by the parent Node to register states used by this operator
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>);
+
+    /// Processes an element and hands every output element to `emit`
+    ///
+    /// The state session is passed along to `emit` so that the next operator of a chain can use it.
+    fn process(
+        &self,
+        element: ArconElement<Self::IN>,
+        session: &mut state::Session<B>,
+        emit: &mut dyn FnMut(ArconElement<Self::OUT>, &mut state::Session<B>),
+    );
+
+    /// Fuses `next` behind this operator
+    fn chain<N>(self, next: N) -> Chain<Self, N>
+    where
+        N: ChainableOperator<B, IN = Self::OUT>,
+    {
+        Chain::new(self, next)
+    }
+}
+
+/// Two fused operators, where the output of `first` is fed directly into `second`
+///
+/// A Chain is itself chainable, so a whole run of forward-connected operators
+/// may be executed by a single Node.
+pub struct Chain<A, N> {
+    first: A,
+    second: N,
+}
+
+impl<A, N> Chain<A, N> {
+    pub fn new(first: A, second: N) -> Self {
+        Chain { first, second }
+    }
+}
+
+impl<A, N, B> ChainableOperator<B> for Chain<A, N>
+where
+    A: ChainableOperator<B>,
+    N: ChainableOperator<B, IN = A::OUT>,
+    B: state::Backend,
+{
+    type IN = A::IN;
+    type OUT = N::OUT;
+
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+        self.first.register_states(registration_token);
+        self.second.register_states(registration_token);
+    }
+
+    fn process(
+        &self,
+        element: ArconElement<A::IN>,
+        session: &mut state::Session<B>,
+        emit: &mut dyn FnMut(ArconElement<N::OUT>, &mut state::Session<B>),
+    ) {
+        let second = &self.second;
+        self.first
+            .process(element, session, &mut |element, session| {
+                second.process(element, session, emit)
+            });
+    }
+}
+
+impl<A, N, B> Operator<B> for Chain<A, N>
+where
+    A: ChainableOperator<B>,
+    N: ChainableOperator<B, IN = A::OUT>,
+    B: state::Backend,
+{
+    type IN = A::IN;
+    type OUT = N::OUT;
+    type TimerState = ArconNever;
+
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+        ChainableOperator::register_states(self, registration_token);
+    }
+
+    fn init(&mut self, _session: &mut state::Session<B>) {}
+
+    fn handle_element<CD>(
+        &self,
+        element: ArconElement<A::IN>,
+        source: &CD,
+        ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let OperatorContext {
+            channel_strategy,
+            state_session,
+            ..
+        } = ctx;
+        self.process(element, state_session, &mut |element, _| {
+            channel_strategy.add(ArconEvent::Element(element), source)
+        });
+    }
+    crate::ignore_watermark!(B);
+    crate::ignore_epoch!(B);
+    crate::ignore_timeout!(B);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prelude::*,
+        state::{Backend, InMemory},
+    };
+
+    #[test]
+    fn chain_process_test() {
+        let state_backend = InMemory::create("test".as_ref()).unwrap();
+        let mut session = state_backend.session();
+
+        fn split(x: i32) -> Vec<i32> {
+            vec![x, x * 10]
+        }
+        fn is_even(x: &i32) -> bool {
+            x % 2 == 0
+        }
+        fn to_u64(x: i32) -> u64 {
+            x as u64 + 1
+        }
+
+        let mut chain = FlatMap::new(&split)
+            .chain(Filter::new(&is_even))
+            .chain(Map::new(&to_u64));
+        ChainableOperator::register_states(&mut chain, &mut unsafe {
+            state::RegistrationToken::new(&mut session)
+        });
+
+        let mut results = Vec::new();
+        for x in 1..=3 {
+            chain.process(
+                ArconElement::with_timestamp(x, x as u64),
+                &mut session,
+                &mut |e, _| results.push((e.data, e.timestamp)),
+            );
+        }
+
+        assert_eq!(results, vec![
+            (11, Some(1)),
+            (3, Some(2)),
+            (21, Some(2)),
+            (31, Some(3))
+        ]);
+    }
+
+    #[test]
+    fn chain_node_test() {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let comp = system.create(move || DebugNode::<i32>::new());
+        system.start(&comp);
+
+        let actor_ref: ActorRefStrong<ArconMessage<i32>> =
+            comp.actor_ref().hold().expect("failed to fetch");
+        let channel_strategy =
+            ChannelStrategy::Forward(Forward::new(Channel::Local(actor_ref), 1.into(), pool_info));
+
+        fn add_ten(x: i32) -> i32 {
+            x + 10
+        }
+        fn below_17(x: &i32) -> bool {
+            x < &17
+        }
+        fn double(x: &mut i32) {
+            *x *= 2
+        }
+
+        let chain_node = system.create(move || {
+            Node::new(
+                String::from("chain_node"),
+                0.into(),
+                vec![1.into()],
+                channel_strategy,
+                Map::new(&add_ten)
+                    .chain(Filter::new(&below_17))
+                    .chain(MapInPlace::new(&double)),
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::none(),
+            )
+        });
+        system.start(&chain_node);
+
+        let chain_ref: ActorRefStrong<ArconMessage<i32>> =
+            chain_node.actor_ref().hold().expect("failed to fetch");
+        for x in 5..8 {
+            chain_ref.tell(ArconMessage::element(x, None, 1.into()));
+        }
+        chain_ref.tell(ArconMessage::watermark(100, 1.into()));
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        comp.on_definition(|cd| {
+            let results: Vec<i32> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(results, vec![30, 32]);
+            assert_eq!(cd.watermarks.last().map(|w| w.timestamp), Some(100));
+        });
+
+        pipeline.shutdown();
+    }
+}
@@ -0,0 +1,348 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    data::{ArconElement, ArconEvent, ArconType, Epoch, Watermark},
+    prelude::state::{self, Handle, MapState},
+    stream::{
+        node::NodeMetrics,
+        operator::{Operator, OperatorContext},
+    },
+    timer::TimerBackend,
+    util::SafelySendableFn,
+};
+use kompact::prelude::ComponentDefinition;
+use prost::Message;
+#[cfg(feature = "arcon_serde")]
+use serde::{Deserialize, Serialize};
+use std::{cell::Cell, hash::Hasher};
+use twox_hash::XxHash64;
+
+/// Decides how long a [Dedup] operator remembers the ids it has seen
+#[derive(Debug, Clone, Copy)]
+pub enum DedupMode {
+    /// Forget an id once the watermark passes its first timestamp plus the horizon
+    ///
+    /// Elements without a timestamp are stamped with the current watermark.
+    Horizon(u64),
+    /// Remember ids in a Bloom filter sized for `expected_items` ids
+    ///
+    /// The filter never grows, but it also never forgets. A unique element is wrongly
+    /// dropped with probability `false_positive_rate` as long as at most `expected_items`
+    /// ids have been seen, and with an increasing probability after that.
+    Probabilistic {
+        expected_items: u64,
+        false_positive_rate: f64,
+    },
+}
+
+enum Filter {
+    Horizon(u64),
+    Bloom { bits: u64, hashes: u32 },
+}
+
+impl From<DedupMode> for Filter {
+    fn from(mode: DedupMode) -> Self {
+        match mode {
+            DedupMode::Horizon(horizon) => Filter::Horizon(horizon),
+            DedupMode::Probabilistic {
+                expected_items,
+                false_positive_rate,
+            } => {
+                assert!(expected_items > 0, "expected_items must be positive");
+                assert!(
+                    false_positive_rate > 0.0 && false_positive_rate < 1.0,
+                    "false_positive_rate must be within (0, 1)"
+                );
+                let ln2 = std::f64::consts::LN_2;
+                let n = expected_items as f64;
+                let bits = (-n * false_positive_rate.ln() / (ln2 * ln2)).ceil();
+                let hashes = (bits / n * ln2).round().max(1.0);
+                Filter::Bloom {
+                    bits: bits as u64,
+                    hashes: hashes as u32,
+                }
+            }
+        }
+    }
+}
+
+/// Timer that evicts an id from the state of a [Dedup] operator
+#[cfg_attr(feature = "arcon_serde", derive(Serialize, Deserialize))]
+#[derive(Message, PartialEq, Clone)]
+pub struct DedupTimer {
+    /// The protobuf encoded id
+    #[prost(bytes, tag = "1")]
+    id: Vec<u8>,
+}
+
+/// An Operator that drops elements whose id has been seen before
+///
+/// Ids are kept in a [MapState], or in a Bloom filter, that is shared by all keys of the
+/// Node, so upstream should partition the stream such that duplicates end up at the same
+/// Node. The Node has to use a [wheel](crate::timer::wheel) timer backend. The number of
+/// dropped elements is reported as the duplicate counter of the [NodeMetrics].
+pub struct Dedup<IN, ID>
+where
+    IN: ArconType,
+    ID: state::Key,
+{
+    extract_id: &'static dyn SafelySendableFn(&IN) -> ID,
+    filter: Filter,
+    seen: Handle<MapState<ID, ()>>,
+    bloom: Handle<MapState<u64, u64>>,
+    duplicates: Cell<u64>,
+}
+
+impl<IN, ID> Dedup<IN, ID>
+where
+    IN: ArconType,
+    ID: state::Key,
+{
+    pub fn new(extract_id: &'static dyn SafelySendableFn(&IN) -> ID, mode: DedupMode) -> Self {
+        Dedup {
+            extract_id,
+            filter: mode.into(),
+            seen: Handle::map("dedup_seen"),
+            bloom: Handle::map("dedup_bloom"),
+            duplicates: Cell::new(0),
+        }
+    }
+
+    fn encode_id(id: &ID) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(id.encoded_len());
+        id.encode(&mut buf).expect("failed to encode id");
+        buf
+    }
+
+    /// Returns whether `id` was seen within the horizon, and starts remembering it otherwise
+    fn check_horizon<B: state::Backend>(
+        &self,
+        id: ID,
+        time: u64,
+        horizon: u64,
+        ctx: &mut OperatorContext<Self, B, impl TimerBackend<DedupTimer>>,
+    ) -> bool {
+        if self
+            .seen
+            .activate(ctx.state_session)
+            .contains(&id)
+            .expect("dedup state error")
+        {
+            return true;
+        }
+
+        let timer = DedupTimer {
+            id: Self::encode_id(&id),
+        };
+        // Ids that would be evicted right away are not worth storing
+        if ctx.schedule_at(time.saturating_add(horizon), timer).is_ok() {
+            self.seen
+                .activate(ctx.state_session)
+                .fast_insert(id, ())
+                .expect("dedup state error");
+        }
+        false
+    }
+
+    /// Returns whether all bits of `id` were set, and sets them otherwise
+    fn check_bloom<B: state::Backend>(
+        &self,
+        id: &ID,
+        bits: u64,
+        hashes: u32,
+        session: &mut state::Session<B>,
+    ) -> bool {
+        let encoded = Self::encode_id(id);
+        let hash = |seed| {
+            let mut hasher = XxHash64::with_seed(seed);
+            hasher.write(&encoded);
+            hasher.finish()
+        };
+        // Double hashing, see Kirsch and Mitzenmacher, "Less Hashing, Same Performance"
+        let (h1, h2) = (hash(0), hash(1));
+
+        let mut bloom = self.bloom.activate(session);
+        let mut duplicate = true;
+        for i in 0..hashes as u64 {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % bits;
+            let (index, mask) = (bit / 64, 1u64 << (bit % 64));
+            let word = bloom.get(&index).expect("dedup state error").unwrap_or(0);
+            if word & mask == 0 {
+                duplicate = false;
+                bloom
+                    .fast_insert(index, word | mask)
+                    .expect("dedup state error");
+            }
+        }
+        duplicate
+    }
+}
+
+impl<IN, ID, B> Operator<B> for Dedup<IN, ID>
+where
+    IN: ArconType,
+    ID: state::Key,
+    B: state::Backend,
+{
+    type IN = IN;
+    type OUT = IN;
+    type TimerState = DedupTimer;
+
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+        self.seen.register(registration_token);
+        self.bloom.register(registration_token);
+    }
+
+    fn init(&mut self, _session: &mut state::Session<B>) {}
+
+    fn handle_element<CD>(
+        &self,
+        element: ArconElement<IN>,
+        source: &CD,
+        mut ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let id = (self.extract_id)(&element.data);
+        let duplicate = match self.filter {
+            Filter::Horizon(horizon) => {
+                let time = match element.timestamp {
+                    Some(ts) => ts,
+                    None => ctx.current_time(),
+                };
+                self.check_horizon(id, time, horizon, &mut ctx)
+            }
+            Filter::Bloom { bits, hashes } => {
+                self.check_bloom(&id, bits, hashes, ctx.state_session)
+            }
+        };
+
+        if duplicate {
+            self.duplicates.set(self.duplicates.get() + 1);
+        } else {
+            ctx.output(ArconEvent::Element(element), source);
+        }
+    }
+
+    crate::ignore_watermark!(B);
+    crate::ignore_epoch!(B);
+
+    fn handle_timeout<CD>(
+        &self,
+        timeout: Self::TimerState,
+        _source: &CD,
+        ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let id = ID::decode(&timeout.id[..]).expect("failed to decode id");
+        self.seen
+            .activate(ctx.state_session)
+            .fast_remove(&id)
+            .expect("dedup state error");
+    }
+
+    fn report_metrics(&self, metrics: &mut NodeMetrics) {
+        metrics
+            .duplicate_counter
+            .inc_n(self.duplicates.replace(0) as usize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::*, state::InMemory, timer};
+
+    // Elements are `id + 100 * n`, so that duplicates can be told apart
+    fn extract_id(x: &u64) -> u64 {
+        x % 100
+    }
+
+    fn dedup_test(mode: DedupMode, input: Vec<ArconMessage<u64>>) -> Vec<u64> {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let comp = system.create(move || DebugNode::<u64>::new());
+        system.start(&comp);
+
+        let actor_ref: ActorRefStrong<ArconMessage<u64>> =
+            comp.actor_ref().hold().expect("failed to fetch");
+        let channel_strategy =
+            ChannelStrategy::Forward(Forward::new(Channel::Local(actor_ref), 1.into(), pool_info));
+
+        let node = system.create(move || {
+            Node::new(
+                String::from("dedup_node"),
+                0.into(),
+                vec![1.into()],
+                channel_strategy,
+                Dedup::new(&extract_id, mode),
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::wheel(),
+            )
+        });
+        system.start(&node);
+
+        let node_ref: ActorRefStrong<ArconMessage<u64>> =
+            node.actor_ref().hold().expect("failed to fetch");
+        for message in input {
+            node_ref.tell(message);
+        }
+        // A watermark past all of the input flushes what the Node emitted
+        node_ref.tell(ArconMessage::watermark(100, 1.into()));
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let mut results = Vec::new();
+        comp.on_definition(|cd| {
+            results = cd.data.iter().map(|e| e.data).collect();
+        });
+
+        pipeline.shutdown();
+        results
+    }
+
+    #[test]
+    fn dedup_horizon_test() {
+        let element = |x, ts| ArconMessage::element(x, Some(ts), 1.into());
+        let results = dedup_test(DedupMode::Horizon(3), vec![
+            element(1, 1),
+            element(101, 2),
+            // Evicts id 1, which was first seen at 1
+            ArconMessage::watermark(5, 1.into()),
+            element(2, 6),
+            element(201, 7),
+            element(102, 8),
+        ]);
+        assert_eq!(results, vec![1, 2, 201]);
+    }
+
+    #[test]
+    fn dedup_probabilistic_test() {
+        let input = (0..100)
+            .chain(100..200)
+            .map(|x| ArconMessage::element(x, None, 1.into()))
+            .collect();
+        let results = dedup_test(
+            DedupMode::Probabilistic {
+                expected_items: 1000,
+                false_positive_rate: 0.01,
+            },
+            input,
+        );
+        assert_eq!(results, (0..100).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn dedup_reports_duplicates() {
+        let dedup: Dedup<u64, u64> = Dedup::new(&extract_id, DedupMode::Horizon(10));
+        dedup.duplicates.set(3);
+
+        let mut metrics = NodeMetrics::new();
+        Operator::<InMemory>::report_metrics(&dedup, &mut metrics);
+        Operator::<InMemory>::report_metrics(&dedup, &mut metrics);
+        assert_eq!(metrics.duplicate_counter.get(), 3);
+    }
+}
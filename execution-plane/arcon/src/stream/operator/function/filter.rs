@@ -4,7 +4,7 @@
 use crate::{
     data::{ArconElement, ArconEvent, ArconNever, ArconType, Epoch, Watermark},
     prelude::state,
-    stream::operator::{Operator, OperatorContext},
+    stream::operator::{function::ChainableOperator, Operator, OperatorContext},
     timer::TimerBackend,
     util::SafelySendableFn,
 };
@@ -102,6 +102,32 @@ where
     crate::ignore_timeout!(B);
 }
 
+impl<IN, B, S, F> ChainableOperator<B> for Filter<IN, F, B, S>
+where
+    IN: ArconType,
+    B: state::Backend,
+    S: state::GenericBundle<B>,
+    F: SafelySendableFn(&IN, &S, &mut state::Session<B>) -> bool,
+{
+    type IN = IN;
+    type OUT = IN;
+
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+        self.state.register_states(registration_token);
+    }
+
+    fn process(
+        &self,
+        element: ArconElement<IN>,
+        session: &mut state::Session<B>,
+        emit: &mut dyn FnMut(ArconElement<IN>, &mut state::Session<B>),
+    ) {
+        if (self.udf)(&element.data, &self.state, session) {
+            emit(element, session);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -4,7 +4,7 @@
 use crate::{
     data::{ArconElement, ArconEvent, ArconNever, ArconType, Epoch, Watermark},
     prelude::state,
-    stream::operator::{Operator, OperatorContext},
+    stream::operator::{function::ChainableOperator, Operator, OperatorContext},
     timer::TimerBackend,
     util::SafelySendableFn,
 };
@@ -95,11 +95,17 @@ where
     ) where
         CD: ComponentDefinition + Sized + 'static,
     {
-        let result = (self.udf)(element.data, &self.state, ctx.state_session);
+        let ArconElement {
+            data,
+            timestamp,
+            change,
+        } = element;
+        let result = (self.udf)(data, &self.state, ctx.state_session);
         for item in result {
             let event = ArconEvent::Element(ArconElement {
                 data: item,
-                timestamp: element.timestamp,
+                timestamp,
+                change,
             });
 
             ctx.output(event, source);
@@ -110,6 +116,45 @@ where
     crate::ignore_timeout!(B);
 }
 
+impl<IN, OUTS, F, B, S> ChainableOperator<B> for FlatMap<IN, OUTS, F, B, S>
+where
+    IN: ArconType,
+    OUTS: IntoIterator,
+    OUTS::Item: ArconType,
+    F: SafelySendableFn(IN, &S, &mut state::Session<B>) -> OUTS,
+    B: state::Backend,
+    S: state::GenericBundle<B>,
+{
+    type IN = IN;
+    type OUT = OUTS::Item;
+
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+        self.state.register_states(registration_token);
+    }
+
+    fn process(
+        &self,
+        element: ArconElement<IN>,
+        session: &mut state::Session<B>,
+        emit: &mut dyn FnMut(ArconElement<OUTS::Item>, &mut state::Session<B>),
+    ) {
+        let ArconElement {
+            data,
+            timestamp,
+            change,
+        } = element;
+        let result = (self.udf)(data, &self.state, session);
+        for item in result {
+            let out_elem = ArconElement {
+                data: item,
+                timestamp,
+                change,
+            };
+            emit(out_elem, session);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
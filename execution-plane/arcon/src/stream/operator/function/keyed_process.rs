@@ -0,0 +1,484 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    data::{ArconElement, ArconEvent, ArconType, Epoch, Watermark},
+    prelude::state::{self, KeyedBundle},
+    stream::operator::{Operator, OperatorContext},
+    timer::{event_timer::TimerTypeBounds, TimerBackend, TimerId},
+};
+use kompact::prelude::ComponentDefinition;
+use prost::Message;
+#[cfg(feature = "arcon_serde")]
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+/// A timer registered by a [KeyedProcessFunction] for a single key
+#[cfg_attr(feature = "arcon_serde", derive(Serialize, Deserialize))]
+#[derive(Message, PartialEq, Clone)]
+#[cfg_attr(feature = "arcon_serde", serde(bound = "E: TimerTypeBounds"))]
+pub struct KeyedTimer<E: TimerTypeBounds> {
+    /// Key that was active when the timer was registered
+    #[prost(uint64, tag = "1")]
+    pub key: u64,
+    /// Time at which the timer fires
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    /// User-defined timer payload
+    #[prost(message, required, tag = "3")]
+    pub payload: E,
+    /// Whether `timestamp` is a processing time rather than an event time
+    #[prost(bool, tag = "4")]
+    pub processing_time: bool,
+}
+
+impl<E: TimerTypeBounds> KeyedTimer<E> {
+    fn new(key: u64, timestamp: u64, payload: E, processing_time: bool) -> Self {
+        KeyedTimer {
+            key,
+            timestamp,
+            payload,
+            processing_time,
+        }
+    }
+}
+
+/// User-defined logic that is executed by a [KeyedProcess] operator
+///
+/// Every element and timer is processed in the context of a key. Before
+/// calling into the function, the operator scopes the handles of its
+/// [state](KeyedProcessFunction::state) to the key of the element (as given by
+/// [ArconType::get_key]) or the key the timer was registered for.
+pub trait KeyedProcessFunction<B: state::Backend>: Send + Sized {
+    /// The type of input elements this function processes
+    type IN: ArconType;
+    /// The type of output elements this function produces
+    type OUT: ArconType;
+    /// Payload type of the timers registered by this function
+    type Timer: TimerTypeBounds;
+    /// Keyed state of this function, whose handles all use `u64` item keys
+    type State: KeyedBundle<u64>;
+
+    /// Register states used by this function
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>);
+
+    /// Returns the keyed state of this function
+    fn state(&self) -> &Self::State;
+
+    /// Called for each element of the stream
+    fn process_element<CD>(
+        &self,
+        element: ArconElement<Self::IN>,
+        source: &CD,
+        ctx: KeyedContext<Self, B, impl TimerBackend<KeyedTimer<Self::Timer>>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static;
+
+    /// Called when a timer registered earlier fires
+    fn on_timer<CD>(
+        &self,
+        timestamp: u64,
+        payload: Self::Timer,
+        source: &CD,
+        ctx: KeyedContext<Self, B, impl TimerBackend<KeyedTimer<Self::Timer>>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static;
+}
+
+/// Context handed to a [KeyedProcessFunction]
+///
+/// Timers registered through the context are scoped to the current key.
+pub struct KeyedContext<'c, 's, 'b, 't, P, B, T>
+where
+    P: KeyedProcessFunction<B>,
+    B: state::Backend,
+    T: TimerBackend<KeyedTimer<P::Timer>>,
+{
+    key: u64,
+    inner: OperatorContext<'c, 's, 'b, 't, KeyedProcess<P, B>, B, T>,
+}
+
+impl<'c, 's, 'b, 't, P, B, T> KeyedContext<'c, 's, 'b, 't, P, B, T>
+where
+    P: KeyedProcessFunction<B>,
+    B: state::Backend,
+    T: TimerBackend<KeyedTimer<P::Timer>>,
+{
+    /// Returns the key currently being processed
+    #[inline]
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// Returns the state session of the parent Node
+    #[inline]
+    pub fn state_session(&mut self) -> &mut state::Session<'b, B> {
+        self.inner.state_session
+    }
+
+    /// Send an element downstream
+    #[inline]
+    pub fn output<CD>(&mut self, element: ArconElement<P::OUT>, source: &CD)
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        self.inner.output(ArconEvent::Element(element), source)
+    }
+
+    /// Returns the current event time
+    #[inline]
+    pub fn current_time(&mut self) -> u64 {
+        self.inner.current_time()
+    }
+
+    /// Register an event-time timer for the current key
+    ///
+    /// Returns `Err(payload)` if `time` has already passed.
+    #[inline]
+    pub fn schedule_event_timer(
+        &mut self,
+        time: u64,
+        payload: P::Timer,
+    ) -> Result<TimerId, P::Timer> {
+        self.inner
+            .schedule_at(time, KeyedTimer::new(self.key, time, payload, false))
+            .map_err(|timer| timer.payload)
+    }
+
+    /// Returns the current processing time in milliseconds since the UNIX epoch
+    #[inline]
+    pub fn current_processing_time(&self) -> u64 {
+        self.inner.current_processing_time()
+    }
+
+    /// Register a processing-time timer for the current key
+    ///
+    /// Returns `Err(payload)` if `time` has already passed.
+    #[inline]
+    pub fn schedule_processing_timer(
+        &mut self,
+        time: u64,
+        payload: P::Timer,
+    ) -> Result<TimerId, P::Timer> {
+        self.inner
+            .schedule_processing_at(time, KeyedTimer::new(self.key, time, payload, true))
+            .map_err(|timer| timer.payload)
+    }
+
+    /// Cancel a pending timer, returning its payload
+    #[inline]
+    pub fn cancel_timer(&mut self, id: &TimerId) -> Option<P::Timer> {
+        self.inner.cancel_timer(id).map(|timer| timer.payload)
+    }
+
+    /// Move a pending timer to `time` within its own time domain, keeping its id
+    ///
+    /// The timer still fires for the key it was created for. Returns `Ok(false)` if no
+    /// timer with that id is pending, or `Err(payload)` if `time` has already passed.
+    #[inline]
+    pub fn reschedule_timer(&mut self, id: &TimerId, time: u64) -> Result<bool, P::Timer> {
+        self.inner
+            .reschedule_timer_with(id, time, |timer| timer.timestamp = time)
+            .map_err(|timer| timer.payload)
+    }
+}
+
+/// An Operator that runs a [KeyedProcessFunction]
+pub struct KeyedProcess<P, B>
+where
+    P: KeyedProcessFunction<B>,
+    B: state::Backend,
+{
+    function: P,
+    _marker: PhantomData<fn(B)>,
+}
+
+impl<P, B> KeyedProcess<P, B>
+where
+    P: KeyedProcessFunction<B>,
+    B: state::Backend,
+{
+    pub fn new(function: P) -> Self {
+        KeyedProcess {
+            function,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<P, B> Operator<B> for KeyedProcess<P, B>
+where
+    P: KeyedProcessFunction<B>,
+    B: state::Backend,
+{
+    type IN = P::IN;
+    type OUT = P::OUT;
+    type TimerState = KeyedTimer<P::Timer>;
+
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+        self.function.register_states(registration_token)
+    }
+
+    fn init(&mut self, _session: &mut state::Session<B>) {}
+
+    fn handle_element<CD>(
+        &self,
+        element: ArconElement<Self::IN>,
+        source: &CD,
+        ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let key = element.data.get_key();
+        self.function.state().set_item_key(key);
+        self.function
+            .process_element(element, source, KeyedContext { key, inner: ctx });
+    }
+
+    crate::ignore_watermark!(B);
+    crate::ignore_epoch!(B);
+
+    fn handle_timeout<CD>(
+        &self,
+        timeout: Self::TimerState,
+        source: &CD,
+        ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let KeyedTimer {
+            key,
+            timestamp,
+            payload,
+            ..
+        } = timeout;
+        self.function.state().set_item_key(key);
+        self.function
+            .on_timer(timestamp, payload, source, KeyedContext { key, inner: ctx });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prelude::*,
+        state::{Bundle, InMemory},
+        timer,
+    };
+
+    arcon_state::bundle! {
+        struct CountState {
+            count: Handle<ValueState<u64>, u64>
+        }
+    }
+
+    /// Counts elements per key and emits the count 10 time units after the first element
+    struct DelayedCount {
+        state: CountState,
+    }
+
+    impl<B: state::Backend> KeyedProcessFunction<B> for DelayedCount {
+        type IN = u64;
+        type OUT = u64;
+        type Timer = u64;
+        type State = CountState;
+
+        fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+            self.state.register_states(registration_token)
+        }
+
+        fn state(&self) -> &CountState {
+            &self.state
+        }
+
+        fn process_element<CD>(
+            &self,
+            element: ArconElement<u64>,
+            _source: &CD,
+            mut ctx: KeyedContext<Self, B, impl TimerBackend<KeyedTimer<u64>>>,
+        ) where
+            CD: ComponentDefinition + Sized + 'static,
+        {
+            let mut state = self.state.activate(ctx.state_session());
+            let count = state.count().get().unwrap().unwrap_or(0) + 1;
+            state.count().set(count).unwrap();
+            drop(state);
+
+            if count == 1 {
+                let ts = element.timestamp.unwrap() + 10;
+                ctx.schedule_event_timer(ts, element.data).unwrap();
+            }
+        }
+
+        fn on_timer<CD>(
+            &self,
+            timestamp: u64,
+            _payload: u64,
+            source: &CD,
+            mut ctx: KeyedContext<Self, B, impl TimerBackend<KeyedTimer<u64>>>,
+        ) where
+            CD: ComponentDefinition + Sized + 'static,
+        {
+            let count = self
+                .state
+                .activate(ctx.state_session())
+                .count()
+                .get()
+                .unwrap()
+                .unwrap();
+            ctx.output(ArconElement::with_timestamp(count, timestamp), source);
+        }
+    }
+
+    /// Echoes each element once 50 milliseconds of processing time have passed
+    struct ProcessingTimeEcho {
+        state: CountState,
+    }
+
+    impl<B: state::Backend> KeyedProcessFunction<B> for ProcessingTimeEcho {
+        type IN = u64;
+        type OUT = u64;
+        type Timer = u64;
+        type State = CountState;
+
+        fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+            self.state.register_states(registration_token)
+        }
+
+        fn state(&self) -> &CountState {
+            &self.state
+        }
+
+        fn process_element<CD>(
+            &self,
+            element: ArconElement<u64>,
+            _source: &CD,
+            mut ctx: KeyedContext<Self, B, impl TimerBackend<KeyedTimer<u64>>>,
+        ) where
+            CD: ComponentDefinition + Sized + 'static,
+        {
+            let deadline = ctx.current_processing_time() + 50;
+            ctx.schedule_processing_timer(deadline, element.data)
+                .unwrap();
+        }
+
+        fn on_timer<CD>(
+            &self,
+            _timestamp: u64,
+            payload: u64,
+            source: &CD,
+            mut ctx: KeyedContext<Self, B, impl TimerBackend<KeyedTimer<u64>>>,
+        ) where
+            CD: ComponentDefinition + Sized + 'static,
+        {
+            ctx.output(ArconElement::new(payload), source);
+        }
+    }
+
+    #[test]
+    fn keyed_process_processing_time_test() {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let comp = system.create(move || DebugNode::<u64>::new());
+        system.start(&comp);
+
+        let actor_ref: ActorRefStrong<ArconMessage<u64>> =
+            comp.actor_ref().hold().expect("failed to fetch");
+        let channel_strategy =
+            ChannelStrategy::Forward(Forward::new(Channel::Local(actor_ref), 1.into(), pool_info));
+
+        let function = ProcessingTimeEcho {
+            state: CountState {
+                count: Handle::value("count").with_item_key(0),
+            },
+        };
+
+        let process_node = system.create(move || {
+            Node::new(
+                String::from("keyed_process_node"),
+                1.into(),
+                vec![0.into()],
+                channel_strategy,
+                KeyedProcess::new(function),
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::wheel(),
+            )
+        });
+        system.start(&process_node);
+
+        let process_ref: ActorRefStrong<ArconMessage<u64>> =
+            process_node.actor_ref().hold().expect("failed to fetch");
+
+        // No watermarks are sent, the timers are driven by processing time alone
+        process_ref.tell(ArconMessage::element(7, None, 0.into()));
+        process_ref.tell(ArconMessage::element(8, None, 0.into()));
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        comp.on_definition(|cd| {
+            let mut data: Vec<u64> = cd.data.iter().map(|e| e.data).collect();
+            data.sort();
+            assert_eq!(data, vec![7, 8]);
+        });
+
+        pipeline.shutdown();
+    }
+
+    #[test]
+    fn keyed_process_test() {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let comp = system.create(move || DebugNode::<u64>::new());
+        system.start(&comp);
+
+        let actor_ref: ActorRefStrong<ArconMessage<u64>> =
+            comp.actor_ref().hold().expect("failed to fetch");
+        let channel_strategy =
+            ChannelStrategy::Forward(Forward::new(Channel::Local(actor_ref), 1.into(), pool_info));
+
+        let function = DelayedCount {
+            state: CountState {
+                count: Handle::value("count").with_item_key(0),
+            },
+        };
+
+        let process_node = system.create(move || {
+            Node::new(
+                String::from("keyed_process_node"),
+                1.into(),
+                vec![0.into()],
+                channel_strategy,
+                KeyedProcess::new(function),
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::wheel(),
+            )
+        });
+        system.start(&process_node);
+
+        let process_ref: ActorRefStrong<ArconMessage<u64>> =
+            process_node.actor_ref().hold().expect("failed to fetch");
+
+        process_ref.tell(ArconMessage::element(1, Some(1), 0.into()));
+        process_ref.tell(ArconMessage::element(1, Some(2), 0.into()));
+        process_ref.tell(ArconMessage::element(2, Some(3), 0.into()));
+        process_ref.tell(ArconMessage::watermark(20, 0.into()));
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        comp.on_definition(|cd| {
+            assert_eq!(cd.data.len(), 2);
+            // key of element 1 fires first at time 11
+            assert_eq!(cd.data[0].data, 2);
+            assert_eq!(cd.data[0].timestamp, Some(11));
+            assert_eq!(cd.data[1].data, 1);
+            assert_eq!(cd.data[1].timestamp, Some(13));
+        });
+
+        pipeline.shutdown();
+    }
+}
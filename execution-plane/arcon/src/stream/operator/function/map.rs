@@ -4,7 +4,7 @@
 use crate::{
     data::{ArconElement, ArconEvent, ArconNever, ArconType, Epoch, Watermark},
     prelude::state,
-    stream::operator::{Operator, OperatorContext},
+    stream::operator::{function::ChainableOperator, Operator, OperatorContext},
     timer::TimerBackend,
     util::SafelySendableFn,
 };
@@ -90,10 +90,16 @@ where
     ) where
         CD: ComponentDefinition + Sized + 'static,
     {
-        let result = (self.udf)(element.data, &self.state, ctx.state_session);
+        let ArconElement {
+            data,
+            timestamp,
+            change,
+        } = element;
+        let result = (self.udf)(data, &self.state, ctx.state_session);
         let out_elem = ArconElement {
             data: result,
-            timestamp: element.timestamp,
+            timestamp,
+            change,
         };
         ctx.output(ArconEvent::Element(out_elem), source);
     }
@@ -102,6 +108,42 @@ where
     crate::ignore_timeout!(B);
 }
 
+impl<IN, OUT, F, B, S> ChainableOperator<B> for Map<IN, OUT, F, B, S>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    F: SafelySendableFn(IN, &S, &mut state::Session<B>) -> OUT,
+    B: state::Backend,
+    S: state::GenericBundle<B>,
+{
+    type IN = IN;
+    type OUT = OUT;
+
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+        self.state.register_states(registration_token)
+    }
+
+    fn process(
+        &self,
+        element: ArconElement<IN>,
+        session: &mut state::Session<B>,
+        emit: &mut dyn FnMut(ArconElement<OUT>, &mut state::Session<B>),
+    ) {
+        let ArconElement {
+            data,
+            timestamp,
+            change,
+        } = element;
+        let result = (self.udf)(data, &self.state, session);
+        let out_elem = ArconElement {
+            data: result,
+            timestamp,
+            change,
+        };
+        emit(out_elem, session);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -4,7 +4,7 @@
 use crate::{
     data::{ArconElement, ArconEvent, ArconNever, ArconType, Epoch, Watermark},
     prelude::state,
-    stream::operator::{Operator, OperatorContext},
+    stream::operator::{function::ChainableOperator, Operator, OperatorContext},
     timer::TimerBackend,
     util::SafelySendableFn,
 };
@@ -92,3 +92,29 @@ where
     crate::ignore_epoch!(B);
     crate::ignore_timeout!(B);
 }
+
+impl<IN, F, B, S> ChainableOperator<B> for MapInPlace<IN, F, B, S>
+where
+    IN: ArconType,
+    F: SafelySendableFn(&mut IN, &S, &mut state::Session<B>),
+    B: state::Backend,
+    S: state::GenericBundle<B>,
+{
+    type IN = IN;
+    type OUT = IN;
+
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+        self.state.register_states(registration_token)
+    }
+
+    fn process(
+        &self,
+        element: ArconElement<IN>,
+        session: &mut state::Session<B>,
+        emit: &mut dyn FnMut(ArconElement<IN>, &mut state::Session<B>),
+    ) {
+        let mut elem = element;
+        (self.udf)(&mut elem.data, &self.state, session);
+        emit(elem, session);
+    }
+}
@@ -1,12 +1,29 @@
 // Copyright (c) 2020, KTH Royal Institute of Technology.
 // SPDX-License-Identifier: AGPL-3.0-only
 
+#[cfg(feature = "async_io")]
+pub mod async_map;
+pub mod broadcast_process;
+pub mod chain;
+pub mod dedup;
 pub mod filter;
 pub mod flatmap;
+pub mod keyed_process;
 pub mod map;
 pub mod map_in_place;
+pub mod sort;
 
+#[cfg(feature = "async_io")]
+pub use async_map::{AsyncMap, AsyncOrder, AsyncPoll};
+pub use broadcast_process::{
+    BroadcastContext, BroadcastEvent, BroadcastInput, BroadcastProcess, BroadcastProcessFunction,
+    ReadOnlyBroadcastState,
+};
+pub use chain::{Chain, ChainableOperator};
+pub use dedup::{Dedup, DedupMode, DedupTimer};
 pub use filter::Filter;
 pub use flatmap::FlatMap;
+pub use keyed_process::{KeyedContext, KeyedProcess, KeyedProcessFunction, KeyedTimer};
 pub use map::Map;
 pub use map_in_place::MapInPlace;
+pub use sort::EventTimeSort;
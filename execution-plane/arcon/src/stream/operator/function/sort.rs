@@ -0,0 +1,306 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    data::{ArconElement, ArconEvent, ArconNever, ArconType, Epoch, Watermark},
+    prelude::state::{self, Handle, MapState, ValueState},
+    stream::operator::{Operator, OperatorContext},
+    timer::TimerBackend,
+};
+use kompact::prelude::ComponentDefinition;
+use prost::Message;
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, BTreeSet},
+};
+
+/// Elements of a single timestamp that were spilled to the state backend
+#[derive(Message, Clone)]
+pub struct SortBucket<IN: ArconType> {
+    #[prost(message, repeated, tag = "1")]
+    pub elements: Vec<ArconElement<IN>>,
+}
+
+/// An Operator that emits elements in timestamp order
+///
+/// Elements are held back until the watermark passes their timestamp, and elements with
+/// equal timestamps are emitted in arrival order. Elements without a timestamp are
+/// forwarded right away. Late elements, whose timestamp is not after the last watermark,
+/// can no longer be emitted in order and are dropped.
+///
+/// At most `memory_capacity` elements are buffered in memory. Beyond that, the elements
+/// with the latest timestamps are spilled to a [MapState] in the state backend. Only the
+/// spilled timestamps are kept in memory to find them again. The memory buffer is also
+/// spilled on every epoch, so that checkpoints contain all buffered elements.
+pub struct EventTimeSort<IN: ArconType> {
+    memory_capacity: usize,
+    memory: RefCell<BTreeMap<u64, Vec<ArconElement<IN>>>>,
+    in_memory: Cell<usize>,
+    spilled: Handle<MapState<u64, SortBucket<IN>>>,
+    spilled_timestamps: RefCell<BTreeSet<u64>>,
+    watermark: Handle<ValueState<u64>>,
+    current_watermark: Cell<Option<u64>>,
+}
+
+impl<IN: ArconType> EventTimeSort<IN> {
+    pub fn new(memory_capacity: usize) -> Self {
+        EventTimeSort {
+            memory_capacity,
+            memory: RefCell::new(BTreeMap::new()),
+            in_memory: Cell::new(0),
+            spilled: Handle::map("event_time_sort_spilled"),
+            spilled_timestamps: RefCell::new(BTreeSet::new()),
+            watermark: Handle::value("event_time_sort_watermark"),
+            current_watermark: Cell::new(None),
+        }
+    }
+
+    /// Moves the buckets with the latest timestamps to the state backend until at most
+    /// `keep` elements are left in memory
+    fn spill<B: state::Backend>(&self, keep: usize, session: &mut state::Session<B>) {
+        let mut memory = self.memory.borrow_mut();
+        let mut spilled_timestamps = self.spilled_timestamps.borrow_mut();
+        let mut spilled = self.spilled.activate(session);
+
+        while self.in_memory.get() > keep {
+            let timestamp = match memory.keys().next_back() {
+                Some(timestamp) => *timestamp,
+                None => break,
+            };
+            let elements = memory.remove(&timestamp).unwrap();
+            self.in_memory.set(self.in_memory.get() - elements.len());
+
+            let mut bucket = spilled
+                .get(&timestamp)
+                .expect("sort buffer state error")
+                .unwrap_or_default();
+            bucket.elements.extend(elements);
+            spilled
+                .fast_insert(timestamp, bucket)
+                .expect("sort buffer state error");
+            spilled_timestamps.insert(timestamp);
+        }
+    }
+}
+
+impl<IN, B> Operator<B> for EventTimeSort<IN>
+where
+    IN: ArconType,
+    B: state::Backend,
+{
+    type IN = IN;
+    type OUT = IN;
+    type TimerState = ArconNever;
+
+    fn register_states(&mut self, registration_token: &mut state::RegistrationToken<B>) {
+        self.spilled.register(registration_token);
+        self.watermark.register(registration_token);
+    }
+
+    fn init(&mut self, session: &mut state::Session<B>) {
+        // Buckets may have been restored from a checkpoint
+        let spilled = self.spilled.activate(session);
+        let timestamps = spilled
+            .keys()
+            .expect("sort buffer state error")
+            .collect::<Result<BTreeSet<u64>, _>>()
+            .expect("sort buffer state error");
+        *self.spilled_timestamps.borrow_mut() = timestamps;
+
+        let watermark = self
+            .watermark
+            .activate(session)
+            .get()
+            .expect("sort watermark state error");
+        self.current_watermark.set(watermark);
+    }
+
+    fn handle_element<CD>(
+        &self,
+        element: ArconElement<IN>,
+        source: &CD,
+        mut ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let timestamp = match element.timestamp {
+            Some(timestamp) => timestamp,
+            None => return ctx.output(ArconEvent::Element(element), source),
+        };
+
+        if let Some(watermark) = self.current_watermark.get() {
+            if timestamp <= watermark {
+                // Late arrival: everything up to the watermark has already been emitted
+                return;
+            }
+        }
+
+        self.memory
+            .borrow_mut()
+            .entry(timestamp)
+            .or_insert_with(Vec::new)
+            .push(element);
+        self.in_memory.set(self.in_memory.get() + 1);
+
+        if self.in_memory.get() > self.memory_capacity {
+            self.spill(self.memory_capacity, ctx.state_session);
+        }
+    }
+
+    fn handle_watermark<CD>(
+        &self,
+        watermark: Watermark,
+        source: &CD,
+        mut ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        self.current_watermark.set(Some(watermark.timestamp));
+        self.watermark
+            .activate(ctx.state_session)
+            .set(watermark.timestamp)
+            .expect("sort watermark state error");
+
+        let (mut ready, ready_spilled) = {
+            let mut memory = self.memory.borrow_mut();
+            let mut spilled_timestamps = self.spilled_timestamps.borrow_mut();
+            match watermark.timestamp.checked_add(1) {
+                Some(first_pending) => {
+                    let pending = memory.split_off(&first_pending);
+                    let pending_spilled = spilled_timestamps.split_off(&first_pending);
+                    (
+                        std::mem::replace(&mut *memory, pending),
+                        std::mem::replace(&mut *spilled_timestamps, pending_spilled),
+                    )
+                }
+                None => (
+                    std::mem::take(&mut *memory),
+                    std::mem::take(&mut *spilled_timestamps),
+                ),
+            }
+        };
+        self.in_memory
+            .set(self.in_memory.get() - ready.values().map(Vec::len).sum::<usize>());
+
+        let timestamps: BTreeSet<u64> = ready
+            .keys()
+            .copied()
+            .chain(ready_spilled.iter().copied())
+            .collect();
+
+        for timestamp in timestamps {
+            // Spilled elements of a timestamp always arrived before those still in memory
+            if ready_spilled.contains(&timestamp) {
+                let bucket = self
+                    .spilled
+                    .activate(ctx.state_session)
+                    .remove(&timestamp)
+                    .expect("sort buffer state error")
+                    .unwrap_or_default();
+                for element in bucket.elements {
+                    ctx.output(ArconEvent::Element(element), source);
+                }
+            }
+            if let Some(elements) = ready.remove(&timestamp) {
+                for element in elements {
+                    ctx.output(ArconEvent::Element(element), source);
+                }
+            }
+        }
+    }
+
+    fn handle_epoch<CD>(
+        &self,
+        _epoch: Epoch,
+        _source: &CD,
+        ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        self.spill(0, ctx.state_session);
+    }
+
+    crate::ignore_timeout!(B);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        prelude::{
+            state::{with_backend_type, Backend, BackendContainer, BackendType},
+            *,
+        },
+        timer,
+    };
+
+    fn sort_test<B: Backend>(state_backend: BackendContainer<B>) {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let comp = system.create(move || DebugNode::<u64>::new());
+        system.start(&comp);
+
+        let actor_ref: ActorRefStrong<ArconMessage<u64>> =
+            comp.actor_ref().hold().expect("failed to fetch");
+        let channel_strategy =
+            ChannelStrategy::Forward(Forward::new(Channel::Local(actor_ref), 1.into(), pool_info));
+
+        let node = system.create(move || {
+            Node::new(
+                String::from("sort_node"),
+                0.into(),
+                vec![1.into()],
+                channel_strategy,
+                EventTimeSort::new(3),
+                state_backend,
+                timer::none(),
+            )
+        });
+        system.start(&node);
+
+        let node_ref: ActorRefStrong<ArconMessage<u64>> =
+            node.actor_ref().hold().expect("failed to fetch");
+        // The data is the timestamp times ten, plus the arrival order within the timestamp
+        for (data, ts) in &[
+            (50, 5),
+            (20, 2),
+            (80, 8),
+            (10, 1),
+            (51, 5),
+            (30, 3),
+            (90, 9),
+        ] {
+            node_ref.tell(ArconMessage::element(*data, Some(*ts), 1.into()));
+        }
+        node_ref.tell(ArconMessage::watermark(5, 1.into()));
+        // Late, since the watermark has passed its timestamp
+        node_ref.tell(ArconMessage::element(40, Some(4), 1.into()));
+        node_ref.tell(ArconMessage::element(52, Some(5), 1.into()));
+        node_ref.tell(ArconMessage::element(70, Some(7), 1.into()));
+        node_ref.tell(ArconMessage::epoch(1, 1.into()));
+        node_ref.tell(ArconMessage::element(60, Some(6), 1.into()));
+        node_ref.tell(ArconMessage::element(91, Some(9), 1.into()));
+        node_ref.tell(ArconMessage::watermark(10, 1.into()));
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        comp.on_definition(|cd| {
+            let results: Vec<u64> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(results, vec![10, 20, 30, 50, 51, 60, 70, 80, 90, 91]);
+            assert_eq!(cd.watermarks.last().map(|w| w.timestamp), Some(10));
+        });
+
+        pipeline.shutdown();
+    }
+
+    #[test]
+    fn event_time_sort_on_every_backend() {
+        for backend_type in BackendType::VARIANTS {
+            let dir = tempfile::tempdir().unwrap();
+            with_backend_type!(*backend_type, |SB| sort_test(
+                SB::create(dir.path()).unwrap()
+            ));
+        }
+    }
+}
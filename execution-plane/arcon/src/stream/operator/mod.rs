@@ -1,6 +1,10 @@
 // Copyright (c) 2020, KTH Royal Institute of Technology.
 // SPDX-License-Identifier: AGPL-3.0-only
 
+/// Built-in aggregation operators
+pub mod aggregate;
+/// Complex event processing over keyed streams
+pub mod cep;
 /// Available function operators
 pub mod function;
 /// Available sink operators
@@ -9,13 +13,14 @@ pub mod sink;
 pub mod window;
 
 use crate::{
-    data::{ArconElement, ArconEvent, ArconType, Epoch, Watermark},
+    data::{ArconElement, ArconEvent, ArconType, ChangeKind, Epoch, Watermark},
     prelude::state,
-    stream::channel::strategy::ChannelStrategy,
-    timer::TimerBackend,
+    stream::{channel::strategy::ChannelStrategy, node::NodeMetrics},
+    timer::{TimerBackend, TimerId},
 };
-use kompact::prelude::ComponentDefinition;
+use kompact::prelude::{warn, ComponentDefinition};
 use prost::Message;
+use std::cell::Cell;
 
 /// Defines the methods an `Operator` must implement
 pub trait Operator<B: state::Backend>: Send + Sized {
@@ -66,6 +71,15 @@ pub trait Operator<B: state::Backend>: Send + Sized {
         ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
     ) where
         CD: ComponentDefinition + Sized + 'static;
+
+    /// Called by the parent Node to add metrics collected by the `Operator` before reporting them
+    fn report_metrics(&self, _metrics: &mut NodeMetrics) {}
+
+    /// How far behind the watermark of its sender an element may be for the parent Node
+    /// to still pass it on to the `Operator`
+    fn allowed_lateness(&self) -> u64 {
+        0
+    }
 }
 
 /// Helper macro to implement an empty ´handle_watermark` function
@@ -116,6 +130,51 @@ macro_rules! ignore_timeout {
     };
 }
 
+/// Drops changelog rows that an insert-only operator cannot process
+///
+/// Aggregating operators have no way to retract input they already aggregated, so every
+/// [ChangeKind] other than [ChangeKind::Insert] is dropped rather than treated as an insert.
+/// Only the first drop is logged, while all of them are reported as the changelog drop
+/// counter of the [NodeMetrics].
+#[derive(Default)]
+pub(crate) struct InsertOnly {
+    /// Rows dropped since the last metrics report
+    dropped: Cell<u64>,
+    /// Whether a drop has been logged
+    logged: Cell<bool>,
+}
+
+impl InsertOnly {
+    /// Returns true if the element was rejected
+    pub(crate) fn reject<IN, CD>(&self, element: &ArconElement<IN>, source: &CD) -> bool
+    where
+        IN: ArconType,
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let kind = element.change_kind();
+        if kind == ChangeKind::Insert {
+            return false;
+        }
+        if !self.logged.replace(true) {
+            warn!(
+                source.ctx().log(),
+                "Dropping {:?} element, the operator only supports inserts. Further drops are \
+                 only counted in the NodeMetrics",
+                kind
+            );
+        }
+        self.dropped.set(self.dropped.get() + 1);
+        true
+    }
+
+    /// Adds the rows dropped since the last report to `metrics`
+    pub(crate) fn report_metrics(&self, metrics: &mut NodeMetrics) {
+        metrics
+            .changelog_drop_counter
+            .inc_n(self.dropped.replace(0) as usize);
+    }
+}
+
 pub struct OperatorContext<'c, 's, 'b, 't, OP, B, T>
 where
     OP: Operator<B>,
@@ -168,14 +227,70 @@ where
         &mut self,
         delay: u64,
         entry: OP::TimerState,
-    ) -> Result<(), OP::TimerState> {
+    ) -> Result<TimerId, OP::TimerState> {
         self.timer_backend
             .schedule_after(delay, entry, self.state_session)
     }
 
     #[inline]
-    pub fn schedule_at(&mut self, time: u64, entry: OP::TimerState) -> Result<(), OP::TimerState> {
+    pub fn schedule_at(
+        &mut self,
+        time: u64,
+        entry: OP::TimerState,
+    ) -> Result<TimerId, OP::TimerState> {
         self.timer_backend
             .schedule_at(time, entry, self.state_session)
     }
+
+    /// Returns the current processing time in milliseconds since the UNIX epoch
+    #[inline]
+    pub fn current_processing_time(&self) -> u64 {
+        crate::util::get_system_time()
+    }
+
+    #[inline]
+    pub fn schedule_processing_at(
+        &mut self,
+        time: u64,
+        entry: OP::TimerState,
+    ) -> Result<TimerId, OP::TimerState> {
+        self.timer_backend
+            .schedule_processing_at(time, entry, self.state_session)
+    }
+
+    #[inline]
+    pub fn schedule_processing_after(
+        &mut self,
+        delay: u64,
+        entry: OP::TimerState,
+    ) -> Result<TimerId, OP::TimerState> {
+        let time = self.current_processing_time() + delay;
+        self.schedule_processing_at(time, entry)
+    }
+
+    #[inline]
+    pub fn cancel_timer(&mut self, id: &TimerId) -> Option<OP::TimerState> {
+        self.timer_backend.cancel(id, self.state_session)
+    }
+
+    #[inline]
+    pub fn reschedule_timer(
+        &mut self,
+        id: &TimerId,
+        new_time: u64,
+    ) -> Result<bool, OP::TimerState> {
+        self.timer_backend
+            .reschedule(id, new_time, self.state_session)
+    }
+
+    #[inline]
+    pub fn reschedule_timer_with(
+        &mut self,
+        id: &TimerId,
+        new_time: u64,
+        update: impl FnOnce(&mut OP::TimerState),
+    ) -> Result<bool, OP::TimerState> {
+        self.timer_backend
+            .reschedule_with(id, new_time, update, self.state_session)
+    }
 }
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 
 pub mod local_file;
+pub mod upsert;
 
 #[cfg(feature = "socket")]
 pub mod socket;
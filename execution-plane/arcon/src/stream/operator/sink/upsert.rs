@@ -0,0 +1,152 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{prelude::*, stream::operator::OperatorContext, timer::TimerBackend};
+use arcon_state::{RegistrationToken, Session};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
+/// An external table that a changelog stream can be applied to
+///
+/// Rows are identified by the key given by [ArconType::get_key].
+pub trait UpsertTarget<IN>: Send
+where
+    IN: ArconType,
+{
+    /// Insert the row or replace the existing row with the same key
+    fn upsert(&mut self, key: u64, row: IN);
+    /// Remove the row with the given key
+    fn delete(&mut self, key: u64);
+}
+
+/// A shared in-memory table
+impl<IN> UpsertTarget<IN> for Arc<Mutex<HashMap<u64, IN>>>
+where
+    IN: ArconType,
+{
+    fn upsert(&mut self, key: u64, row: IN) {
+        self.lock().unwrap().insert(key, row);
+    }
+
+    fn delete(&mut self, key: u64) {
+        self.lock().unwrap().remove(&key);
+    }
+}
+
+/// A sink that applies the [ChangeKind] of incoming elements to an [UpsertTarget]
+///
+/// Inserts and the new versions of updated rows are upserted, while deletes remove the row.
+/// Retractions of updated rows are skipped, since the following update replaces the row anyway.
+pub struct UpsertSink<IN, T>
+where
+    IN: ArconType,
+    T: UpsertTarget<IN>,
+{
+    target: RefCell<T>,
+    _marker: PhantomData<IN>,
+}
+
+impl<IN, T> UpsertSink<IN, T>
+where
+    IN: ArconType,
+    T: UpsertTarget<IN>,
+{
+    pub fn new(target: T) -> Self {
+        UpsertSink {
+            target: RefCell::new(target),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<IN, T, B> Operator<B> for UpsertSink<IN, T>
+where
+    IN: ArconType,
+    T: UpsertTarget<IN>,
+    B: state::Backend,
+{
+    type IN = IN;
+    type OUT = ArconNever;
+    type TimerState = ArconNever;
+
+    fn register_states(&mut self, _registration_token: &mut RegistrationToken<B>) {}
+
+    fn init(&mut self, _session: &mut Session<B>) {}
+
+    fn handle_element<CD>(
+        &self,
+        element: ArconElement<IN>,
+        _source: &CD,
+        _ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let key = element.data.get_key();
+        let mut target = self.target.borrow_mut();
+        match element.change_kind() {
+            ChangeKind::Insert | ChangeKind::UpdateAfter => target.upsert(key, element.data),
+            ChangeKind::Delete => target.delete(key),
+            ChangeKind::UpdateBefore => (),
+        }
+    }
+    crate::ignore_watermark!(B);
+    crate::ignore_epoch!(B);
+    crate::ignore_timeout!(B);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::ChannelStrategy, state::InMemory, timer};
+
+    #[test]
+    fn upsert_sink_test() {
+        let system = KompactConfig::default().build().expect("KompactSystem");
+
+        let table: Arc<Mutex<HashMap<u64, u64>>> = Arc::new(Mutex::new(HashMap::new()));
+        let target = table.clone();
+
+        let node_id = NodeID::new(1);
+        let sink_comp = system.create(move || {
+            Node::new(
+                String::from("sink_comp"),
+                0.into(),
+                vec![node_id],
+                ChannelStrategy::Mute,
+                UpsertSink::new(target),
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::none(),
+            )
+        });
+        system.start(&sink_comp);
+
+        let target_ref: ActorRefStrong<ArconMessage<u64>> =
+            sink_comp.actor_ref().hold().expect("Failed to fetch");
+        target_ref.tell(ArconMessage::element(1, None, node_id));
+        target_ref.tell(ArconMessage::element(2, None, node_id));
+        target_ref.tell(ArconMessage::change(
+            2,
+            None,
+            ChangeKind::UpdateBefore,
+            node_id,
+        ));
+        target_ref.tell(ArconMessage::change(
+            2,
+            None,
+            ChangeKind::UpdateAfter,
+            node_id,
+        ));
+        target_ref.tell(ArconMessage::change(1, None, ChangeKind::Delete, node_id));
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let table = table.lock().unwrap();
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.get(&2u64.get_key()), Some(&2));
+        let _ = system.shutdown();
+    }
+}
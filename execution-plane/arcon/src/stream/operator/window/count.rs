@@ -0,0 +1,315 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    prelude::{
+        state::{Backend, Bundle, Handle, RegistrationToken, Session, ValueState},
+        *,
+    },
+    stream::{
+        node::NodeMetrics,
+        operator::{window::WindowContext, InsertOnly, OperatorContext},
+    },
+    timer::TimerBackend,
+};
+use std::marker::PhantomData;
+
+/*
+    CountWindowAssigner
+        * Assigns messages to windows based on the number of elements seen per key
+        * A window of length N covers N consecutive elements of a key
+        * A new window starts every `slide` elements, so tumbling windows have slide == length
+        * Windows are emitted as soon as their last element arrives and need no timers
+        * Input must be insert-only, elements of any other ChangeKind are dropped and counted
+*/
+
+type Key = u64;
+
+arcon_state::bundle! {
+    struct CountWindowAssignerState {
+        // number of elements seen per key (via state backend api)
+        element_count: Handle<ValueState<u64>, Key>,
+    }
+}
+
+impl CountWindowAssignerState {
+    fn new() -> Self {
+        CountWindowAssignerState {
+            element_count: Handle::value("count_window_element_count").with_item_key(0),
+        }
+    }
+}
+
+/// Window Assigner Based on Element Counts
+///
+/// IN: Input event
+/// OUT: Output of Window
+pub struct CountWindowAssigner<IN, OUT, W>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    W: Window<IN, OUT>,
+{
+    // effectively immutable, so no reason to persist
+    window_length: u64,
+    window_slide: u64,
+    keyed: bool,
+
+    // window keeps its own state per key and index (via state backend api)
+    window: W,
+
+    // simply persisted state
+    state: CountWindowAssignerState,
+
+    // drops changelog rows other than inserts
+    insert_only: InsertOnly,
+
+    _marker: PhantomData<(IN, OUT)>,
+}
+
+impl<IN, OUT, W> CountWindowAssigner<IN, OUT, W>
+where
+    IN: 'static + ArconType,
+    OUT: 'static + ArconType,
+    W: Window<IN, OUT>,
+{
+    /// Creates a sliding count window assigner
+    pub fn new(window: W, length: u64, slide: u64, keyed: bool) -> Self {
+        // Sanity check on slide and length
+        if slide == 0 {
+            panic!("Window slide must be greater than zero!");
+        }
+        if length < slide {
+            panic!("Window Length lower than slide!");
+        }
+        if length % slide != 0 {
+            panic!("Window Length not divisible by slide!");
+        }
+
+        CountWindowAssigner {
+            window_length: length,
+            window_slide: slide,
+            keyed,
+            window,
+
+            state: CountWindowAssignerState::new(),
+            insert_only: InsertOnly::default(),
+            _marker: Default::default(),
+        }
+    }
+
+    /// Creates a tumbling count window assigner
+    pub fn tumbling(window: W, length: u64, keyed: bool) -> Self {
+        Self::new(window, length, length, keyed)
+    }
+
+    // Extracts the key from ArconElements
+    fn get_key(&self, e: &ArconElement<IN>) -> u64 {
+        if !self.keyed {
+            return 0;
+        }
+        e.data.get_key()
+    }
+}
+
+impl<IN, OUT, W, B> Operator<B> for CountWindowAssigner<IN, OUT, W>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    W: Window<IN, OUT>,
+    B: Backend,
+{
+    type IN = IN;
+    type OUT = OUT;
+    type TimerState = ArconNever;
+
+    fn register_states(&mut self, registration_token: &mut RegistrationToken<B>) {
+        self.state.register_states(registration_token);
+        self.window.register_states(registration_token);
+    }
+
+    fn init(&mut self, _session: &mut Session<B>) {
+        ()
+    }
+
+    fn handle_element<CD>(
+        &self,
+        element: ArconElement<IN>,
+        source: &CD,
+        mut ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        if self.insert_only.reject(&element, source) {
+            return;
+        }
+
+        let key = self.get_key(&element);
+
+        let mut state = self.state.activate(ctx.state_session);
+        state.element_count().set_item_key(key);
+
+        // Position of this element within the stream of its key
+        let n = state
+            .element_count()
+            .get()
+            .expect("element count get error")
+            .unwrap_or(0);
+        state
+            .element_count()
+            .set(n + 1)
+            .expect("element count set error");
+
+        // temporarily deactivate state, so we can borrow the session mutably again
+        drop(state);
+
+        // Get the highest and lowest window the element goes into
+        let ceil = n / self.window_slide;
+        let windows_per_element = self.window_length / self.window_slide;
+        let floor = (ceil + 1).saturating_sub(windows_per_element);
+
+        for index in floor..=ceil {
+            self.window
+                .on_element(
+                    element.data.clone(),
+                    WindowContext::new(ctx.state_session, key, index),
+                )
+                .expect("window error");
+        }
+
+        // The element completes the window with the lowest index if it is its last one
+        if n + 1 == floor * self.window_slide + self.window_length {
+            let e = self
+                .window
+                .result(WindowContext::new(ctx.state_session, key, floor))
+                .expect("window result error");
+
+            self.window
+                .clear(WindowContext::new(ctx.state_session, key, floor))
+                .expect("window clear error");
+
+            let window_result = ArconEvent::Element(ArconElement {
+                data: e,
+                timestamp: element.timestamp,
+                change: ChangeKind::Insert as i32,
+            });
+            ctx.output(window_result, source);
+        }
+    }
+
+    crate::ignore_watermark!(B);
+    crate::ignore_epoch!(B);
+    crate::ignore_timeout!(B);
+
+    fn report_metrics(&self, metrics: &mut NodeMetrics) {
+        self.insert_only.report_metrics(metrics);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::InMemory;
+    use kompact::prelude::Component;
+    use std::{sync::Arc, thread, time};
+
+    fn count_window_test_setup(
+        length: u64,
+        slide: u64,
+    ) -> (
+        ActorRefStrong<ArconMessage<u64>>,
+        Arc<Component<DebugNode<u64>>>,
+    ) {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let sink = system.create(move || DebugNode::new());
+        system.start(&sink);
+        let sink_ref: ActorRefStrong<ArconMessage<u64>> =
+            sink.actor_ref().hold().expect("failed to get strong ref");
+
+        let channel_strategy = ChannelStrategy::Forward(Forward::new(
+            Channel::Local(sink_ref),
+            NodeID::new(1),
+            pool_info,
+        ));
+
+        fn sum(buffer: &[u64]) -> u64 {
+            buffer.iter().sum()
+        }
+
+        let window = AppenderWindow::new(&sum);
+        let window_assigner = CountWindowAssigner::new(window, length, slide, true);
+
+        let window_node = system.create(move || {
+            Node::new(
+                String::from("count_window_node"),
+                1.into(),
+                vec![0.into()],
+                channel_strategy,
+                window_assigner,
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::none(),
+            )
+        });
+        system.start(&window_node);
+
+        let win_ref: ActorRefStrong<ArconMessage<u64>> = window_node
+            .actor_ref()
+            .hold()
+            .expect("failed to get strong ref");
+        (win_ref, sink)
+    }
+
+    fn wait(time: u64) {
+        thread::sleep(time::Duration::from_secs(time));
+    }
+
+    /// A watermark makes the window Node flush its channel buffers
+    fn flush(assigner_ref: &ActorRefStrong<ArconMessage<u64>>) {
+        assigner_ref.tell(ArconMessage::watermark(1, 0.into()));
+    }
+
+    #[test]
+    fn tumbling_count_window() {
+        let (assigner_ref, sink) = count_window_test_setup(3, 3);
+        for i in 1..=7 {
+            assigner_ref.tell(ArconMessage::element(i, None, 0.into()));
+        }
+        flush(&assigner_ref);
+        wait(1);
+        sink.on_definition(|cd| {
+            let results: Vec<u64> = cd.data.iter().map(|e| e.data).collect();
+            // every element is its own key, so only windows of equal elements complete
+            assert!(results.is_empty());
+        });
+
+        let (assigner_ref, sink) = count_window_test_setup(3, 3);
+        for _ in 0..7 {
+            assigner_ref.tell(ArconMessage::element(2, None, 0.into()));
+        }
+        flush(&assigner_ref);
+        wait(1);
+        sink.on_definition(|cd| {
+            let results: Vec<u64> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(results, vec![6, 6]);
+        });
+    }
+
+    #[test]
+    fn sliding_count_window() {
+        let (assigner_ref, sink) = count_window_test_setup(4, 2);
+        for _ in 0..8 {
+            assigner_ref.tell(ArconMessage::element(1, None, 0.into()));
+        }
+        assigner_ref.tell(ArconMessage::element(5, None, 0.into()));
+        flush(&assigner_ref);
+        wait(1);
+        sink.on_definition(|cd| {
+            let results: Vec<u64> = cd.data.iter().map(|e| e.data).collect();
+            // windows end at the 4th, 6th and 8th element of key 1
+            assert_eq!(results, vec![4, 4, 4]);
+        });
+    }
+}
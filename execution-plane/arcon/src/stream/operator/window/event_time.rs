@@ -6,7 +6,10 @@ use crate::{
         state::{Backend, Bundle, Handle, MapState, RegistrationToken, Session, ValueState},
         *,
     },
-    stream::operator::{window::WindowContext, OperatorContext},
+    stream::{
+        node::NodeMetrics,
+        operator::{window::WindowContext, InsertOnly, OperatorContext},
+    },
     timer::TimerBackend,
 };
 use prost::Message;
@@ -18,6 +21,9 @@ use std::marker::PhantomData;
         * Time stored as unix timestamps in u64 format (seconds)
         * Windows created on the fly when events for it come in
         * Events need to implement Hash, use "arcon_keyed" macro when setting up the pipeline
+        * Input must be insert-only, elements of any other ChangeKind are dropped and counted
+        * Windows fire once the watermark passes their end and are kept for another
+          `late` time units, late elements re-fire them as UpdateBefore/UpdateAfter pairs
 */
 
 type Key = u64;
@@ -33,14 +39,18 @@ pub struct WindowEvent {
     index: Index,
     #[prost(uint64, tag = "3")]
     timestamp: Timestamp,
+    // set for the timer which drops the window once its allowed lateness has passed
+    #[prost(bool, tag = "4")]
+    cleanup: bool,
 }
 
 impl WindowEvent {
-    fn new(key: Key, index: Index, timestamp: Timestamp) -> WindowEvent {
+    fn new(key: Key, index: Index, timestamp: Timestamp, cleanup: bool) -> WindowEvent {
         WindowEvent {
             key,
             index,
             timestamp,
+            cleanup,
         }
     }
 }
@@ -94,6 +104,9 @@ where
     // simply persisted state
     state: EventTimeWindowAssignerState,
 
+    // drops changelog rows other than inserts
+    insert_only: InsertOnly,
+
     _marker: PhantomData<(IN, OUT)>,
 }
 
@@ -120,32 +133,52 @@ where
             keyed,
 
             state: EventTimeWindowAssignerState::new(),
+            insert_only: InsertOnly::default(),
             _marker: Default::default(),
         }
     }
 
-    // Creates the window trigger for a key and "window index"
+    // Creates the window trigger for a key and "window index" that fires at the window end
     fn new_window_trigger(
         &self,
         key: Key,
         index: Index,
+        end: Timestamp,
         ctx: &mut OperatorContext<Self, impl state::Backend, impl TimerBackend<WindowEvent>>,
     ) -> Result<(), WindowEvent> {
-        let mut active_state = self.state.activate(ctx.state_session);
-        active_state.window_start().set_item_key(key);
-
-        let w_start = active_state
-            .window_start()
-            .get()
-            .expect("window start state get error")
-            .expect("tried to schedule window_trigger for key which hasn't started");
-
-        let ts = w_start + (index * self.window_slide) + self.window_length;
+        ctx.schedule_at(end, WindowEvent::new(key, index, end, false))
+            .map(|_| ())
+    }
 
+    // Creates the trigger that drops a fired window once late elements may no longer arrive
+    fn new_cleanup_trigger(
+        &self,
+        key: Key,
+        index: Index,
+        end: Timestamp,
+        ctx: &mut OperatorContext<Self, impl state::Backend, impl TimerBackend<WindowEvent>>,
+    ) -> Result<(), WindowEvent> {
         ctx.schedule_at(
-            ts + self.late_arrival_time,
-            WindowEvent::new(key, index, ts),
+            end + self.late_arrival_time,
+            WindowEvent::new(key, index, end, true),
         )
+        .map(|_| ())
+    }
+
+    fn clear_window(
+        &self,
+        key: Key,
+        index: Index,
+        ctx: &mut OperatorContext<Self, impl state::Backend, impl TimerBackend<WindowEvent>>,
+    ) {
+        self.window
+            .clear(WindowContext::new(ctx.state_session, key, index))
+            .expect("window clear error");
+        self.state
+            .activate(ctx.state_session)
+            .active_windows()
+            .remove(&KeyAndIndex { key, index })
+            .expect("active window remove error");
     }
 
     // Extracts the key from ArconElements
@@ -179,11 +212,15 @@ where
     fn handle_element<CD>(
         &self,
         element: ArconElement<IN>,
-        _source: &CD,
+        source: &CD,
         mut ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
     ) where
         CD: ComponentDefinition + Sized + 'static,
     {
+        if self.insert_only.reject(&element, source) {
+            return;
+        }
+
         let ts = element.timestamp.unwrap_or(1);
 
         let time = ctx.current_time();
@@ -202,7 +239,7 @@ where
         // Will store the index of the highest and lowest window it should go into
         let mut floor = 0;
         let mut ceil = 0;
-        if let Some(start) = state
+        let start = if let Some(start) = state
             .window_start()
             .get()
             .expect("window start state get error")
@@ -212,19 +249,42 @@ where
             if ceil >= (self.window_length / self.window_slide) {
                 floor = ceil - (self.window_length / self.window_slide) + 1;
             }
+            start
         } else {
             // Window starting now, first element only goes into the first window
             state
                 .window_start()
                 .set(ts)
                 .expect("window start set error");
-        }
+            ts
+        };
 
         // temporarily deactivate state, so we can borrow the session mutably again
         drop(state);
 
         // Insert the element into all windows and create new where necessary
         for index in floor..=ceil {
+            let end = start + (index * self.window_slide) + self.window_length;
+            // The window has already fired if the watermark passed its end
+            let fired = end <= time;
+
+            let active = self
+                .state
+                .activate(ctx.state_session)
+                .active_windows()
+                .contains(&KeyAndIndex { key, index })
+                .expect("window active check error");
+
+            let previous = if fired && active {
+                Some(
+                    self.window
+                        .result(WindowContext::new(ctx.state_session, key, index))
+                        .expect("window result error"),
+                )
+            } else {
+                None
+            };
+
             self.window
                 .on_element(
                     element.data.clone(),
@@ -232,23 +292,55 @@ where
                 )
                 .expect("window error");
 
-            let mut state = self.state.activate(ctx.state_session);
-
-            if !state
-                .active_windows()
-                .contains(&KeyAndIndex { key, index })
-                .expect("window active check error")
-            {
-                state
+            if !active {
+                self.state
+                    .activate(ctx.state_session)
                     .active_windows()
                     .insert(KeyAndIndex { key, index }, ())
                     .expect("active windows insert error");
-                // Create the window trigger
-                if let Err(event) = self.new_window_trigger(key, index, &mut ctx) {
+                // Create the window trigger, or only its cleanup if the window fires right away
+                let trigger = if fired {
+                    self.new_cleanup_trigger(key, index, end, &mut ctx)
+                } else {
+                    self.new_window_trigger(key, index, end, &mut ctx)
+                };
+                if let Err(event) = trigger {
                     // I'm pretty sure this shouldn't happen
                     unreachable!("Window was expired when scheduled: {:?}", event);
                 }
             }
+
+            if fired {
+                // Re-fire the window with the late element
+                let result = self
+                    .window
+                    .result(WindowContext::new(ctx.state_session, key, index))
+                    .expect("window result error");
+
+                if let Some(previous) = previous {
+                    ctx.output(
+                        ArconEvent::Element(ArconElement::with_change(
+                            previous,
+                            Some(end),
+                            ChangeKind::UpdateBefore,
+                        )),
+                        source,
+                    );
+                    ctx.output(
+                        ArconEvent::Element(ArconElement::with_change(
+                            result,
+                            Some(end),
+                            ChangeKind::UpdateAfter,
+                        )),
+                        source,
+                    );
+                } else {
+                    ctx.output(
+                        ArconEvent::Element(ArconElement::with_timestamp(result, end)),
+                        source,
+                    );
+                }
+            }
         }
     }
 
@@ -267,24 +359,38 @@ where
             key,
             index,
             timestamp,
+            cleanup,
         } = timeout;
 
+        if cleanup {
+            self.clear_window(key, index, &mut ctx);
+            return;
+        }
+
         let e = self
             .window
             .result(WindowContext::new(ctx.state_session, key, index))
             .expect("window result error");
 
-        self.window
-            .clear(WindowContext::new(ctx.state_session, key, index))
-            .expect("window clear error");
-        self.state
-            .activate(ctx.state_session)
-            .active_windows()
-            .remove(&KeyAndIndex { key, index })
-            .expect("active window remove error");
-
         let window_result = ArconEvent::Element(ArconElement::with_timestamp(e, timestamp));
         ctx.output(window_result, source);
+
+        // Keep the window around for late elements, unless the watermark is already past them
+        if self.late_arrival_time == 0
+            || self
+                .new_cleanup_trigger(key, index, timestamp, &mut ctx)
+                .is_err()
+        {
+            self.clear_window(key, index, &mut ctx);
+        }
+    }
+
+    fn report_metrics(&self, metrics: &mut NodeMetrics) {
+        self.insert_only.report_metrics(metrics);
+    }
+
+    fn allowed_lateness(&self) -> u64 {
+        self.late_arrival_time
     }
 }
 
@@ -444,6 +550,33 @@ mod tests {
         });
     }
     #[test]
+    fn window_late_arrival_refires() {
+        // A late element within the allowed lateness re-fires the window as an update
+        let (assigner_ref, sink) = window_assigner_test_setup(10, 10, 10);
+        wait(1);
+        let moment = now();
+        assigner_ref.tell(timestamped_event(moment));
+        assigner_ref.tell(timestamped_event(moment));
+        assigner_ref.tell(watermark(moment + 10));
+        wait(1);
+        assigner_ref.tell(timestamped_event(moment));
+        // Drops the window, so the next late element is discarded
+        assigner_ref.tell(watermark(moment + 21));
+        wait(1);
+        assigner_ref.tell(timestamped_event(moment));
+        assigner_ref.tell(watermark(moment + 22));
+        wait(1);
+        sink.on_definition(|cd| {
+            let results: Vec<(u64, ChangeKind)> =
+                cd.data.iter().map(|e| (e.data, e.change_kind())).collect();
+            assert_eq!(results, vec![
+                (2, ChangeKind::Insert),
+                (2, ChangeKind::UpdateBefore),
+                (3, ChangeKind::UpdateAfter)
+            ]);
+        });
+    }
+    #[test]
     fn window_very_long_windows_1() {
         // Use long windows to check for timer not going out of sync in ms conversion
         let (assigner_ref, sink) = window_assigner_test_setup(10000, 10000, 0);
@@ -0,0 +1,557 @@
+// Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use crate::{
+    prelude::{
+        state::{Backend, Bundle, Handle, MapState, RegistrationToken, Session, ValueState},
+        *,
+    },
+    stream::{
+        node::NodeMetrics,
+        operator::{window::WindowContext, InsertOnly, OperatorContext},
+    },
+    timer::TimerBackend,
+    util::SafelySendableFn,
+};
+use std::marker::PhantomData;
+
+/*
+    GlobalWindowAssigner
+        * Assigns all messages of a key to a single, never-ending window
+        * When the window is evaluated is decided solely by a Trigger
+        * Triggers are consulted for every element and every watermark
+        * Firing a window again before it is purged emits an UpdateBefore/UpdateAfter pair,
+          so that downstream operators see a changelog of the window result
+        * Input must be insert-only, elements of any other ChangeKind are dropped and counted
+*/
+
+type Key = u64;
+
+// The global window of each key always has the same index
+const GLOBAL_WINDOW_INDEX: u64 = 0;
+
+/// Decision made by a [Trigger] about the window of a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerResult {
+    /// Do nothing
+    Continue,
+    /// Emit the window result and keep its contents
+    ///
+    /// Once a window has fired, later results are emitted as updates of the previous one.
+    Fire,
+    /// Emit the window result and clear its contents
+    FireAndPurge,
+    /// Clear the window contents without emitting anything
+    Purge,
+}
+
+/// `Trigger` decides when the window of a [GlobalWindowAssigner] is evaluated
+///
+/// The passed [WindowContext] is scoped to the key of the window.
+pub trait Trigger<IN>: Send
+where
+    IN: ArconType,
+{
+    fn register_states(
+        &mut self,
+        registration_token: &mut state::RegistrationToken<impl state::Backend>,
+    );
+
+    /// Called for every element after it has been added to the window
+    fn on_element(
+        &self,
+        element: &ArconElement<IN>,
+        ctx: WindowContext<impl state::Backend>,
+    ) -> ArconResult<TriggerResult>;
+
+    /// Called for every window with contents when the watermark advances
+    fn on_watermark(
+        &self,
+        _watermark: u64,
+        _ctx: WindowContext<impl state::Backend>,
+    ) -> ArconResult<TriggerResult> {
+        Ok(TriggerResult::Continue)
+    }
+
+    /// Called when the window is purged, allowing the trigger to reset its own state
+    fn clear(&self, ctx: WindowContext<impl state::Backend>) -> ArconResult<()>;
+}
+
+/// Trigger that fires and purges the window of a key every `count` elements
+pub struct CountTrigger {
+    count: u64,
+    elements: state::Handle<ValueState<u64>, u64>,
+}
+
+impl CountTrigger {
+    pub fn new(count: u64) -> CountTrigger {
+        CountTrigger {
+            count,
+            elements: state::Handle::value("count_trigger_elements").with_item_key(0),
+        }
+    }
+}
+
+impl<IN: ArconType> Trigger<IN> for CountTrigger {
+    fn register_states(
+        &mut self,
+        registration_token: &mut state::RegistrationToken<impl state::Backend>,
+    ) {
+        self.elements.register(registration_token)
+    }
+
+    fn on_element(
+        &self,
+        _element: &ArconElement<IN>,
+        ctx: WindowContext<impl state::Backend>,
+    ) -> ArconResult<TriggerResult> {
+        self.elements.set_item_key(ctx.key);
+        let mut elements = self.elements.activate(ctx.state_session);
+        let seen = elements.get()?.unwrap_or(0) + 1;
+        elements.set(seen)?;
+
+        if seen >= self.count {
+            Ok(TriggerResult::FireAndPurge)
+        } else {
+            Ok(TriggerResult::Continue)
+        }
+    }
+
+    fn clear(&self, ctx: WindowContext<impl state::Backend>) -> ArconResult<()> {
+        self.elements.set_item_key(ctx.key);
+        self.elements.activate(ctx.state_session).clear()?;
+        Ok(())
+    }
+}
+
+/// Trigger that fires and purges the window of a key once an element matches a predicate
+pub struct PunctuationTrigger<IN: ArconType> {
+    predicate: &'static dyn SafelySendableFn(&IN) -> bool,
+}
+
+impl<IN: ArconType> PunctuationTrigger<IN> {
+    pub fn new(predicate: &'static dyn SafelySendableFn(&IN) -> bool) -> PunctuationTrigger<IN> {
+        PunctuationTrigger { predicate }
+    }
+}
+
+impl<IN: ArconType> Trigger<IN> for PunctuationTrigger<IN> {
+    fn register_states(
+        &mut self,
+        _registration_token: &mut state::RegistrationToken<impl state::Backend>,
+    ) {
+        ()
+    }
+
+    fn on_element(
+        &self,
+        element: &ArconElement<IN>,
+        _ctx: WindowContext<impl state::Backend>,
+    ) -> ArconResult<TriggerResult> {
+        if (self.predicate)(&element.data) {
+            Ok(TriggerResult::FireAndPurge)
+        } else {
+            Ok(TriggerResult::Continue)
+        }
+    }
+
+    fn clear(&self, _ctx: WindowContext<impl state::Backend>) -> ArconResult<()> {
+        Ok(())
+    }
+}
+
+arcon_state::bundle! {
+    struct GlobalWindowAssignerState<OUT: ArconType> {
+        // keys that currently have window contents
+        active_keys: Handle<MapState<Key, ()>>,
+        // last result emitted per key since the window was last purged
+        last_result: Handle<ValueState<OUT>, Key>,
+    }
+}
+
+impl<OUT: ArconType> GlobalWindowAssignerState<OUT> {
+    fn new() -> Self {
+        GlobalWindowAssignerState {
+            active_keys: Handle::map("global_window_active_keys"),
+            last_result: Handle::value("global_window_last_result").with_item_key(0),
+        }
+    }
+}
+
+/// Window Assigner with a single window per key that is evaluated by a [Trigger]
+///
+/// IN: Input event
+/// OUT: Output of Window
+pub struct GlobalWindowAssigner<IN, OUT, W, T>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    W: Window<IN, OUT>,
+    T: Trigger<IN>,
+{
+    keyed: bool,
+
+    // window keeps its own state per key (via state backend api)
+    window: W,
+    trigger: T,
+
+    // simply persisted state
+    state: GlobalWindowAssignerState<OUT>,
+
+    // drops changelog rows other than inserts
+    insert_only: InsertOnly,
+
+    _marker: PhantomData<(IN, OUT)>,
+}
+
+impl<IN, OUT, W, T> GlobalWindowAssigner<IN, OUT, W, T>
+where
+    IN: 'static + ArconType,
+    OUT: 'static + ArconType,
+    W: Window<IN, OUT>,
+    T: Trigger<IN>,
+{
+    pub fn new(window: W, trigger: T, keyed: bool) -> Self {
+        GlobalWindowAssigner {
+            keyed,
+            window,
+            trigger,
+            state: GlobalWindowAssignerState::new(),
+            insert_only: InsertOnly::default(),
+            _marker: Default::default(),
+        }
+    }
+
+    // Extracts the key from ArconElements
+    fn get_key(&self, e: &ArconElement<IN>) -> u64 {
+        if !self.keyed {
+            return 0;
+        }
+        e.data.get_key()
+    }
+
+    // Acts upon the decision of the trigger for the window of `key`
+    fn apply<CD>(
+        &self,
+        result: TriggerResult,
+        key: Key,
+        timestamp: Option<u64>,
+        source: &CD,
+        ctx: &mut OperatorContext<Self, impl Backend, impl TimerBackend<ArconNever>>,
+    ) -> ArconResult<()>
+    where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        if let TriggerResult::Fire | TriggerResult::FireAndPurge = result {
+            let e = self.window.result(WindowContext::new(
+                ctx.state_session,
+                key,
+                GLOBAL_WINDOW_INDEX,
+            ))?;
+
+            let mut state = self.state.activate(ctx.state_session);
+            state.last_result().set_item_key(key);
+            let previous = state.last_result().get()?;
+            if result == TriggerResult::Fire {
+                state.last_result().set(e.clone())?;
+            }
+            drop(state);
+
+            match previous {
+                Some(previous) => {
+                    let retraction =
+                        ArconElement::with_change(previous, timestamp, ChangeKind::UpdateBefore);
+                    ctx.output(ArconEvent::Element(retraction), source);
+                    let update = ArconElement::with_change(e, timestamp, ChangeKind::UpdateAfter);
+                    ctx.output(ArconEvent::Element(update), source);
+                }
+                None => {
+                    let insert = ArconElement::with_change(e, timestamp, ChangeKind::Insert);
+                    ctx.output(ArconEvent::Element(insert), source);
+                }
+            }
+        }
+
+        if let TriggerResult::Purge | TriggerResult::FireAndPurge = result {
+            self.window.clear(WindowContext::new(
+                ctx.state_session,
+                key,
+                GLOBAL_WINDOW_INDEX,
+            ))?;
+            self.trigger.clear(WindowContext::new(
+                ctx.state_session,
+                key,
+                GLOBAL_WINDOW_INDEX,
+            ))?;
+            let mut state = self.state.activate(ctx.state_session);
+            state.active_keys().remove(&key)?;
+            state.last_result().set_item_key(key);
+            state.last_result().clear()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<IN, OUT, W, T, B> Operator<B> for GlobalWindowAssigner<IN, OUT, W, T>
+where
+    IN: ArconType,
+    OUT: ArconType,
+    W: Window<IN, OUT>,
+    T: Trigger<IN>,
+    B: Backend,
+{
+    type IN = IN;
+    type OUT = OUT;
+    type TimerState = ArconNever;
+
+    fn register_states(&mut self, registration_token: &mut RegistrationToken<B>) {
+        self.state.register_states(registration_token);
+        self.window.register_states(registration_token);
+        self.trigger.register_states(registration_token);
+    }
+
+    fn init(&mut self, _session: &mut Session<B>) {
+        ()
+    }
+
+    fn handle_element<CD>(
+        &self,
+        element: ArconElement<IN>,
+        source: &CD,
+        mut ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        if self.insert_only.reject(&element, source) {
+            return;
+        }
+
+        let key = self.get_key(&element);
+
+        self.window
+            .on_element(
+                element.data.clone(),
+                WindowContext::new(ctx.state_session, key, GLOBAL_WINDOW_INDEX),
+            )
+            .expect("window error");
+
+        self.state
+            .activate(ctx.state_session)
+            .active_keys()
+            .fast_insert(key, ())
+            .expect("active keys insert error");
+
+        let result = self
+            .trigger
+            .on_element(
+                &element,
+                WindowContext::new(ctx.state_session, key, GLOBAL_WINDOW_INDEX),
+            )
+            .expect("trigger error");
+
+        self.apply(result, key, element.timestamp, source, &mut ctx)
+            .expect("window trigger error");
+    }
+
+    fn handle_watermark<CD>(
+        &self,
+        watermark: Watermark,
+        source: &CD,
+        mut ctx: OperatorContext<Self, B, impl TimerBackend<Self::TimerState>>,
+    ) where
+        CD: ComponentDefinition + Sized + 'static,
+    {
+        let keys: Vec<Key> = self
+            .state
+            .activate(ctx.state_session)
+            .active_keys()
+            .keys()
+            .expect("active keys get error")
+            .collect::<Result<_, _>>()
+            .expect("active keys iteration error");
+
+        for key in keys {
+            let result = self
+                .trigger
+                .on_watermark(
+                    watermark.timestamp,
+                    WindowContext::new(ctx.state_session, key, GLOBAL_WINDOW_INDEX),
+                )
+                .expect("trigger error");
+
+            self.apply(result, key, Some(watermark.timestamp), source, &mut ctx)
+                .expect("window trigger error");
+        }
+    }
+
+    crate::ignore_epoch!(B);
+    crate::ignore_timeout!(B);
+
+    fn report_metrics(&self, metrics: &mut NodeMetrics) {
+        self.insert_only.report_metrics(metrics);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::InMemory;
+    use kompact::prelude::Component;
+    use std::{sync::Arc, thread, time};
+
+    fn global_window_test_setup<T: Trigger<u64> + 'static>(
+        trigger: T,
+    ) -> (
+        ActorRefStrong<ArconMessage<u64>>,
+        Arc<Component<DebugNode<u64>>>,
+    ) {
+        let mut pipeline = ArconPipeline::new();
+        let pool_info = pipeline.get_pool_info();
+        let system = pipeline.system();
+
+        let sink = system.create(move || DebugNode::new());
+        system.start(&sink);
+        let sink_ref: ActorRefStrong<ArconMessage<u64>> =
+            sink.actor_ref().hold().expect("failed to get strong ref");
+
+        let channel_strategy = ChannelStrategy::Forward(Forward::new(
+            Channel::Local(sink_ref),
+            NodeID::new(1),
+            pool_info,
+        ));
+
+        fn init(i: u64) -> u64 {
+            i
+        }
+        fn aggregation(i: u64, agg: &u64) -> u64 {
+            agg + i
+        }
+
+        let window = IncrementalWindow::new(&init, &aggregation);
+        let window_assigner = GlobalWindowAssigner::new(window, trigger, false);
+
+        let window_node = system.create(move || {
+            Node::new(
+                String::from("global_window_node"),
+                1.into(),
+                vec![0.into()],
+                channel_strategy,
+                window_assigner,
+                InMemory::create("test".as_ref()).unwrap(),
+                timer::none(),
+            )
+        });
+        system.start(&window_node);
+
+        let win_ref: ActorRefStrong<ArconMessage<u64>> = window_node
+            .actor_ref()
+            .hold()
+            .expect("failed to get strong ref");
+        (win_ref, sink)
+    }
+
+    fn wait(time: u64) {
+        thread::sleep(time::Duration::from_secs(time));
+    }
+
+    /// A watermark makes the window Node flush its channel buffers
+    fn flush(assigner_ref: &ActorRefStrong<ArconMessage<u64>>) {
+        assigner_ref.tell(ArconMessage::watermark(1, 0.into()));
+    }
+
+    #[test]
+    fn global_window_count_trigger() {
+        let (assigner_ref, sink) = global_window_test_setup(CountTrigger::new(2));
+        for i in 1..=5 {
+            assigner_ref.tell(ArconMessage::element(i, None, 0.into()));
+        }
+        flush(&assigner_ref);
+        wait(1);
+        sink.on_definition(|cd| {
+            let results: Vec<u64> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(results, vec![3, 7]);
+        });
+    }
+
+    #[test]
+    fn global_window_drops_retractions() {
+        let (assigner_ref, sink) = global_window_test_setup(CountTrigger::new(2));
+        assigner_ref.tell(ArconMessage::element(1, None, 0.into()));
+        assigner_ref.tell(ArconMessage::change(1, None, ChangeKind::Delete, 0.into()));
+        assigner_ref.tell(ArconMessage::change(
+            5,
+            None,
+            ChangeKind::UpdateAfter,
+            0.into(),
+        ));
+        assigner_ref.tell(ArconMessage::element(2, None, 0.into()));
+        flush(&assigner_ref);
+        wait(1);
+        sink.on_definition(|cd| {
+            let results: Vec<u64> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(results, vec![3]);
+        });
+    }
+
+    /// Fires on every element without ever purging the window
+    struct EveryElementTrigger;
+
+    impl Trigger<u64> for EveryElementTrigger {
+        fn register_states(
+            &mut self,
+            _registration_token: &mut state::RegistrationToken<impl state::Backend>,
+        ) {
+        }
+
+        fn on_element(
+            &self,
+            _element: &ArconElement<u64>,
+            _ctx: WindowContext<impl state::Backend>,
+        ) -> ArconResult<TriggerResult> {
+            Ok(TriggerResult::Fire)
+        }
+
+        fn clear(&self, _ctx: WindowContext<impl state::Backend>) -> ArconResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn global_window_refire_emits_updates() {
+        let (assigner_ref, sink) = global_window_test_setup(EveryElementTrigger);
+        for i in 1..=3 {
+            assigner_ref.tell(ArconMessage::element(i, None, 0.into()));
+        }
+        flush(&assigner_ref);
+        wait(1);
+        sink.on_definition(|cd| {
+            let results: Vec<(u64, ChangeKind)> =
+                cd.data.iter().map(|e| (e.data, e.change_kind())).collect();
+            assert_eq!(results, vec![
+                (1, ChangeKind::Insert),
+                (1, ChangeKind::UpdateBefore),
+                (3, ChangeKind::UpdateAfter),
+                (3, ChangeKind::UpdateBefore),
+                (6, ChangeKind::UpdateAfter),
+            ]);
+        });
+    }
+
+    #[test]
+    fn global_window_punctuation_trigger() {
+        fn is_zero(i: &u64) -> bool {
+            *i == 0
+        }
+        let (assigner_ref, sink) = global_window_test_setup(PunctuationTrigger::new(&is_zero));
+        for i in &[4, 5, 0, 1, 2, 0, 3] {
+            assigner_ref.tell(ArconMessage::element(*i, None, 0.into()));
+        }
+        flush(&assigner_ref);
+        wait(1);
+        sink.on_definition(|cd| {
+            let results: Vec<u64> = cd.data.iter().map(|e| e.data).collect();
+            assert_eq!(results, vec![9, 3]);
+        });
+    }
+}
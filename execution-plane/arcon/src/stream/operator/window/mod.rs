@@ -1,9 +1,13 @@
 // Copyright (c) 2020, KTH Royal Institute of Technology.
 // SPDX-License-Identifier: AGPL-3.0-only
 
+pub mod count;
 pub mod event_time;
+pub mod global;
 
+pub use count::CountWindowAssigner;
 pub use event_time::EventTimeWindowAssigner;
+pub use global::{CountTrigger, GlobalWindowAssigner, PunctuationTrigger, Trigger, TriggerResult};
 
 use crate::{
     prelude::*,
@@ -25,6 +29,21 @@ impl<'s, 'b, B: state::Backend> WindowContext<'s, 'b, B> {
             index,
         }
     }
+
+    /// Returns the key of the window
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// Returns the index of the window
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns the state session of the parent Node
+    pub fn state_session(&mut self) -> &mut state::Session<'b, B> {
+        self.state_session
+    }
 }
 
 /// `Window` consists of the methods required by each window implementation
@@ -216,6 +235,77 @@ where
     }
 }
 
+/// A window that aggregates its elements with an [Aggregator](state::Aggregator)
+///
+/// Any of the aggregators in [aggregate](crate::stream::operator::aggregate) may be used
+/// as long as its input and result types match the elements and output of the window.
+pub struct AggregatorWindow<A>
+where
+    A: state::Aggregator,
+    A::Input: ArconType,
+    A::Result: ArconType,
+{
+    aggregator: state::Handle<AggregatorState<A>, u64, u64>,
+}
+
+impl<A> AggregatorWindow<A>
+where
+    A: state::Aggregator,
+    A::Input: ArconType,
+    A::Result: ArconType,
+{
+    pub fn new(aggregator: A) -> AggregatorWindow<A> {
+        let aggregator = state::Handle::aggregator("aggregator_window_state", aggregator)
+            .with_item_key(0)
+            .with_namespace(0);
+
+        AggregatorWindow { aggregator }
+    }
+}
+
+impl<A> Window<A::Input, A::Result> for AggregatorWindow<A>
+where
+    A: state::Aggregator,
+    A::Input: ArconType,
+    A::Result: ArconType,
+{
+    fn register_states(
+        &mut self,
+        registration_token: &mut state::RegistrationToken<impl state::Backend>,
+    ) {
+        self.aggregator.register(registration_token)
+    }
+
+    fn on_element(
+        &self,
+        element: A::Input,
+        ctx: WindowContext<impl state::Backend>,
+    ) -> ArconResult<()> {
+        self.aggregator.set_item_key(ctx.key);
+        self.aggregator.set_namespace(ctx.index);
+
+        self.aggregator
+            .activate(ctx.state_session)
+            .aggregate(element)?;
+
+        Ok(())
+    }
+
+    fn result(&self, ctx: WindowContext<impl state::Backend>) -> ArconResult<A::Result> {
+        self.aggregator.set_item_key(ctx.key);
+        self.aggregator.set_namespace(ctx.index);
+
+        Ok(self.aggregator.activate(ctx.state_session).get()?)
+    }
+
+    fn clear(&self, ctx: WindowContext<impl state::Backend>) -> ArconResult<()> {
+        self.aggregator.set_item_key(ctx.key);
+        self.aggregator.set_namespace(ctx.index);
+
+        Ok(self.aggregator.activate(ctx.state_session).clear()?)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -268,4 +358,33 @@ mod tests {
         let expected: u64 = 45;
         assert_eq!(sum, expected);
     }
+
+    #[test]
+    fn max_aggregator_window_test() {
+        use crate::stream::operator::aggregate::Max;
+
+        let state_backend = InMemory::create("test".as_ref()).unwrap();
+        let mut session = state_backend.session();
+
+        let mut window = AggregatorWindow::new(Max::<i64>::new());
+        window.register_states(&mut unsafe { state::RegistrationToken::new(&mut session) });
+
+        for i in &[3, -1, 7, 2] {
+            let _ = window.on_element(*i, WindowContext::new(&mut session, 0, 0));
+        }
+        let _ = window.on_element(100, WindowContext::new(&mut session, 0, 1));
+
+        let max = window
+            .result(WindowContext::new(&mut session, 0, 0))
+            .unwrap();
+        assert_eq!(max, 7);
+
+        window
+            .clear(WindowContext::new(&mut session, 0, 0))
+            .unwrap();
+        let max = window
+            .result(WindowContext::new(&mut session, 0, 1))
+            .unwrap();
+        assert_eq!(max, 100);
+    }
 }
@@ -15,7 +15,7 @@ use hierarchical_hash_wheel_timer::{
 use prost::Message;
 #[cfg(feature = "arcon_serde")]
 use serde::{Deserialize, Serialize};
-use std::{fmt, fmt::Debug};
+use std::{collections::BTreeSet, fmt, fmt::Debug};
 use uuid::Uuid;
 
 /*
@@ -34,6 +34,10 @@ use uuid::Uuid;
 
     Usage is thus to store the timer within a component, schedule events on it and using
         advance_to to return ordered set of actions to perform.
+
+    Processing-time timers are kept apart from the wheel, ordered by their wall-clock deadline
+        (UNIX timestamps in milliseconds). They are collected through advance_processing_to,
+        which the owning Node calls from its Kompact scheduler.
 */
 
 pub trait TimerTypeBoundsNoSerde: Message + Default + PartialEq + Clone + 'static {}
@@ -70,6 +74,43 @@ impl<E: TimerTypeBounds> EventTimerEvent<E> {
             payload,
         }
     }
+
+    #[inline]
+    fn due(&self) -> u64 {
+        self.time_when_scheduled + self.timeout_millis
+    }
+}
+
+// Entries in the wheel carry their due time next to the timer id, so that wheel entries
+// left behind by a cancelled or rescheduled timer can be told apart from the live one.
+#[inline]
+fn wheel_token(id: &str, due: u64) -> String {
+    format!("{}#{}", id, due)
+}
+
+#[inline]
+fn parse_wheel_token(token: &str) -> (&str, u64) {
+    let split = token.rfind('#').expect("malformed timer wheel entry");
+    let due = token[split + 1..]
+        .parse()
+        .expect("malformed timer wheel entry");
+    (&token[..split], due)
+}
+
+#[cfg_attr(feature = "arcon_serde", derive(Serialize, Deserialize))]
+#[derive(Message, PartialEq, Clone)]
+#[cfg_attr(feature = "arcon_serde", serde(bound = "E: TimerTypeBounds"))]
+pub struct ProcessingTimerEvent<E: TimerTypeBounds> {
+    #[prost(uint64, tag = "1")]
+    deadline: u64,
+    #[prost(message, required, tag = "2")]
+    payload: E,
+}
+
+impl<E: TimerTypeBounds> ProcessingTimerEvent<E> {
+    fn new(deadline: u64, payload: E) -> Self {
+        ProcessingTimerEvent { deadline, payload }
+    }
 }
 
 arcon_state::bundle! {
@@ -77,6 +118,7 @@ arcon_state::bundle! {
     struct TimerState<E: TimerTypeBounds> {
         current_time: Handle<ValueState<u64>>,
         timeouts: Handle<MapState<String, EventTimerEvent<E>>>,
+        processing_timeouts: Handle<MapState<String, ProcessingTimerEvent<E>>>,
     }
 }
 
@@ -85,6 +127,7 @@ impl<E: TimerTypeBounds> TimerState<E> {
         TimerState {
             current_time: Handle::value("__event_timer_current_time"),
             timeouts: Handle::map("__event_timer_timeouts"),
+            processing_timeouts: Handle::map("__event_timer_processing_timeouts"),
         }
     }
 }
@@ -92,12 +135,15 @@ impl<E: TimerTypeBounds> TimerState<E> {
 pub struct EventTimer<E: TimerTypeBounds> {
     // Since the backing store uses string keys, there's no point in storing Uuids here and converting back and forth
     timer: QuadWheelWithOverflow<String>,
+    // Pending processing-time timers ordered by deadline
+    processing_queue: BTreeSet<(u64, String)>,
     state: TimerState<E>,
 }
 impl<E: TimerTypeBounds> EventTimer<E> {
     pub fn new() -> EventTimer<E> {
         let timer = EventTimer {
             timer: QuadWheelWithOverflow::default(),
+            processing_queue: BTreeSet::new(),
             state: TimerState::new(),
         };
         timer
@@ -112,6 +158,7 @@ impl<E: TimerTypeBounds> EventTimer<E> {
     {
         EventTimerWithState {
             timer: &mut self.timer,
+            processing_queue: &mut self.processing_queue,
             state: self.state.activate(state_session),
         }
     }
@@ -124,6 +171,7 @@ where
     'backend: 'session,
 {
     timer: &'this mut QuadWheelWithOverflow<String>,
+    processing_queue: &'this mut BTreeSet<(u64, String)>,
     state: <TimerState<E> as Bundle<'this, 'session, 'backend, B>>::Active,
 }
 
@@ -138,16 +186,28 @@ impl<E: TimerTypeBounds, B: state::Backend> EventTimerWithState<'_, '_, '_, E, B
             .expect("could not get timeouts")
         {
             let (id, entry) = res.expect("could not get timeout entry");
-            let delay = entry.time_when_scheduled + entry.timeout_millis - time;
+            let delay = entry.due() - time;
             if let Err(f) = self
                 .timer
-                .insert_with_delay(id, Duration::from_millis(delay))
+                .insert_with_delay(wheel_token(&id, entry.due()), Duration::from_millis(delay))
             {
                 panic!("A timeout has expired during replay: {:?}", f);
             }
         }
     }
 
+    fn replay_processing_events(&mut self) {
+        for res in self
+            .state
+            .processing_timeouts()
+            .iter()
+            .expect("could not get processing timeouts")
+        {
+            let (id, entry) = res.expect("could not get processing timeout entry");
+            self.processing_queue.insert((entry.deadline, id));
+        }
+    }
+
     fn set_time(&mut self, ts: u64) {
         self.state
             .current_time()
@@ -200,14 +260,20 @@ impl<E: TimerTypeBounds, B: state::Backend> EventTimerWithState<'_, '_, '_, E, B
     }
 
     // Lookup id, remove from storage, and return Executable action
+    //
+    // Returns None if the timer has since been cancelled or rescheduled.
     #[inline(always)]
-    fn take_entry(&mut self, id: String) -> Option<E> {
-        //self.inner.handles.remove(&id).map(|x| x.payload)
-        self.state
-            .timeouts()
-            .remove(&id)
-            .expect("no timeout found for id") // this wouldn't necessarily be an error anymore if we add a cancellation API at some point
-            .map(|e| e.payload)
+    fn take_entry(&mut self, token: String) -> Option<E> {
+        let (id, due) = parse_wheel_token(&token);
+        let id = id.to_string();
+        let mut timeouts = self.state.timeouts();
+        match timeouts.get(&id).expect("could not get timeout") {
+            Some(e) if e.due() == due => {
+                timeouts.remove(&id).expect("could not remove timeout");
+                Some(e.payload)
+            }
+            _ => None,
+        }
     }
 
     #[inline(always)]
@@ -218,24 +284,29 @@ impl<E: TimerTypeBounds, B: state::Backend> EventTimerWithState<'_, '_, '_, E, B
             .expect("couldn't persist timeout");
     }
 
-    fn schedule_after(&mut self, delay: u64, entry: E) -> Result<(), E> {
+    fn schedule_after(&mut self, delay: u64, entry: E) -> Result<TimerId, E> {
         // this seems a bit silly, but it is A way to generate a unique string, I suppose^^
         let id = Uuid::new_v4().to_string();
+        self.insert_event(id, delay, entry)
+    }
 
-        match self
-            .timer
-            .insert_with_delay(id.clone(), Duration::from_millis(delay))
-        {
+    fn insert_event(&mut self, id: TimerId, delay: u64, entry: E) -> Result<TimerId, E> {
+        let cur_time = self.current_time();
+        match self.timer.insert_with_delay(
+            wheel_token(&id, cur_time + delay),
+            Duration::from_millis(delay),
+        ) {
             Ok(_) => {
-                let e = EventTimerEvent::new(self.current_time(), delay, entry);
-                self.put_entry(id, e);
-                Ok(())
+                let e = EventTimerEvent::new(cur_time, delay, entry);
+                self.put_entry(id.clone(), e);
+                Ok(id)
             }
             Err(TimerError::Expired(_)) => Err(entry),
             Err(f) => panic!("Could not insert timer entry! {:?}", f),
         }
     }
-    fn schedule_at(&mut self, time: u64, entry: E) -> Result<(), E> {
+
+    fn schedule_at(&mut self, time: u64, entry: E) -> Result<TimerId, E> {
         let cur_time = self.current_time();
         // Check for expired target time
         if time <= cur_time {
@@ -245,6 +316,94 @@ impl<E: TimerTypeBounds, B: state::Backend> EventTimerWithState<'_, '_, '_, E, B
         }
     }
 
+    fn schedule_processing_at(&mut self, time: u64, entry: E) -> Result<TimerId, E> {
+        let id = Uuid::new_v4().to_string();
+        self.insert_processing_event(id, time, entry)
+    }
+
+    fn insert_processing_event(&mut self, id: TimerId, time: u64, entry: E) -> Result<TimerId, E> {
+        if time <= crate::util::get_system_time() {
+            return Err(entry);
+        }
+
+        self.state
+            .processing_timeouts()
+            .fast_insert(id.clone(), ProcessingTimerEvent::new(time, entry))
+            .expect("couldn't persist processing timeout");
+        self.processing_queue.insert((time, id.clone()));
+        Ok(id)
+    }
+
+    fn cancel(&mut self, id: &TimerId) -> Option<E> {
+        // Wheel entries of event-time timers are left behind and skipped once they expire
+        if let Some(e) = self
+            .state
+            .timeouts()
+            .remove(id)
+            .expect("could not remove timeout")
+        {
+            return Some(e.payload);
+        }
+
+        let e = self
+            .state
+            .processing_timeouts()
+            .remove(id)
+            .expect("could not remove processing timeout")?;
+        self.processing_queue.remove(&(e.deadline, id.clone()));
+        Some(e.payload)
+    }
+
+    fn reschedule(
+        &mut self,
+        id: &TimerId,
+        new_time: u64,
+        update: impl FnOnce(&mut E),
+    ) -> Result<bool, E> {
+        let is_event_timer = self
+            .state
+            .timeouts()
+            .contains(id)
+            .expect("could not check timeouts");
+
+        let mut entry = match self.cancel(id) {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+        update(&mut entry);
+
+        let res = if is_event_timer {
+            let cur_time = self.current_time();
+            if new_time <= cur_time {
+                return Err(entry);
+            }
+            self.insert_event(id.clone(), new_time - cur_time, entry)
+        } else {
+            self.insert_processing_event(id.clone(), new_time, entry)
+        };
+
+        res.map(|_| true)
+    }
+
+    fn advance_processing_to(&mut self, now: u64) -> Vec<E> {
+        let mut res = Vec::new();
+        while let Some((deadline, id)) = self.processing_queue.iter().next().cloned() {
+            if deadline > now {
+                break;
+            }
+            self.processing_queue.remove(&(deadline, id.clone()));
+            if let Some(e) = self
+                .state
+                .processing_timeouts()
+                .remove(&id)
+                .expect("could not remove processing timeout")
+            {
+                res.push(e.payload);
+            }
+        }
+        res
+    }
+
     fn current_time(&mut self) -> u64 {
         self.state
             .current_time()
@@ -294,6 +453,7 @@ impl<E: TimerTypeBounds> TimerBackend<E> for EventTimer<E> {
 
         let mut twt = self.with_state(session);
         twt.replay_events(); // in case something was already in the storage
+        twt.replay_processing_events();
     }
 
     fn schedule_after(
@@ -301,7 +461,7 @@ impl<E: TimerTypeBounds> TimerBackend<E> for EventTimer<E> {
         delay: u64,
         entry: E,
         state_session: &mut state::Session<impl state::Backend>,
-    ) -> Result<(), E> {
+    ) -> Result<TimerId, E> {
         self.with_state(state_session).schedule_after(delay, entry)
     }
 
@@ -310,7 +470,7 @@ impl<E: TimerTypeBounds> TimerBackend<E> for EventTimer<E> {
         time: u64,
         entry: E,
         state_session: &mut state::Session<impl state::Backend>,
-    ) -> Result<(), E> {
+    ) -> Result<TimerId, E> {
         self.with_state(state_session).schedule_at(time, entry)
     }
 
@@ -326,6 +486,50 @@ impl<E: TimerTypeBounds> TimerBackend<E> for EventTimer<E> {
         self.with_state(state_session).advance_to(ts)
     }
 
+    fn schedule_processing_at(
+        &mut self,
+        time: u64,
+        entry: E,
+        state_session: &mut state::Session<impl state::Backend>,
+    ) -> Result<TimerId, E> {
+        self.with_state(state_session)
+            .schedule_processing_at(time, entry)
+    }
+
+    fn cancel(
+        &mut self,
+        id: &TimerId,
+        state_session: &mut state::Session<impl state::Backend>,
+    ) -> Option<E> {
+        self.with_state(state_session).cancel(id)
+    }
+
+    fn reschedule_with(
+        &mut self,
+        id: &TimerId,
+        new_time: u64,
+        update: impl FnOnce(&mut E),
+        state_session: &mut state::Session<impl state::Backend>,
+    ) -> Result<bool, E> {
+        self.with_state(state_session)
+            .reschedule(id, new_time, update)
+    }
+
+    fn next_processing_deadline(&self) -> Option<u64> {
+        self.processing_queue
+            .iter()
+            .next()
+            .map(|(deadline, _)| *deadline)
+    }
+
+    fn advance_processing_to(
+        &mut self,
+        now: u64,
+        state_session: &mut state::Session<impl state::Backend>,
+    ) -> Vec<E> {
+        self.with_state(state_session).advance_processing_to(now)
+    }
+
     fn handle_epoch(
         &mut self,
         _epoch: Epoch,
@@ -340,3 +544,88 @@ impl<E: TimerTypeBounds> Debug for EventTimer<E> {
         write!(f, "<EventTimer>")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{Backend, InMemory};
+
+    #[test]
+    fn processing_timers_survive_restore() {
+        let backend = InMemory::create("test".as_ref()).unwrap();
+        let mut session = backend.session();
+        let deadline = crate::util::get_system_time() + 60_000;
+
+        let mut timer = EventTimer::<u64>::new();
+        {
+            let mut token = unsafe { state::RegistrationToken::new(&mut session) };
+            timer.register_states(&mut token);
+        }
+        timer.init(&mut session);
+        assert!(timer
+            .schedule_processing_at(deadline, 1, &mut session)
+            .is_ok());
+        assert!(timer.schedule_processing_at(0, 2, &mut session).is_err());
+
+        // A fresh timer over the same state picks up the pending timer
+        let mut restored = EventTimer::<u64>::new();
+        {
+            let mut token = unsafe { state::RegistrationToken::new(&mut session) };
+            restored.register_states(&mut token);
+        }
+        restored.init(&mut session);
+        assert_eq!(restored.next_processing_deadline(), Some(deadline));
+        assert!(restored
+            .advance_processing_to(deadline - 1, &mut session)
+            .is_empty());
+        assert_eq!(
+            restored.advance_processing_to(deadline, &mut session),
+            vec![1]
+        );
+        assert_eq!(restored.next_processing_deadline(), None);
+    }
+
+    #[test]
+    fn cancel_and_reschedule() {
+        let backend = InMemory::create("test".as_ref()).unwrap();
+        let mut session = backend.session();
+
+        let mut timer = EventTimer::<u64>::new();
+        {
+            let mut token = unsafe { state::RegistrationToken::new(&mut session) };
+            timer.register_states(&mut token);
+        }
+        timer.init(&mut session);
+
+        let cancelled = timer.schedule_at(10, 1, &mut session).unwrap();
+        let moved = timer.schedule_at(20, 2, &mut session).unwrap();
+        let _kept = timer.schedule_at(30, 3, &mut session).unwrap();
+
+        assert_eq!(timer.cancel(&cancelled, &mut session), Some(1));
+        assert_eq!(timer.cancel(&cancelled, &mut session), None);
+        assert_eq!(timer.reschedule(&moved, 40, &mut session), Ok(true));
+        assert_eq!(timer.reschedule(&cancelled, 40, &mut session), Ok(false));
+
+        // Neither the cancelled timer nor the old slot of the moved one fire
+        assert_eq!(timer.advance_to(35, &mut session), vec![3]);
+        assert_eq!(timer.advance_to(45, &mut session), vec![2]);
+
+        // Rescheduling into the past hands the entry back
+        let past = timer.schedule_at(50, 4, &mut session).unwrap();
+        assert_eq!(timer.reschedule(&past, 40, &mut session), Err(4));
+        assert!(timer.advance_to(60, &mut session).is_empty());
+
+        // Processing-time timers keep their domain
+        let deadline = crate::util::get_system_time() + 60_000;
+        let processing = timer
+            .schedule_processing_at(deadline, 5, &mut session)
+            .unwrap();
+        assert_eq!(
+            timer.reschedule(&processing, deadline + 1_000, &mut session),
+            Ok(true)
+        );
+        assert_eq!(timer.next_processing_deadline(), Some(deadline + 1_000));
+        assert_eq!(timer.cancel(&processing, &mut session), Some(5));
+        assert_eq!(timer.next_processing_deadline(), None);
+    }
+}
@@ -8,6 +8,9 @@ use crate::{
 
 pub mod event_timer;
 
+/// Identifier of a scheduled timer
+pub type TimerId = String;
+
 /// This can be used as fake backend for components that don't need one
 pub fn none() -> impl TimerBackend<ArconNever> {
     ()
@@ -32,29 +35,85 @@ pub trait TimerBackend<E>: Send + 'static {
 
     /// Basic scheduling function
     ///
-    /// Returns Ok if the entry was schedulled successfully
+    /// Returns the id of the timer if the entry was schedulled successfully
     /// or `Err(entry)` if it has already expired.
     fn schedule_after(
         &mut self,
         delay: u64,
         entry: E,
         state_session: &mut state::Session<impl state::Backend>,
-    ) -> Result<(), E>;
+    ) -> Result<TimerId, E>;
 
     /// Schedule at a specific time in the future
     ///
-    /// Returns Ok if the entry was scheduled successfully
+    /// Returns the id of the timer if the entry was scheduled successfully
     /// or `Err(entry)` if it has already expired.
     fn schedule_at(
         &mut self,
         time: u64,
         entry: E,
         state_session: &mut state::Session<impl state::Backend>,
-    ) -> Result<(), E>;
+    ) -> Result<TimerId, E>;
 
     /// Returns the current time value of the timer
     fn current_time(&mut self, state_session: &mut state::Session<impl state::Backend>) -> u64;
 
+    /// Schedule a processing-time timer
+    ///
+    /// `time` is a wall-clock deadline in milliseconds since the UNIX epoch.
+    /// Returns the id of the timer if the entry was scheduled successfully
+    /// or `Err(entry)` if the deadline has already passed.
+    fn schedule_processing_at(
+        &mut self,
+        time: u64,
+        entry: E,
+        state_session: &mut state::Session<impl state::Backend>,
+    ) -> Result<TimerId, E>;
+
+    /// Cancel a pending timer
+    ///
+    /// Returns the entry of the timer, or `None` if no timer with that id is pending.
+    fn cancel(
+        &mut self,
+        id: &TimerId,
+        state_session: &mut state::Session<impl state::Backend>,
+    ) -> Option<E>;
+
+    /// Move a pending timer to `new_time`, keeping its id
+    ///
+    /// The timer stays in its time domain, so `new_time` is an event time for timers
+    /// scheduled with `schedule_at`/`schedule_after` and a processing time otherwise.
+    /// Returns `Ok(false)` if no timer with that id is pending, or `Err(entry)` if
+    /// `new_time` has already passed, in which case the timer is removed.
+    fn reschedule(
+        &mut self,
+        id: &TimerId,
+        new_time: u64,
+        state_session: &mut state::Session<impl state::Backend>,
+    ) -> Result<bool, E> {
+        self.reschedule_with(id, new_time, |_| (), state_session)
+    }
+
+    /// Same as [reschedule](TimerBackend::reschedule), but lets `update` modify the entry
+    /// of the timer before it is moved
+    fn reschedule_with(
+        &mut self,
+        id: &TimerId,
+        new_time: u64,
+        update: impl FnOnce(&mut E),
+        state_session: &mut state::Session<impl state::Backend>,
+    ) -> Result<bool, E>;
+
+    /// Returns the earliest deadline among the pending processing-time timers
+    fn next_processing_deadline(&self) -> Option<u64>;
+
+    /// Collect all processing-time timers with a deadline at or before `now`
+    fn advance_processing_to(
+        &mut self,
+        now: u64,
+        state_session: &mut state::Session<impl state::Backend>,
+    ) -> Vec<E>;
+
     /// Move the timer to the given timestamp, triggering all scheduled events between it and the previous timestamps
     fn advance_to(
         &mut self,
@@ -89,7 +148,7 @@ impl TimerBackend<ArconNever> for () {
         _delay: u64,
         _entry: ArconNever,
         _state_session: &mut state::Session<impl state::Backend>,
-    ) -> Result<(), ArconNever> {
+    ) -> Result<TimerId, ArconNever> {
         unreachable!(ArconNever::IS_UNREACHABLE);
     }
 
@@ -98,7 +157,7 @@ impl TimerBackend<ArconNever> for () {
         _time: u64,
         _entry: ArconNever,
         _state_session: &mut state::Session<impl state::Backend>,
-    ) -> Result<(), ArconNever> {
+    ) -> Result<TimerId, ArconNever> {
         unreachable!(ArconNever::IS_UNREACHABLE);
     }
 
@@ -114,6 +173,45 @@ impl TimerBackend<ArconNever> for () {
         Vec::new()
     }
 
+    fn schedule_processing_at(
+        &mut self,
+        _time: u64,
+        _entry: ArconNever,
+        _state_session: &mut state::Session<impl state::Backend>,
+    ) -> Result<TimerId, ArconNever> {
+        unreachable!(ArconNever::IS_UNREACHABLE);
+    }
+
+    fn next_processing_deadline(&self) -> Option<u64> {
+        None
+    }
+
+    fn cancel(
+        &mut self,
+        _id: &TimerId,
+        _state_session: &mut state::Session<impl state::Backend>,
+    ) -> Option<ArconNever> {
+        None
+    }
+
+    fn reschedule_with(
+        &mut self,
+        _id: &TimerId,
+        _new_time: u64,
+        _update: impl FnOnce(&mut ArconNever),
+        _state_session: &mut state::Session<impl state::Backend>,
+    ) -> Result<bool, ArconNever> {
+        Ok(false)
+    }
+
+    fn advance_processing_to(
+        &mut self,
+        _now: u64,
+        _state_session: &mut state::Session<impl state::Backend>,
+    ) -> Vec<ArconNever> {
+        Vec::new()
+    }
+
     fn handle_epoch(
         &mut self,
         _epoch: Epoch,
@@ -3,11 +3,15 @@
 // Modifications Copyright (c) 2020, KTH Royal Institute of Technology.
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use crate::tui::{
-    colorscheme::Colorscheme,
-    widgets::{cpu::CpuWidget, mem::MemWidget, net::NetWidget, node::NodeWidget},
+use crate::{
+    allocator::ArconAllocator,
+    tui::{
+        colorscheme::Colorscheme,
+        widgets::{cpu::CpuWidget, mem::MemWidget, net::NetWidget, node::NodeWidget},
+    },
 };
 use num_rational::Ratio;
+use std::sync::{Arc, Mutex};
 
 pub struct App<'a, 'b> {
     pub widgets: Widgets<'a, 'b>,
@@ -23,10 +27,11 @@ pub struct Widgets<'a, 'b> {
 pub fn setup_app<'a, 'b>(
     cpu_update_interval: Ratio<u64>,
     mem_update_interval: Ratio<u64>,
+    allocator: Arc<Mutex<ArconAllocator>>,
     colorscheme: &'a Colorscheme,
 ) -> App<'a, 'b> {
     let cpu = CpuWidget::new(colorscheme, cpu_update_interval, false, true);
-    let mem = MemWidget::new(colorscheme, mem_update_interval);
+    let mem = MemWidget::new(colorscheme, mem_update_interval, allocator);
     let net = NetWidget::new(colorscheme, "all");
     let node = NodeWidget::new(colorscheme);
 
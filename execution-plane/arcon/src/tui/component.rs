@@ -38,6 +38,10 @@ impl Actor for TuiComponent {
             inbound_channels: msg.metrics.inbound_channels.get() as usize,
             outbound_channels: msg.metrics.outbound_channels.get() as usize,
             inbound_throughput_one_min: msg.metrics.inbound_throughput.get_one_min_rate(),
+            pool_capacity: msg.metrics.buffer_pool.capacity.get() as usize,
+            pool_buffer_size: msg.metrics.buffer_pool.buffer_size.get() as usize,
+            pool_exhaustions: msg.metrics.buffer_pool.exhaustion_counter.get(),
+            pool_wait_micros: msg.metrics.buffer_pool.wait_micros.get(),
         };
 
         self.sender.send(node).unwrap();
@@ -1,16 +1,30 @@
 // Copyright (c) 2019 Caleb Bassi
 // SPDX-License-Identifier: MIT
+// Modifications Copyright (c) 2020, KTH Royal Institute of Technology.
+// SPDX-License-Identifier: AGPL-3.0-only
 
 use num_rational::Ratio;
 use psutil::memory;
 use size::Size;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 use tui::{
     buffer::Buffer,
     layout::Rect,
     widgets::{Axis, Chart, Dataset, GraphType, Marker, Widget},
 };
 
-use crate::tui::{colorscheme::Colorscheme, update::UpdatableWidget, widgets::block};
+use crate::{
+    allocator::ArconAllocator,
+    metrics::memory::AllocatorMetrics,
+    tui::{
+        colorscheme::Colorscheme,
+        update::UpdatableWidget,
+        widgets::{block, node::Node},
+    },
+};
 
 const HORIZONTAL_SCALE_DELTA: u64 = 25;
 
@@ -21,6 +35,15 @@ struct MemData {
     percents: Vec<(f64, f64)>,
 }
 
+/// BufferPool metrics of a single Node
+#[derive(Default)]
+struct PoolData {
+    capacity: usize,
+    buffer_size: usize,
+    exhaustions: u128,
+    wait_micros: u128,
+}
+
 pub struct MemWidget<'a> {
     title: String,
     update_interval: Ratio<u64>,
@@ -32,10 +55,18 @@ pub struct MemWidget<'a> {
 
     main: MemData,
     swap: Option<MemData>,
+
+    allocator: Arc<Mutex<ArconAllocator>>,
+    arcon: AllocatorMetrics,
+    pools: HashMap<(u32, String), PoolData>,
 }
 
 impl MemWidget<'_> {
-    pub fn new(colorscheme: &Colorscheme, update_interval: Ratio<u64>) -> MemWidget {
+    pub fn new(
+        colorscheme: &Colorscheme,
+        update_interval: Ratio<u64>,
+        allocator: Arc<Mutex<ArconAllocator>>,
+    ) -> MemWidget {
         let update_count = 0;
 
         let mut main = MemData::default();
@@ -52,9 +83,23 @@ impl MemWidget<'_> {
 
             main,
             swap: None,
+
+            allocator,
+            arcon: AllocatorMetrics::default(),
+            pools: HashMap::new(),
         }
     }
 
+    pub fn pool_update(&mut self, node: &Node) {
+        let pool = PoolData {
+            capacity: node.pool_capacity,
+            buffer_size: node.pool_buffer_size,
+            exhaustions: node.pool_exhaustions,
+            wait_micros: node.pool_wait_micros,
+        };
+        self.pools.insert((node.id, node.description.clone()), pool);
+    }
+
     pub fn scale_in(&mut self) {
         if self.horizontal_scale > HORIZONTAL_SCALE_DELTA {
             self.horizontal_scale -= HORIZONTAL_SCALE_DELTA;
@@ -72,6 +117,7 @@ impl UpdatableWidget for MemWidget<'_> {
 
         let main = memory::virtual_memory().unwrap();
         let swap = memory::swap_memory().unwrap();
+        self.arcon = self.allocator.lock().unwrap().metrics();
 
         self.main.total = main.total();
         self.main.used = main.used();
@@ -157,5 +203,45 @@ impl Widget for MemWidget<'_> {
                 self.colorscheme.mem_swap,
             );
         }
+
+        let arcon_used = (self.arcon.allocated_bytes + self.arcon.reserved_bytes) as u64;
+        let arcon_percent = if self.arcon.limit == 0 {
+            0.0
+        } else {
+            arcon_used as f64 / self.arcon.limit as f64 * 100.0
+        };
+        buf.set_string(
+            area.x + 3,
+            area.y + 4,
+            format!(
+                "Arcon {:3.0}% {}/{} ({} allocs)",
+                arcon_percent,
+                Size::Bytes(arcon_used),
+                Size::Bytes(self.arcon.limit as u64),
+                self.arcon.total_allocations,
+            ),
+            self.colorscheme.text,
+        );
+
+        let buffers: usize = self.pools.values().map(|p| p.capacity).sum();
+        let events: usize = self
+            .pools
+            .values()
+            .map(|p| p.capacity * p.buffer_size)
+            .sum();
+        let exhaustions: u128 = self.pools.values().map(|p| p.exhaustions).sum();
+        let wait_micros: u128 = self.pools.values().map(|p| p.wait_micros).sum();
+        buf.set_string(
+            area.x + 3,
+            area.y + 5,
+            format!(
+                "Pools {} buffers ({} events), {} exhausted, {:.1}ms waiting",
+                buffers,
+                events,
+                exhaustions,
+                wait_micros as f64 / 1000.0,
+            ),
+            self.colorscheme.text,
+        );
     }
 }
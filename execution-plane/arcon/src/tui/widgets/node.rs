@@ -24,6 +24,10 @@ pub struct Node {
     pub(crate) inbound_channels: usize,
     pub(crate) outbound_channels: usize,
     pub(crate) inbound_throughput_one_min: f64,
+    pub(crate) pool_capacity: usize,
+    pub(crate) pool_buffer_size: usize,
+    pub(crate) pool_exhaustions: u128,
+    pub(crate) pool_wait_micros: u128,
 }
 
 pub struct NodeWidget<'a> {
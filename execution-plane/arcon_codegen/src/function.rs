@@ -8,24 +8,86 @@ use crate::{
     types::to_token_stream,
     GENERATED_FUNCTIONS,
 };
-use arcon_proto::arcon_spec::{Function, FunctionKind};
+use arcon_proto::arcon_spec::{node::NodeKind, Function, FunctionKind};
 use proc_macro2::{Ident, Span, TokenStream};
 
+/// Returns true if `downstream` may be fused into the same Node as the function `upstream`
+///
+/// This is the case if `upstream` forwards all of its output to `downstream` over a local channel,
+/// both run with the same parallelism and both are executed by a `ChainableOperator`.
+pub fn is_chainable(
+    upstream: &spec::Node,
+    downstream: &spec::Node,
+    downstream_fn: &Function,
+) -> bool {
+    match &upstream.node_kind {
+        Some(NodeKind::Function(upstream_fn)) => {
+            has_chainable_operator(upstream_fn.kind)
+                && has_chainable_operator(downstream_fn.kind)
+                && upstream.parallelism == downstream.parallelism
+                && downstream_fn.predecessor == upstream.id
+                && upstream_fn.channel_strategy == spec::ChannelStrategy::Forward as i32
+                && upstream_fn.successors.len() == 1
+                && match upstream_fn.successors[0].channel_kind {
+                    Some(ChannelKind::Local(_)) => true,
+                    _ => false,
+                }
+        }
+        _ => false,
+    }
+}
+
+/// Returns true if functions of `kind` are executed by an operator that implements `ChainableOperator`
+fn has_chainable_operator(kind: i32) -> bool {
+    match FunctionKind::from_i32(kind) {
+        Some(FunctionKind::FlatMap) | Some(FunctionKind::Filter) | Some(FunctionKind::Map) => true,
+        None => false,
+    }
+}
+
+/// Pops the run of functions in `nodes` that can be fused in front of the function `node`
+///
+/// Returns the functions of the whole run, including `func`, in dataflow order.
+pub fn collect_chain(
+    nodes: &mut Vec<spec::Node>,
+    node: &spec::Node,
+    func: &Function,
+) -> Vec<Function> {
+    let mut chain = vec![func.clone()];
+    let mut downstream = node.clone();
+    while nodes
+        .last()
+        .map(|upstream| is_chainable(upstream, &downstream, &chain[0]))
+        .unwrap_or(false)
+    {
+        downstream = nodes.pop().unwrap();
+        if let Some(NodeKind::Function(upstream_fn)) = &downstream.node_kind {
+            chain.insert(0, upstream_fn.clone());
+        }
+    }
+    chain
+}
+
+/// Generates a single Node that executes a chain of functions
+///
+/// The functions are fused with `ChainableOperator::chain`, so elements are passed between
+/// them by direct calls. The Node takes the NodeID `id` of the last function in the chain,
+/// which is what its successor expects as predecessor.
 pub fn function(
     id: u32,
     target_name: &str,
     parallelism: u32,
-    func: &Function,
+    chain: &[Function],
     spec_id: &str,
 ) -> TokenStream {
-    let fn_ident = function_gen(spec_id, &func.id, func.udf.clone());
+    let first = chain.first().expect("empty function chain");
+    let last = chain.last().expect("empty function chain");
     let node_id = id;
     let node_name = id_to_ident(id);
-    let input_type = to_token_stream(&func.input_type.clone().unwrap(), spec_id);
-    let output_type = to_token_stream(&func.output_type.clone().unwrap(), spec_id);
+    let input_type = to_token_stream(&first.input_type.clone().unwrap(), spec_id);
 
-    let successors: &Vec<spec::ChannelKind> = &func.successors;
-    let predecessor = func.predecessor;
+    let successors: &Vec<spec::ChannelKind> = &last.successors;
+    let predecessor = first.predecessor;
 
     if parallelism == 1 {
         assert_eq!(successors.len(), 1);
@@ -33,38 +95,23 @@ pub fn function(
         match &successors.get(0).unwrap().channel_kind.as_ref() {
             Some(ChannelKind::Local(_)) => {
                 let target = Ident::new(target_name, Span::call_site());
-                let kind: FunctionKind = unsafe { ::std::mem::transmute(func.kind) };
-                let task_signature = {
-                    match &kind {
-                        FunctionKind::FlatMap => {
-                            quote! {
-                                FlatMap::<#input_type, #output_type>::new(&#fn_ident)
-                            }
-                        }
-                        FunctionKind::Map => {
-                            quote! {
-                                Map::<#input_type, #output_type>::new(&#fn_ident)
-                            }
-                        }
-                        FunctionKind::Filter => {
-                            quote! {
-                                Filter::<#input_type>::new(&#fn_ident)
-                            }
-                        }
-                    }
-                };
-                let channel_strategy_quote = match &kind {
+                let task_signature = chain
+                    .iter()
+                    .map(|func| task_signature(func, spec_id))
+                    .fold(None, |chained: Option<TokenStream>, task| match chained {
+                        None => Some(task),
+                        Some(chained) => Some(quote! { #chained.chain(#task) }),
+                    })
+                    .unwrap();
+
+                let last_kind: FunctionKind = unsafe { ::std::mem::transmute(last.kind) };
+                let output_type = match &last_kind {
                     FunctionKind::Filter => {
-                        quote! {
-                            let channel_strategy: Box<ChannelStrategy<#input_type>> = Box::new(Forward::new(channel));
-                        }
-                    }
-                    _ => {
-                        quote! {
-                            let channel_strategy: Box<ChannelStrategy<#output_type>> = Box::new(Forward::new(channel));
-                        }
+                        to_token_stream(&last.input_type.clone().unwrap(), spec_id)
                     }
+                    _ => to_token_stream(&last.output_type.clone().unwrap(), spec_id),
                 };
+                let channel_strategy_quote = channel_strategy(last, node_id, &output_type, spec_id);
 
                 let verify = verify_and_start(&node_name, "system");
 
@@ -96,6 +143,65 @@ pub fn function(
     }
 }
 
+/// Generates the ChannelStrategy that the Node `node_id` uses to send the output of `func` to `channel`
+fn channel_strategy(
+    func: &Function,
+    node_id: u32,
+    output_type: &TokenStream,
+    spec_id: &str,
+) -> TokenStream {
+    match spec::ChannelStrategy::from_i32(func.channel_strategy) {
+        Some(spec::ChannelStrategy::Forward) => quote! {
+            let channel_strategy: ChannelStrategy<#output_type> = ChannelStrategy::Forward(
+                Forward::new(channel, NodeID::new(#node_id), pool_info.clone())
+            );
+        },
+        Some(spec::ChannelStrategy::Custom) => {
+            let name = format!("{}_partitioner", func.id);
+            let partitioner = function_gen(spec_id, &name, func.partitioner.clone());
+            quote! {
+                let channel_strategy: ChannelStrategy<#output_type> = ChannelStrategy::Custom(
+                    Custom::new(&#partitioner, vec![channel], NodeID::new(#node_id), pool_info.clone())
+                );
+            }
+        }
+        // The remaining strategies spread data over several channels, while a function
+        // is only generated with a single successor so far
+        Some(other) => panic!(
+            "Channel strategy {:?} of function {} is not supported by codegen",
+            other, func.id
+        ),
+        None => panic!(
+            "Unknown channel strategy {} of function {}",
+            func.channel_strategy, func.id
+        ),
+    }
+}
+
+fn task_signature(func: &Function, spec_id: &str) -> TokenStream {
+    let fn_ident = function_gen(spec_id, &func.id, func.udf.clone());
+    let input_type = to_token_stream(&func.input_type.clone().unwrap(), spec_id);
+    let output_type = to_token_stream(&func.output_type.clone().unwrap(), spec_id);
+    let kind: FunctionKind = unsafe { ::std::mem::transmute(func.kind) };
+    match &kind {
+        FunctionKind::FlatMap => {
+            quote! {
+                FlatMap::<#input_type, #output_type>::new(&#fn_ident)
+            }
+        }
+        FunctionKind::Map => {
+            quote! {
+                Map::<#input_type, #output_type>::new(&#fn_ident)
+            }
+        }
+        FunctionKind::Filter => {
+            quote! {
+                Filter::<#input_type>::new(&#fn_ident)
+            }
+        }
+    }
+}
+
 fn function_gen(spec_id: &str, name: &str, code: String) -> Ident {
     let mut func_map = GENERATED_FUNCTIONS.lock().unwrap();
     if let Some(map) = func_map.get_mut(spec_id) {
@@ -107,4 +213,102 @@ fn function_gen(spec_id: &str, name: &str, code: String) -> Ident {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use arcon_proto::arcon_spec::{type_holder::ArconType, LocalChannel, TypeHolder};
+
+    fn function_node(id: u32, predecessor: u32, kind: FunctionKind) -> spec::Node {
+        let ty = TypeHolder {
+            arcon_type: Some(ArconType::Scalar("u64".into())),
+        };
+        spec::Node {
+            id,
+            parallelism: 1,
+            node_kind: Some(NodeKind::Function(Function {
+                input_type: Some(ty.clone()),
+                output_type: Some(ty),
+                udf: format!("fn udf_{}() {{}}", id),
+                id: format!("udf_{}", id),
+                predecessor,
+                channel_strategy: spec::ChannelStrategy::Forward as i32,
+                successors: vec![spec::ChannelKind {
+                    channel_kind: Some(ChannelKind::Local(LocalChannel {
+                        id: format!("node{}", id + 1),
+                    })),
+                }],
+                kind: kind as i32,
+                partitioner: String::new(),
+            })),
+        }
+    }
+
+    fn func(node: &spec::Node) -> Function {
+        match &node.node_kind {
+            Some(NodeKind::Function(f)) => f.clone(),
+            _ => panic!("not a function"),
+        }
+    }
+
+    #[test]
+    fn forward_functions_are_chained() {
+        let mut nodes = vec![
+            function_node(2, 1, FunctionKind::Map),
+            function_node(3, 2, FunctionKind::Filter),
+            function_node(4, 3, FunctionKind::Map),
+        ];
+        // A KeyBy edge in front of the first function stops the chain
+        if let Some(NodeKind::Function(f)) = &mut nodes[0].node_kind {
+            f.channel_strategy = spec::ChannelStrategy::KeyBy as i32;
+        }
+
+        let node = nodes.pop().unwrap();
+        let chain = collect_chain(&mut nodes, &node, &func(&node));
+        assert_eq!(
+            chain.iter().map(|f| f.id.as_str()).collect::<Vec<_>>(),
+            vec!["udf_3", "udf_4"]
+        );
+        assert_eq!(nodes.len(), 1);
+
+        let code = function(node.id, "node5", 1, &chain, "chain_spec").to_string();
+        assert_eq!(code.matches("create_and_register").count(), 1);
+        assert!(code.contains("Filter :: < u64 > :: new (& udf_3) . chain (Map"));
+        assert!(code.contains("vec ! (2u32 . into ())"));
+    }
+
+    #[test]
+    fn unknown_function_kind_is_not_chained() {
+        let mut nodes = vec![function_node(2, 1, FunctionKind::Map)];
+        if let Some(NodeKind::Function(f)) = &mut nodes[0].node_kind {
+            f.kind = 42;
+        }
+        let node = function_node(3, 2, FunctionKind::Map);
+        assert!(!is_chainable(&nodes[0], &node, &func(&node)));
+
+        let chain = collect_chain(&mut nodes, &node, &func(&node));
+        assert_eq!(chain.len(), 1);
+        assert_eq!(nodes.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "PartialKeyGrouping of function udf_2 is not supported by codegen")]
+    fn unsupported_channel_strategy() {
+        let node = function_node(2, 1, FunctionKind::Map);
+        let mut func = func(&node);
+        func.channel_strategy = spec::ChannelStrategy::PartialKeyGrouping as i32;
+        function(node.id, "node3", 1, &[func], "pkg_spec");
+    }
+
+    #[test]
+    fn custom_channel_strategy() {
+        let node = function_node(2, 1, FunctionKind::Map);
+        let mut func = func(&node);
+        func.channel_strategy = spec::ChannelStrategy::Custom as i32;
+        func.partitioner = String::from("fn udf_2_partitioner(x: &u64, n: usize) -> usize { 0 }");
+
+        let code = function(node.id, "node3", 1, &[func], "custom_spec").to_string();
+        assert!(code.contains(
+            "ChannelStrategy :: Custom (Custom :: new (& udf_2_partitioner , vec ! [channel] , \
+             NodeID :: new (2u32) , pool_info . clone ()))"
+        ));
+    }
+}
@@ -120,11 +120,13 @@ pub fn generate(
                 stream.push(window::window(node.id, &window, &spec.id));
             }
             Some(NodeKind::Function(func)) => {
+                // Forward-connected functions in front of this one are fused into its Node
+                let chain = function::collect_chain(&mut nodes, &node, func);
                 stream.push(function::function(
                     node.id,
                     &previous_node,
                     node.parallelism,
-                    &func,
+                    &chain,
                     &spec.id,
                 ));
             }
@@ -18,6 +18,21 @@ pub fn system(
 
         let system = cfg.build().expect("Failed to create KompactSystem");
 
+        // Buffer pools of the channel strategies are allocated from a shared ArconAllocator
+        let arcon_conf = ArconConf::default();
+        let allocator = std::sync::Arc::new(std::sync::Mutex::new(ArconAllocator::new(
+            arcon_conf.allocator_capacity,
+        )));
+        let pool_info = PoolInfo::new(
+            arcon_conf.channel_batch_size,
+            arcon_conf.buffer_pool_size,
+            arcon_conf.buffer_pool_limit,
+            allocator,
+        )
+        .with_wait_timeout(std::time::Duration::from_millis(
+            arcon_conf.buffer_pool_wait_timeout,
+        ));
+
         // Connect Components, Create ActorPaths, Create Tasks
 
         #kompact_connections